@@ -1,7 +1,16 @@
+extern crate alloc;
+
+use rusttype::{point, Font, Scale};
 use std::env;
-use std::fs::File;
+use std::fmt::Write as _;
+use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+// The image decoders of `lcd::image`, which only depend on `core` and `alloc`
+#[allow(dead_code)]
+#[path = "src/lcd/image/codec/mod.rs"]
+mod codec;
 
 fn main() {
     // Put the linker script somewhere the linker can find it
@@ -12,6 +21,216 @@ fn main() {
         .unwrap();
     println!("cargo:rustc-link-search={}", out.display());
 
+    // Rasterize the TrueType fonts for `lcd::font::fonts`
+    File::create(out.join("fonts.rs"))
+        .unwrap()
+        .write_all(generate_fonts(Path::new("fonts")).as_bytes())
+        .unwrap();
+
+    // Convert the images for `lcd::image::assets`
+    File::create(out.join("assets.rs"))
+        .unwrap()
+        .write_all(generate_assets(Path::new("assets"), out).as_bytes())
+        .unwrap();
+
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=memory.x");
+    println!("cargo:rerun-if-changed=fonts");
+    println!("cargo:rerun-if-changed=assets");
+}
+
+const DEFAULT_FONT_SIZES: &[u32] = &[12, 16, 24];
+
+// Printable ASCII, printable Latin-1 and the replacement character.
+fn font_chars() -> impl Iterator<Item = char> {
+    (0x20u8..=0x7e)
+        .chain(0xa0..=0xff)
+        .map(char::from)
+        .chain(Some('\u{fffd}'))
+}
+
+fn generate_fonts(dir: &Path) -> String {
+    let mut code = String::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return code, // no fonts
+    };
+    let mut paths: Vec<_> = entries
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "ttf"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let sizes_path = path.with_extension("sizes");
+        println!("cargo:rerun-if-changed={}", sizes_path.display());
+
+        let name = path.file_stem().unwrap().to_str().unwrap();
+        let data = fs::read(&path).unwrap();
+        let font = Font::from_bytes(&data[..])
+            .unwrap_or_else(|err| panic!("invalid font {}: {:?}", path.display(), err));
+        let sizes = match fs::read_to_string(&sizes_path) {
+            Ok(sizes) => sizes
+                .split_whitespace()
+                .map(|size| size.parse().expect("invalid font size"))
+                .collect(),
+            Err(_) => DEFAULT_FONT_SIZES.to_vec(),
+        };
+        for size in sizes {
+            generate_font(&mut code, &font, name, size);
+        }
+    }
+    if !code.is_empty() {
+        code.insert_str(0, "use crate::lcd::font::{BitmapFont, GlyphInfo};\n\n");
+    }
+    code
+}
+
+fn generate_font(code: &mut String, font: &Font, name: &str, size: u32) {
+    let scale = Scale::uniform(size as f32);
+    let v_metrics = font.v_metrics(scale);
+    let ascent = v_metrics.ascent.ceil() as i32;
+    let line_height = (v_metrics.ascent - v_metrics.descent + v_metrics.line_gap).ceil() as i32;
+
+    let mut glyphs = String::new();
+    let mut bitmaps = Vec::new();
+    let mut chars = Vec::new();
+    for c in font_chars() {
+        let glyph = font.glyph(c);
+        if glyph.id().0 == 0 {
+            continue; // not in the font
+        }
+        let glyph = glyph.scaled(scale);
+        let advance = glyph.h_metrics().advance_width.round() as u16;
+        let glyph = glyph.positioned(point(0.0, 0.0));
+        let (x_offset, y_offset, width, height) = match glyph.pixel_bounding_box() {
+            Some(bb) => (bb.min.x, bb.min.y, bb.width(), bb.height()),
+            None => (0, 0, 0, 0),
+        };
+        let bitmap_offset = bitmaps.len();
+        bitmaps.resize(bitmap_offset + (width * height) as usize, 0u8);
+        glyph.draw(|x, y, coverage| {
+            let index = bitmap_offset + (y * width as u32 + x) as usize;
+            bitmaps[index] = (coverage * 255.0).round() as u8;
+        });
+        writeln!(
+            glyphs,
+            "        GlyphInfo {{ c: {:?}, width: {}, height: {}, x_offset: {}, y_offset: {}, \
+             advance: {}, bitmap_offset: {} }},",
+            c, width, height, x_offset, y_offset, advance, bitmap_offset
+        )
+        .unwrap();
+        chars.push(c);
+    }
+
+    let mut kerning = String::new();
+    let ascii = || chars.iter().cloned().filter(char::is_ascii);
+    for left in ascii() {
+        for right in ascii() {
+            let value = font.pair_kerning(scale, left, right).round() as i32;
+            if value != 0 {
+                let value = value.max(i32::from(i8::min_value())).min(i32::from(i8::max_value()));
+                writeln!(kerning, "        ({:?}, {:?}, {}),", left, right, value).unwrap();
+            }
+        }
+    }
+
+    let mut bitmap_data = String::new();
+    for line in bitmaps.chunks(32) {
+        bitmap_data.push_str("        ");
+        for byte in line {
+            write!(bitmap_data, "{},", byte).unwrap();
+        }
+        bitmap_data.push('\n');
+    }
+
+    let ident = format!("{}_{}", identifier(name), size);
+    writeln!(code, "/// {} at {} pixels.", name, size).unwrap();
+    writeln!(code, "pub static {}: BitmapFont = BitmapFont::new(", ident).unwrap();
+    writeln!(code, "    {},\n    {},", ascent, line_height).unwrap();
+    writeln!(code, "    &[\n{}    ],", glyphs).unwrap();
+    writeln!(code, "    &[\n{}    ],", bitmap_data).unwrap();
+    writeln!(code, "    &[\n{}    ],", kerning).unwrap();
+    writeln!(code, ");").unwrap();
+}
+
+const IMAGE_EXTENSIONS: &[&str] = &["bmp", "png", "qoi"];
+
+fn generate_assets(dir: &Path, out: &Path) -> String {
+    let mut code = String::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return code, // no assets
+    };
+    let mut paths: Vec<_> = entries
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+            IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str())
+        })
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        return code;
+    }
+
+    let raw_dir = out.join("assets");
+    fs::create_dir_all(&raw_dir).unwrap();
+    code.push_str("use crate::lcd::image::RawImage;\nuse crate::lcd::PixelFormat;\n\n");
+    code.push_str("#[repr(align(4))]\nstruct Aligned<T>(T);\n");
+
+    for path in paths {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let format_path = path.with_extension("format");
+        println!("cargo:rerun-if-changed={}", format_path.display());
+
+        let name = path.file_stem().unwrap().to_str().unwrap();
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        let format = match fs::read_to_string(&format_path) {
+            Ok(format) => match format.trim().to_ascii_lowercase().as_str() {
+                "al88" => codec::RawFormat::Al88,
+                "argb8888" => codec::RawFormat::Argb8888,
+                other => panic!("unsupported format {} in {}", other, format_path.display()),
+            },
+            Err(_) => codec::RawFormat::Argb8888,
+        };
+
+        let data = fs::read(&path).unwrap();
+        let (info, pixels) = codec::convert(&data, format)
+            .unwrap_or_else(|err| panic!("invalid image {}: {:?}", path.display(), err));
+        fs::write(raw_dir.join(format!("{}.raw", name)), &pixels).unwrap();
+
+        let ident = identifier(name);
+        writeln!(code, "\n/// `{}`, {}x{} pixels.", file_name, info.width, info.height).unwrap();
+        writeln!(
+            code,
+            "pub static {0}: RawImage =\n    \
+             RawImage::new({1}, {2}, PixelFormat::{3:?}, &{0}_DATA.0);",
+            ident, info.width, info.height, format
+        )
+        .unwrap();
+        writeln!(
+            code,
+            "static {}_DATA: Aligned<[u8; {}]> =\n    \
+             Aligned(*include_bytes!(concat!(env!(\"OUT_DIR\"), \"/assets/{}.raw\")));",
+            ident,
+            pixels.len(),
+            name
+        )
+        .unwrap();
+    }
+    code
+}
+
+// Converts a file name to an upper case identifier, e.g. `DejaVu-Sans` to `DEJAVU_SANS`.
+fn identifier(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    ident
 }
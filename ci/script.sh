@@ -5,6 +5,8 @@ main() {
     cargo build --release
     cargo build --examples
     cargo build --examples --release
+    cargo build --features embedded-graphics
+    cargo test --lib --target "$(rustc -vV | sed -n 's/^host: //p')"
 }
 
 main
@@ -36,6 +36,7 @@ fn main() -> ! {
     let mut flash = peripherals.FLASH;
     let mut fmc = peripherals.FMC;
     let mut ltdc = peripherals.LTDC;
+    let mut dma2d = peripherals.DMA2D;
 
     init::init_system_clock_216mhz(&mut rcc, &mut pwr, &mut flash);
     init::enable_gpio_ports(&mut rcc);
@@ -63,15 +64,17 @@ fn main() -> ! {
     let mut layer_1 = lcd.layer_1().unwrap();
     let mut layer_2 = lcd.layer_2().unwrap();
 
-    layer_1.clear();
-    layer_2.clear();
-    lcd::init_stdout(layer_2);
+    let mut dma2d = lcd::Dma2d::new(&mut dma2d);
+    layer_1.clear(&mut dma2d);
+    layer_2.clear(&mut dma2d);
 
-    println!("Try pressing the blue button one the left side!");
-
-    // Initialize the allocator BEFORE you use it
+    // Initialize the allocator BEFORE you use it, the stdout terminal needs it
     unsafe { ALLOCATOR.init(cortex_m_rt::heap_start() as usize, HEAP_SIZE) }
 
+    lcd::init_stdout(&mut lcd, layer_2);
+
+    println!("Try pressing the blue button one the left side!");
+
     nvic.enable(Interrupt::EXTI0);
 
     let mut previous_button_state = pins.button.get();
@@ -8,6 +8,7 @@ use interrupture::handle_isr;
 use core::convert::TryFrom;
 use cortex_m_rt::exception;
 
+pub use interrupture::{Error, InterruptHandle, InterruptTable};
 pub use stm32f7::stm32f7x6::Interrupt as InterruptRequest;
 use stm32f7::stm32f7x6::{NVIC, NVIC_STIR};
 use interrupture::Nr;
@@ -0,0 +1,213 @@
+//! DMA transfers between the SAI2 FIFOs and memory.
+//!
+//! Block B of SAI2 is connected to channel 0 of the DMA2 stream 7, which is used for receiving.
+//! Block A is connected to channel 3 of the DMA2 stream 4, which is used for transmitting. The
+//! streams run in circular mode over a buffer with two halves and set the half transfer or the
+//! transfer complete flag whenever they finished a half.
+
+use super::Error;
+use stm32f7::stm32f7x6::{DMA2, SAI2};
+
+const SAI2_A_CHANNEL: u8 = 3;
+const SAI2_B_CHANNEL: u8 = 0;
+
+/// The direction of a DMA transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From the FIFO of block B to memory (stream 7).
+    Rx,
+    /// From memory to the FIFO of block A (stream 4).
+    Tx,
+}
+
+/// A half of the ping-pong buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Half {
+    /// The half at the start of the buffer.
+    First,
+    /// The half at the end of the buffer.
+    Second,
+}
+
+/// Configures and enables the stream for `direction` to transfer `buffer` in a loop.
+///
+/// The buffer must stay valid until the stream is stopped with `stop()`. If `interrupts` is
+/// set, the stream triggers its interrupt for every finished half.
+pub fn start(dma: &DMA2, sai: &SAI2, direction: Direction, buffer: &mut [i16], interrupts: bool) {
+    let buffer_address = buffer.as_mut_ptr() as u32;
+    let samples = buffer.len() as u16;
+
+    stop(dma, direction);
+    clear_flags(dma, direction);
+
+    match direction {
+        Direction::Rx => {
+            let data_address = &sai.bdr as *const _ as u32;
+            dma.s7par.write(|w| unsafe { w.pa().bits(data_address) });
+            dma.s7m0ar
+                .write(|w| unsafe { w.m0a().bits(buffer_address) });
+            dma.s7ndtr.write(|w| unsafe { w.ndt().bits(samples) });
+            dma.s7fcr.write(|w| unsafe {
+                w.dmdis().set_bit(); // use the FIFO instead of direct mode
+                w.fth().bits(0b11); // full FIFO threshold
+                w
+            });
+            dma.s7cr.write(|w| unsafe {
+                w.chsel().bits(SAI2_B_CHANNEL);
+                w.mburst().bits(0b00); // single transfers
+                w.pburst().bits(0b00); // single transfers
+                w.pl().bits(0b10); // high priority
+                w.msize().bits(0b01); // 16 bit
+                w.psize().bits(0b01); // 16 bit
+                w.minc().set_bit();
+                w.pinc().clear_bit();
+                w.circ().set_bit();
+                w.dir().bits(0b00); // peripheral to memory
+                w.pfctrl().clear_bit(); // the DMA controls the flow
+                w.htie().bit(interrupts);
+                w.tcie().bit(interrupts);
+                w.teie().bit(interrupts);
+                w
+            });
+            dma.s7cr.modify(|_, w| w.en().set_bit());
+        }
+        Direction::Tx => {
+            let data_address = &sai.adr as *const _ as u32;
+            dma.s4par.write(|w| unsafe { w.pa().bits(data_address) });
+            dma.s4m0ar
+                .write(|w| unsafe { w.m0a().bits(buffer_address) });
+            dma.s4ndtr.write(|w| unsafe { w.ndt().bits(samples) });
+            dma.s4fcr.write(|w| unsafe {
+                w.dmdis().set_bit(); // use the FIFO instead of direct mode
+                w.fth().bits(0b11); // full FIFO threshold
+                w
+            });
+            dma.s4cr.write(|w| unsafe {
+                w.chsel().bits(SAI2_A_CHANNEL);
+                w.mburst().bits(0b00); // single transfers
+                w.pburst().bits(0b00); // single transfers
+                w.pl().bits(0b10); // high priority
+                w.msize().bits(0b01); // 16 bit
+                w.psize().bits(0b01); // 16 bit
+                w.minc().set_bit();
+                w.pinc().clear_bit();
+                w.circ().set_bit();
+                w.dir().bits(0b01); // memory to peripheral
+                w.pfctrl().clear_bit(); // the DMA controls the flow
+                w.htie().bit(interrupts);
+                w.tcie().bit(interrupts);
+                w.teie().bit(interrupts);
+                w
+            });
+            dma.s4cr.modify(|_, w| w.en().set_bit());
+        }
+    }
+}
+
+/// Disables the stream for `direction` and its interrupts and waits until the disable takes
+/// effect.
+pub fn stop(dma: &DMA2, direction: Direction) {
+    match direction {
+        Direction::Rx => {
+            dma.s7cr.modify(|_, w| {
+                w.en().clear_bit();
+                w.htie().clear_bit();
+                w.tcie().clear_bit();
+                w.teie().clear_bit();
+                w
+            });
+            while dma.s7cr.read().en().bit_is_set() {}
+        }
+        Direction::Tx => {
+            dma.s4cr.modify(|_, w| {
+                w.en().clear_bit();
+                w.htie().clear_bit();
+                w.tcie().clear_bit();
+                w.teie().clear_bit();
+                w
+            });
+            while dma.s4cr.read().en().bit_is_set() {}
+        }
+    }
+}
+
+/// Clears all interrupt flags of the stream for `direction`.
+pub fn clear_flags(dma: &DMA2, direction: Direction) {
+    match direction {
+        Direction::Rx => dma.hifcr.write(|w| {
+            w.ctcif7().set_bit();
+            w.chtif7().set_bit();
+            w.cteif7().set_bit();
+            w.cdmeif7().set_bit();
+            w.cfeif7().set_bit();
+            w
+        }),
+        Direction::Tx => dma.hifcr.write(|w| {
+            w.ctcif4().set_bit();
+            w.chtif4().set_bit();
+            w.cteif4().set_bit();
+            w.cdmeif4().set_bit();
+            w.cfeif4().set_bit();
+            w
+        }),
+    }
+}
+
+/// Returns the half that the stream for `direction` finished since the last call and clears
+/// its flag.
+///
+/// If both halves were finished, one of them was missed and `Error::Overrun` (receiving) or
+/// `Error::Underrun` (transmitting) is returned.
+pub fn take_finished(dma: &DMA2, direction: Direction) -> Option<Result<Half, Error>> {
+    let hisr = dma.hisr.read();
+    let (transfer_error, half, complete) = match direction {
+        Direction::Rx => (
+            hisr.teif7().bit_is_set(),
+            hisr.htif7().bit_is_set(),
+            hisr.tcif7().bit_is_set(),
+        ),
+        Direction::Tx => (
+            hisr.teif4().bit_is_set(),
+            hisr.htif4().bit_is_set(),
+            hisr.tcif4().bit_is_set(),
+        ),
+    };
+    // only the flags that were read are cleared
+    let clear = |transfer_error: bool, half: bool, complete: bool| match direction {
+        Direction::Rx => dma.hifcr.write(|w| {
+            w.cteif7().bit(transfer_error);
+            w.chtif7().bit(half);
+            w.ctcif7().bit(complete);
+            w
+        }),
+        Direction::Tx => dma.hifcr.write(|w| {
+            w.cteif4().bit(transfer_error);
+            w.chtif4().bit(half);
+            w.ctcif4().bit(complete);
+            w
+        }),
+    };
+
+    if transfer_error {
+        clear(true, false, false);
+        return Some(Err(Error::Transfer));
+    }
+    match (half, complete) {
+        (true, true) => {
+            clear(false, true, true);
+            Some(Err(match direction {
+                Direction::Rx => Error::Overrun,
+                Direction::Tx => Error::Underrun,
+            }))
+        }
+        (true, false) => {
+            clear(false, true, false);
+            Some(Ok(Half::First))
+        }
+        (false, true) => {
+            clear(false, false, true);
+            Some(Ok(Half::Second))
+        }
+        (false, false) => None,
+    }
+}
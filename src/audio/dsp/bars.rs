@@ -0,0 +1,262 @@
+//! Drawing a spectrum as vertical bars on a layer.
+
+use super::meter::dbfs;
+use crate::lcd::graphics::{self, Point};
+use crate::lcd::{Color, Framebuffer, HEIGHT, WIDTH};
+use alloc::vec::Vec;
+
+/// The configuration of a [`SpectrumBars`](SpectrumBars) renderer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BarsConfig {
+    /// The x coordinate of the left edge of the drawing area, which must be on the display.
+    pub x: usize,
+    /// The y coordinate of the top edge of the drawing area.
+    pub y: usize,
+    /// The width of the drawing area in pixels.
+    pub width: usize,
+    /// The height of the drawing area in pixels, which is the height of a full bar.
+    pub height: usize,
+    /// The number of bars. The bins of the spectrum are grouped linearly into the bars.
+    pub bars: usize,
+    /// The color of the bars.
+    pub color: Color,
+    /// The color of the area above the bars.
+    pub background: Color,
+    /// The level in dBFS that is drawn as an empty bar.
+    pub min_db: f32,
+    /// The level in dBFS that is drawn as a full bar.
+    pub max_db: f32,
+}
+
+impl Default for BarsConfig {
+    fn default() -> BarsConfig {
+        BarsConfig {
+            x: 0,
+            y: 0,
+            width: crate::lcd::WIDTH,
+            height: crate::lcd::HEIGHT,
+            bars: 32,
+            color: Color::rgb(0, 0xff, 0),
+            background: Color::rgba(0, 0, 0, 0),
+            min_db: -80.0,
+            max_db: 0.0,
+        }
+    }
+}
+
+/// Draws the magnitudes of a [`SpectrumAnalyzer`](super::SpectrumAnalyzer) as bars.
+///
+/// The renderer remembers the bar heights of the last frame and only redraws the pixels that
+/// changed, so it is fast enough to be called for every audio block. It is the frequency domain
+/// counterpart of the [`AudioWriter`](crate::lcd::AudioWriter).
+///
+/// # Examples
+/// ```rust
+/// let mut analyzer = dsp::SpectrumAnalyzer::new(256, dsp::Window::Hann);
+/// let mut magnitudes = [0.0; 128];
+/// let mut bars = dsp::SpectrumBars::new(dsp::BarsConfig::default());
+/// bars.clear(&mut layer_2);
+/// input.record(|samples| {
+///     analyzer.analyze(samples, &mut magnitudes);
+///     bars.draw(&mut layer_2, &magnitudes);
+///     true
+/// })?;
+/// ```
+pub struct SpectrumBars {
+    config: BarsConfig,
+    heights: Vec<usize>,
+}
+
+impl SpectrumBars {
+    /// Creates a renderer. The drawing area is assumed to be filled with the background color,
+    /// otherwise call [`clear`](SpectrumBars::clear) first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the drawing area is not on the display, if there are no bars or if the bars are
+    /// wider than the drawing area.
+    pub fn new(config: BarsConfig) -> SpectrumBars {
+        let right = config.x.checked_add(config.width);
+        let bottom = config.y.checked_add(config.height);
+        assert!(
+            right.map_or(false, |right| right <= WIDTH)
+                && bottom.map_or(false, |bottom| bottom <= HEIGHT),
+            "the drawing area must be on the display"
+        );
+        assert!(config.bars > 0, "there must be at least one bar");
+        assert!(
+            config.bars <= config.width,
+            "the bars must be at least one pixel wide"
+        );
+        SpectrumBars {
+            config,
+            heights: vec![0; config.bars],
+        }
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> &BarsConfig {
+        &self.config
+    }
+
+    /// Fills the drawing area with the background color.
+    pub fn clear<F: Framebuffer>(&mut self, fb: &mut F) {
+        let c = self.config;
+        fill(fb, c.x, c.y, c.width, c.height, c.background);
+        for height in self.heights.iter_mut() {
+            *height = 0;
+        }
+    }
+
+    /// Draws the magnitudes, which are relative to full scale.
+    ///
+    /// The bins are distributed evenly over the bars and each bar shows the loudest of its bins.
+    /// If there are fewer bins than bars, a bin is shown by several neighbouring bars.
+    pub fn draw<F: Framebuffer>(&mut self, fb: &mut F, magnitudes: &[f32]) {
+        let c = self.config;
+        let bar_width = c.width / c.bars;
+        // leave a gap between bars that are wide enough
+        let fill_width = if bar_width > 2 {
+            bar_width - 1
+        } else {
+            bar_width
+        };
+        let bottom = c.y + c.height;
+
+        for i in 0..c.bars {
+            let start = i * magnitudes.len() / c.bars;
+            let end = (i + 1) * magnitudes.len() / c.bars;
+            let magnitude = magnitudes[start..end.max(start + 1).min(magnitudes.len())]
+                .iter()
+                .fold(
+                    0.0,
+                    |max, &magnitude| if magnitude > max { magnitude } else { max },
+                );
+            let height = self.bar_height(magnitude);
+
+            let old_height = self.heights[i];
+            let (top, rows, color) = if height > old_height {
+                (bottom - height, height - old_height, c.color)
+            } else {
+                (bottom - old_height, old_height - height, c.background)
+            };
+            fill(fb, c.x + i * bar_width, top, fill_width, rows, color);
+            self.heights[i] = height;
+        }
+    }
+
+    // Maps a magnitude to a bar height in pixels.
+    fn bar_height(&self, magnitude: f32) -> usize {
+        let c = &self.config;
+        let fraction = (dbfs(magnitude) - c.min_db) / (c.max_db - c.min_db);
+        if fraction.is_nan() || fraction <= 0.0 {
+            0
+        } else if fraction >= 1.0 {
+            c.height
+        } else {
+            (fraction * c.height as f32) as usize
+        }
+    }
+}
+
+// Fills a rectangle of the drawing area, which `SpectrumBars::new` checked to be on the display.
+fn fill<F: Framebuffer>(fb: &mut F, x: usize, y: usize, width: usize, height: usize, color: Color) {
+    let top_left = Point::new(x as i32, y as i32);
+    graphics::fill_rect(fb, top_left, width as u32, height as u32, color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Pixels(Vec<Option<Color>>);
+
+    impl Framebuffer for Pixels {
+        fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+            self.0[y * WIDTH + x] = Some(color);
+        }
+    }
+
+    impl Pixels {
+        fn get(&self, x: usize, y: usize) -> Option<Color> {
+            self.0[y * WIDTH + x]
+        }
+    }
+
+    const GREEN: Color = Color::rgb(0, 0xff, 0);
+    const BLACK: Color = Color::rgb(0, 0, 0);
+
+    fn config() -> BarsConfig {
+        BarsConfig {
+            x: 10,
+            y: 20,
+            width: 40,
+            height: 100,
+            bars: 4,
+            color: GREEN,
+            background: BLACK,
+            ..BarsConfig::default()
+        }
+    }
+
+    #[test]
+    fn draw_and_clear() {
+        let mut pixels = Pixels(vec![None; WIDTH * HEIGHT]);
+        let mut bars = SpectrumBars::new(config());
+
+        // 0 dBFS, -40 dBFS, silence and a level below the minimum
+        bars.draw(&mut pixels, &[1.0, 0.01, 0.0, 1e-5]);
+        assert_eq!(pixels.get(10, 20), Some(GREEN));
+        assert_eq!(pixels.get(18, 119), Some(GREEN));
+        // the gap between the bars and the area above the bars are not drawn
+        assert_eq!(pixels.get(19, 119), None);
+        assert_eq!(pixels.get(20, 60), None);
+        assert_eq!(pixels.get(20, 80), Some(GREEN));
+        assert_eq!(pixels.get(30, 119), None);
+        assert_eq!(pixels.get(10, 120), None);
+
+        bars.draw(&mut pixels, &[0.01, 1.0, 0.0, 0.0]);
+        assert_eq!(pixels.get(10, 20), Some(BLACK));
+        assert_eq!(pixels.get(10, 80), Some(GREEN));
+        assert_eq!(pixels.get(20, 20), Some(GREEN));
+
+        bars.clear(&mut pixels);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let inside = x >= 10 && x < 50 && y >= 20 && y < 120;
+                let expected = if inside { Some(BLACK) } else { None };
+                assert_eq!(pixels.get(x, y), expected, "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn whole_display() {
+        let mut pixels = Pixels(vec![None; WIDTH * HEIGHT]);
+        let mut bars = SpectrumBars::new(BarsConfig::default());
+        bars.draw(&mut pixels, &[1.0; 64]);
+        assert_eq!(pixels.get(0, 0), Some(GREEN));
+        assert_eq!(pixels.get(WIDTH - 1, HEIGHT - 1), None);
+        assert_eq!(pixels.get(WIDTH - 2, HEIGHT - 1), Some(GREEN));
+    }
+
+    #[test]
+    #[should_panic(expected = "the drawing area must be on the display")]
+    fn area_outside_of_display() {
+        SpectrumBars::new(BarsConfig {
+            y: 1,
+            ..BarsConfig::default()
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "the drawing area must be on the display")]
+    fn area_overflows() {
+        SpectrumBars::new(BarsConfig {
+            x: usize::max_value(),
+            width: 2,
+            bars: 1,
+            ..BarsConfig::default()
+        });
+    }
+}
@@ -0,0 +1,241 @@
+//! Second order IIR filters.
+//!
+//! The coefficients follow the formulas of the Audio EQ Cookbook by Robert Bristow-Johnson.
+
+use super::math;
+use core::f32::consts::PI;
+
+/// The quality factor of a Butterworth filter, which has no resonance peak.
+pub const BUTTERWORTH_Q: f32 = 0.707_106_77;
+
+/// The normalized coefficients of a biquad filter, with `a0` equal to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coefficients {
+    /// The feed-forward coefficient of the current input.
+    pub b0: f32,
+    /// The feed-forward coefficient of the previous input.
+    pub b1: f32,
+    /// The feed-forward coefficient of the input before the previous one.
+    pub b2: f32,
+    /// The feedback coefficient of the previous output.
+    pub a1: f32,
+    /// The feedback coefficient of the output before the previous one.
+    pub a2: f32,
+}
+
+impl Coefficients {
+    /// Returns a low-pass filter that attenuates frequencies above `cutoff` Hz.
+    pub fn low_pass(sample_rate: f32, cutoff: f32, q: f32) -> Coefficients {
+        let (cos, alpha) = intermediates(sample_rate, cutoff, q);
+        Coefficients::normalize(
+            (1.0 - cos) / 2.0,
+            1.0 - cos,
+            (1.0 - cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    /// Returns a high-pass filter that attenuates frequencies below `cutoff` Hz.
+    pub fn high_pass(sample_rate: f32, cutoff: f32, q: f32) -> Coefficients {
+        let (cos, alpha) = intermediates(sample_rate, cutoff, q);
+        Coefficients::normalize(
+            (1.0 + cos) / 2.0,
+            -(1.0 + cos),
+            (1.0 + cos) / 2.0,
+            1.0 + alpha,
+            -2.0 * cos,
+            1.0 - alpha,
+        )
+    }
+
+    /// Returns a band-pass filter around `center` Hz with a gain of 0 dB at the center.
+    ///
+    /// The bandwidth is `center / q`.
+    pub fn band_pass(sample_rate: f32, center: f32, q: f32) -> Coefficients {
+        let (cos, alpha) = intermediates(sample_rate, center, q);
+        Coefficients::normalize(alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+    }
+
+    fn normalize(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Coefficients {
+        Coefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+}
+
+// Returns cos(w0) and alpha of the cookbook formulas.
+fn intermediates(sample_rate: f32, frequency: f32, q: f32) -> (f32, f32) {
+    let w0 = 2.0 * PI * frequency / sample_rate;
+    (math::cos(w0), math::sin(w0) / (2.0 * q))
+}
+
+/// A biquad filter in transposed direct form II.
+///
+/// A filter has state, so every channel needs its own filter.
+///
+/// # Examples
+/// ```rust
+/// let coefficients = dsp::Coefficients::high_pass(16000.0, 100.0, dsp::BUTTERWORTH_Q);
+/// let mut left = dsp::Biquad::new(coefficients);
+/// let mut right = dsp::Biquad::new(coefficients);
+/// for frame in block.chunks_mut(2) {
+///     frame[0] = left.process_sample(frame[0]);
+///     frame[1] = right.process_sample(frame[1]);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Biquad {
+    coefficients: Coefficients,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    /// Creates a filter with the coefficients and an empty state.
+    pub fn new(coefficients: Coefficients) -> Biquad {
+        Biquad {
+            coefficients,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Returns the coefficients.
+    pub fn coefficients(&self) -> &Coefficients {
+        &self.coefficients
+    }
+
+    /// Replaces the coefficients and keeps the state, so the filter can be tuned while it runs.
+    pub fn set_coefficients(&mut self, coefficients: Coefficients) {
+        self.coefficients = coefficients;
+    }
+
+    /// Clears the state, as if the filter only received zeros so far.
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+
+    /// Filters the next input value and returns the output value.
+    pub fn process(&mut self, input: f32) -> f32 {
+        let c = &self.coefficients;
+        let output = c.b0 * input + self.z1;
+        self.z1 = c.b1 * input - c.a1 * output + self.z2;
+        self.z2 = c.b2 * input - c.a2 * output;
+        output
+    }
+
+    /// Filters the next sample, saturating at the limits of 16 bit.
+    pub fn process_sample(&mut self, sample: i16) -> i16 {
+        let output = self.process(f32::from(sample));
+        if output >= f32::from(i16::max_value()) {
+            i16::max_value()
+        } else if output <= f32::from(i16::min_value()) {
+            i16::min_value()
+        } else {
+            output as i16
+        }
+    }
+
+    /// Filters the samples of a single channel in place.
+    pub fn process_samples(&mut self, samples: &mut [i16]) {
+        for sample in samples.iter_mut() {
+            *sample = self.process_sample(*sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_coefficients(actual: Coefficients, expected: [f32; 5]) {
+        let actual = [actual.b0, actual.b1, actual.b2, actual.a1, actual.a2];
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    // Returns the amplitude of the filtered sine after the filter settled.
+    fn gain(coefficients: Coefficients, frequency: f32) -> f32 {
+        let mut filter = Biquad::new(coefficients);
+        let mut amplitude = 0.0f32;
+        for i in 0..16000 {
+            let input = math::sin(2.0 * PI * frequency * i as f32 / 16000.0);
+            let output = filter.process(input);
+            if i > 12000 {
+                amplitude = amplitude.max(output.abs());
+            }
+        }
+        amplitude
+    }
+
+    #[test]
+    fn cookbook_values() {
+        // computed with the cookbook formulas in double precision
+        let low_pass = Coefficients::low_pass(48000.0, 1000.0, BUTTERWORTH_Q);
+        assert_coefficients(
+            low_pass,
+            [0.003_916_127, 0.007_832_253, 0.003_916_127, -1.815_341_1, 0.831_005_6],
+        );
+        let high_pass = Coefficients::high_pass(48000.0, 1000.0, BUTTERWORTH_Q);
+        assert_coefficients(
+            high_pass,
+            [0.911_586_7, -1.823_173_3, 0.911_586_7, -1.815_341_1, 0.831_005_6],
+        );
+        let band_pass = Coefficients::band_pass(16000.0, 2000.0, 2.0);
+        assert_coefficients(
+            band_pass,
+            [0.150_221_1, 0.0, -0.150_221_1, -1.201_768_8, 0.699_557_8],
+        );
+    }
+
+    #[test]
+    fn frequency_response() {
+        let low_pass = Coefficients::low_pass(16000.0, 1000.0, BUTTERWORTH_Q);
+        assert!((gain(low_pass, 50.0) - 1.0).abs() < 0.01);
+        // -3 dB at the cutoff frequency
+        assert!((gain(low_pass, 1000.0) - 0.7071).abs() < 0.01);
+        assert!(gain(low_pass, 6000.0) < 0.05);
+
+        let high_pass = Coefficients::high_pass(16000.0, 1000.0, BUTTERWORTH_Q);
+        assert!(gain(high_pass, 50.0) < 0.01);
+        assert!((gain(high_pass, 1000.0) - 0.7071).abs() < 0.01);
+        assert!((gain(high_pass, 6000.0) - 1.0).abs() < 0.01);
+
+        let band_pass = Coefficients::band_pass(16000.0, 1000.0, 2.0);
+        assert!((gain(band_pass, 1000.0) - 1.0).abs() < 0.01);
+        assert!(gain(band_pass, 100.0) < 0.1);
+        assert!(gain(band_pass, 6000.0) < 0.1);
+    }
+
+    #[test]
+    fn samples_saturate() {
+        let amplify = Coefficients {
+            b0: 4.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+        };
+        let mut filter = Biquad::new(amplify);
+        let mut samples = [20000, -20000, 100];
+        filter.process_samples(&mut samples);
+        assert_eq!(samples, [i16::max_value(), i16::min_value(), 400]);
+    }
+
+    #[test]
+    fn reset() {
+        let mut filter = Biquad::new(Coefficients::low_pass(16000.0, 1000.0, BUTTERWORTH_Q));
+        let first = filter.process(1.0);
+        filter.process(0.5);
+        filter.reset();
+        assert_eq!(filter.process(1.0), first);
+    }
+}
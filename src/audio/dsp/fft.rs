@@ -0,0 +1,282 @@
+//! An in-place radix-2 fast Fourier transform.
+
+use super::math;
+use alloc::vec::Vec;
+use core::f32::consts::PI;
+use core::ops::{Add, Mul, Sub};
+
+/// A complex number.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Complex {
+    /// The real part.
+    pub re: f32,
+    /// The imaginary part.
+    pub im: f32,
+}
+
+impl Complex {
+    /// Creates a complex number from its real and imaginary part.
+    pub const fn new(re: f32, im: f32) -> Complex {
+        Complex { re, im }
+    }
+
+    /// Returns the squared absolute value.
+    pub fn norm_sqr(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// Returns the absolute value.
+    pub fn abs(self) -> f32 {
+        math::sqrt(self.norm_sqr())
+    }
+
+    /// Returns the complex conjugate.
+    pub fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+/// A radix-2 FFT of a fixed length with precomputed twiddle factors.
+///
+/// # Examples
+/// ```rust
+/// let fft = dsp::Fft::new(8);
+/// let mut data = [dsp::Complex::new(1.0, 0.0); 8];
+/// fft.forward(&mut data);
+/// // a constant signal only has a DC component
+/// assert!((data[0] - dsp::Complex::new(8.0, 0.0)).abs() < 1e-6);
+/// assert!(data[1..].iter().all(|bin| bin.abs() < 1e-6));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Fft {
+    // e^(-2 pi i k / len) for k in 0..len / 2
+    twiddles: Vec<Complex>,
+    len: usize,
+}
+
+impl Fft {
+    /// Creates an FFT for `len` points.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is not a power of two.
+    pub fn new(len: usize) -> Fft {
+        assert!(
+            len.is_power_of_two(),
+            "the FFT length must be a power of two"
+        );
+        let twiddles = (0..len / 2)
+            .map(|k| {
+                let angle = -2.0 * PI * k as f32 / len as f32;
+                Complex::new(math::cos(angle), math::sin(angle))
+            })
+            .collect();
+        Fft { twiddles, len }
+    }
+
+    /// Returns the number of points.
+    pub fn size(&self) -> usize {
+        self.len
+    }
+
+    /// Replaces `data` with its discrete Fourier transform.
+    ///
+    /// The result is not normalized, so bin `k` is the sum of `data[n] * e^(-2 pi i k n / len)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` doesn't have the length of the FFT.
+    pub fn forward(&self, data: &mut [Complex]) {
+        assert_eq!(data.len(), self.len, "wrong FFT input length");
+        bit_reverse(data);
+
+        let mut size = 2;
+        while size <= self.len {
+            let half = size / 2;
+            let twiddle_step = self.len / size;
+            for start in (0..self.len).step_by(size) {
+                for k in 0..half {
+                    let twiddle = self.twiddles[k * twiddle_step];
+                    let even = data[start + k];
+                    let odd = data[start + k + half] * twiddle;
+                    data[start + k] = even + odd;
+                    data[start + k + half] = even - odd;
+                }
+            }
+            size *= 2;
+        }
+    }
+
+    /// Replaces `data` with its inverse discrete Fourier transform, which is normalized so that
+    /// `inverse` undoes `forward`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` doesn't have the length of the FFT.
+    pub fn inverse(&self, data: &mut [Complex]) {
+        // ifft(x) = conj(fft(conj(x))) / len
+        for value in data.iter_mut() {
+            *value = value.conj();
+        }
+        self.forward(data);
+        let scale = 1.0 / self.len as f32;
+        for value in data.iter_mut() {
+            *value = Complex::new(value.re * scale, -value.im * scale);
+        }
+    }
+}
+
+// Reorders `data` so that the element at index i is moved to the bit-reversed index of i.
+fn bit_reverse(data: &mut [Complex]) {
+    let mut j = 0;
+    for i in 1..data.len() {
+        let mut bit = data.len() >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEN: usize = 64;
+
+    fn assert_close(actual: &[Complex], expected: &[Complex], tolerance: f32) {
+        assert_eq!(actual.len(), expected.len());
+        for (i, (&a, &e)) in actual.iter().zip(expected).enumerate() {
+            assert!((a - e).abs() < tolerance, "{}: {:?} != {:?}", i, a, e);
+        }
+    }
+
+    // Computes the DFT directly from its definition.
+    fn dft(input: &[Complex]) -> Vec<Complex> {
+        let len = input.len();
+        (0..len)
+            .map(|k| {
+                let (mut re, mut im) = (0.0f64, 0.0f64);
+                for (n, x) in input.iter().enumerate() {
+                    let angle = -2.0 * core::f64::consts::PI * (k * n % len) as f64 / len as f64;
+                    let (sin, cos) = (angle.sin(), angle.cos());
+                    re += f64::from(x.re) * cos - f64::from(x.im) * sin;
+                    im += f64::from(x.re) * sin + f64::from(x.im) * cos;
+                }
+                Complex::new(re as f32, im as f32)
+            })
+            .collect()
+    }
+
+    fn signal() -> Vec<Complex> {
+        (0..LEN)
+            .map(|i| Complex::new(math::sin(i as f32 * 0.37), math::cos(i as f32 * 0.11)))
+            .collect()
+    }
+
+    #[test]
+    fn impulse() {
+        let fft = Fft::new(LEN);
+        // an impulse at 0 has a flat spectrum
+        let mut data = vec![Complex::default(); LEN];
+        data[0] = Complex::new(1.0, 0.0);
+        fft.forward(&mut data);
+        assert_close(&data, &[Complex::new(1.0, 0.0); LEN], 1e-6);
+
+        // a delayed impulse rotates the phase of bin k by -2 pi k d / len
+        let mut data = vec![Complex::default(); LEN];
+        data[3] = Complex::new(1.0, 0.0);
+        fft.forward(&mut data);
+        let expected: Vec<_> = (0..LEN)
+            .map(|k| {
+                let angle = -2.0 * PI * (3 * k) as f32 / LEN as f32;
+                Complex::new(math::cos(angle), math::sin(angle))
+            })
+            .collect();
+        assert_close(&data, &expected, 1e-5);
+    }
+
+    #[test]
+    fn dc() {
+        let fft = Fft::new(LEN);
+        let mut data = vec![Complex::new(0.5, 0.0); LEN];
+        fft.forward(&mut data);
+        let mut expected = vec![Complex::default(); LEN];
+        expected[0] = Complex::new(0.5 * LEN as f32, 0.0);
+        assert_close(&data, &expected, 1e-4);
+    }
+
+    #[test]
+    fn bin_centred_sine() {
+        let fft = Fft::new(LEN);
+        let bin = 5;
+        let mut data: Vec<_> = (0..LEN)
+            .map(|n| Complex::new(math::sin(2.0 * PI * (bin * n) as f32 / LEN as f32), 0.0))
+            .collect();
+        fft.forward(&mut data);
+        // sin(x) = (e^(ix) - e^(-ix)) / 2i, so the energy is split between the positive and the
+        // negative frequency
+        let mut expected = vec![Complex::default(); LEN];
+        expected[bin] = Complex::new(0.0, -(LEN as f32) / 2.0);
+        expected[LEN - bin] = Complex::new(0.0, LEN as f32 / 2.0);
+        assert_close(&data, &expected, 1e-4);
+    }
+
+    #[test]
+    fn matches_dft() {
+        for &len in [1, 2, 4, 16, LEN].iter() {
+            let input = &signal()[..len];
+            let mut data = input.to_vec();
+            Fft::new(len).forward(&mut data);
+            assert_close(&data, &dft(input), 1e-4);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let fft = Fft::new(LEN);
+        let input = signal();
+        let mut data = input.clone();
+        fft.forward(&mut data);
+        fft.inverse(&mut data);
+        assert_close(&data, &input, 1e-6);
+        assert_eq!(fft.size(), LEN);
+    }
+
+    #[test]
+    #[should_panic]
+    fn not_a_power_of_two() {
+        Fft::new(48);
+    }
+}
@@ -0,0 +1,162 @@
+//! Floating point functions that are not available in `core`.
+//!
+//! The approximations are accurate to a few ULP in the ranges that occur in audio processing,
+//! which is enough for filter design, windows and level meters.
+
+use core::f32::consts::{FRAC_PI_2, LN_2, LOG10_E, PI, SQRT_2};
+
+/// Returns the square root of `x`, or zero if `x` is not positive.
+pub fn sqrt(x: f32) -> f32 {
+    if x.is_nan() || x <= 0.0 {
+        return 0.0;
+    }
+    if x.is_infinite() {
+        return x;
+    }
+    // halving the exponent gives a first guess, which is refined by newton iterations
+    let mut y = f32::from_bits((x.to_bits() >> 1) + 0x1FBD_1DF5);
+    for _ in 0..3 {
+        y = 0.5 * (y + x / y);
+    }
+    y
+}
+
+/// Returns the sine of `x` in radians.
+pub fn sin(x: f32) -> f32 {
+    // reduce to -pi..=pi
+    let turns = x / (2.0 * PI);
+    let x = x - round(turns) * (2.0 * PI);
+    // reduce to -pi/2..=pi/2 with sin(pi - x) = sin(x)
+    let x = if x > FRAC_PI_2 {
+        PI - x
+    } else if x < -FRAC_PI_2 {
+        -PI - x
+    } else {
+        x
+    };
+    // taylor series, the first omitted term is below 1e-7 for |x| <= pi/2
+    let x2 = x * x;
+    x * (1.0
+        - x2 / 6.0 * (1.0 - x2 / 20.0 * (1.0 - x2 / 42.0 * (1.0 - x2 / 72.0 * (1.0 - x2 / 110.0)))))
+}
+
+/// Returns the cosine of `x` in radians.
+pub fn cos(x: f32) -> f32 {
+    sin(x + FRAC_PI_2)
+}
+
+/// Returns the natural logarithm of `x`, negative infinity for zero and NaN for negative
+/// values.
+pub fn ln(x: f32) -> f32 {
+    if x == 0.0 {
+        return core::f32::NEG_INFINITY;
+    }
+    if x.is_nan() || x < 0.0 {
+        return core::f32::NAN;
+    }
+    if x.is_infinite() {
+        return x;
+    }
+
+    // x = mantissa * 2^exponent with the mantissa in 1/sqrt(2)..sqrt(2)
+    let bits = x.to_bits();
+    let (mut exponent, mantissa_bits) = if bits < 0x0080_0000 {
+        // subnormal numbers are scaled into the normal range first
+        let scaled = (x * 8_388_608.0).to_bits();
+        (((scaled >> 23) as i32) - 127 - 23, scaled & 0x007F_FFFF)
+    } else {
+        (((bits >> 23) as i32) - 127, bits & 0x007F_FFFF)
+    };
+    let mut mantissa = f32::from_bits(mantissa_bits | 0x3F80_0000);
+    if mantissa > SQRT_2 {
+        mantissa *= 0.5;
+        exponent += 1;
+    }
+
+    // ln(m) = 2 * atanh(s) with s = (m - 1) / (m + 1), |s| <= 0.172
+    let s = (mantissa - 1.0) / (mantissa + 1.0);
+    let s2 = s * s;
+    let ln_mantissa =
+        2.0 * s * (1.0 + s2 * (1.0 / 3.0 + s2 * (1.0 / 5.0 + s2 * (1.0 / 7.0 + s2 / 9.0))));
+    exponent as f32 * LN_2 + ln_mantissa
+}
+
+/// Returns the base 10 logarithm of `x`.
+pub fn log10(x: f32) -> f32 {
+    ln(x) * LOG10_E
+}
+
+// Rounds half away from zero. Values outside of the i32 range are not supported.
+fn round(x: f32) -> f32 {
+    if x >= 0.0 {
+        (x + 0.5) as i32 as f32
+    } else {
+        (x - 0.5) as i32 as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Returns values from `start` to `end` that grow by `factor`.
+    fn geometric(start: f32, end: f32, factor: f32) -> impl Iterator<Item = f32> {
+        let mut x = start;
+        core::iter::from_fn(move || {
+            x *= factor;
+            if x < end {
+                Some(x)
+            } else {
+                None
+            }
+        })
+    }
+
+    #[test]
+    fn sin_and_cos() {
+        // within a period, the error is dominated by the rounding of the result
+        for i in -10_000..=10_000 {
+            let x = i as f32 * PI / 5000.0;
+            assert!((f64::from(sin(x)) - f64::from(x).sin()).abs() < 5e-7, "sin {}", x);
+            assert!((f64::from(cos(x)) - f64::from(x).cos()).abs() < 5e-7, "cos {}", x);
+        }
+        // larger values lose precision in the range reduction
+        for i in -10_000..=10_000 {
+            let x = i as f32 * 0.01;
+            assert!((f64::from(sin(x)) - f64::from(x).sin()).abs() < 1e-5, "sin {}", x);
+            assert!((f64::from(cos(x)) - f64::from(x).cos()).abs() < 1e-5, "cos {}", x);
+        }
+        assert_eq!(sin(0.0), 0.0);
+    }
+
+    #[test]
+    fn square_root() {
+        for x in geometric(1e-38, 1e38, 1.0137) {
+            let expected = f64::from(x).sqrt();
+            let error = (f64::from(sqrt(x)) - expected).abs() / expected;
+            assert!(error < 2e-7, "sqrt {}", x);
+        }
+        assert_eq!(sqrt(4.0), 2.0);
+        assert_eq!(sqrt(0.0), 0.0);
+        assert_eq!(sqrt(-1.0), 0.0);
+        assert_eq!(sqrt(core::f32::NAN), 0.0);
+        assert_eq!(sqrt(core::f32::INFINITY), core::f32::INFINITY);
+    }
+
+    #[test]
+    fn logarithm() {
+        for x in geometric(1e-4, 1e4, 1.001) {
+            assert!((f64::from(ln(x)) - f64::from(x).ln()).abs() < 2e-6, "ln {}", x);
+        }
+        // including subnormal numbers
+        for x in geometric(1e-40, 1e38, 1.0137) {
+            assert!((f64::from(ln(x)) - f64::from(x).ln()).abs() < 1e-5, "ln {}", x);
+        }
+        assert_eq!(ln(1.0), 0.0);
+        assert_eq!(ln(0.0), core::f32::NEG_INFINITY);
+        assert!(ln(-1.0).is_nan());
+        assert!(ln(core::f32::NAN).is_nan());
+        assert_eq!(ln(core::f32::INFINITY), core::f32::INFINITY);
+        assert!((log10(1000.0) - 3.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,188 @@
+//! Level meters.
+//!
+//! Levels are relative to the full scale of 16 bit samples, so a level of 1.0 is 0 dBFS.
+
+use super::math;
+
+// The absolute value of the smallest sample.
+const FULL_SCALE: f32 = 32768.0;
+
+/// Returns the root mean square of the samples relative to full scale, or zero if there are no
+/// samples.
+///
+/// A full scale sine has a RMS level of about 0.707, i.e. -3 dBFS.
+pub fn rms<I: IntoIterator<Item = i16>>(samples: I) -> f32 {
+    let mut sum = 0.0;
+    let mut count = 0;
+    for sample in samples {
+        let sample = f32::from(sample);
+        sum += sample * sample;
+        count += 1;
+    }
+    if count == 0 {
+        return 0.0;
+    }
+    math::sqrt(sum / count as f32) / FULL_SCALE
+}
+
+/// Returns the largest absolute value of the samples relative to full scale.
+pub fn peak<I: IntoIterator<Item = i16>>(samples: I) -> f32 {
+    let peak = samples
+        .into_iter()
+        .map(|sample| i32::from(sample).abs())
+        .max()
+        .unwrap_or(0);
+    peak as f32 / FULL_SCALE
+}
+
+/// Converts a level relative to full scale to dBFS.
+///
+/// Returns negative infinity for a level of zero.
+pub fn dbfs(level: f32) -> f32 {
+    20.0 * math::log10(level)
+}
+
+/// The levels of a block of samples.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Level {
+    /// The root mean square relative to full scale.
+    pub rms: f32,
+    /// The largest absolute value relative to full scale.
+    pub peak: f32,
+}
+
+impl Level {
+    /// Measures the levels of the samples.
+    pub fn measure<I>(samples: I) -> Level
+    where
+        I: IntoIterator<Item = i16>,
+        I::IntoIter: Clone,
+    {
+        let samples = samples.into_iter();
+        Level {
+            rms: rms(samples.clone()),
+            peak: peak(samples),
+        }
+    }
+
+    /// Returns the RMS level in dBFS.
+    pub fn rms_dbfs(&self) -> f32 {
+        dbfs(self.rms)
+    }
+
+    /// Returns the peak level in dBFS.
+    pub fn peak_dbfs(&self) -> f32 {
+        dbfs(self.peak)
+    }
+}
+
+/// A level meter with a peak hold that falls back slowly, like the meters of a mixing desk.
+///
+/// # Examples
+/// ```rust
+/// let mut meter = dsp::LevelMeter::new(0.9);
+/// // in the audio task, with stereo blocks
+/// let left = meter.update(block.iter().step_by(2).cloned());
+/// println!("{:.1} dBFS (peak {:.1} dBFS)", left.rms_dbfs(), dsp::dbfs(meter.peak_hold()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevelMeter {
+    release: f32,
+    peak_hold: f32,
+}
+
+impl LevelMeter {
+    /// Creates a meter whose peak hold is multiplied with `release` on every update, e.g. 0.9
+    /// to fall by about 0.9 dB per block.
+    pub fn new(release: f32) -> LevelMeter {
+        LevelMeter {
+            release,
+            peak_hold: 0.0,
+        }
+    }
+
+    /// Measures the levels of the next block and updates the peak hold.
+    pub fn update<I>(&mut self, samples: I) -> Level
+    where
+        I: IntoIterator<Item = i16>,
+        I::IntoIter: Clone,
+    {
+        let level = Level::measure(samples);
+        let released = self.peak_hold * self.release;
+        self.peak_hold = if level.peak > released {
+            level.peak
+        } else {
+            released
+        };
+        level
+    }
+
+    /// Returns the held peak level relative to full scale.
+    pub fn peak_hold(&self) -> f32 {
+        self.peak_hold
+    }
+
+    /// Resets the peak hold to zero.
+    pub fn reset(&mut self) {
+        self.peak_hold = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use core::f32::consts::PI;
+
+    // Returns full periods of a sine with the amplitude relative to full scale.
+    fn sine(amplitude: f32) -> Vec<i16> {
+        (0..1600)
+            .map(|i| (math::sin(2.0 * PI * i as f32 / 16.0) * amplitude * 32767.0) as i16)
+            .collect()
+    }
+
+    #[test]
+    fn rms_levels() {
+        assert_eq!(rms(Vec::new()), 0.0);
+        assert_eq!(rms(vec![0; 10]), 0.0);
+        // a square wave has the RMS level of its amplitude
+        assert_eq!(rms(vec![16384, -16384, 16384, -16384]), 0.5);
+        assert!((rms(sine(1.0)) - 0.707_1).abs() < 1e-3);
+        assert!((rms(sine(0.1)) - 0.070_71).abs() < 1e-4);
+    }
+
+    #[test]
+    fn peak_levels() {
+        assert_eq!(peak(Vec::new()), 0.0);
+        assert_eq!(peak(vec![-32768, 5]), 1.0);
+        assert_eq!(peak(vec![100, -16384, 8192]), 0.5);
+        assert!((peak(sine(1.0)) - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn decibels() {
+        assert_eq!(dbfs(0.0), core::f32::NEG_INFINITY);
+        assert!(dbfs(1.0).abs() < 1e-6);
+        assert!((dbfs(0.5) + 6.020_6).abs() < 1e-3);
+        assert!((dbfs(0.001) + 60.0).abs() < 1e-3);
+
+        let level = Level::measure(sine(1.0));
+        assert!((level.rms_dbfs() + 3.01).abs() < 0.02);
+        assert!(level.peak_dbfs().abs() < 1e-3);
+    }
+
+    #[test]
+    fn peak_hold() {
+        let mut meter = LevelMeter::new(0.5);
+        meter.update(vec![16384]);
+        assert_eq!(meter.peak_hold(), 0.5);
+        meter.update(vec![0]);
+        assert_eq!(meter.peak_hold(), 0.25);
+        // a louder block raises the peak hold immediately
+        let level = meter.update(vec![-32768, 0]);
+        assert_eq!(level.peak, 1.0);
+        assert_eq!(meter.peak_hold(), 1.0);
+        meter.reset();
+        assert_eq!(meter.peak_hold(), 0.0);
+    }
+}
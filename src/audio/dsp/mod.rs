@@ -0,0 +1,63 @@
+//! Signal processing for audio samples.
+//!
+//! The module contains the building blocks for visualizing and filtering the samples of an
+//! [`AudioInput`](super::AudioInput) or an [`AudioOutput`](super::AudioOutput):
+//!
+//! - [`SpectrumAnalyzer`](SpectrumAnalyzer) computes the magnitude spectrum of a block with a
+//!   windowed [`Fft`](Fft).
+//! - [`rms`](rms), [`peak`](peak) and the [`LevelMeter`](LevelMeter) measure levels, which
+//!   [`dbfs`](dbfs) converts to decibels relative to full scale.
+//! - [`Biquad`](Biquad) filters implement low-pass, high-pass and band-pass filters.
+//! - [`SpectrumBars`](SpectrumBars) draws a spectrum on a layer, complementing the waveform
+//!   display of the [`AudioWriter`](crate::lcd::AudioWriter).
+//!
+//! Everything except the bar renderer is independent of the hardware. The functions work on
+//! single channel samples, so stereo blocks have to be split into their channels first.
+//!
+//! # Examples
+//! ```rust
+//! let mut analyzer = dsp::SpectrumAnalyzer::new(512, dsp::Window::Hann);
+//! let mut magnitudes = [0.0; 256];
+//! let mut meter = dsp::LevelMeter::new(0.9);
+//! let mut bars = dsp::SpectrumBars::new(dsp::BarsConfig {
+//!     height: 200,
+//!     ..dsp::BarsConfig::default()
+//! });
+//! let mut high_pass = dsp::Biquad::new(dsp::Coefficients::high_pass(
+//!     16000.0,
+//!     80.0,
+//!     dsp::BUTTERWORTH_Q,
+//! ));
+//!
+//! let config = audio::InputConfig {
+//!     channels: audio::Channels::Mono,
+//!     block_frames: 512,
+//!     ..audio::InputConfig::default()
+//! };
+//! let mut input = audio::AudioInput::new(sai_2, &dma_2, &mut rcc, &mut i2c_3, config)?;
+//! let mut block = [0; 512];
+//! input.record(|samples| {
+//!     block.copy_from_slice(samples);
+//!     high_pass.process_samples(&mut block);
+//!     let level = meter.update(block.iter().cloned());
+//!     analyzer.analyze(&block, &mut magnitudes);
+//!     bars.draw(&mut layer_2, &magnitudes);
+//!     println!("{:.1} dBFS", level.rms_dbfs());
+//!     true
+//! })?;
+//! ```
+
+pub use self::bars::{BarsConfig, SpectrumBars};
+pub use self::biquad::{Biquad, Coefficients, BUTTERWORTH_Q};
+pub use self::fft::{Complex, Fft};
+pub use self::meter::{dbfs, peak, rms, Level, LevelMeter};
+pub use self::spectrum::SpectrumAnalyzer;
+pub use self::window::Window;
+
+mod bars;
+mod biquad;
+mod fft;
+mod math;
+mod meter;
+mod spectrum;
+mod window;
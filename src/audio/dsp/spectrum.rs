@@ -0,0 +1,130 @@
+//! Magnitude spectra of blocks of samples.
+
+use super::fft::{Complex, Fft};
+use super::window::Window;
+use alloc::vec::Vec;
+
+/// Computes the magnitude spectrum of blocks of samples with a windowed FFT.
+///
+/// The analyzer owns its buffers, so no memory is allocated after creation.
+///
+/// # Examples
+/// ```rust
+/// let mut analyzer = dsp::SpectrumAnalyzer::new(256, dsp::Window::Hann);
+/// let mut magnitudes = [0.0; 128];
+/// input.record(|samples| {
+///     // the samples of the left channel
+///     let left: Vec<i16> = samples.iter().step_by(2).cloned().collect();
+///     analyzer.analyze(&left, &mut magnitudes);
+///     let mut loudest = 1;
+///     for bin in 2..magnitudes.len() {
+///         if magnitudes[bin] > magnitudes[loudest] {
+///             loudest = bin;
+///         }
+///     }
+///     println!("{} Hz", analyzer.bin_frequency(loudest, 16000));
+///     true
+/// })?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct SpectrumAnalyzer {
+    fft: Fft,
+    window: Vec<f32>,
+    // 2 / sum of the window, so that a full scale sine has a magnitude of 1
+    scale: f32,
+    buffer: Vec<Complex>,
+}
+
+impl SpectrumAnalyzer {
+    /// Creates an analyzer for blocks of `len` samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is not a power of two.
+    pub fn new(len: usize, window: Window) -> SpectrumAnalyzer {
+        let fft = Fft::new(len);
+        let mut coefficients = vec![0.0; len];
+        window.coefficients(&mut coefficients);
+        let sum: f32 = coefficients.iter().sum();
+        SpectrumAnalyzer {
+            fft,
+            window: coefficients,
+            scale: 2.0 / sum,
+            buffer: vec![Complex::default(); len],
+        }
+    }
+
+    /// Returns the number of samples per block.
+    pub fn block_len(&self) -> usize {
+        self.fft.size()
+    }
+
+    /// Returns the number of frequency bins, which is half the block length.
+    pub fn bins(&self) -> usize {
+        self.fft.size() / 2
+    }
+
+    /// Returns the center frequency of a bin in Hz.
+    pub fn bin_frequency(&self, bin: usize, sample_rate: u32) -> f32 {
+        bin as f32 * sample_rate as f32 / self.fft.size() as f32
+    }
+
+    /// Computes the magnitudes of the frequency bins of a block of single channel samples.
+    ///
+    /// Missing samples are treated as zeros and surplus samples are ignored. The magnitudes
+    /// are relative to full scale, so a full scale sine at the center frequency of a bin gives
+    /// a magnitude of about 1 in that bin. Only the first [`bins`](SpectrumAnalyzer::bins)
+    /// elements of `magnitudes` are written.
+    pub fn analyze(&mut self, samples: &[i16], magnitudes: &mut [f32]) {
+        for (i, value) in self.buffer.iter_mut().enumerate() {
+            let sample = samples
+                .get(i)
+                .map_or(0.0, |&sample| f32::from(sample) / 32768.0);
+            *value = Complex::new(sample * self.window[i], 0.0);
+        }
+        self.fft.forward(&mut self.buffer);
+
+        let bins = self.bins();
+        for (magnitude, value) in magnitudes.iter_mut().zip(&self.buffer[..bins]) {
+            *magnitude = value.abs() * self.scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::math;
+    use core::f32::consts::PI;
+
+    #[test]
+    fn full_scale_sine() {
+        let windows = [
+            Window::Rectangular,
+            Window::Hann,
+            Window::Hamming,
+            Window::Blackman,
+        ];
+        let samples: Vec<i16> = (0..256)
+            .map(|i| (math::sin(2.0 * PI * 16.0 * i as f32 / 256.0) * 32767.0) as i16)
+            .collect();
+        for &window in windows.iter() {
+            let mut analyzer = SpectrumAnalyzer::new(256, window);
+            let mut magnitudes = vec![0.0; analyzer.bins()];
+            analyzer.analyze(&samples, &mut magnitudes);
+            assert!((magnitudes[16] - 1.0).abs() < 0.01, "{:?}", window);
+            assert!(magnitudes[60] < 1e-3, "{:?}", window);
+            assert_eq!(analyzer.bin_frequency(16, 16000), 1000.0);
+        }
+    }
+
+    #[test]
+    fn silence() {
+        let mut analyzer = SpectrumAnalyzer::new(64, Window::Hann);
+        // missing samples are zeros
+        let mut magnitudes = [1.0; 40];
+        analyzer.analyze(&[], &mut magnitudes);
+        assert!(magnitudes[..32].iter().all(|&magnitude| magnitude == 0.0));
+        assert!(magnitudes[32..].iter().all(|&magnitude| magnitude == 1.0));
+    }
+}
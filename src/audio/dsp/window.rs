@@ -0,0 +1,41 @@
+//! Window functions that reduce the spectral leakage of an FFT.
+
+use super::math;
+use core::f32::consts::PI;
+
+/// A window function.
+///
+/// The windows are periodic, i.e. they are one sample longer than the block and the last
+/// sample is left out, which is the variant that is used for spectral analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    /// All coefficients are 1.
+    Rectangular,
+    /// The raised cosine window, zero at the edges.
+    Hann,
+    /// A raised cosine window with a lower first side lobe than `Hann`.
+    Hamming,
+    /// A window with very low side lobes and a wide main lobe.
+    Blackman,
+}
+
+impl Window {
+    /// Returns the coefficient at index `i` of a window with `len` coefficients.
+    pub fn coefficient(self, i: usize, len: usize) -> f32 {
+        let phase = 2.0 * PI * i as f32 / len as f32;
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * math::cos(phase),
+            Window::Hamming => 0.54 - 0.46 * math::cos(phase),
+            Window::Blackman => 0.42 - 0.5 * math::cos(phase) + 0.08 * math::cos(2.0 * phase),
+        }
+    }
+
+    /// Fills `coefficients` with the coefficients of a window of the same length.
+    pub fn coefficients(self, coefficients: &mut [f32]) {
+        let len = coefficients.len();
+        for (i, coefficient) in coefficients.iter_mut().enumerate() {
+            *coefficient = self.coefficient(i, len);
+        }
+    }
+}
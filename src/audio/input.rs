@@ -0,0 +1,291 @@
+//! Recording the microphones.
+
+use super::dma::{self, Direction, Half};
+use super::{init_codec, sai, Channels, Error, SampleRate};
+use crate::i2c::I2C;
+use crate::interrupts::{self, Ic, InterruptHandle, InterruptRequest, InterruptTable, Priority};
+use crate::task_runtime::mpsc;
+use crate::wm8994;
+use alloc::vec::Vec;
+use core::pin::Pin;
+use core::sync::atomic::{self, Ordering};
+use futures::{
+    prelude::*,
+    task::{Context, Poll},
+};
+use stm32f7::stm32f7x6::{self as device, DMA2, RCC, SAI2};
+
+/// The configuration of an [`AudioInput`](AudioInput).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputConfig {
+    /// The number of samples per second and channel.
+    pub sample_rate: SampleRate,
+    /// Whether both microphones are recorded.
+    pub channels: Channels,
+    /// The number of frames per block, where a frame contains one sample of each channel.
+    pub block_frames: usize,
+}
+
+impl InputConfig {
+    /// Returns the number of samples per block.
+    pub fn block_len(&self) -> usize {
+        self.block_frames * self.channels.count()
+    }
+}
+
+impl Default for InputConfig {
+    fn default() -> InputConfig {
+        InputConfig {
+            sample_rate: SampleRate::Hz16000,
+            channels: Channels::Stereo,
+            block_frames: 256,
+        }
+    }
+}
+
+/// Records the microphones of the board into a ping-pong buffer of two blocks.
+///
+/// The samples are received by block B of SAI2 and copied by the DMA2 stream 7, which must not
+/// be used otherwise.
+pub struct AudioInput<'a> {
+    sai: SAI2,
+    dma: &'a DMA2,
+    config: InputConfig,
+    // the DMA fills one half while the other one is processed
+    buffer: Vec<i16>,
+}
+
+impl<'a> AudioInput<'a> {
+    /// Configures SAI2 and its clock, starts the master clock and initializes the codec to
+    /// record the digital microphones with the configured sample rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block is empty or if two blocks have more than 65535 samples.
+    pub fn new(
+        sai: SAI2,
+        dma: &'a DMA2,
+        rcc: &mut RCC,
+        i2c_3: &mut I2C<device::I2C3>,
+        config: InputConfig,
+    ) -> Result<AudioInput<'a>, Error> {
+        let block_len = config.block_len();
+        assert!(block_len > 0, "empty audio blocks");
+        assert!(
+            2 * block_len <= usize::from(u16::max_value()),
+            "audio blocks too large"
+        );
+
+        sai::enable_clocks(rcc);
+        sai::disable(&sai);
+        let mckdiv = sai::configure_clock(rcc, config.sample_rate);
+        sai::configure_rx(&sai, mckdiv, config.channels);
+        // the codec is clocked by the master clock of block A
+        sai::enable_master(&sai);
+        let codec_config = wm8994::Config {
+            input: Some(wm8994::InputSource::DigitalMicrophone2),
+            output: None,
+            sample_rate: config.sample_rate.into(),
+            ..wm8994::Config::default()
+        };
+        init_codec(i2c_3, &codec_config)?;
+
+        Ok(AudioInput {
+            sai,
+            dma,
+            config,
+            buffer: vec![0; 2 * block_len],
+        })
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> &InputConfig {
+        &self.config
+    }
+
+    /// Records blocks and passes them to `f` until it returns `false`.
+    ///
+    /// This function busy-waits for the DMA and doesn't need interrupts. The next block is
+    /// recorded while `f` runs, so `f` must return before the next block is filled.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Overrun` if `f` took too long and `Error::Transfer` if the DMA failed.
+    /// The recording is stopped in both cases.
+    pub fn record<F>(&mut self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&[i16]) -> bool,
+    {
+        self.start(false);
+        let result = loop {
+            let half = match dma::take_finished(self.dma, Direction::Rx) {
+                Some(Ok(half)) => half,
+                Some(Err(err)) => break Err(err),
+                None => continue,
+            };
+            if !f(self.block(half)) {
+                break Ok(());
+            }
+        };
+        self.stop();
+        result
+    }
+
+    /// Starts the recording and returns a stream of the recorded blocks.
+    ///
+    /// The DMA2_STREAM7 interrupt is registered to wake the stream whenever a block is filled.
+    /// The recording stops when the stream is dropped. Use
+    /// [`SampleBlocks::release`](SampleBlocks::release) to also unregister the interrupt.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let mut blocks = input
+    ///     .into_stream(interrupt_table, Priority::P1)
+    ///     .expect("registering dma2_stream7 interrupt failed");
+    ///
+    /// // in an async task
+    /// loop {
+    ///     let block = await!(blocks.next()).expect("audio stream closed")?;
+    ///     let peak = block.iter().map(|&sample| i32::from(sample).abs()).max();
+    ///     println!("{}", peak.unwrap_or(0));
+    ///     // avoids an allocation for the next block
+    ///     blocks.recycle(block);
+    /// }
+    /// ```
+    pub fn into_stream<'t>(
+        mut self,
+        interrupt_table: &mut InterruptTable<'t, Ic<'t>>,
+        priority: Priority,
+    ) -> Result<SampleBlocks<'a>, interrupts::Error> {
+        let (sink, filled) = mpsc::unbounded();
+        let interrupt = interrupt_table.register(InterruptRequest::DMA2_STREAM7, priority, move || {
+            // The handler only accesses the flags of stream 7, which are not touched otherwise
+            // while the stream triggers interrupts.
+            let dma = unsafe { &*DMA2::ptr() };
+            if let Some(result) = dma::take_finished(dma, Direction::Rx) {
+                // fails only if the stream was dropped
+                let _ = sink.unbounded_send(result);
+            }
+        })?;
+        self.start(true);
+        Ok(SampleBlocks {
+            input: Some(self),
+            filled,
+            interrupt: Some(interrupt),
+            spare: None,
+        })
+    }
+
+    /// Stops the master clock and returns the SAI2 peripheral.
+    pub fn release(self) -> SAI2 {
+        sai::disable(&self.sai);
+        self.sai
+    }
+
+    fn start(&mut self, interrupts: bool) {
+        dma::start(
+            self.dma,
+            &self.sai,
+            Direction::Rx,
+            &mut self.buffer,
+            interrupts,
+        );
+        sai::enable_rx(&self.sai);
+    }
+
+    fn stop(&mut self) {
+        sai::disable_rx(&self.sai);
+        dma::stop(self.dma, Direction::Rx);
+        dma::clear_flags(self.dma, Direction::Rx);
+    }
+
+    fn block(&self, half: Half) -> &[i16] {
+        // the buffer was written by the DMA, not by the compiler's view of the program
+        atomic::compiler_fence(Ordering::SeqCst);
+        let (first, second) = self.buffer.split_at(self.buffer.len() / 2);
+        match half {
+            Half::First => first,
+            Half::Second => second,
+        }
+    }
+}
+
+/// A stream of the blocks recorded by an [`AudioInput`](AudioInput).
+///
+/// Each item is a copy of a block. If the blocks are not taken fast enough, the queued blocks
+/// are overwritten by the DMA before they are copied. The copies are allocated on the heap,
+/// unless a previous block is passed back to [`recycle`](SampleBlocks::recycle).
+///
+/// Dropping the stream stops the recording, but the interrupt handler stays registered until
+/// the end of the interrupt scope. Use [`release`](SampleBlocks::release) to unregister it.
+#[must_use = "streams do nothing unless polled"]
+pub struct SampleBlocks<'a> {
+    // only `None` after `release`
+    input: Option<AudioInput<'a>>,
+    filled: mpsc::UnboundedReceiver<Result<Half, Error>>,
+    interrupt: Option<InterruptHandle<(), InterruptRequest>>,
+    // a returned block that is reused for the next copy
+    spare: Option<Vec<i16>>,
+}
+
+impl<'a> SampleBlocks<'a> {
+    /// Returns the configuration of the input.
+    pub fn config(&self) -> &InputConfig {
+        self.input().config()
+    }
+
+    /// Passes a block that is no longer needed back to the stream, which copies the next
+    /// block into it instead of allocating a new one.
+    pub fn recycle(&mut self, block: Vec<i16>) {
+        self.spare = Some(block);
+    }
+
+    /// Stops the recording, unregisters the DMA2_STREAM7 interrupt and returns the SAI2
+    /// peripheral.
+    ///
+    /// The `interrupt_table` must be the one that was passed to
+    /// [`AudioInput::into_stream`](AudioInput::into_stream).
+    pub fn release<'t>(mut self, interrupt_table: &mut InterruptTable<'t, Ic<'t>>) -> SAI2 {
+        let mut input = self.input.take().expect("audio input already released");
+        input.stop();
+        if let Some(interrupt) = self.interrupt.take() {
+            interrupt_table.unregister(interrupt);
+        }
+        input.release()
+    }
+
+    fn input(&self) -> &AudioInput<'a> {
+        self.input.as_ref().expect("audio input already released")
+    }
+
+    // Copies the block into the spare buffer or a new one.
+    fn copy_block(&mut self, half: Half) -> Vec<i16> {
+        let block = self.input().block(half);
+        let mut copy = self.spare.take().unwrap_or_default();
+        copy.clear();
+        copy.extend_from_slice(block);
+        copy
+    }
+}
+
+impl<'a> Stream for SampleBlocks<'a> {
+    type Item = Result<Vec<i16>, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.filled).poll_next(cx) {
+            Poll::Ready(Some(Ok(half))) => Poll::Ready(Some(Ok(this.copy_block(half)))),
+            Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<'a> Drop for SampleBlocks<'a> {
+    fn drop(&mut self) {
+        if let Some(ref mut input) = self.input {
+            input.stop();
+        }
+    }
+}
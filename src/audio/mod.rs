@@ -0,0 +1,160 @@
+//! Audio input and output through the WM8994 codec and the SAI2 controller.
+//!
+//! The digital microphones of the board are connected to the WM8994 codec, which sends the
+//! samples to block B of SAI2. Block A is the master of the audio interface and generates the
+//! clocks for the codec. An [`AudioInput`](AudioInput) copies the received samples via DMA into
+//! a ping-pong buffer: while the DMA fills one half of the buffer, the other half can be
+//! processed.
+//!
+//! For playback, block A transmits the samples to the codec, which drives the headphone jack and
+//! the speaker outputs. An [`AudioOutput`](AudioOutput) uses the same kind of ping-pong buffer:
+//! while the DMA plays one half, the next block is written into the other half. Input and output
+//! both need SAI2, so only one of them can exist at a time. Use `release` to get SAI2 back.
+//!
+//! The samples are signed 16 bit values. In stereo mode, the samples of the left and the right
+//! channel are interleaved.
+//!
+//! The codec is configured with the [`wm8994`](crate::wm8994) driver. The [`wav`](wav) module
+//! records and plays WAV files on the SD card. The [`dsp`](dsp) module analyzes, meters and
+//! filters the samples.
+//!
+//! # Examples
+//! ```rust
+//! let config = audio::InputConfig {
+//!     sample_rate: audio::SampleRate::Hz8000,
+//!     ..audio::InputConfig::default()
+//! };
+//! let mut input = audio::AudioInput::new(sai_2, &dma_2, &mut rcc, &mut i2c_3, config)?;
+//! input.record(|samples| {
+//!     let peak = samples.iter().map(|&sample| i32::from(sample).abs()).max();
+//!     println!("{}", peak.unwrap_or(0));
+//!     true
+//! })?;
+//!
+//! let sai_2 = input.release();
+//! let config = audio::OutputConfig::default();
+//! let mut output = audio::AudioOutput::new(sai_2, &dma_2, &mut rcc, &mut i2c_3, config)?;
+//! let beep: Vec<i16> = (0..8000).map(|i| if i / 16 % 2 == 0 { 4000 } else { -4000 }).collect();
+//! let mut samples = beep.chunks(output.config().block_len());
+//! output.play(|block| match samples.next() {
+//!     Some(chunk) => {
+//!         block[..chunk.len()].copy_from_slice(chunk);
+//!         chunk.len()
+//!     }
+//!     None => 0,
+//! })?;
+//! ```
+
+pub use self::input::{AudioInput, InputConfig, SampleBlocks};
+pub use self::output::{
+    set_output_mute, set_output_volume, AudioOutput, AudioPlayer, OutputConfig, WriteBlock,
+    MAX_VOLUME, MIN_VOLUME,
+};
+pub use crate::wm8994::OutputDevice;
+
+use crate::i2c::{self, I2C};
+use crate::system_clock;
+use crate::wm8994;
+use stm32f7::stm32f7x6 as device;
+
+mod dma;
+pub mod dsp;
+mod input;
+mod output;
+mod sai;
+pub mod wav;
+
+/// The number of samples per second and channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleRate {
+    /// 8 kHz
+    Hz8000,
+    /// 16 kHz
+    Hz16000,
+    /// 32 kHz
+    Hz32000,
+    /// 48 kHz
+    Hz48000,
+}
+
+impl SampleRate {
+    /// Returns the sample rate in Hz.
+    pub fn hz(self) -> u32 {
+        match self {
+            SampleRate::Hz8000 => 8000,
+            SampleRate::Hz16000 => 16000,
+            SampleRate::Hz32000 => 32000,
+            SampleRate::Hz48000 => 48000,
+        }
+    }
+}
+
+/// The channel layout of the samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channels {
+    /// Only the left channel.
+    Mono,
+    /// The left and the right channel, interleaved starting with the left one.
+    Stereo,
+}
+
+impl Channels {
+    /// Returns the number of channels.
+    pub fn count(self) -> usize {
+        match self {
+            Channels::Mono => 1,
+            Channels::Stereo => 2,
+        }
+    }
+}
+
+/// Errors that can occur during audio transfers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The communication with the codec failed.
+    I2c(i2c::Error),
+    /// The codec is not a WM8994. Contains the read device ID.
+    UnknownCodec(u16),
+    /// The DMA stream reported a transfer error.
+    Transfer,
+    /// A block was overwritten before it was processed.
+    Overrun,
+    /// A block was played again because the next one was not written in time.
+    Underrun,
+}
+
+impl From<i2c::Error> for Error {
+    fn from(err: i2c::Error) -> Error {
+        Error::I2c(err)
+    }
+}
+
+impl From<wm8994::Error<i2c::Error>> for Error {
+    fn from(err: wm8994::Error<i2c::Error>) -> Error {
+        match err {
+            wm8994::Error::Access(err) => Error::I2c(err),
+            wm8994::Error::UnknownDevice(id) => Error::UnknownCodec(id),
+        }
+    }
+}
+
+impl From<SampleRate> for wm8994::SampleRate {
+    fn from(sample_rate: SampleRate) -> wm8994::SampleRate {
+        match sample_rate {
+            SampleRate::Hz8000 => wm8994::SampleRate::Hz8000,
+            SampleRate::Hz16000 => wm8994::SampleRate::Hz16000,
+            SampleRate::Hz32000 => wm8994::SampleRate::Hz32000,
+            SampleRate::Hz48000 => wm8994::SampleRate::Hz48000,
+        }
+    }
+}
+
+// Resets the codec and applies the configuration.
+fn init_codec(i2c_3: &mut I2C<device::I2C3>, config: &wm8994::Config) -> Result<(), Error> {
+    let mut result = Ok(());
+    i2c_3.connect::<u16, _>(wm8994::ADDRESS, |mut conn| {
+        result = wm8994::init(&mut conn, config, system_clock::wait_ms);
+        Ok(())
+    })?;
+    result.map_err(Error::from)
+}
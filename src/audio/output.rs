@@ -0,0 +1,423 @@
+//! Playing samples on the headphone jack and the speaker outputs.
+
+use super::dma::{self, Direction, Half};
+use super::{init_codec, sai, Channels, Error, OutputDevice, SampleRate};
+use crate::i2c::{self, I2C};
+use crate::interrupts::{self, Ic, InterruptHandle, InterruptRequest, InterruptTable, Priority};
+use crate::task_runtime::mpsc;
+use crate::wm8994;
+use alloc::vec::Vec;
+use core::iter;
+use core::pin::Pin;
+use core::sync::atomic::{self, Ordering};
+use futures::{
+    prelude::*,
+    task::{Context, Poll},
+};
+use stm32f7::stm32f7x6::{self as device, DMA2, RCC, SAI2};
+
+/// The lowest output volume in dB.
+pub const MIN_VOLUME: i8 = wm8994::MIN_OUTPUT_VOLUME;
+/// The highest output volume in dB.
+pub const MAX_VOLUME: i8 = wm8994::MAX_OUTPUT_VOLUME;
+
+/// The configuration of an [`AudioOutput`](AudioOutput).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputConfig {
+    /// The number of samples per second and channel.
+    pub sample_rate: SampleRate,
+    /// The channel layout of the played samples. Mono samples are played on both channels.
+    pub channels: Channels,
+    /// The number of frames per block, where a frame contains one sample of each channel.
+    pub block_frames: usize,
+    /// The outputs that are enabled.
+    pub device: OutputDevice,
+    /// The initial volume in dB, see [`set_output_volume`](set_output_volume).
+    pub volume: i8,
+}
+
+impl OutputConfig {
+    /// Returns the number of samples per block.
+    pub fn block_len(&self) -> usize {
+        self.block_frames * self.channels.count()
+    }
+}
+
+impl Default for OutputConfig {
+    fn default() -> OutputConfig {
+        OutputConfig {
+            sample_rate: SampleRate::Hz16000,
+            channels: Channels::Stereo,
+            block_frames: 256,
+            device: OutputDevice::Headphone,
+            volume: -10,
+        }
+    }
+}
+
+/// Plays samples from a ping-pong buffer of two blocks.
+///
+/// The samples are copied by the DMA2 stream 4, which must not be used otherwise, and sent by
+/// block A of SAI2. While the DMA plays one half of the buffer, the next block is written into
+/// the other half.
+pub struct AudioOutput<'a> {
+    sai: SAI2,
+    dma: &'a DMA2,
+    config: OutputConfig,
+    // always contains interleaved stereo frames
+    buffer: Vec<i16>,
+}
+
+impl<'a> AudioOutput<'a> {
+    /// Configures SAI2 and its clock, starts the master clock and initializes the codec for
+    /// playback on the configured outputs.
+    ///
+    /// This takes up to 650 ms because the output stages of the codec have to settle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the block is empty or if two blocks have more than 65535 stereo samples.
+    pub fn new(
+        sai: SAI2,
+        dma: &'a DMA2,
+        rcc: &mut RCC,
+        i2c_3: &mut I2C<device::I2C3>,
+        config: OutputConfig,
+    ) -> Result<AudioOutput<'a>, Error> {
+        let buffer_len = 2 * 2 * config.block_frames;
+        assert!(config.block_frames > 0, "empty audio blocks");
+        assert!(
+            buffer_len <= usize::from(u16::max_value()),
+            "audio blocks too large"
+        );
+
+        sai::enable_clocks(rcc);
+        sai::disable(&sai);
+        let mckdiv = sai::configure_clock(rcc, config.sample_rate);
+        sai::configure_tx(&sai, mckdiv);
+        // the codec is clocked by the master clock of block A
+        sai::enable_master(&sai);
+        let codec_config = wm8994::Config {
+            input: None,
+            output: Some(config.device),
+            sample_rate: config.sample_rate.into(),
+            output_volume: config.volume,
+            ..wm8994::Config::default()
+        };
+        init_codec(i2c_3, &codec_config)?;
+
+        Ok(AudioOutput {
+            sai,
+            dma,
+            config,
+            buffer: vec![0; buffer_len],
+        })
+    }
+
+    /// Returns the configuration.
+    pub fn config(&self) -> &OutputConfig {
+        &self.config
+    }
+
+    /// Plays the blocks that `f` writes into the passed slice.
+    ///
+    /// `f` returns the number of samples it wrote. The rest of the block is filled with
+    /// silence. A block that is not full is the last one and the function returns after it
+    /// was played.
+    ///
+    /// This function busy-waits for the DMA and doesn't need interrupts. The next block is
+    /// written while the current one is played, so `f` must return before the current block
+    /// ends.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Underrun` if `f` took too long and `Error::Transfer` if the DMA failed.
+    /// The playback is stopped in both cases.
+    ///
+    /// # Examples
+    /// ```rust
+    /// // a 1 kHz square wave for one second at 16 kHz
+    /// let mut frames = 0;
+    /// output.play(|block| {
+    ///     for frame in block.chunks_mut(2) {
+    ///         let sample = if frames / 8 % 2 == 0 { 8000 } else { -8000 };
+    ///         frame[0] = sample;
+    ///         frame[1] = sample;
+    ///         frames += 1;
+    ///     }
+    ///     if frames <= 16000 { block.len() } else { 0 }
+    /// })?;
+    /// ```
+    pub fn play<F>(&mut self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&mut [i16]) -> usize,
+    {
+        let mut block = vec![0; self.config.block_len()];
+        let mut last = self.fill(Half::First, None, &mut block, &mut f);
+        last = self.fill(Half::Second, last, &mut block, &mut f);
+
+        self.start(false);
+        let result = loop {
+            let half = match dma::take_finished(self.dma, Direction::Tx) {
+                Some(Ok(half)) => half,
+                Some(Err(err)) => break Err(err),
+                None => continue,
+            };
+            if last == Some(half) {
+                break Ok(());
+            }
+            last = self.fill(half, last, &mut block, &mut f);
+        };
+        self.stop();
+        result
+    }
+
+    /// Starts the playback of silence and returns a player that accepts the next blocks.
+    ///
+    /// The DMA2_STREAM4 interrupt is registered to wake the player whenever a block was played.
+    /// The playback stops when the player is dropped. Use
+    /// [`AudioPlayer::release`](AudioPlayer::release) to also unregister the interrupt.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let mut player = output
+    ///     .into_player(interrupt_table, Priority::P1)
+    ///     .expect("registering dma2_stream4 interrupt failed");
+    ///
+    /// // in an async task
+    /// for block in tone.chunks(player.config().block_len()) {
+    ///     await!(player.write(block))?;
+    /// }
+    /// ```
+    pub fn into_player<'t>(
+        mut self,
+        interrupt_table: &mut InterruptTable<'t, Ic<'t>>,
+        priority: Priority,
+    ) -> Result<AudioPlayer<'a>, interrupts::Error> {
+        let (sink, finished) = mpsc::unbounded();
+        let interrupt = interrupt_table.register(InterruptRequest::DMA2_STREAM4, priority, move || {
+            // The handler only accesses the flags of stream 4, which are not touched otherwise
+            // while the stream triggers interrupts.
+            let dma = unsafe { &*DMA2::ptr() };
+            if let Some(result) = dma::take_finished(dma, Direction::Tx) {
+                // fails only if the player was dropped
+                let _ = sink.unbounded_send(result);
+            }
+        })?;
+        for sample in self.buffer.iter_mut() {
+            *sample = 0;
+        }
+        self.start(true);
+        Ok(AudioPlayer {
+            output: Some(self),
+            finished,
+            interrupt: Some(interrupt),
+            underrun: false,
+        })
+    }
+
+    /// Stops the master clock and returns the SAI2 peripheral.
+    pub fn release(self) -> SAI2 {
+        sai::disable(&self.sai);
+        self.sai
+    }
+
+    fn start(&mut self, interrupts: bool) {
+        // the buffer was written by the CPU before the DMA reads it
+        atomic::compiler_fence(Ordering::SeqCst);
+        dma::start(
+            self.dma,
+            &self.sai,
+            Direction::Tx,
+            &mut self.buffer,
+            interrupts,
+        );
+    }
+
+    fn stop(&mut self) {
+        dma::stop(self.dma, Direction::Tx);
+        dma::clear_flags(self.dma, Direction::Tx);
+    }
+
+    // Writes the next block of `f` into `half`, or silence if the last block was already
+    // written. Returns the half that contains the last block.
+    fn fill<F>(
+        &mut self,
+        half: Half,
+        last: Option<Half>,
+        block: &mut [i16],
+        f: &mut F,
+    ) -> Option<Half>
+    where
+        F: FnMut(&mut [i16]) -> usize,
+    {
+        if last.is_some() {
+            self.write_block(half, &[]);
+            return last;
+        }
+        let len = f(block).min(block.len());
+        self.write_block(half, &block[..len]);
+        if len < block.len() {
+            Some(half)
+        } else {
+            None
+        }
+    }
+
+    // Copies the samples into `half` and fills the rest of it with silence.
+    fn write_block(&mut self, half: Half, samples: &[i16]) {
+        let (first, second) = self.buffer.split_at_mut(self.buffer.len() / 2);
+        let block = match half {
+            Half::First => first,
+            Half::Second => second,
+        };
+        match self.config.channels {
+            Channels::Mono => {
+                let samples = samples.iter().chain(iter::repeat(&0));
+                for (frame, &sample) in block.chunks_mut(2).zip(samples) {
+                    frame[0] = sample;
+                    frame[1] = sample;
+                }
+            }
+            Channels::Stereo => {
+                let len = samples.len().min(block.len());
+                block[..len].copy_from_slice(&samples[..len]);
+                for sample in block[len..].iter_mut() {
+                    *sample = 0;
+                }
+            }
+        }
+        // the buffer is read by the DMA, not by the compiler's view of the program
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
+
+/// Plays the blocks that are written to it, see
+/// [`AudioOutput::into_player`](AudioOutput::into_player).
+///
+/// Until the next block is written, the DMA repeats the previous block of the half.
+///
+/// Dropping the player stops the playback, but the interrupt handler stays registered until the
+/// end of the interrupt scope. Use [`release`](AudioPlayer::release) to unregister it.
+pub struct AudioPlayer<'a> {
+    // only `None` after `release`
+    output: Option<AudioOutput<'a>>,
+    finished: mpsc::UnboundedReceiver<Result<Half, Error>>,
+    interrupt: Option<InterruptHandle<(), InterruptRequest>>,
+    // a block was repeated since the last write
+    underrun: bool,
+}
+
+impl<'a> AudioPlayer<'a> {
+    /// Returns the configuration of the output.
+    pub fn config(&self) -> &OutputConfig {
+        self.output().config()
+    }
+
+    /// Returns a future that waits until a block was played and replaces it with `samples`.
+    ///
+    /// Samples that don't fit into a block are ignored and a shorter block is filled with
+    /// silence.
+    ///
+    /// The future resolves to `Error::Underrun` if a block was played more than once since the
+    /// last write. The samples are written anyway.
+    pub fn write<'p>(&'p mut self, samples: &'p [i16]) -> WriteBlock<'p, 'a> {
+        WriteBlock {
+            player: self,
+            samples,
+        }
+    }
+
+    /// Stops the playback, unregisters the DMA2_STREAM4 interrupt and returns the SAI2
+    /// peripheral.
+    ///
+    /// The `interrupt_table` must be the one that was passed to
+    /// [`AudioOutput::into_player`](AudioOutput::into_player).
+    pub fn release<'t>(mut self, interrupt_table: &mut InterruptTable<'t, Ic<'t>>) -> SAI2 {
+        let mut output = self.output.take().expect("audio output already released");
+        output.stop();
+        if let Some(interrupt) = self.interrupt.take() {
+            interrupt_table.unregister(interrupt);
+        }
+        output.release()
+    }
+
+    fn output(&self) -> &AudioOutput<'a> {
+        self.output.as_ref().expect("audio output already released")
+    }
+
+    fn output_mut(&mut self) -> &mut AudioOutput<'a> {
+        self.output.as_mut().expect("audio output already released")
+    }
+}
+
+impl<'a> Drop for AudioPlayer<'a> {
+    fn drop(&mut self) {
+        if let Some(ref mut output) = self.output {
+            output.stop();
+        }
+    }
+}
+
+/// A future that writes a block to an [`AudioPlayer`](AudioPlayer).
+#[must_use = "futures do nothing unless polled"]
+pub struct WriteBlock<'p, 'a> {
+    player: &'p mut AudioPlayer<'a>,
+    samples: &'p [i16],
+}
+
+impl<'p, 'a> Future for WriteBlock<'p, 'a> {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Error>> {
+        let this = self.get_mut();
+        let player = &mut this.player;
+
+        // only the half that finished last is not played at the moment
+        let mut newest = None;
+        loop {
+            match Pin::new(&mut player.finished).poll_next(cx) {
+                Poll::Ready(Some(Ok(half))) => {
+                    player.underrun |= newest.is_some();
+                    newest = Some(half);
+                }
+                Poll::Ready(Some(Err(Error::Underrun))) => player.underrun = true,
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Err(err)),
+                Poll::Ready(None) => panic!("dma2_stream4 interrupt handler was dropped"),
+                Poll::Pending => break,
+            }
+        }
+
+        match newest {
+            Some(half) => {
+                player.output_mut().write_block(half, this.samples);
+                if player.underrun {
+                    player.underrun = false;
+                    Poll::Ready(Err(Error::Underrun))
+                } else {
+                    Poll::Ready(Ok(()))
+                }
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Sets the volume of the headphone and the speaker outputs in dB.
+///
+/// The volume is clamped to [`MIN_VOLUME`](MIN_VOLUME)..=[`MAX_VOLUME`](MAX_VOLUME) and has a
+/// resolution of 1 dB.
+pub fn set_output_volume(i2c_3: &mut I2C<device::I2C3>, volume: i8) -> Result<(), i2c::Error> {
+    i2c_3.connect::<u16, _>(wm8994::ADDRESS, |mut conn| {
+        wm8994::set_output_volume(&mut conn, volume)
+    })
+}
+
+/// Mutes or unmutes the playback.
+///
+/// The codec ramps the volume down or up, so muting doesn't cause clicks.
+pub fn set_output_mute(i2c_3: &mut I2C<device::I2C3>, mute: bool) -> Result<(), i2c::Error> {
+    i2c_3.connect::<u16, _>(wm8994::ADDRESS, |mut conn| {
+        wm8994::set_output_mute(&mut conn, mute)
+    })
+}
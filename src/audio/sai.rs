@@ -0,0 +1,226 @@
+//! Configuration of the SAI2 blocks and their clock.
+//!
+//! The audio frame has four 16 bit slots. The codec sends the left microphone in slot 1 and the
+//! right microphone in slot 3 and receives the left output in slot 0 and the right output in
+//! slot 2.
+
+use super::{Channels, SampleRate};
+use stm32f7::stm32f7x6::{RCC, SAI2};
+
+// The frequency of the clock that is fed into the PLLs.
+const PLL_SOURCE_FREQUENCY: u32 = 25_000_000;
+const PLLI2SN: u16 = 344;
+const PLLI2SQ: u8 = 7;
+
+/// Enables the clocks of SAI2 and DMA2.
+pub fn enable_clocks(rcc: &mut RCC) {
+    rcc.apb2enr.modify(|_, w| w.sai2en().set_bit());
+    rcc.ahb1enr.modify(|_, w| w.dma2en().enabled());
+    while !rcc.ahb1enr.read().dma2en().is_enabled() {}
+}
+
+/// Configures the PLLI2S as clock source of SAI2 and returns the master clock divider for the
+/// sample rate.
+///
+/// All supported sample rates are multiples of 8 kHz, so the SAI clock is always
+/// 1 MHz * 344 / 7 = 49.142 MHz.
+pub fn configure_clock(rcc: &mut RCC, sample_rate: SampleRate) -> u8 {
+    // sai2_clock_source plli2s
+    rcc.dkcfgr1.modify(|_, w| unsafe { w.sai2sel().bits(0b01) });
+
+    // Disable the PLLI2S
+    rcc.cr.modify(|_, w| w.plli2son().clear_bit());
+    while rcc.cr.read().plli2srdy().bit_is_set() {}
+
+    // PLLI2S_VCO Output = PLL_SOURCE / PLLM * PLLI2SN
+    // SAI_CLK = PLLI2S_VCO Output / PLLI2SQ / PLLI2SDIVQ
+    rcc.plli2scfgr.modify(|_, w| unsafe {
+        w.plli2sn().bits(PLLI2SN);
+        w.plli2sq().bits(PLLI2SQ);
+        w
+    });
+    rcc.dkcfgr1
+        .modify(|_, w| unsafe { w.plli2sdiv().bits(1 - 1) });
+
+    // Enable the PLLI2S
+    rcc.cr.modify(|_, w| w.plli2son().set_bit());
+    while rcc.cr.read().plli2srdy().bit_is_clear() {}
+
+    let vco_input = PLL_SOURCE_FREQUENCY / u32::from(rcc.pllcfgr.read().pllm().bits());
+    let sai_clock = vco_input * u32::from(PLLI2SN) / u32::from(PLLI2SQ);
+
+    // MCLK = SAI_CLK / (MCKDIV * 2) with MCLK = 256 * FS, rounded to the nearest divider
+    let frequency = sample_rate.hz();
+    ((sai_clock + frequency * 256) / (frequency * 512)) as u8
+}
+
+/// Disables both blocks and waits until the disable takes effect.
+pub fn disable(sai: &SAI2) {
+    sai.acr1.modify(|_, w| w.saiaen().clear_bit());
+    sai.bcr1.modify(|_, w| w.saiben().clear_bit());
+    while sai.acr1.read().saiaen().bit_is_set() {}
+    while sai.bcr1.read().saiben().bit_is_set() {}
+}
+
+// Returns the bits of the slot enable field of the receiver for the channel layout.
+fn slots(channels: Channels) -> u16 {
+    match channels {
+        Channels::Mono => 1 << 1,
+        Channels::Stereo => 1 << 1 | 1 << 3,
+    }
+}
+
+// The bits of the slot enable field of the transmitter, which always sends both channels.
+const TX_SLOTS: u16 = 1 << 0 | 1 << 2;
+
+/// Configures block A as master receiver, which only generates the clocks, and block B as
+/// receiver that is synchronous to block A and requests DMA transfers.
+///
+/// The blocks must be disabled.
+pub fn configure_rx(sai: &SAI2, mckdiv: u8, channels: Channels) {
+    // disable synchronization outputs
+    sai.gcr.modify(|_, w| unsafe { w.syncout().bits(0) });
+
+    sai.acr1.write(|w| unsafe {
+        w.mode().bits(0b01); // MasterReceiver
+        w.prtcfg().bits(0b00); // protocol free
+        w.ds().bits(0b100); // data_size 16 bits
+        w.lsbfirst().clear_bit();
+        w.ckstr().set_bit(); // clock_strobing_edge
+        w.syncen().bits(0b00); // synchronization asynchronous
+        w.mono().clear_bit();
+        w.out_dri().set_bit(); // output_drive
+        w.nodiv().clear_bit(); // no_divider
+        w.mcjdiv().bits(mckdiv); // master_clock_divider
+        w
+    });
+    sai.acr2.write(|w| unsafe {
+        w.fth().bits(0b001); // fifo_threshold QuarterFifo
+        w.fflus().set_bit(); // fifo_flush
+        w
+    });
+    configure_frame_and_slots(sai, slots(channels));
+
+    sai.bcr1.write(|w| unsafe {
+        w.mode().bits(0b11); // SlaveReceiver
+        w.prtcfg().bits(0b00); // protocol free
+        w.ds().bits(0b100); // data_size 16 bits
+        w.lsbfirst().clear_bit();
+        w.ckstr().set_bit(); // clock_strobing_edge
+        w.syncen().bits(0b01); // synchronization SynchronousWithOtherSubBlock
+        w.mono().clear_bit();
+        w.out_dri().set_bit(); // output_drive
+        w.nodiv().clear_bit(); // no_divider
+        w.mcjdiv().bits(mckdiv); // master_clock_divider
+        w.dmaen().set_bit(); // the FIFO is served by DMA2
+        w
+    });
+    sai.bcr2.write(|w| unsafe {
+        w.fth().bits(0b001); // fifo_threshold QuarterFifo
+        w.fflus().set_bit(); // fifo_flush
+        w
+    });
+
+    // Disable all interrupts and clear all flags
+    sai.bim.write(|w| w);
+    sai.bclrfr.write(|w| {
+        w.lfsdet().set_bit();
+        w.cafsdet().set_bit();
+        w.cnrdy().set_bit();
+        w.wckcfg().set_bit();
+        w.mutedet().set_bit();
+        w.ovrudr().set_bit();
+        w
+    });
+}
+
+/// Configures block A as master transmitter that requests DMA transfers. Block B is not used.
+///
+/// The blocks must be disabled.
+pub fn configure_tx(sai: &SAI2, mckdiv: u8) {
+    // disable synchronization outputs
+    sai.gcr.modify(|_, w| unsafe { w.syncout().bits(0) });
+
+    sai.acr1.write(|w| unsafe {
+        w.mode().bits(0b00); // MasterTransmitter
+        w.prtcfg().bits(0b00); // protocol free
+        w.ds().bits(0b100); // data_size 16 bits
+        w.lsbfirst().clear_bit();
+        w.ckstr().set_bit(); // clock_strobing_edge
+        w.syncen().bits(0b00); // synchronization asynchronous
+        w.mono().clear_bit();
+        w.out_dri().set_bit(); // output_drive
+        w.nodiv().clear_bit(); // no_divider
+        w.mcjdiv().bits(mckdiv); // master_clock_divider
+        w.dmaen().set_bit(); // the FIFO is served by DMA2
+        w
+    });
+    sai.acr2.write(|w| unsafe {
+        w.fth().bits(0b001); // fifo_threshold QuarterFifo
+        w.fflus().set_bit(); // fifo_flush
+        w
+    });
+    configure_frame_and_slots(sai, TX_SLOTS);
+
+    // Disable all interrupts and clear all flags
+    sai.aim.write(|w| w);
+    sai.aclrfr.write(|w| {
+        w.lfsdet().set_bit();
+        w.cafsdet().set_bit();
+        w.cnrdy().set_bit();
+        w.wckcfg().set_bit();
+        w.mutedet().set_bit();
+        w.ovrudr().set_bit();
+        w
+    });
+}
+
+fn configure_frame_and_slots(sai: &SAI2, slots: u16) {
+    sai.afrcr.write(|w| unsafe {
+        w.frl().bits(64 - 1); // frame_length
+        w.fsall().bits(32 - 1); // sync_active_level_length
+        w.fsdef().set_bit(); // frame_sync_definition
+        w.fspol().clear_bit(); // frame_sync_polarity
+        w.fsoff().set_bit(); // frame_sync_offset
+        w
+    });
+    sai.aslotr.write(|w| unsafe {
+        w.fboff().bits(0); // first_bit_offset
+        w.slotsz().bits(0b00); // slot_size DataSize
+        w.nbslot().bits(4 - 1); // number_of_slots
+        w.sloten().bits(slots); // enable_slots
+        w
+    });
+    sai.bfrcr.write(|w| unsafe {
+        w.frl().bits(64 - 1);
+        w.fsall().bits(32 - 1);
+        w.fsdef().set_bit();
+        w.fspol().clear_bit();
+        w.fsoff().set_bit();
+        w
+    });
+    sai.bslotr.write(|w| unsafe {
+        w.fboff().bits(0);
+        w.slotsz().bits(0b00);
+        w.nbslot().bits(4 - 1);
+        w.sloten().bits(slots);
+        w
+    });
+}
+
+/// Enables block A, which generates the clocks for the codec.
+pub fn enable_master(sai: &SAI2) {
+    sai.acr1.modify(|_, w| w.saiaen().set_bit());
+}
+
+/// Flushes the FIFO of block B and enables it, so that it starts receiving with the next frame.
+pub fn enable_rx(sai: &SAI2) {
+    sai.bcr2.modify(|_, w| w.fflus().set_bit());
+    sai.bcr1.modify(|_, w| w.saiben().set_bit());
+}
+
+/// Disables block B and waits until the disable takes effect.
+pub fn disable_rx(sai: &SAI2) {
+    sai.bcr1.modify(|_, w| w.saiben().clear_bit());
+    while sai.bcr1.read().saiben().bit_is_set() {}
+}
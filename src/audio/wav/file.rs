@@ -0,0 +1,184 @@
+//! Streaming WAV files between the audio interface and a filesystem.
+
+use super::{
+    decode_header, decode_samples, encode_header, encode_samples, Format, Header, SampleFormat,
+    HEADER_LEN,
+};
+use crate::audio::{self, AudioInput, AudioOutput};
+use crate::sd::block_device::BLOCK_SIZE;
+use crate::sd::fs::{self, File, FileSystem, SeekFrom};
+use crate::sd::BlockDevice;
+use alloc::vec::Vec;
+use core::cmp::min;
+
+// The number of bytes at the start of a file that are searched for the data chunk.
+const HEADER_SEARCH_LEN: usize = 512;
+
+/// Errors that can occur while streaming a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileError<E> {
+    /// Accessing the file failed.
+    Fs(fs::Error<E>),
+    /// The file is not a supported WAV file.
+    Wav(super::Error),
+    /// The audio transfer failed.
+    Audio(audio::Error),
+    /// The sample rate or the channels of the file differ from the ones of the output.
+    FormatMismatch,
+}
+
+impl<E> From<fs::Error<E>> for FileError<E> {
+    fn from(err: fs::Error<E>) -> FileError<E> {
+        FileError::Fs(err)
+    }
+}
+
+impl<E> From<super::Error> for FileError<E> {
+    fn from(err: super::Error) -> FileError<E> {
+        FileError::Wav(err)
+    }
+}
+
+impl<E> From<audio::Error> for FileError<E> {
+    fn from(err: audio::Error) -> FileError<E> {
+        FileError::Audio(err)
+    }
+}
+
+/// Reads the header of the WAV file and moves the position of `file` to the first sample.
+///
+/// The data chunk must start within the first 512 bytes of the file. The data length of the
+/// returned header is limited to the size of the file.
+pub fn read_file_header<D: BlockDevice>(
+    fs: &mut FileSystem<D>,
+    file: &mut File,
+) -> Result<Header, FileError<D::Error>> {
+    let mut bytes = [0; HEADER_SEARCH_LEN];
+    fs.seek(file, SeekFrom::Start(0))?;
+    let len = fs.read(file, &mut bytes)?;
+    let mut header = decode_header(&bytes[..len])?;
+
+    let available = file.size().saturating_sub(header.data_offset);
+    header.data_len = min(header.data_len, available);
+    fs.seek(file, SeekFrom::Start(min(header.data_offset, file.size())))?;
+    Ok(header)
+}
+
+/// Plays the WAV file at `path` on `output` and returns its header.
+///
+/// The samples are read block by block while the previous block is played. 8 bit samples are
+/// converted to 16 bit.
+///
+/// # Errors
+///
+/// Returns `FileError::FormatMismatch` if the sample rate or the channels of the file differ
+/// from the configuration of the output.
+pub fn play_file<D: BlockDevice>(
+    output: &mut AudioOutput,
+    fs: &mut FileSystem<D>,
+    path: &str,
+) -> Result<Header, FileError<D::Error>> {
+    let mut file = fs.open(path)?;
+    let header = read_file_header(fs, &mut file)?;
+    let config = *output.config();
+    if header.format.channels != config.channels
+        || header.format.sample_rate != config.sample_rate.hz()
+    {
+        return Err(FileError::FormatMismatch);
+    }
+
+    let sample_len = header.format.sample_format.size();
+    let mut bytes = vec![0; config.block_len() * sample_len];
+    let mut remaining = header.data_len as usize;
+    let mut fs_error = None;
+    output.play(|block| {
+        let len = min(bytes.len(), remaining);
+        match fs.read(&mut file, &mut bytes[..len - len % sample_len]) {
+            Ok(read) => {
+                remaining -= read;
+                decode_samples(header.format.sample_format, &bytes[..read], block)
+            }
+            Err(err) => {
+                fs_error = Some(err);
+                0
+            }
+        }
+    })?;
+
+    match fs_error {
+        Some(err) => Err(err.into()),
+        None => Ok(header),
+    }
+}
+
+/// Records `frames` frames from `input` into a new WAV file at `path` and returns its header.
+///
+/// The file is written block by block while the next block is recorded, so the SD card must
+/// be fast enough to write a block within the duration of a block. The header and the samples
+/// are buffered until they fill whole blocks of the SD card, so that the blocks are overwritten
+/// without reading them first. The sample rate and the channels are the ones of the input.
+/// 16 bit samples are truncated to 8 bit if `sample_format` is `SampleFormat::U8`.
+///
+/// The header is updated with the number of written samples even if the recording fails.
+///
+/// # Errors
+///
+/// Fails with `fs::Error::AlreadyExists` if the file exists.
+pub fn record_file<D: BlockDevice>(
+    input: &mut AudioInput,
+    fs: &mut FileSystem<D>,
+    path: &str,
+    sample_format: SampleFormat,
+    frames: u32,
+) -> Result<Header, FileError<D::Error>> {
+    let config = *input.config();
+    let format = Format {
+        channels: config.channels,
+        sample_rate: config.sample_rate.hz(),
+        sample_format,
+    };
+
+    let mut file = fs.create(path)?;
+    let mut bytes = vec![0; config.block_len() * sample_format.size()];
+    // the header is written with the first samples
+    let mut pending = Vec::with_capacity(HEADER_LEN + bytes.len() + BLOCK_SIZE);
+    pending.extend_from_slice(&encode_header(&format, 0));
+
+    let mut remaining = (frames as usize).saturating_mul(config.channels.count());
+    let mut fs_error = None;
+    let result = input.record(|samples| {
+        let len = min(samples.len(), remaining);
+        let bytes_len = encode_samples(sample_format, &samples[..len], &mut bytes);
+        pending.extend_from_slice(&bytes[..bytes_len]);
+        let aligned_len = pending.len() - pending.len() % BLOCK_SIZE;
+        if let Err(err) = fs.write(&mut file, &pending[..aligned_len]) {
+            fs_error = Some(err);
+            return false;
+        }
+        pending.drain(..aligned_len);
+        remaining -= len;
+        remaining > 0
+    });
+    // the samples that don't fill a whole block
+    if fs_error.is_none() {
+        if let Err(err) = fs.write(&mut file, &pending) {
+            fs_error = Some(err);
+        }
+    }
+
+    let data_offset = HEADER_LEN as u32;
+    let header = Header {
+        format,
+        data_offset,
+        data_len: file.position().saturating_sub(data_offset),
+    };
+    fs.seek(&mut file, SeekFrom::Start(0))?;
+    fs.write(&mut file, &encode_header(&format, header.data_len))?;
+    fs.flush()?;
+
+    if let Some(err) = fs_error {
+        return Err(err.into());
+    }
+    result?;
+    Ok(header)
+}
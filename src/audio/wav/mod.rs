@@ -0,0 +1,427 @@
+//! Encoding and decoding of RIFF/WAV files with PCM samples.
+//!
+//! The header and sample functions only work on byte slices, so they don't depend on the
+//! hardware. Supported are 8 bit unsigned and 16 bit signed samples in mono or stereo.
+//!
+//! [`record_file`](record_file) and [`play_file`](play_file) stream WAV files between the
+//! audio interface and a [`FileSystem`](crate::sd::fs::FileSystem). They only buffer a single
+//! block of samples, so the files can be larger than the RAM.
+//!
+//! # Examples
+//! ```rust
+//! let format = wav::Format {
+//!     channels: audio::Channels::Mono,
+//!     sample_rate: 8000,
+//!     sample_format: wav::SampleFormat::I16,
+//! };
+//! let samples = [0, 1000, 2000, 1000, 0, -1000, -2000, -1000];
+//! let mut bytes = [0; 16];
+//! let len = wav::encode_samples(format.sample_format, &samples, &mut bytes);
+//!
+//! let mut file = Vec::new();
+//! file.extend_from_slice(&wav::encode_header(&format, len as u32));
+//! file.extend_from_slice(&bytes[..len]);
+//!
+//! let header = wav::decode_header(&file).unwrap();
+//! assert_eq!(header.format, format);
+//! assert_eq!(header.frames(), 8);
+//! ```
+
+pub use self::file::{play_file, read_file_header, record_file, FileError};
+
+use super::Channels;
+use byteorder::{ByteOrder, LittleEndian};
+
+mod file;
+
+/// The length of the header that is written by [`encode_header`](encode_header).
+pub const HEADER_LEN: usize = 44;
+
+// The format tag of uncompressed PCM samples.
+const FORMAT_PCM: u16 = 1;
+// The length of the PCM format chunk without its chunk header.
+const FORMAT_CHUNK_LEN: u32 = 16;
+
+/// The encoding of a single sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    /// 8 bit unsigned samples, where 128 is silence.
+    U8,
+    /// 16 bit signed little endian samples.
+    I16,
+}
+
+impl SampleFormat {
+    /// Returns the number of bytes per sample.
+    pub fn size(self) -> usize {
+        match self {
+            SampleFormat::U8 => 1,
+            SampleFormat::I16 => 2,
+        }
+    }
+
+    fn bits(self) -> u16 {
+        8 * self.size() as u16
+    }
+}
+
+/// The format of the samples in a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    /// The channel layout. Stereo samples are interleaved starting with the left channel.
+    pub channels: Channels,
+    /// The number of samples per second and channel.
+    pub sample_rate: u32,
+    /// The encoding of a single sample.
+    pub sample_format: SampleFormat,
+}
+
+impl Format {
+    /// Returns the number of bytes per frame, where a frame contains one sample of each
+    /// channel.
+    pub fn frame_len(&self) -> usize {
+        self.channels.count() * self.sample_format.size()
+    }
+
+    /// Returns the number of bytes per second.
+    pub fn byte_rate(&self) -> u32 {
+        self.sample_rate * self.frame_len() as u32
+    }
+}
+
+/// The decoded header of a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// The format of the samples.
+    pub format: Format,
+    /// The position of the first sample in the file.
+    pub data_offset: u32,
+    /// The number of sample bytes.
+    pub data_len: u32,
+}
+
+impl Header {
+    /// Returns the number of frames, where a frame contains one sample of each channel.
+    pub fn frames(&self) -> u32 {
+        self.data_len / self.format.frame_len() as u32
+    }
+}
+
+/// Errors that can occur while decoding a WAV header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The file doesn't start with a RIFF header.
+    NotRiff,
+    /// The RIFF file doesn't contain WAVE data.
+    NotWave,
+    /// The bytes end before the start of the data chunk.
+    Truncated,
+    /// The data chunk comes before the format chunk.
+    MissingFormat,
+    /// The samples are not uncompressed PCM, contains the format tag.
+    UnsupportedEncoding(u16),
+    /// The file has neither one nor two channels, contains the number of channels.
+    UnsupportedChannels(u16),
+    /// The samples have neither 8 nor 16 bits, contains the number of bits.
+    UnsupportedSampleFormat(u16),
+}
+
+/// Returns the header of a WAV file with a RIFF, a format and a data chunk.
+///
+/// `data_len` is the number of sample bytes that follow the header. It can be written as zero
+/// first and be updated after the samples were written.
+pub fn encode_header(format: &Format, data_len: u32) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[0..4].copy_from_slice(b"RIFF");
+    LittleEndian::write_u32(&mut header[4..8], HEADER_LEN as u32 - 8 + data_len);
+    header[8..12].copy_from_slice(b"WAVE");
+
+    header[12..16].copy_from_slice(b"fmt ");
+    LittleEndian::write_u32(&mut header[16..20], FORMAT_CHUNK_LEN);
+    LittleEndian::write_u16(&mut header[20..22], FORMAT_PCM);
+    LittleEndian::write_u16(&mut header[22..24], format.channels.count() as u16);
+    LittleEndian::write_u32(&mut header[24..28], format.sample_rate);
+    LittleEndian::write_u32(&mut header[28..32], format.byte_rate());
+    LittleEndian::write_u16(&mut header[32..34], format.frame_len() as u16);
+    LittleEndian::write_u16(&mut header[34..36], format.sample_format.bits());
+
+    header[36..40].copy_from_slice(b"data");
+    LittleEndian::write_u32(&mut header[40..44], data_len);
+    header
+}
+
+/// Decodes the header at the start of a WAV file.
+///
+/// `bytes` must contain the file up to the header of the data chunk. Chunks other than the
+/// format and the data chunk are skipped. The returned data length is the one of the chunk
+/// header, so it can be larger than the file if the file was not finished.
+pub fn decode_header(bytes: &[u8]) -> Result<Header, Error> {
+    if bytes.len() < 12 {
+        return Err(Error::Truncated);
+    }
+    if &bytes[0..4] != b"RIFF" {
+        return Err(Error::NotRiff);
+    }
+    if &bytes[8..12] != b"WAVE" {
+        return Err(Error::NotWave);
+    }
+
+    let mut format = None;
+    let mut offset = 12;
+    loop {
+        let chunk_header = bytes
+            .get(offset..)
+            .and_then(|rest| rest.get(..8))
+            .ok_or(Error::Truncated)?;
+        let chunk_len = LittleEndian::read_u32(&chunk_header[4..8]);
+        let body_offset = offset + 8;
+
+        match &chunk_header[0..4] {
+            b"fmt " => {
+                let body = bytes
+                    .get(body_offset..)
+                    .and_then(|rest| rest.get(..FORMAT_CHUNK_LEN as usize))
+                    .ok_or(Error::Truncated)?;
+                format = Some(decode_format(body)?);
+            }
+            b"data" => {
+                return Ok(Header {
+                    format: format.ok_or(Error::MissingFormat)?,
+                    data_offset: body_offset as u32,
+                    data_len: chunk_len,
+                });
+            }
+            _ => {}
+        }
+
+        // chunks are padded to an even length
+        offset = (chunk_len as usize)
+            .checked_add(chunk_len as usize & 1)
+            .and_then(|len| body_offset.checked_add(len))
+            .ok_or(Error::Truncated)?;
+    }
+}
+
+// Decodes the first 16 bytes of the body of a format chunk.
+fn decode_format(body: &[u8]) -> Result<Format, Error> {
+    let encoding = LittleEndian::read_u16(&body[0..2]);
+    if encoding != FORMAT_PCM {
+        return Err(Error::UnsupportedEncoding(encoding));
+    }
+    let channels = match LittleEndian::read_u16(&body[2..4]) {
+        1 => Channels::Mono,
+        2 => Channels::Stereo,
+        other => return Err(Error::UnsupportedChannels(other)),
+    };
+    let sample_format = match LittleEndian::read_u16(&body[14..16]) {
+        8 => SampleFormat::U8,
+        16 => SampleFormat::I16,
+        other => return Err(Error::UnsupportedSampleFormat(other)),
+    };
+    Ok(Format {
+        channels,
+        sample_rate: LittleEndian::read_u32(&body[4..8]),
+        sample_format,
+    })
+}
+
+/// Decodes samples from `bytes` into `samples` and returns the number of decoded samples.
+///
+/// The samples are converted to 16 bit. Decoding stops at the end of `samples` or at the last
+/// complete sample in `bytes`.
+pub fn decode_samples(sample_format: SampleFormat, bytes: &[u8], samples: &mut [i16]) -> usize {
+    let len = (bytes.len() / sample_format.size()).min(samples.len());
+    match sample_format {
+        SampleFormat::U8 => {
+            for (sample, &byte) in samples[..len].iter_mut().zip(bytes) {
+                *sample = (i16::from(byte) - 128) << 8;
+            }
+        }
+        SampleFormat::I16 => LittleEndian::read_i16_into(&bytes[..2 * len], &mut samples[..len]),
+    }
+    len
+}
+
+/// Encodes `samples` into `bytes` and returns the number of written bytes.
+///
+/// 16 bit samples are truncated to 8 bit if necessary. Encoding stops at the end of `samples`
+/// or if `bytes` has no space for another sample.
+pub fn encode_samples(sample_format: SampleFormat, samples: &[i16], bytes: &mut [u8]) -> usize {
+    let len = (bytes.len() / sample_format.size()).min(samples.len());
+    match sample_format {
+        SampleFormat::U8 => {
+            for (byte, &sample) in bytes.iter_mut().zip(&samples[..len]) {
+                *byte = ((sample >> 8) + 128) as u8;
+            }
+        }
+        SampleFormat::I16 => LittleEndian::write_i16_into(&samples[..len], &mut bytes[..2 * len]),
+    }
+    len * sample_format.size()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    const FORMATS: [(Channels, SampleFormat); 4] = [
+        (Channels::Mono, SampleFormat::U8),
+        (Channels::Stereo, SampleFormat::U8),
+        (Channels::Mono, SampleFormat::I16),
+        (Channels::Stereo, SampleFormat::I16),
+    ];
+
+    // Returns a WAV file with the samples.
+    fn encode_file(format: &Format, samples: &[i16]) -> Vec<u8> {
+        let mut bytes = vec![0; samples.len() * format.sample_format.size()];
+        assert_eq!(
+            encode_samples(format.sample_format, samples, &mut bytes),
+            bytes.len()
+        );
+        let mut file = encode_header(format, bytes.len() as u32).to_vec();
+        file.extend_from_slice(&bytes);
+        file
+    }
+
+    fn mono_i16() -> Format {
+        Format {
+            channels: Channels::Mono,
+            sample_rate: 8000,
+            sample_format: SampleFormat::I16,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        // multiples of 256 survive the conversion to 8 bit
+        let samples: Vec<i16> = (0..64).map(|i| (i * 1024 - 32768) as i16).collect();
+        for &(channels, sample_format) in FORMATS.iter() {
+            let format = Format {
+                channels,
+                sample_rate: 44100,
+                sample_format,
+            };
+            let file = encode_file(&format, &samples);
+            assert_eq!(file.len(), HEADER_LEN + samples.len() * sample_format.size());
+
+            let header = decode_header(&file).unwrap();
+            assert_eq!(header.format, format);
+            assert_eq!(header.data_offset, HEADER_LEN as u32);
+            assert_eq!(header.data_len as usize, file.len() - HEADER_LEN);
+            assert_eq!(header.frames() as usize, samples.len() / channels.count());
+            assert_eq!(LittleEndian::read_u32(&file[4..8]) as usize, file.len() - 8);
+            assert_eq!(LittleEndian::read_u32(&file[28..32]), format.byte_rate());
+
+            let mut decoded = vec![0; samples.len() + 1];
+            let len = decode_samples(sample_format, &file[HEADER_LEN..], &mut decoded);
+            assert_eq!(&decoded[..len], &samples[..], "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn header_bytes() {
+        let format = Format {
+            channels: Channels::Stereo,
+            sample_rate: 16000,
+            sample_format: SampleFormat::I16,
+        };
+        let expected = [
+            b'R', b'I', b'F', b'F', 48, 0, 0, 0, b'W', b'A', b'V', b'E', b'f', b'm', b't', b' ',
+            16, 0, 0, 0, 1, 0, 2, 0, 0x80, 0x3E, 0, 0, 0, 0xFA, 0, 0, 4, 0, 16, 0, b'd', b'a',
+            b't', b'a', 12, 0, 0, 0,
+        ];
+        assert_eq!(encode_header(&format, 12)[..], expected[..]);
+    }
+
+    #[test]
+    fn sample_conversion() {
+        let mut bytes = [0; 5];
+        let samples = [0, i16::min_value(), i16::max_value(), 255, -1];
+        assert_eq!(encode_samples(SampleFormat::U8, &samples, &mut bytes), 5);
+        assert_eq!(bytes, [128, 0, 255, 128, 127]);
+
+        // decoding stops at the end of the samples and at the last complete sample
+        let mut decoded = [0; 2];
+        assert_eq!(decode_samples(SampleFormat::U8, &bytes, &mut decoded), 2);
+        assert_eq!(decoded, [0, i16::min_value()]);
+        let mut decoded = [0; 4];
+        assert_eq!(decode_samples(SampleFormat::I16, &[1, 2, 3], &mut decoded), 1);
+        assert_eq!(decoded[0], 0x0201);
+        assert_eq!(encode_samples(SampleFormat::I16, &samples, &mut bytes), 4);
+    }
+
+    #[test]
+    fn truncated() {
+        let file = encode_file(&mono_i16(), &[1, 2, 3]);
+        for len in 0..HEADER_LEN {
+            assert_eq!(decode_header(&file[..len]), Err(Error::Truncated), "{}", len);
+        }
+        // the samples are not needed
+        assert!(decode_header(&file[..HEADER_LEN]).is_ok());
+    }
+
+    #[test]
+    fn not_riff() {
+        let mut file = encode_file(&mono_i16(), &[]);
+        file[3] = b'X';
+        assert_eq!(decode_header(&file), Err(Error::NotRiff));
+
+        let mut file = encode_file(&mono_i16(), &[]);
+        file[8..12].copy_from_slice(b"AVI ");
+        assert_eq!(decode_header(&file), Err(Error::NotWave));
+    }
+
+    #[test]
+    fn unsupported_format() {
+        let file = encode_file(&mono_i16(), &[]);
+
+        // IEEE float samples
+        let mut float = file.clone();
+        LittleEndian::write_u16(&mut float[20..22], 3);
+        assert_eq!(decode_header(&float), Err(Error::UnsupportedEncoding(3)));
+
+        let mut surround = file.clone();
+        LittleEndian::write_u16(&mut surround[22..24], 6);
+        assert_eq!(decode_header(&surround), Err(Error::UnsupportedChannels(6)));
+
+        let mut bits_24 = file.clone();
+        LittleEndian::write_u16(&mut bits_24[34..36], 24);
+        assert_eq!(decode_header(&bits_24), Err(Error::UnsupportedSampleFormat(24)));
+    }
+
+    #[test]
+    fn missing_format() {
+        let header = encode_header(&mono_i16(), 0);
+        let mut file = header[..12].to_vec();
+        file.extend_from_slice(&header[36..]);
+        assert_eq!(decode_header(&file), Err(Error::MissingFormat));
+    }
+
+    #[test]
+    fn unknown_chunks() {
+        let header = encode_header(&mono_i16(), 2);
+        // chunks with an odd length are followed by a padding byte
+        let mut file = header[..12].to_vec();
+        file.extend_from_slice(b"LIST\x03\0\0\0abc\0");
+        file.extend_from_slice(&header[12..36]);
+        file.extend_from_slice(b"fact\x01\0\0\0x\0");
+        file.extend_from_slice(&header[36..]);
+        file.extend_from_slice(&[1, 2]);
+        let decoded = decode_header(&file).unwrap();
+        assert_eq!(decoded.format, mono_i16());
+        assert_eq!(decoded.data_offset as usize, HEADER_LEN + 12 + 10);
+        assert_eq!(decoded.data_len, 2);
+
+        // without the padding byte, the next chunk header is misread
+        let mut unpadded = header[..12].to_vec();
+        unpadded.extend_from_slice(b"LIST\x03\0\0\0abc");
+        unpadded.extend_from_slice(&header[12..]);
+        assert_eq!(decode_header(&unpadded), Err(Error::Truncated));
+
+        // chunk lengths beyond the end of the bytes
+        let mut huge = header[..12].to_vec();
+        huge.extend_from_slice(b"junk\xFF\xFF\xFF\xFF");
+        huge.extend_from_slice(&header[12..]);
+        assert_eq!(decode_header(&huge), Err(Error::Truncated));
+    }
+}
@@ -12,6 +12,7 @@ extern crate stm32f7;
 #[macro_use]
 extern crate stm32f7_discovery;
 
+use alloc::boxed::Box;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use alloc_cortex_m::CortexMHeap;
@@ -30,16 +31,15 @@ use smoltcp::{
     wire::{EthernetAddress, IpEndpoint},
 };
 use stm32f7::stm32f7x6::{
-    self as device, CorePeripherals, Interrupt, Peripherals, ETHERNET_DMA, ETHERNET_MAC, RCC, SAI2,
-    SYSCFG,
+    CorePeripherals, Interrupt, Peripherals, ETHERNET_DMA, ETHERNET_MAC, RCC, SYSCFG,
 };
 use stm32f7_discovery::{
+    audio,
     ethernet,
     future_mutex::FutureMutex,
     gpio::{GpioPort, InputPin, OutputPin},
-    i2c::I2C,
     init,
-    interrupts::{self, InterruptRequest, Priority},
+    interrupts::{self, exti::Exti15To10, InterruptRequest, Priority},
     lcd::{self, AudioWriter, Color, Framebuffer, Layer},
     random::Rng,
     sd,
@@ -69,15 +69,17 @@ fn run() -> ! {
     let mut flash = peripherals.FLASH;
     let mut fmc = peripherals.FMC;
     let mut ltdc = peripherals.LTDC;
-    let mut sai_2 = peripherals.SAI2;
+    let mut dma2d = peripherals.DMA2D;
+    let sai_2 = peripherals.SAI2;
     let mut rng = peripherals.RNG;
-    let mut sdmmc = peripherals.SDMMC1;
-    let syscfg = peripherals.SYSCFG;
+    let sdmmc = peripherals.SDMMC1;
+    let dma_2 = peripherals.DMA2;
+    let mut syscfg = peripherals.SYSCFG;
     let ethernet_mac = peripherals.ETHERNET_MAC;
     let ethernet_dma = peripherals.ETHERNET_DMA;
     let mut nvic_stir = peripherals.NVIC_STIR;
     let mut tim6 = peripherals.TIM6;
-    let exti = peripherals.EXTI;
+    let mut exti = peripherals.EXTI;
 
     init::init_system_clock_216mhz(&mut rcc, &mut pwr, &mut flash);
     init::enable_gpio_ports(&mut rcc);
@@ -111,13 +113,15 @@ fn run() -> ! {
     unsafe { ALLOCATOR.init(cortex_m_rt::heap_start() as usize, HEAP_SIZE) }
 
     lcd.set_background_color(Color::from_hex(0x006600));
-    let layer_1 = lcd.layer_1().unwrap();
+    let mut layer_1 = lcd.layer_1().unwrap();
     let mut layer_2 = lcd.layer_2().unwrap();
 
-    layer_2.clear();
+    let mut dma2d = lcd::Dma2d::new(&mut dma2d);
+    layer_1.clear(&mut dma2d);
+    layer_2.clear(&mut dma2d);
 
     // Make `println` print to the LCD
-    lcd::init_stdout(layer_2);
+    lcd::init_stdout(&mut lcd, layer_2);
 
     println!("Hello World");
 
@@ -131,12 +135,16 @@ fn run() -> ! {
     // TODO: is this needed?
     nvic.enable(Interrupt::EXTI0);
 
-    // TODO: do something with this type
-    let _sd = sd::Sd::new(&mut sdmmc, &mut rcc, &pins.sdcard_present);
+    // The sd card task runs as long as the executor, so the SD handle needs 'static references.
+    let sdmmc: &'static mut _ = Box::leak(Box::new(sdmmc));
+    let dma_2: &'static _ = Box::leak(Box::new(dma_2));
+    let sdcard_present: &'static _ = Box::leak(Box::new(pins.sdcard_present));
+    let sd = sd::Sd::new(sdmmc, dma_2, &mut rcc, sdcard_present);
 
     // audio initialization
-    init::init_sai_2(&mut sai_2, &mut rcc);
-    init::init_wm8994(&mut i2c_3).expect("WM8994 init failed");
+    let audio_config = audio::InputConfig::default();
+    let audio_input = audio::AudioInput::new(sai_2, dma_2, &mut rcc, &mut i2c_3, audio_config)
+        .expect("audio input init failed");
 
     // touch initialization should be done after audio initialization, because the touch
     // controller might not be ready yet
@@ -186,8 +194,6 @@ fn run() -> ! {
             // own channel type that uses an atomic counter instead of storing any items.
             let (idle_waker_sink, mut idle_waker_stream) = mpsc::unbounded();
             let (tim6_sink, tim6_stream) = mpsc::unbounded();
-            let (button_sink, button_stream) = mpsc::unbounded();
-            let (touch_int_sink, touch_int_stream) = mpsc::unbounded();
 
             // Interrupt handler for the TIM6_DAC interrupt, which is the interrupt triggered by
             // the tim6 timer.
@@ -202,6 +208,15 @@ fn run() -> ! {
                 })
                 .expect("registering tim6 interrupt failed");
 
+            // The SDMMC1 interrupt wakes the running sd transfer.
+            let sdmmc_interrupts = sd::SdmmcInterrupts::register(interrupt_table, Priority::P1)
+                .expect("registering sdmmc1 interrupt failed");
+
+            // The EXTI15_10 interrupt is shared by the button, the touch controller and the
+            // audio codec, the dispatcher forwards each line to its own stream.
+            let mut exti_15_10 = Exti15To10::register(interrupt_table, Priority::P1)
+                .expect("registering exti15_10 interrupt failed");
+
             // choose pin I-11 for exti11 line, which is the GPIO pin for the hardware button
             syscfg
                 .exticr3
@@ -210,18 +225,9 @@ fn run() -> ! {
             exti.rtsr.modify(|_, w| w.tr11().set_bit());
             // unmask exti11 line
             exti.imr.modify(|_, w| w.mr11().set_bit());
+            let button_stream = exti_15_10.line(11);
 
-            // choose pin I-13 for exti13 line, which is the GPIO pin signalizing a touch event
-            syscfg
-                .exticr4
-                .modify(|_, w| unsafe { w.exti13().bits(0b1000) });
-            // trigger exti13 on rising
-            exti.rtsr.modify(|_, w| w.tr13().set_bit());
-            // unmask exti13 line
-            exti.imr.modify(|_, w| w.mr13().set_bit());
-
-            // choose pin H-15 for exti15 line, which is the GPIO pin signalizing new audio data
-            // TODO: the audio interrupt doesn't work yet
+            // choose pin H-15 for exti15 line, which is the interrupt pin of the audio codec
             syscfg
                 .exticr4
                 .modify(|_, w| unsafe { w.exti15().bits(0b0111) });
@@ -229,29 +235,14 @@ fn run() -> ! {
             exti.rtsr.modify(|_, w| w.tr15().set_bit());
             // unmask exti15 line
             exti.imr.modify(|_, w| w.mr15().set_bit());
+            let audio_in_stream = exti_15_10.line(15);
 
-            // Interrupt handler for the EXTI15_10 interrupt, which is triggered by different
-            // sources.
-            interrupt_table
-                .register(InterruptRequest::EXTI15_10, Priority::P1, move || {
-                    exti.pr.modify(|r, w| {
-                        if r.pr11().bit_is_set() {
-                            button_sink
-                                .unbounded_send(())
-                                .expect("sending on button channel failed");
-                            w.pr11().set_bit();
-                        } else if r.pr13().bit_is_set() {
-                            touch_int_sink
-                                .unbounded_send(())
-                                .expect("sending on touch_int channel failed");
-                            w.pr13().set_bit();
-                        } else {
-                            panic!("unknown exti15_10 interrupt");
-                        }
-                        w
-                    });
-                })
-                .expect("registering exti15_10 interrupt failed");
+            let i2c_3_mutex = Arc::new(FutureMutex::new(i2c_3));
+            let layer_1_mutex = Arc::new(FutureMutex::new(layer_1));
+
+            // connects pin I-13, which signalizes a touch event, to the exti13 line
+            let touch_stream =
+                touch::TouchStream::register(&mut exti_15_10, &mut syscfg, &mut exti, i2c_3_mutex);
 
             let idle_stream = task_runtime::IdleStream::new(idle_waker_sink.clone());
 
@@ -259,25 +250,25 @@ fn run() -> ! {
             let ethernet_task =
                 EthernetTask::new(idle_stream.clone(), rcc, syscfg, ethernet_mac, ethernet_dma);
 
-            let i2c_3_mutex = Arc::new(FutureMutex::new(i2c_3));
-            let layer_1_mutex = Arc::new(FutureMutex::new(layer_1));
-
-            let touch_task = TouchTask {
-                touch_int_stream,
-                i2c_3_mutex: i2c_3_mutex.clone(),
-                layer_mutex: layer_1_mutex.clone(),
-            };
-
-            let audio_task = AudioTask::new(layer_1_mutex.clone(), sai_2, idle_stream.clone());
+            let audio_blocks = audio_input
+                .into_stream(interrupt_table, Priority::P1)
+                .expect("registering dma2_stream7 interrupt failed");
 
             let mut executor = task_runtime::Executor::new();
             executor.spawn_local(button_task(button_stream)).unwrap();
             executor.spawn_local(tim6_task(tim6_stream)).unwrap();
-            executor.spawn_local(touch_task.run()).unwrap();
+            executor
+                .spawn_local(touch_task(touch_stream, layer_1_mutex.clone()))
+                .unwrap();
             executor
                 .spawn_local(count_up_on_idle_task(idle_stream.clone()))
                 .unwrap();
-            executor.spawn_local(audio_task.run()).unwrap();
+            executor
+                .spawn_local(audio_task(audio_blocks, layer_1_mutex.clone()))
+                .unwrap();
+            executor
+                .spawn_local(audio_in_task(audio_in_stream))
+                .unwrap();
 
             //executor.spawn_local(print_x);
 
@@ -285,8 +276,13 @@ fn run() -> ! {
             // see https://github.com/rust-embedded/cortex-m-rt/issues/157
             executor.spawn_local(ethernet_task.run()).unwrap();
 
-            // FIXME: Does not work currently due to borrowing errors
-            // executor.spawn_local(sd_card_task(sd, idle_stream.clone())).unwrap();
+            // The exti13 line is already connected to the touch interrupt pin I-13 and can't be
+            // connected to the sd card present pin C-13 at the same time. So the present pin is
+            // checked on idle instead. Without the touch task, `sd::enable_card_detect_interrupt`
+            // and the stream `exti_15_10.line(13)` can be used instead.
+            executor
+                .spawn_local(sd_card_task(sd, idle_stream.clone(), sdmmc_interrupts))
+                .unwrap();
 
             let idle = async move {
                 loop {
@@ -299,9 +295,6 @@ fn run() -> ! {
 
             loop {
                 executor.run();
-                if pins.audio_in.get() == false {
-                    println!("audio pin false");
-                }
             }
         },
     )
@@ -316,6 +309,15 @@ async fn button_task(button_stream: impl Stream<Item = ()>) {
     }
 }
 
+async fn audio_in_task(audio_in_stream: impl Stream<Item = ()>) {
+    pin_mut!(audio_in_stream);
+    loop {
+        let next = await!(audio_in_stream.next());
+        assert!(next.is_some(), "audio_in channel closed");
+        println!("audio pin interrupt");
+    }
+}
+
 async fn tim6_task(tim6_stream: impl Stream<Item = ()>) {
     pin_mut!(tim6_stream);
     loop {
@@ -325,98 +327,38 @@ async fn tim6_task(tim6_stream: impl Stream<Item = ()>) {
     }
 }
 
-struct TouchTask<S, F>
-where
-    S: Stream<Item = ()>,
-    F: Framebuffer,
-{
-    touch_int_stream: S,
-    i2c_3_mutex: Arc<FutureMutex<I2C<device::I2C3>>>,
+async fn touch_task<F: Framebuffer>(
+    touch_stream: touch::TouchStream<impl Stream<Item = ()> + Unpin>,
     layer_mutex: Arc<FutureMutex<Layer<F>>>,
-}
-
-impl<S, F> TouchTask<S, F>
-where
-    S: Stream<Item = ()>,
-    F: Framebuffer,
-{
-    async fn run(self) {
-        let Self {
-            touch_int_stream,
-            i2c_3_mutex,
-            layer_mutex,
-        } = self;
-        pin_mut!(touch_int_stream);
-        await!(layer_mutex.with(|l| l.clear()));
-        loop {
-            await!(touch_int_stream.next()).expect("touch channel closed");
-            let touches = await!(i2c_3_mutex.with(|i2c_3| touch::touches(i2c_3))).unwrap();
-            await!(layer_mutex.with(|layer| for touch in touches {
-                layer.print_point_color_at(
-                    touch.x as usize,
-                    touch.y as usize,
-                    Color::from_hex(0xffff00),
-                );
-            }))
-        }
+) {
+    pin_mut!(touch_stream);
+    loop {
+        let event = await!(touch_stream.next()).expect("touch stream closed");
+        await!(layer_mutex.with(|layer| {
+            layer.print_point_color_at(
+                event.x as usize,
+                event.y as usize,
+                Color::from_hex(0xffff00),
+            );
+        }));
     }
 }
 
-struct AudioTask<F, S>
-where
-    F: Framebuffer,
-    S: Stream<Item = ()>,
-{
-    sai_2: SAI2,
-    idle_stream: S,
+async fn audio_task<F: Framebuffer>(
+    mut audio_blocks: audio::SampleBlocks<'static>,
     layer_mutex: Arc<FutureMutex<Layer<F>>>,
-    audio_writer: AudioWriter,
-}
-
-impl<F, S> AudioTask<F, S>
-where
-    F: Framebuffer,
-    S: Stream<Item = ()>,
-{
-    fn new(layer_mutex: Arc<FutureMutex<Layer<F>>>, sai_2: SAI2, idle_stream: S) -> Self {
-        Self {
-            sai_2,
-            idle_stream,
-            layer_mutex,
-            audio_writer: AudioWriter::new(),
-        }
-    }
-
-    async fn run(self) {
-        let Self {
-            idle_stream,
-            layer_mutex,
-            mut audio_writer,
-            sai_2,
-        } = self;
-        pin_mut!(idle_stream);
-
-        let mut data0_buffer = None;
-        loop {
-            // FIXME: replace with actual interrupt stream when we get audio interrupts working
-            await!(idle_stream.next());
-
-            // poll for new audio data
-            if sai_2.bsr.read().freq().bit_is_set() {
-                // fifo_request_flag is set -> new data available
-                let data = sai_2.bdr.read().data().bits();
-                match data0_buffer {
-                    None => {
-                        data0_buffer = Some(data);
-                    }
-                    Some(data0) => {
-                        let data1 = data;
-                        await!(layer_mutex.with(|l| audio_writer.set_next_col(l, data0, data1)));
-                        data0_buffer = None;
-                    }
-                }
-            }
-        }
+) {
+    let mut audio_writer = AudioWriter::new();
+    loop {
+        let block = await!(audio_blocks.next())
+            .expect("audio stream closed")
+            .expect("audio input failed");
+        // the stereo block contains pairs of left and right samples
+        await!(layer_mutex.with(|layer| for frame in block.chunks(2) {
+            let (left, right) = (i32::from(frame[0]) as u32, i32::from(frame[1]) as u32);
+            audio_writer.set_next_col(layer, left, right);
+        }));
+        audio_blocks.recycle(block);
     }
 }
 
@@ -432,21 +374,30 @@ async fn count_up_on_idle_task(idle_stream: impl Stream<Item = ()>) {
     }
 }
 
-async fn sd_card_task<S, P>(mut sd: sd::Sd<'static, P>, idle_stream: S)
-where
-    S: Stream<Item = ()>,
+async fn sd_card_task<P, I>(
+    mut sd: sd::Sd<'static, P>,
+    card_detect_stream: I,
+    mut sdmmc_interrupts: sd::SdmmcInterrupts,
+) where
     P: InputPin,
+    I: Stream<Item = ()> + Unpin,
 {
-    pin_mut!(idle_stream);
+    let mut card_events = sd.card_detect(card_detect_stream);
     // Initialize the SD Card on insert and deinitialize on extract.
     loop {
-        await!(idle_stream.next());
-        if sd.card_present() && !sd.card_initialized() {
-            if let Some(i_err) = sd::init(&mut sd).err() {
-                println!("{:?}", i_err);
+        match await!(card_events.next()).expect("card detect stream closed") {
+            sd::CardEvent::Inserted => {
+                if let Some(i_err) = sd::init(&mut sd).err() {
+                    println!("{:?}", i_err);
+                    continue;
+                }
+                // read the first block, which contains the master boot record
+                match await!(sd.read_blocks_async(0, 1, &mut sdmmc_interrupts)) {
+                    Ok(data) => println!("sd card inserted, first words: {:x?}", &data[..4]),
+                    Err(r_err) => println!("{:?}", r_err),
+                }
             }
-        } else if !sd.card_present() && sd.card_initialized() {
-            sd::de_init(&mut sd);
+            sd::CardEvent::Removed => sd::de_init(&mut sd),
         }
     }
 }
@@ -58,9 +58,11 @@ fn main() -> ! {
     let mut flash = peripherals.FLASH;
     let mut fmc = peripherals.FMC;
     let mut ltdc = peripherals.LTDC;
+    let mut dma2d = peripherals.DMA2D;
     let mut sai_2 = peripherals.SAI2;
     let mut rng = peripherals.RNG;
     let mut sdmmc = peripherals.SDMMC1;
+    let dma_2 = peripherals.DMA2;
     let mut syscfg = peripherals.SYSCFG;
     let mut ethernet_mac = peripherals.ETHERNET_MAC;
     let ethernet_dma = peripherals.ETHERNET_DMA;
@@ -95,15 +97,17 @@ fn main() -> ! {
     let mut layer_1 = lcd.layer_1().unwrap();
     let mut layer_2 = lcd.layer_2().unwrap();
 
-    layer_1.clear();
-    layer_2.clear();
-    lcd::init_stdout(layer_2);
+    let mut dma2d = lcd::Dma2d::new(&mut dma2d);
+    layer_1.clear(&mut dma2d);
+    layer_2.clear(&mut dma2d);
 
-    println!("Hello World");
-
-    // Initialize the allocator BEFORE you use it
+    // Initialize the allocator BEFORE you use it, the stdout terminal needs it
     unsafe { ALLOCATOR.init(cortex_m_rt::heap_start() as usize, HEAP_SIZE) }
 
+    lcd::init_stdout(&mut lcd, layer_2);
+
+    println!("Hello World");
+
     let _xs = vec![1, 2, 3];
 
     let mut i2c_3 = init::init_i2c_3(peripherals.I2C3, &mut rcc);
@@ -112,7 +116,7 @@ fn main() -> ! {
 
     nvic.enable(Interrupt::EXTI0);
 
-    let mut sd = sd::Sd::new(&mut sdmmc, &mut rcc, &pins.sdcard_present);
+    let mut sd = sd::Sd::new(&mut sdmmc, &dma_2, &mut rcc, &pins.sdcard_present);
 
     init::init_sai_2(&mut sai_2, &mut rcc);
     init::init_wm8994(&mut i2c_3).expect("WM8994 init failed");
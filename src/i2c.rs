@@ -19,7 +19,7 @@ impl I2cTrait for device::I2C3 {}
 pub struct I2C<I: I2cTrait>(I);
 
 /// Errors that can happen while accessing the I2C bus.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     /// A NACK flag (negative acknowledgement) was detected.
     Nack,
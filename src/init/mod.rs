@@ -3,6 +3,7 @@
 use crate::i2c::{self, I2C};
 use crate::lcd::{self, Lcd};
 use crate::system_clock;
+use crate::wm8994;
 use stm32f7::stm32f7x6::{self as device, FLASH, FMC, LTDC, PWR, RCC, SAI2, SYST};
 
 pub use self::pins::init as pins;
@@ -305,7 +306,8 @@ pub fn init_i2c_3(i2c: device::I2C3, rcc: &mut RCC) -> I2C<device::I2C3> {
 
 /// Initializes the SAI2 controller.
 ///
-/// Required for audio input.
+/// Required for audio input by polling the data register of block B. The
+/// [`AudioInput`](crate::audio::AudioInput) configures SAI2 itself.
 pub fn init_sai_2(sai: &mut SAI2, rcc: &mut RCC) {
     let audio_frequency = 16000;
 
@@ -521,95 +523,19 @@ pub fn init_sai_2(sai: &mut SAI2, rcc: &mut RCC) {
     sai.bcr1.modify(|_, w| w.saiben().set_bit()); // audio_block_enable
 }
 
-const WM8994_ADDRESS: i2c::Address = i2c::Address::bits_7(0b001_1010);
-
-/// Initializes the WM8994 audio controller.
+/// Initializes the WM8994 audio controller with the default
+/// [`wm8994::Config`](crate::wm8994::Config), which records the digital microphones.
 ///
 /// Required for audio input.
-pub fn init_wm8994(i2c_3: &mut i2c::I2C<device::I2C3>) -> Result<(), i2c::Error> {
-    i2c_3.connect::<u16, _>(WM8994_ADDRESS, |mut conn| {
-        // read and check device family ID
-        assert_eq!(conn.read(0).ok(), Some(0x8994));
-        // reset device
-        conn.write(0, 0)?;
-
-        // wm8994 Errata Work-Arounds
-        conn.write(0x102, 0x0003)?;
-        conn.write(0x817, 0x0000)?;
-        conn.write(0x102, 0x0000)?;
-
-        // Enable VMID soft start (fast), Start-up Bias Current Enabled
-        conn.write(0x39, 0x006C)?;
-
-        // Enable bias generator, Enable VMID
-        conn.write(0x01, 0x0003)?;
-
-        system_clock::wait_ms(50);
-
-        // INPUT_DEVICE_DIGITAL_MICROPHONE_2 :
-
-        // Enable AIF1ADC2 (Left), Enable AIF1ADC2 (Right)
-        // Enable DMICDAT2 (Left), Enable DMICDAT2 (Right)
-        // Enable Left ADC, Enable Right ADC
-        conn.write(0x04, 0x0C30)?;
-
-        // Enable AIF1 DRC2 Signal Detect & DRC in AIF1ADC2 Left/Right Timeslot 1
-        conn.write(0x450, 0x00DB)?;
-
-        // Disable IN1L, IN1R, IN2L, IN2R, Enable Thermal sensor & shutdown
-        conn.write(0x02, 0x6000)?;
-
-        // Enable the DMIC2(Left) to AIF1 Timeslot 1 (Left) mixer path
-        conn.write(0x608, 0x0002)?;
-
-        // Enable the DMIC2(Right) to AIF1 Timeslot 1 (Right) mixer path
-        conn.write(0x609, 0x0002)?;
-
-        // GPIO1 pin configuration GP1_DIR = output, GP1_FN = AIF1 DRC2 signal detect
-        conn.write(0x700, 0x000E)?;
-
-        // Clock Configurations
-
-        // AIF1 Sample Rate = 16 (KHz), ratio=256
-        conn.write(0x210, 0x0033)?;
-
-        // AIF1 Word Length = 16-bits, AIF1 Format = I2S (Default Register Value)
-        conn.write(0x300, 0x4010)?;
-
-        // slave mode
-        conn.write(0x302, 0x0000)?;
-
-        // Enable the DSP processing clock for AIF1, Enable the core clock
-        conn.write(0x208, 0x000A)?;
-
-        // Enable AIF1 Clock, AIF1 Clock Source = MCLK1 pin
-        conn.write(0x200, 0x0001)?;
-
-        // Enable Microphone bias 1 generator, Enable VMID
-        conn.write(0x01, 0x0013)?;
-
-        // ADC oversample enable
-        conn.write(0x620, 0x0002)?;
-
-        // AIF ADC2 HPF enable, HPF cut = voice mode 1 fc=127Hz at fs=8kHz
-        conn.write(0x411, 0x3800)?;
-
-        // set volume
-
-        let convertedvol = 239; // 100(+17.625dB)
-
-        // Left AIF1 ADC1 volume
-        conn.write(0x400, convertedvol | 0x100)?;
-
-        // Right AIF1 ADC1 volume
-        conn.write(0x401, convertedvol | 0x100)?;
-
-        // Left AIF1 ADC2 volume
-        conn.write(0x404, convertedvol | 0x100)?;
-
-        // Right AIF1 ADC2 volume
-        conn.write(0x405, convertedvol | 0x100)?;
-
-        Ok(())
-    })
+pub fn init_wm8994(
+    i2c_3: &mut i2c::I2C<device::I2C3>,
+) -> Result<(), wm8994::Error<i2c::Error>> {
+    let mut result = Ok(());
+    i2c_3
+        .connect::<u16, _>(wm8994::ADDRESS, |mut conn| {
+            result = wm8994::init(&mut conn, &wm8994::Config::default(), system_clock::wait_ms);
+            Ok(())
+        })
+        .map_err(wm8994::Error::Access)?;
+    result
 }
@@ -25,9 +25,11 @@ pub struct Pins<
     pub backlight: Backlight,
     /// This pin reports whether there is a card in the SD card slot.
     pub sdcard_present: SdcardPresent,
-    /// This pin reports whether there is new audio data from the microphone.
+    /// This pin is the interrupt line of the WM8994 audio codec.
     ///
-    /// **Does not work currently**
+    /// The microphone samples don't arrive through this pin but through block B of SAI2, which
+    /// is configured together with it. Use [`AudioInput`](crate::audio::AudioInput) to record
+    /// them.
     pub audio_in: AudioIn,
 }
 
@@ -0,0 +1,119 @@
+//! Sharing the EXTI15_10 interrupt between the EXTI lines 10 to 15.
+//!
+//! Only one handler can be registered for an interrupt, but the lines 10 to 15 all trigger the
+//! EXTI15_10 interrupt, e.g. the button on pin I-11 and the touch controller on pin I-13. The
+//! [`Exti15To10`](Exti15To10) dispatcher registers the handler once and forwards the interrupts
+//! of each line to its own stream.
+
+use super::primask_mutex::PrimaskMutex;
+use super::{Error, Ic, InterruptHandle, InterruptRequest, InterruptTable, Priority};
+use crate::task_runtime::mpsc;
+use alloc::sync::Arc;
+use stm32f7::stm32f7x6::EXTI;
+
+const FIRST_LINE: u8 = 10;
+const LAST_LINE: u8 = 15;
+
+type Sinks = [Option<mpsc::UnboundedSender<()>>; (LAST_LINE - FIRST_LINE + 1) as usize];
+
+/// Dispatches the EXTI15_10 interrupt to a stream per EXTI line.
+///
+/// The handler clears the pending bits of the lines 10 to 15 and sends an item to the streams of
+/// the pending lines. The lines themselves are connected to their pins and unmasked by the
+/// drivers, e.g. [`touch::TouchStream::register`](crate::touch::TouchStream::register).
+///
+/// Dropping the dispatcher doesn't unregister the handler, it stays registered until the end of
+/// the interrupt scope. Use [`release`](Exti15To10::release) to unregister it.
+///
+/// # Examples
+/// ```rust
+/// let mut exti_15_10 = Exti15To10::register(interrupt_table, Priority::P1)
+///     .expect("registering exti15_10 interrupt failed");
+///
+/// // choose pin I-11 for exti11 line, which is the GPIO pin for the hardware button
+/// syscfg.exticr3.modify(|_, w| unsafe { w.exti11().bits(0b1000) });
+/// exti.rtsr.modify(|_, w| w.tr11().set_bit());
+/// exti.imr.modify(|_, w| w.mr11().set_bit());
+/// let button_stream = exti_15_10.line(11);
+///
+/// let touch_stream =
+///     touch::TouchStream::register(&mut exti_15_10, &mut syscfg, &mut exti, i2c_3_mutex);
+/// ```
+pub struct Exti15To10 {
+    sinks: Arc<PrimaskMutex<Sinks>>,
+    interrupt: Option<InterruptHandle<(), InterruptRequest>>,
+}
+
+impl Exti15To10 {
+    /// Registers the handler of the EXTI15_10 interrupt with the given priority.
+    ///
+    /// # Errors
+    ///
+    /// Returns an Error if an EXTI15_10 handler is already registered.
+    pub fn register<'t>(
+        interrupt_table: &mut InterruptTable<'t, Ic<'t>>,
+        priority: Priority,
+    ) -> Result<Exti15To10, Error> {
+        let sinks: Arc<PrimaskMutex<Sinks>> = Arc::new(PrimaskMutex::new(Default::default()));
+        let handler_sinks = sinks.clone();
+        let interrupt =
+            interrupt_table.register(InterruptRequest::EXTI15_10, priority, move || {
+                // PR is only written with the pending bits of the lines 10 to 15, which are
+                // cleared by writing a 1, so the other lines are not affected.
+                let exti = unsafe { &*EXTI::ptr() };
+                let pending = exti.pr.read().bits() & line_mask();
+                exti.pr.write(|w| unsafe { w.bits(pending) });
+                handler_sinks.lock(|sinks| {
+                    for (line, sink) in (FIRST_LINE..=LAST_LINE).zip(sinks.iter()) {
+                        if let Some(sink) = sink {
+                            if pending & (1 << line) != 0 {
+                                // fails only if the stream was dropped
+                                let _ = sink.unbounded_send(());
+                            }
+                        }
+                    }
+                });
+            })?;
+        Ok(Exti15To10 {
+            sinks,
+            interrupt: Some(interrupt),
+        })
+    }
+
+    /// Returns a stream that yields an item whenever `line` triggers the interrupt. A previous
+    /// stream of the line ends.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `line` is not between 10 and 15.
+    pub fn line(&mut self, line: u8) -> mpsc::UnboundedReceiver<()> {
+        assert!(
+            line >= FIRST_LINE && line <= LAST_LINE,
+            "EXTI15_10 only serves the lines 10 to 15"
+        );
+        let (sink, stream) = mpsc::unbounded();
+        self.sinks
+            .lock(|sinks| sinks[usize::from(line - FIRST_LINE)] = Some(sink));
+        stream
+    }
+
+    /// Unregisters the EXTI15_10 interrupt handler, which ends the streams of all lines.
+    ///
+    /// The `interrupt_table` must be the one that was passed to
+    /// [`register`](Exti15To10::register).
+    pub fn release<'t>(mut self, interrupt_table: &mut InterruptTable<'t, Ic<'t>>) {
+        if let Some(interrupt) = self.interrupt.take() {
+            interrupt_table.unregister(interrupt);
+        }
+        self.sinks.lock(|sinks| {
+            for sink in sinks.iter_mut() {
+                *sink = None;
+            }
+        });
+    }
+}
+
+// The bits of the lines 10 to 15 in the EXTI registers.
+fn line_mask() -> u32 {
+    (FIRST_LINE..=LAST_LINE).fold(0, |mask, line| mask | 1 << line)
+}
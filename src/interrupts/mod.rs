@@ -4,6 +4,7 @@
 
 pub use interrupture_stm32f7x6::*;
 
+pub mod exti;
 pub mod primask_mutex;
 
 /// Wait for interrupt.
@@ -0,0 +1,268 @@
+//! Runtime configuration of the LTDC layers.
+
+use super::{
+    Blitter, Buffering, Color, Lcd, Layer, LayerId, PixelBuffer, PixelFormat, Rect,
+    BACK_BUFFER_LENGTH, HEIGHT, LAYER_1_BACK_BUFFERS_START, LAYER_1_LENGTH, LAYER_1_START,
+    LAYER_2_BACK_BUFFERS_START, LAYER_2_LENGTH, LAYER_2_START, WIDTH,
+};
+use stm32f7::stm32f7x6::LTDC;
+
+/// Errors that can occur when configuring a layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The window is empty or not completely on the display.
+    InvalidWindow,
+    /// The framebuffer of the layer is too small for the window in the pixel format.
+    ///
+    /// The framebuffer of layer 2 has 2 bytes per pixel for the whole display, so larger
+    /// formats are only possible with a smaller window.
+    BufferTooSmall,
+}
+
+/// Selects how a layer is blended with the layers below it.
+///
+/// The color of a pixel is `factor_1 * layer_color + factor_2 * color_below`. The first factor is
+/// the one selected here, the second factor is `1 - factor_1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendingFactor {
+    /// Uses the constant alpha of the layer.
+    ConstantAlpha,
+    /// Uses the alpha channel of the pixel multiplied with the constant alpha of the layer.
+    PixelAlphaTimesConstantAlpha,
+}
+
+/// The configuration of a layer.
+///
+/// # Examples
+/// ```rust
+/// // show layer 2 as half transparent RGB565 window in the center of the display
+/// let config = lcd::LayerConfig {
+///     window: lcd::Rect::new(140, 86, 200, 100),
+///     constant_alpha: 128,
+///     blending_factor: lcd::BlendingFactor::ConstantAlpha,
+///     ..lcd::LayerConfig::new(lcd::PixelFormat::Rgb565)
+/// };
+/// lcd.configure_layer(lcd::LayerId::Layer2, config).expect("invalid layer config");
+/// let layer_2 = lcd.layer(lcd::LayerId::Layer2, lcd::Buffering::Single, &mut dma2d);
+/// let mut layer_2 = layer_2.unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayerConfig {
+    /// The pixel format of the framebuffer.
+    pub format: PixelFormat,
+    /// The area of the display that shows the layer. The framebuffer has the size of the window.
+    pub window: Rect,
+    /// The constant alpha, which is used by the blending factor.
+    pub constant_alpha: u8,
+    /// How the layer is blended with the layers below it.
+    pub blending_factor: BlendingFactor,
+    /// The color outside of the window.
+    pub default_color: Color,
+    /// Whether the layer is shown.
+    pub enabled: bool,
+}
+
+// Horizontal and vertical positions of the first active pixel (accumulated back porch + 1).
+const ACTIVE_START_X: usize = 41 + 13;
+const ACTIVE_START_Y: usize = 10 + 2;
+
+// Writes the registers of layer `l1` or `l2`. The registers of the two layers have different
+// types, so this can't be a function.
+macro_rules! write_layer_registers {
+    (
+        @registers $ltdc:expr, $config:expr, $whpcr:ident, $wvpcr:ident, $pfcr:ident, $dccr:ident,
+        $cacr:ident, $bfcr:ident, $cfblr:ident, $cfblnr:ident, $cr:ident
+    ) => {{
+        let ltdc: &mut LTDC = $ltdc;
+        let config: LayerConfig = $config;
+        let window = config.window;
+        let line_length = (window.width * config.format.bytes_per_pixel()) as u16;
+        let (factor_1, factor_2) = blending_factors(config.blending_factor);
+
+        // configure horizontal and vertical start and stop position
+        ltdc.$whpcr.modify(|_, w| unsafe {
+            w.whstpos().bits((window.x + ACTIVE_START_X) as u16);
+            w.whsppos().bits((window.x + window.width + ACTIVE_START_X - 1) as u16);
+            w
+        });
+        ltdc.$wvpcr.modify(|_, w| unsafe {
+            w.wvstpos().bits((window.y + ACTIVE_START_Y) as u16);
+            w.wvsppos().bits((window.y + window.height + ACTIVE_START_Y - 1) as u16);
+            w
+        });
+
+        ltdc.$pfcr
+            .modify(|_, w| unsafe { w.pf().bits(config.format.color_mode()) });
+
+        ltdc.$dccr.modify(|_, w| unsafe {
+            w.dcalpha().bits(config.default_color.alpha);
+            w.dcred().bits(config.default_color.red);
+            w.dcgreen().bits(config.default_color.green);
+            w.dcblue().bits(config.default_color.blue);
+            w
+        });
+
+        ltdc.$cacr
+            .modify(|_, w| unsafe { w.consta().bits(config.constant_alpha) });
+        ltdc.$bfcr.modify(|_, w| unsafe {
+            w.bf1().bits(factor_1);
+            w.bf2().bits(factor_2);
+            w
+        });
+
+        // configure color frame buffer line length, pitch and line number
+        ltdc.$cfblr.modify(|_, w| unsafe {
+            w.cfbp().bits(line_length); // pitch
+            w.cfbll().bits(line_length + 3); // line_length
+            w
+        });
+        ltdc.$cfblnr
+            .modify(|_, w| unsafe { w.cfblnbr().bits(window.height as u16) });
+
+        // the color lookup table of the layer is needed by the luminance formats
+        ltdc.$cr.modify(|_, w| {
+            w.len().bit(config.enabled);
+            w.cluten().bit(config.format.is_luminance());
+            w
+        });
+    }};
+    ($ltdc:expr, $config:expr, l1) => {
+        write_layer_registers!(
+            @registers $ltdc, $config, l1whpcr, l1wvpcr, l1pfcr, l1dccr, l1cacr, l1bfcr,
+            l1cfblr, l1cfblnr, l1cr
+        )
+    };
+    ($ltdc:expr, $config:expr, l2) => {
+        write_layer_registers!(
+            @registers $ltdc, $config, l2whpcr, l2wvpcr, l2pfcr, l2dccr, l2cacr, l2bfcr,
+            l2cfblr, l2cfblnr, l2cr
+        )
+    };
+}
+
+impl LayerConfig {
+    /// Creates a configuration for an enabled full-screen layer that is blended by the alpha
+    /// channel of its pixels. This is the configuration that `lcd::init` uses, with ARGB8888 for
+    /// layer 1 and AL88 for layer 2.
+    pub const fn new(format: PixelFormat) -> LayerConfig {
+        LayerConfig {
+            format,
+            window: Rect::new(0, 0, WIDTH, HEIGHT),
+            constant_alpha: 255,
+            blending_factor: BlendingFactor::PixelAlphaTimesConstantAlpha,
+            default_color: Color::rgba(0, 0, 0, 0),
+            enabled: true,
+        }
+    }
+
+    fn framebuffer_length(&self) -> usize {
+        self.window.width * self.window.height * self.format.bytes_per_pixel()
+    }
+}
+
+impl<'a> Lcd<'a> {
+    /// Changes the configuration of a layer.
+    ///
+    /// The change is applied in the next vertical blanking period. The content of the
+    /// framebuffer is not converted, and the layers returned by `layer_1` and `layer_2` assume
+    /// the configuration of `lcd::init`, so use [`layer`](Lcd::layer) to draw on a reconfigured
+    /// layer. The luminance formats show the colors of the lookup table of the layer, see
+    /// [`set_color_lookup_table`](Lcd::set_color_lookup_table).
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidWindow` if the window doesn't fit on the display and `BufferTooSmall` if
+    /// the framebuffer of the layer can't hold the window in the pixel format.
+    pub fn configure_layer(&mut self, id: LayerId, config: LayerConfig) -> Result<(), Error> {
+        let window = config.window;
+        if window.is_empty() || window.x + window.width > WIDTH || window.y + window.height > HEIGHT
+        {
+            return Err(Error::InvalidWindow);
+        }
+        let buffer_length = match id {
+            LayerId::Layer1 => LAYER_1_LENGTH,
+            LayerId::Layer2 => LAYER_2_LENGTH,
+        };
+        if config.framebuffer_length() > buffer_length {
+            return Err(Error::BufferTooSmall);
+        }
+
+        match id {
+            LayerId::Layer1 => write_layer_registers!(self.controller, config, l1),
+            LayerId::Layer2 => write_layer_registers!(self.controller, config, l2),
+        }
+        self.controller.srcr.modify(|_, w| w.vbr().set_bit()); // VERTICAL_BLANKING_RELOAD
+        self.layer_configs[layer_index(id)] = config;
+        Ok(())
+    }
+
+    /// Returns the current configuration of a layer.
+    pub fn layer_config(&self, id: LayerId) -> LayerConfig {
+        self.layer_configs[layer_index(id)]
+    }
+
+    /// Shows or hides a layer. The change is applied in the next vertical blanking period.
+    pub fn set_layer_enabled(&mut self, id: LayerId, enabled: bool) {
+        let mut config = self.layer_config(id);
+        config.enabled = enabled;
+        self.configure_layer(id, config)
+            .expect("current layer config is invalid");
+    }
+
+    /// Returns a reference to a layer that draws in the configured pixel format and window size.
+    ///
+    /// Pixels outside of the window are ignored. The back buffers are cleared with `blitter`,
+    /// see [`swap_buffers`](Lcd::swap_buffers) for the buffering. Each layer can only be taken
+    /// once, also by [`layer_1`](Lcd::layer_1) and [`layer_2`](Lcd::layer_2).
+    pub fn layer<B: Blitter>(
+        &mut self,
+        id: LayerId,
+        buffering: Buffering,
+        blitter: &mut B,
+    ) -> Option<Layer<PixelBuffer>> {
+        let in_use = match id {
+            LayerId::Layer1 => self.layer_1_in_use,
+            LayerId::Layer2 => self.layer_2_in_use,
+        };
+        if in_use {
+            return None;
+        }
+        match id {
+            LayerId::Layer1 => self.layer_1_in_use = true,
+            LayerId::Layer2 => self.layer_2_in_use = true,
+        }
+
+        let config = self.layer_config(id);
+        let (front_buffer, back_buffers_start) = match id {
+            LayerId::Layer1 => (LAYER_1_START, LAYER_1_BACK_BUFFERS_START),
+            LayerId::Layer2 => (LAYER_2_START, LAYER_2_BACK_BUFFERS_START),
+        };
+        let buffer = |addr| unsafe {
+            PixelBuffer::from_raw_parts(
+                addr,
+                config.window.width,
+                config.window.height,
+                config.format,
+            )
+        };
+        let back_buffers = (0..buffering.back_buffers())
+            .map(|i| buffer(back_buffers_start + i * BACK_BUFFER_LENGTH));
+        Some(Layer::new(id, buffer(front_buffer), back_buffers, blitter))
+    }
+}
+
+fn layer_index(id: LayerId) -> usize {
+    match id {
+        LayerId::Layer1 => 0,
+        LayerId::Layer2 => 1,
+    }
+}
+
+fn blending_factors(factor: BlendingFactor) -> (u8, u8) {
+    match factor {
+        // ConstantAlpha and OneMinusConstantAlpha
+        BlendingFactor::ConstantAlpha => (0b100, 0b101),
+        // PixelAlphaTimesConstantAlpha and OneMinusPixelAlphaTimesConstantAlpha
+        BlendingFactor::PixelAlphaTimesConstantAlpha => (0b110, 0b111),
+    }
+}
@@ -0,0 +1,480 @@
+//! Accelerated filling, copying and blending of pixel buffers with the DMA2D (Chrom-ART).
+//!
+//! The operations are defined by the [`Blitter`](Blitter) trait, which is implemented by the
+//! hardware accelerated [`Dma2d`](Dma2d) and by the [`Software`](Software) renderer. The
+//! software renderer only accesses memory, so it can run on the host to compare its results
+//! with the hardware.
+
+use super::{Color, Framebuffer, PixelFormat, Rect};
+use core::ptr;
+use stm32f7::stm32f7x6::DMA2D;
+
+/// Errors reported by the DMA2D.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The DMA2D accessed an invalid address.
+    TransferError,
+    /// The DMA2D was configured with invalid parameters.
+    ConfigurationError,
+    /// The framebuffer is not in memory, see
+    /// [`Framebuffer::pixel_buffer`](Framebuffer::pixel_buffer).
+    NoPixelBuffer,
+}
+
+/// A rectangular area of pixels in memory.
+///
+/// The lines of the buffer can be longer than its width, so that a buffer can describe a part
+/// of a larger buffer, see [`sub_buffer`](PixelBuffer::sub_buffer).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelBuffer {
+    addr: usize,
+    width: usize,
+    height: usize,
+    line_length: usize,
+    format: PixelFormat,
+}
+
+impl PixelBuffer {
+    /// Creates a buffer of `width * height` pixels that starts at `addr`.
+    ///
+    /// # Safety
+    ///
+    /// The memory must be valid for reads and writes of `width * height` pixels of the given
+    /// format for as long as the buffer is used.
+    pub const unsafe fn from_raw_parts(
+        addr: usize,
+        width: usize,
+        height: usize,
+        format: PixelFormat,
+    ) -> Self {
+        PixelBuffer {
+            addr,
+            width,
+            height,
+            line_length: width,
+            format,
+        }
+    }
+
+    /// Returns the address of the first pixel.
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    /// Returns the width of the buffer in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the buffer in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the pixel format of the buffer.
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Returns the buffer for the area of `rect`, which is clipped to this buffer.
+    pub fn sub_buffer(&self, rect: Rect) -> PixelBuffer {
+        let rect = rect.intersection(Rect::new(0, 0, self.width, self.height));
+        PixelBuffer {
+            addr: self.pixel_addr(rect.x, rect.y),
+            width: rect.width,
+            height: rect.height,
+            line_length: self.line_length,
+            format: self.format,
+        }
+    }
+
+    /// Returns the color of the pixel at the specified coordinates.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Color {
+        assert!(x < self.width && y < self.height);
+        let addr = self.pixel_addr(x, y);
+        let mut raw = 0;
+        for i in 0..self.format.bytes_per_pixel() {
+            let byte = unsafe { ptr::read_volatile((addr + i) as *const u8) };
+            raw |= u32::from(byte) << (8 * i);
+        }
+        self.format.decode(raw)
+    }
+
+    /// Sets the pixel at the specified coordinates to the specified color.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        assert!(x < self.width && y < self.height);
+        self.write_raw(self.pixel_addr(x, y), self.format.encode(color));
+    }
+
+    fn pixel_addr(&self, x: usize, y: usize) -> usize {
+        self.addr + (y * self.line_length + x) * self.format.bytes_per_pixel()
+    }
+
+    fn write_raw(&mut self, addr: usize, raw: u32) {
+        match self.format.bytes_per_pixel() {
+            4 if addr % 4 == 0 => unsafe { ptr::write_volatile(addr as *mut u32, raw) },
+            2 if addr % 2 == 0 => unsafe { ptr::write_volatile(addr as *mut u16, raw as u16) },
+            // RGB888 pixels and buffers in unaligned memory are written byte-wise
+            bytes => {
+                for i in 0..bytes {
+                    unsafe { ptr::write_volatile((addr + i) as *mut u8, (raw >> (8 * i)) as u8) };
+                }
+            }
+        }
+    }
+
+    // The number of pixels that are skipped at the end of each line.
+    fn line_offset(&self) -> usize {
+        self.line_length - self.width
+    }
+}
+
+impl Framebuffer for PixelBuffer {
+    /// Sets the pixel at the specified coordinates, if it is inside of the buffer.
+    fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.width && y < self.height {
+            PixelBuffer::set_pixel(self, x, y, color);
+        }
+    }
+
+    fn pixel_buffer(&mut self) -> Option<PixelBuffer> {
+        Some(*self)
+    }
+}
+
+/// Operations on pixel buffers.
+///
+/// If the source and the destination buffer have different sizes, only the area that fits into
+/// both buffers, starting at the top left corner, is processed.
+pub trait Blitter {
+    /// Sets all pixels of `dst` to `color`.
+    fn fill(&mut self, dst: PixelBuffer, color: Color) -> Result<(), Error>;
+
+    /// Copies the pixels of `src` to `dst` and converts them to the format of `dst`.
+    ///
+    /// The buffers must not overlap, unless `dst` starts before `src` in memory.
+    fn copy(&mut self, src: PixelBuffer, dst: PixelBuffer) -> Result<(), Error>;
+
+    /// Blends the pixels of `fg` on top of the pixels of `dst`.
+    ///
+    /// The alpha channel of each foreground pixel is multiplied with `alpha`, so 255 uses the
+    /// alpha channel of the pixels unchanged.
+    fn blend(&mut self, fg: PixelBuffer, dst: PixelBuffer, alpha: u8) -> Result<(), Error>;
+}
+
+/// Renders on the CPU.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Software;
+
+impl Blitter for Software {
+    fn fill(&mut self, mut dst: PixelBuffer, color: Color) -> Result<(), Error> {
+        let raw = dst.format.encode(color);
+        for y in 0..dst.height {
+            for x in 0..dst.width {
+                dst.write_raw(dst.pixel_addr(x, y), raw);
+            }
+        }
+        Ok(())
+    }
+
+    fn copy(&mut self, src: PixelBuffer, dst: PixelBuffer) -> Result<(), Error> {
+        let (src, mut dst) = common_area(src, dst);
+        for y in 0..dst.height {
+            for x in 0..dst.width {
+                dst.set_pixel(x, y, src.get_pixel(x, y));
+            }
+        }
+        Ok(())
+    }
+
+    fn blend(&mut self, fg: PixelBuffer, dst: PixelBuffer, alpha: u8) -> Result<(), Error> {
+        let (fg, mut dst) = common_area(fg, dst);
+        for y in 0..dst.height {
+            for x in 0..dst.width {
+                let color = blend_pixel(fg.get_pixel(x, y), dst.get_pixel(x, y), alpha);
+                dst.set_pixel(x, y, color);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Clips both buffers to the area that fits into both of them.
+fn common_area(a: PixelBuffer, b: PixelBuffer) -> (PixelBuffer, PixelBuffer) {
+    let area = Rect::new(0, 0, a.width.min(b.width), a.height.min(b.height));
+    (a.sub_buffer(area), b.sub_buffer(area))
+}
+
+// Blends two colors with the formula of the DMA2D.
+pub(super) fn blend_pixel(fg: Color, bg: Color, alpha: u8) -> Color {
+    let fg_alpha = u32::from(fg.alpha) * u32::from(alpha) / 255;
+    let bg_alpha = u32::from(bg.alpha);
+    let mult = fg_alpha * bg_alpha / 255;
+    let out_alpha = fg_alpha + bg_alpha - mult;
+    if out_alpha == 0 {
+        return Color::rgba(0, 0, 0, 0);
+    }
+    let channel = |fg: u8, bg: u8| {
+        let (fg, bg) = (u32::from(fg), u32::from(bg));
+        ((fg * fg_alpha + bg * bg_alpha - bg * mult) / out_alpha) as u8
+    };
+    Color::rgba(
+        channel(fg.red, bg.red),
+        channel(fg.green, bg.green),
+        channel(fg.blue, bg.blue),
+        out_alpha as u8,
+    )
+}
+
+/// Renders with the DMA2D.
+///
+/// The DMA2D can't write the luminance formats and has no lookup table loaded, so operations
+/// that would need this fall back to the [`Software`](Software) renderer. Filling and copying
+/// buffers of the same format works for all formats with at least 16 bits per pixel, because
+/// the pixels are transferred unchanged.
+///
+/// The DMA2D clock is enabled by [`lcd::init`](super::init).
+///
+/// # Examples
+/// ```rust
+/// let mut lcd = lcd::init(&mut ltdc, &mut rcc);
+/// let mut dma2d = lcd::Dma2d::new(&mut dma2d);
+/// let mut layer_1 = lcd.layer_1().unwrap();
+///
+/// layer_1
+///     .fill_rect(&mut dma2d, lcd::Rect::new(10, 10, 100, 50), Color::rgb(255, 0, 0))
+///     .expect("DMA2D error");
+/// ```
+pub struct Dma2d<'a> {
+    dma2d: &'a mut DMA2D,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    MemoryToMemory = 0b00,
+    MemoryToMemoryPfc = 0b01,
+    MemoryToMemoryBlending = 0b10,
+    RegisterToMemory = 0b11,
+}
+
+impl<'a> Dma2d<'a> {
+    /// Creates a new renderer that uses the passed DMA2D peripheral.
+    pub fn new(dma2d: &'a mut DMA2D) -> Self {
+        Dma2d { dma2d }
+    }
+
+    fn set_foreground(&mut self, fg: &PixelBuffer, color_mode: u8, alpha: u8) {
+        self.dma2d
+            .fgmar
+            .write(|w| unsafe { w.ma().bits(fg.addr as u32) });
+        self.dma2d
+            .fgor
+            .write(|w| unsafe { w.lo().bits(fg.line_offset() as u16) });
+        self.dma2d.fgpfccr.write(|w| unsafe {
+            w.cm().bits(color_mode);
+            // multiply the alpha channel of the pixels with `alpha`
+            w.am().bits(0b10);
+            w.alpha().bits(alpha)
+        });
+    }
+
+    fn set_background(&mut self, bg: &PixelBuffer) {
+        self.dma2d
+            .bgmar
+            .write(|w| unsafe { w.ma().bits(bg.addr as u32) });
+        self.dma2d
+            .bgor
+            .write(|w| unsafe { w.lo().bits(bg.line_offset() as u16) });
+        self.dma2d
+            .bgpfccr
+            .write(|w| unsafe { w.cm().bits(bg.format.color_mode()) });
+    }
+
+    // Configures the output, starts the transfer and waits until it is finished.
+    fn run(&mut self, mode: Mode, dst: &PixelBuffer, color_mode: u8) -> Result<(), Error> {
+        if dst.width == 0 || dst.height == 0 {
+            return Ok(());
+        }
+
+        self.dma2d
+            .opfccr
+            .write(|w| unsafe { w.cm().bits(color_mode) });
+        self.dma2d
+            .omar
+            .write(|w| unsafe { w.ma().bits(dst.addr as u32) });
+        self.dma2d
+            .oor
+            .write(|w| unsafe { w.lo().bits(dst.line_offset() as u16) });
+        self.dma2d.nlr.write(|w| unsafe {
+            w.pl().bits(dst.width as u16);
+            w.nl().bits(dst.height as u16)
+        });
+
+        self.dma2d.cr.modify(|_, w| unsafe {
+            w.mode().bits(mode as u8);
+            w.start().set_bit()
+        });
+        // the start bit is cleared when the transfer is finished or aborted
+        while self.dma2d.cr.read().start().bit_is_set() {}
+
+        let isr = self.dma2d.isr.read();
+        let result = if isr.teif().bit_is_set() {
+            Err(Error::TransferError)
+        } else if isr.ceif().bit_is_set() {
+            Err(Error::ConfigurationError)
+        } else {
+            Ok(())
+        };
+        self.dma2d.ifcr.write(|w| {
+            w.ctcif().set_bit();
+            w.cteif().set_bit();
+            w.cceif().set_bit()
+        });
+        result
+    }
+}
+
+// The output color mode that writes pixels of the given size unchanged, if there is one.
+fn raw_color_mode(bytes_per_pixel: usize) -> Option<u8> {
+    match bytes_per_pixel {
+        4 => Some(PixelFormat::Argb8888.color_mode()),
+        3 => Some(PixelFormat::Rgb888.color_mode()),
+        2 => Some(PixelFormat::Rgb565.color_mode()),
+        _ => None,
+    }
+}
+
+impl<'a> Blitter for Dma2d<'a> {
+    fn fill(&mut self, dst: PixelBuffer, color: Color) -> Result<(), Error> {
+        match raw_color_mode(dst.format.bytes_per_pixel()) {
+            Some(color_mode) => {
+                // The output color register is written to memory unchanged when it is
+                // encoded in a format of the same size.
+                let raw = dst.format.encode(color);
+                self.dma2d.ocolr.write(|w| unsafe { w.bits(raw) });
+                self.run(Mode::RegisterToMemory, &dst, color_mode)
+            }
+            None => Software.fill(dst, color),
+        }
+    }
+
+    fn copy(&mut self, src: PixelBuffer, dst: PixelBuffer) -> Result<(), Error> {
+        let (src, dst) = common_area(src, dst);
+        if src.format == dst.format {
+            if let Some(color_mode) = raw_color_mode(dst.format.bytes_per_pixel()) {
+                self.set_foreground(&src, color_mode, 255);
+                return self.run(Mode::MemoryToMemory, &dst, color_mode);
+            }
+        } else if !src.format.is_luminance() && !dst.format.is_luminance() {
+            self.set_foreground(&src, src.format.color_mode(), 255);
+            return self.run(Mode::MemoryToMemoryPfc, &dst, dst.format.color_mode());
+        }
+        Software.copy(src, dst)
+    }
+
+    fn blend(&mut self, fg: PixelBuffer, dst: PixelBuffer, alpha: u8) -> Result<(), Error> {
+        if fg.format.is_luminance() || dst.format.is_luminance() {
+            return Software.blend(fg, dst, alpha);
+        }
+        let (fg, dst) = common_area(fg, dst);
+        self.set_foreground(&fg, fg.format.color_mode(), alpha);
+        self.set_background(&dst);
+        self.run(Mode::MemoryToMemoryBlending, &dst, dst.format.color_mode())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn buffer<T>(memory: &mut [T], width: usize, format: PixelFormat) -> PixelBuffer {
+        let height = memory.len() / width;
+        unsafe { PixelBuffer::from_raw_parts(memory.as_mut_ptr() as usize, width, height, format) }
+    }
+
+    #[test]
+    fn sub_buffers_are_clipped() {
+        let mut memory = vec![0u32; 4 * 3];
+        let buffer = buffer(&mut memory, 4, PixelFormat::Argb8888);
+
+        let sub_buffer = buffer.sub_buffer(Rect::new(1, 1, 10, 10));
+        assert_eq!((sub_buffer.width(), sub_buffer.height()), (3, 2));
+        assert_eq!(sub_buffer.addr(), buffer.addr() + 5 * 4);
+        assert_eq!(sub_buffer.line_offset(), 1);
+
+        let outside = buffer.sub_buffer(Rect::new(5, 0, 2, 2));
+        assert_eq!((outside.width(), outside.height()), (0, 2));
+    }
+
+    #[test]
+    fn software_fill() {
+        let mut memory = vec![0u32; 4 * 4];
+        let dst = buffer(&mut memory, 4, PixelFormat::Argb8888);
+        let red = Color::rgb(255, 0, 0);
+        Software
+            .fill(dst.sub_buffer(Rect::new(1, 1, 10, 2)), red)
+            .unwrap();
+        let red = red.to_argb8888();
+        assert_eq!(memory[..4], [0, 0, 0, 0]);
+        assert_eq!(memory[4..8], [0, red, red, red]);
+        assert_eq!(memory[8..12], [0, red, red, red]);
+        assert_eq!(memory[12..], [0, 0, 0, 0]);
+
+        // three bytes per pixel are written byte-wise
+        let mut memory = vec![0u8; 3 * 2 * 2];
+        let addr = memory.as_mut_ptr() as usize;
+        let dst = unsafe { PixelBuffer::from_raw_parts(addr, 2, 2, PixelFormat::Rgb888) };
+        Software.fill(dst, Color::rgb(1, 2, 3)).unwrap();
+        assert_eq!(memory, [3, 2, 1, 3, 2, 1, 3, 2, 1, 3, 2, 1]);
+    }
+
+    #[test]
+    fn software_copy_converts_formats() {
+        let mut src_memory = vec![0xff00_ff00u32, 0xff00_00ff, 0xffff_0000, 0xffff_ffff];
+        let mut dst_memory = vec![0u16; 3 * 3];
+        let src = buffer(&mut src_memory, 2, PixelFormat::Argb8888);
+        let dst = buffer(&mut dst_memory, 3, PixelFormat::Rgb565);
+
+        Software
+            .copy(src, dst.sub_buffer(Rect::new(1, 1, 2, 2)))
+            .unwrap();
+        assert_eq!(dst_memory, [0, 0, 0, 0, 0x07e0, 0x001f, 0, 0xf800, 0xffff]);
+        assert_eq!(dst.get_pixel(1, 2), Color::rgb(255, 0, 0));
+
+        // only the common area is copied
+        let mut dst_memory = vec![0u32; 1];
+        Software
+            .copy(src, buffer(&mut dst_memory, 1, PixelFormat::Argb8888))
+            .unwrap();
+        assert_eq!(dst_memory, [0xff00_ff00]);
+    }
+
+    #[test]
+    fn software_blend() {
+        let mut fg_memory = vec![0x8000_00ffu32, 0xff00_ff00, 0x0000_0000];
+        let mut dst_memory = vec![0xffff_0000u32, 0xffff_0000, 0xffff_0000];
+        let fg = buffer(&mut fg_memory, 3, PixelFormat::Argb8888);
+        let dst = buffer(&mut dst_memory, 3, PixelFormat::Argb8888);
+
+        Software.blend(fg, dst, 255).unwrap();
+        // half transparent blue, opaque green and fully transparent
+        assert_eq!(dst_memory, [0xff7f_0080, 0xff00_ff00, 0xffff_0000]);
+
+        let mut dst_memory = vec![0u32; 1];
+        let dst = buffer(&mut dst_memory, 1, PixelFormat::Argb8888);
+        Software.blend(fg, dst, 0).unwrap();
+        assert_eq!(dst_memory, [0]);
+    }
+
+    #[test]
+    fn pixel_buffer_is_a_framebuffer() {
+        let mut memory: Vec<u16> = vec![0; 2 * 2];
+        let mut buffer = buffer(&mut memory, 2, PixelFormat::Al88);
+        Framebuffer::set_pixel(&mut buffer, 1, 0, Color::rgba(7, 0, 0, 0x80));
+        // pixels outside of the buffer are ignored
+        Framebuffer::set_pixel(&mut buffer, 2, 0, Color::rgba(7, 0, 0, 0x80));
+        assert_eq!(memory, [0, 0x8007, 0, 0]);
+        assert_eq!(buffer.pixel_buffer(), Some(buffer));
+    }
+}
@@ -0,0 +1,390 @@
+//! Support for the [`embedded-graphics`](embedded_graphics) crate.
+//!
+//! The layers implement the `DrawTarget` trait, so the primitives, fonts and images of the
+//! `embedded-graphics` ecosystem can be drawn on them directly. Layer 1 uses `Rgb888` colors.
+//! Layer 2 uses `Gray8` colors, whose luminance is the index into its color lookup table, a
+//! gray ramp unless it was replaced (see
+//! [`Lcd::set_color_lookup_table`](super::Lcd::set_color_lookup_table)). Layers with a
+//! [`PixelBuffer`](super::PixelBuffer) use `Rgb888` colors and convert them to the pixel format
+//! of their window.
+//!
+//! The layers fill areas on the CPU. The [`BlitterTarget`](BlitterTarget) returned by
+//! [`Layer::draw_target`](super::Layer::draw_target) fills them with a
+//! [`Blitter`](super::Blitter) instead, e.g. with the DMA2D.
+//!
+//! This module is only available with the `embedded-graphics` feature.
+//!
+//! # Examples
+//! ```rust
+//! use embedded_graphics::mono_font::{ascii::FONT_6X10, MonoTextStyle};
+//! use embedded_graphics::pixelcolor::Rgb888;
+//! use embedded_graphics::prelude::*;
+//! use embedded_graphics::primitives::{Circle, PrimitiveStyle};
+//! use embedded_graphics::text::Text;
+//!
+//! let mut layer_1 = lcd.layer_1().unwrap();
+//! let style = MonoTextStyle::new(&FONT_6X10, Rgb888::WHITE);
+//! Text::new("Hello", Point::new(10, 20), style)
+//!     .draw(&mut layer_1)
+//!     .unwrap();
+//!
+//! // fill the circle with the DMA2D
+//! let mut dma2d = lcd::Dma2d::new(&mut dma2d);
+//! Circle::new(Point::new(100, 100), 50)
+//!     .into_styled(PrimitiveStyle::with_fill(Rgb888::RED))
+//!     .draw(&mut layer_1.draw_target(&mut dma2d))
+//!     .expect("DMA2D error");
+//! ```
+
+use super::{dma2d, Blitter, Color, Framebuffer, FramebufferAl88, FramebufferArgb8888, Layer};
+use super::{PixelBuffer, Rect, Software, HEIGHT, WIDTH};
+use core::convert::Infallible;
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{OriginDimensions, Size},
+    pixelcolor::{Gray8, GrayColor, PixelColor, Rgb888, RgbColor},
+    primitives::{PointsIter, Rectangle},
+    Pixel,
+};
+
+impl From<Rgb888> for Color {
+    fn from(color: Rgb888) -> Color {
+        Color::rgb(color.r(), color.g(), color.b())
+    }
+}
+
+impl From<Color> for Rgb888 {
+    fn from(color: Color) -> Rgb888 {
+        Rgb888::new(color.red, color.green, color.blue)
+    }
+}
+
+impl From<Gray8> for Color {
+    fn from(color: Gray8) -> Color {
+        let luma = color.luma();
+        Color::rgb(luma, luma, luma)
+    }
+}
+
+impl From<Color> for Gray8 {
+    /// Uses the red channel as luminance, like the AL88 framebuffer.
+    fn from(color: Color) -> Gray8 {
+        Gray8::new(color.red)
+    }
+}
+
+/// A framebuffer that `embedded-graphics` can draw on.
+pub trait TargetFramebuffer: Framebuffer {
+    /// The color of the pixels.
+    type Color: PixelColor + Into<Color>;
+
+    /// Returns the width and the height of the framebuffer.
+    fn size(&self) -> Size;
+}
+
+impl TargetFramebuffer for FramebufferArgb8888 {
+    type Color = Rgb888;
+
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl TargetFramebuffer for FramebufferAl88 {
+    type Color = Gray8;
+
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl TargetFramebuffer for PixelBuffer {
+    type Color = Rgb888;
+
+    fn size(&self) -> Size {
+        Size::new(self.width() as u32, self.height() as u32)
+    }
+}
+
+/// A layer that fills the areas of the drawn shapes with a [`Blitter`](super::Blitter).
+///
+/// Created by [`Layer::draw_target`](super::Layer::draw_target).
+pub struct BlitterTarget<'a, T: Framebuffer, B: Blitter> {
+    layer: &'a mut Layer<T>,
+    blitter: &'a mut B,
+}
+
+impl<T: Framebuffer> Layer<T> {
+    /// Returns a draw target for `embedded-graphics` that fills areas with the blitter.
+    ///
+    /// This method is only available with the `embedded-graphics` feature.
+    pub fn draw_target<'a, B: Blitter>(
+        &'a mut self,
+        blitter: &'a mut B,
+    ) -> BlitterTarget<'a, T, B> {
+        BlitterTarget {
+            layer: self,
+            blitter,
+        }
+    }
+}
+
+impl<T: TargetFramebuffer> OriginDimensions for Layer<T> {
+    fn size(&self) -> Size {
+        self.framebuffer.size()
+    }
+}
+
+impl<T: TargetFramebuffer> DrawTarget for Layer<T> {
+    type Color = T::Color;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Infallible>
+    where
+        I: IntoIterator<Item = Pixel<T::Color>>,
+    {
+        // the software renderer never fails
+        let _ = self.draw_target(&mut Software).draw_iter(pixels);
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Infallible>
+    where
+        I: IntoIterator<Item = T::Color>,
+    {
+        let _ = self
+            .draw_target(&mut Software)
+            .fill_contiguous(area, colors);
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: T::Color) -> Result<(), Infallible> {
+        let _ = self.draw_target(&mut Software).fill_solid(area, color);
+        Ok(())
+    }
+}
+
+impl<'a, T: TargetFramebuffer, B: Blitter> OriginDimensions for BlitterTarget<'a, T, B> {
+    fn size(&self) -> Size {
+        self.layer.framebuffer.size()
+    }
+}
+
+impl<'a, T: TargetFramebuffer, B: Blitter> DrawTarget for BlitterTarget<'a, T, B> {
+    type Color = T::Color;
+    type Error = dma2d::Error;
+
+    // Most drawables yield their pixels row by row, so neighbouring pixels of the same color are
+    // merged into spans that are filled by the blitter.
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), dma2d::Error>
+    where
+        I: IntoIterator<Item = Pixel<T::Color>>,
+    {
+        let size = self.size();
+        let mut span: Option<(Rect, Color)> = None;
+        for Pixel(point, color) in pixels {
+            let (x, y) = (point.x as usize, point.y as usize);
+            if point.x < 0 || point.y < 0 || x >= size.width as usize || y >= size.height as usize {
+                continue;
+            }
+            let color = color.into();
+            match span {
+                Some((ref mut rect, span_color))
+                    if span_color == color && rect.y == y && rect.x + rect.width == x =>
+                {
+                    rect.width += 1;
+                    continue;
+                }
+                Some((rect, span_color)) => self.layer.fill_rect(self.blitter, rect, span_color)?,
+                None => {}
+            }
+            span = Some((Rect::new(x, y, 1, 1), color));
+        }
+        if let Some((rect, color)) = span {
+            self.layer.fill_rect(self.blitter, rect, color)?;
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), dma2d::Error>
+    where
+        I: IntoIterator<Item = T::Color>,
+    {
+        // the colors are in row-major order, so runs of the same color become spans
+        let pixels = area
+            .points()
+            .zip(colors)
+            .map(|(point, color)| Pixel(point, color));
+        self.draw_iter(pixels)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: T::Color) -> Result<(), dma2d::Error> {
+        match clip(area, self.size()) {
+            Some(rect) => self.layer.fill_rect(self.blitter, rect, color.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+// Clips the area to a framebuffer of the size. Returns `None` if nothing is left.
+fn clip(area: &Rectangle, size: Size) -> Option<Rect> {
+    let (x, y) = (i64::from(area.top_left.x), i64::from(area.top_left.y));
+    let (left, top) = (x.max(0), y.max(0));
+    let right = (x + i64::from(area.size.width)).min(i64::from(size.width));
+    let bottom = (y + i64::from(area.size.height)).min(i64::from(size.height));
+    if left >= right || top >= bottom {
+        return None;
+    }
+    let (width, height) = ((right - left) as usize, (bottom - top) as usize);
+    Some(Rect::new(left as usize, top as usize, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{LayerId, PixelFormat};
+    use super::*;
+    use alloc::vec::Vec;
+    use core::iter;
+    use embedded_graphics::geometry::Point;
+    use embedded_graphics::prelude::*;
+    use embedded_graphics::primitives::PrimitiveStyle;
+
+    // The software renderer, which counts the filled areas.
+    #[derive(Default)]
+    struct Counting {
+        fills: usize,
+    }
+
+    impl Blitter for Counting {
+        fn fill(&mut self, dst: PixelBuffer, color: Color) -> Result<(), dma2d::Error> {
+            self.fills += 1;
+            Software.fill(dst, color)
+        }
+
+        fn copy(&mut self, src: PixelBuffer, dst: PixelBuffer) -> Result<(), dma2d::Error> {
+            Software.copy(src, dst)
+        }
+
+        fn blend(
+            &mut self,
+            fg: PixelBuffer,
+            dst: PixelBuffer,
+            alpha: u8,
+        ) -> Result<(), dma2d::Error> {
+            Software.blend(fg, dst, alpha)
+        }
+    }
+
+    fn layer<T: Framebuffer>(framebuffer: T) -> Layer<T> {
+        Layer::new(LayerId::Layer1, framebuffer, iter::empty(), &mut Software)
+    }
+
+    fn buffer(memory: &mut Vec<u32>, width: usize, height: usize) -> PixelBuffer {
+        memory.resize(width * height, 0);
+        let addr = memory.as_mut_ptr() as usize;
+        unsafe { PixelBuffer::from_raw_parts(addr, width, height, PixelFormat::Argb8888) }
+    }
+
+    fn rectangle(x: i32, y: i32, width: u32, height: u32) -> Rectangle {
+        Rectangle::new(Point::new(x, y), Size::new(width, height))
+    }
+
+    #[test]
+    fn fill_solid_with_the_blitter() {
+        let mut memory = Vec::new();
+        let mut layer = layer(buffer(&mut memory, 20, 10));
+        assert_eq!(layer.size(), Size::new(20, 10));
+
+        let mut blitter = Counting::default();
+        let mut target = layer.draw_target(&mut blitter);
+        target
+            .fill_solid(&rectangle(-5, 2, 10, 3), Rgb888::RED)
+            .unwrap();
+        target
+            .fill_solid(&rectangle(20, 0, 10, 10), Rgb888::RED)
+            .unwrap();
+        // the area is clipped without overflows
+        let huge = rectangle(i32::min_value(), 0, u32::max_value(), 1);
+        target.fill_solid(&huge, Rgb888::BLUE).unwrap();
+        assert_eq!(blitter.fills, 2);
+
+        let buffer = layer.pixel_buffer().unwrap();
+        let red = Color::rgb(255, 0, 0);
+        assert_eq!(buffer.get_pixel(19, 0), Color::rgb(0, 0, 255));
+        assert_eq!(buffer.get_pixel(0, 2), red);
+        assert_eq!(buffer.get_pixel(4, 4), red);
+        assert_eq!(buffer.get_pixel(5, 2), Color::rgba(0, 0, 0, 0));
+        assert_eq!(buffer.get_pixel(0, 5), Color::rgba(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn fill_contiguous_in_spans() {
+        let mut memory = Vec::new();
+        let mut layer = layer(buffer(&mut memory, 20, 10));
+        let (r, g, b) = (Rgb888::RED, Rgb888::GREEN, Rgb888::BLUE);
+
+        let mut blitter = Counting::default();
+        let colors = [r, r, g, g, b, b, b, b, r, g, b, r];
+        let area = rectangle(18, 1, 4, 3);
+        layer
+            .draw_target(&mut blitter)
+            .fill_contiguous(&area, colors.iter().cloned())
+            .unwrap();
+        // the columns right of the layer are clipped
+        assert_eq!(blitter.fills, 4);
+
+        let buffer = layer.pixel_buffer().unwrap();
+        let pixels: Vec<Color> = (1..4)
+            .flat_map(|y| (18..20).map(move |x| (x, y)))
+            .map(|(x, y)| buffer.get_pixel(x, y))
+            .collect();
+        let expected: Vec<Color> = [r, r, b, b, r, g].iter().map(|&c| c.into()).collect();
+        assert_eq!(pixels, expected);
+    }
+
+    #[test]
+    fn draw_pixels() {
+        let mut memory = Vec::new();
+        let mut layer = layer(buffer(&mut memory, 20, 10));
+        let pixels = [
+            Pixel(Point::new(-1, 0), Rgb888::RED),
+            Pixel(Point::new(0, 0), Rgb888::RED),
+            Pixel(Point::new(1, 0), Rgb888::RED),
+            Pixel(Point::new(19, 9), Rgb888::GREEN),
+            Pixel(Point::new(20, 9), Rgb888::GREEN),
+            Pixel(Point::new(0, i32::max_value()), Rgb888::GREEN),
+        ];
+        let mut blitter = Counting::default();
+        layer
+            .draw_target(&mut blitter)
+            .draw_iter(pixels.iter().cloned())
+            .unwrap();
+        assert_eq!(blitter.fills, 2);
+
+        // without blitter
+        rectangle(5, 5, 2, 2)
+            .into_styled(PrimitiveStyle::with_fill(Rgb888::BLUE))
+            .draw(&mut layer)
+            .unwrap();
+
+        let buffer = layer.pixel_buffer().unwrap();
+        assert_eq!(buffer.get_pixel(1, 0), Color::rgb(255, 0, 0));
+        assert_eq!(buffer.get_pixel(2, 0), Color::rgba(0, 0, 0, 0));
+        assert_eq!(buffer.get_pixel(19, 9), Color::rgb(0, 255, 0));
+        assert_eq!(buffer.get_pixel(6, 6), Color::rgb(0, 0, 255));
+        assert_eq!(buffer.get_pixel(7, 6), Color::rgba(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn gray_is_the_color_index() {
+        let mut memory = vec![0u16; WIDTH * HEIGHT];
+        let mut layer = layer(FramebufferAl88::new(memory.as_mut_ptr() as usize));
+        assert_eq!(layer.size(), Size::new(WIDTH as u32, HEIGHT as u32));
+
+        // `Layer::clear` clears the layer with a blitter
+        DrawTarget::clear(&mut layer, Gray8::new(42)).unwrap();
+        assert!(memory.iter().all(|&pixel| pixel == 0xff_00 | 42));
+        assert_eq!(Gray8::from(Color::rgb(7, 8, 9)), Gray8::new(7));
+        assert_eq!(Rgb888::from(Color::rgb(7, 8, 9)), Rgb888::new(7, 8, 9));
+    }
+}
@@ -1,77 +1,350 @@
-use alloc::Vec;
+//! Fonts for rendering text on the layers.
+//!
+//! There are two kinds of fonts:
+//!
+//! - [`BitmapFont`](BitmapFont)s are proportional, anti-aliased fonts that are rasterized from
+//!   TrueType fonts at build time. Every `fonts/<name>.ttf` file of the crate is rasterized in
+//!   the sizes listed in `fonts/<name>.sizes` (default: 12, 16 and 24 pixels) and is available
+//!   as `lcd::font::fonts::<NAME>_<SIZE>`. The fonts contain the printable ASCII and Latin-1
+//!   characters and the kerning of the ASCII characters.
+//! - [`Font8x8`](Font8x8) is the monospace 8x8 font of the `font8x8` crate, which covers many
+//!   unicode blocks but has no anti-aliasing.
+//!
+//! Fonts can be combined with [`Fallback`](Fallback), so that characters that are missing in a
+//! font are taken from another one. See the [`text`](super::text) module for drawing text.
 
-pub struct FontRenderer<'a> {
-    font: Font<'a>,
-    height: f32,
+use font8x8::UnicodeFonts;
+
+/// The fonts that were rasterized at build time.
+#[allow(missing_docs)]
+pub mod fonts {
+    include!(concat!(env!("OUT_DIR"), "/fonts.rs"));
 }
 
-impl<'a> FontRenderer<'a> {
-    pub fn new(font_data: &[u8], font_height: f32) -> FontRenderer {
-        let collection = FontCollection::from_bytes(font_data);
-        // only succeeds if collection consists of one font
-        let font = collection.into_font().unwrap();
-        FontRenderer {
-            font,
-            height: font_height,
-        }
+/// A font that provides the glyphs for characters.
+///
+/// All values are in pixels. The y axis points down, so offsets above the baseline are
+/// negative.
+pub trait Font {
+    /// The distance from the top of a line to the baseline.
+    fn ascent(&self) -> i32;
+
+    /// The distance between the baselines of two lines.
+    fn line_height(&self) -> i32;
+
+    /// Returns the glyph of the character, or `None` if the font doesn't contain it.
+    fn glyph(&self, c: char) -> Option<Glyph>;
+
+    /// Returns the horizontal adjustment between the two characters, which is added to the
+    /// advance of `left`.
+    fn kerning(&self, _left: char, _right: char) -> i32 {
+        0
+    }
+}
+
+impl<'a, F: Font + ?Sized> Font for &'a F {
+    fn ascent(&self) -> i32 {
+        (**self).ascent()
+    }
+
+    fn line_height(&self) -> i32 {
+        (**self).line_height()
     }
 
-    pub fn font_height(&self) -> f32 {
-        self.height
-    }
-
-    pub fn layout(&self, s: &str) -> Vec<PositionedGlyph> {
-        let scale = Scale {
-            x: self.height,
-            y: self.height,
-        };
-
-        // The origin of a line of text is at the baseline (roughly where non-descending letters
-        // sit). We don't want to clip the text, so we shift it down with an offset when laying
-        // it out. v_metrics.ascent is the distance between the baseline and the highest edge of
-        // any glyph in the font. That's enough to guarantee that there's no clipping.
-        let v_metrics = self.font.v_metrics(scale);
-        let offset = point(0.0, v_metrics.ascent);
-
-        self.font.layout(s, scale, offset).collect()
-    }
-
-    pub fn render<F>(&self, s: &str, mut draw_pixel: F) -> usize
-    where
-        F: FnMut(usize, usize, f32),
-    {
-        let glyphs = self.layout(s);
-        let pixel_height = self.height.ceil() as usize;
-
-        // Find the most visually pleasing width to display
-        let width = glyphs
-            .iter()
-            .rev()
-            .map(|g| g.position().x as f32 + g.unpositioned().h_metrics().advance_width)
-            .next()
-            .unwrap_or(0.0)
-            .ceil() as usize;
-
-        for g in glyphs {
-            if let Some(bb) = g.pixel_bounding_box() {
-                g.draw(|x, y, mut v| {
-                    assert!(v >= 0.0 - 1.0e-5);
-                    assert!(v <= 1.0 + 1.0e-5);
-                    if v < 0.0 {
-                        v = 0.0;
-                    }
-                    if v > 1.0 {
-                        v = 1.0;
-                    }
-                    let x = x as i32 + bb.min.x;
-                    let y = y as i32 + bb.min.y;
-                    // There's still a possibility that the glyph clips the boundaries of the bitmap
-                    if x >= 0 && x < width as i32 && y >= 0 && y < pixel_height as i32 {
-                        draw_pixel(x as usize, y as usize, v);
-                    }
-                });
+    fn glyph(&self, c: char) -> Option<Glyph> {
+        (**self).glyph(c)
+    }
+
+    fn kerning(&self, left: char, right: char) -> i32 {
+        (**self).kerning(left, right)
+    }
+}
+
+/// The image and the metrics of a character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Glyph<'a> {
+    /// The width of the image.
+    pub width: u16,
+    /// The height of the image.
+    pub height: u16,
+    /// The horizontal distance from the pen position to the left edge of the image.
+    pub x_offset: i16,
+    /// The vertical distance from the baseline to the top edge of the image.
+    pub y_offset: i16,
+    /// The horizontal distance to the pen position of the next character.
+    pub advance: u16,
+    /// The image of the glyph.
+    pub bitmap: Bitmap<'a>,
+}
+
+impl<'a> Glyph<'a> {
+    /// Returns the coverage of the pixel in the image, from 0 (empty) to 255 (covered).
+    pub fn coverage(&self, x: u16, y: u16) -> u8 {
+        assert!(x < self.width && y < self.height);
+        match self.bitmap {
+            Bitmap::Alpha(data) => data[usize::from(y) * usize::from(self.width) + usize::from(x)],
+            Bitmap::Mono8x8(rows) => {
+                if rows[usize::from(y)] & (1 << x) == 0 {
+                    0
+                } else {
+                    255
+                }
             }
         }
-        return width;
+    }
+}
+
+/// The image of a glyph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bitmap<'a> {
+    /// One coverage byte per pixel, row by row.
+    Alpha(&'a [u8]),
+    /// Eight rows of eight pixels, the lowest bit is the leftmost pixel.
+    Mono8x8([u8; 8]),
+}
+
+/// The metrics of a glyph of a `BitmapFont` and the position of its image in the bitmap data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlyphInfo {
+    /// The character.
+    pub c: char,
+    /// The width of the image.
+    pub width: u16,
+    /// The height of the image.
+    pub height: u16,
+    /// The horizontal distance from the pen position to the left edge of the image.
+    pub x_offset: i16,
+    /// The vertical distance from the baseline to the top edge of the image.
+    pub y_offset: i16,
+    /// The horizontal distance to the pen position of the next character.
+    pub advance: u16,
+    /// The index of the first coverage byte of the image in the bitmap data.
+    pub bitmap_offset: u32,
+}
+
+/// A proportional font with anti-aliased glyphs.
+#[derive(Debug)]
+pub struct BitmapFont {
+    ascent: i32,
+    line_height: i32,
+    glyphs: &'static [GlyphInfo],
+    bitmaps: &'static [u8],
+    kerning: &'static [(char, char, i8)],
+}
+
+impl BitmapFont {
+    /// Creates a font from its metrics and data.
+    ///
+    /// The glyphs and the kerning pairs must be sorted by their characters, and the bitmap data
+    /// contains the coverage bytes of all glyph images.
+    pub const fn new(
+        ascent: i32,
+        line_height: i32,
+        glyphs: &'static [GlyphInfo],
+        bitmaps: &'static [u8],
+        kerning: &'static [(char, char, i8)],
+    ) -> BitmapFont {
+        BitmapFont {
+            ascent,
+            line_height,
+            glyphs,
+            bitmaps,
+            kerning,
+        }
+    }
+}
+
+impl Font for BitmapFont {
+    fn ascent(&self) -> i32 {
+        self.ascent
+    }
+
+    fn line_height(&self) -> i32 {
+        self.line_height
+    }
+
+    fn glyph(&self, c: char) -> Option<Glyph> {
+        let index = self.glyphs.binary_search_by_key(&c, |g| g.c).ok()?;
+        let info = &self.glyphs[index];
+        let start = info.bitmap_offset as usize;
+        let end = start + usize::from(info.width) * usize::from(info.height);
+        Some(Glyph {
+            width: info.width,
+            height: info.height,
+            x_offset: info.x_offset,
+            y_offset: info.y_offset,
+            advance: info.advance,
+            bitmap: Bitmap::Alpha(&self.bitmaps[start..end]),
+        })
+    }
+
+    fn kerning(&self, left: char, right: char) -> i32 {
+        self.kerning
+            .binary_search_by_key(&(left, right), |&(l, r, _)| (l, r))
+            .map(|i| i32::from(self.kerning[i].2))
+            .unwrap_or(0)
+    }
+}
+
+/// The 8x8 font of the `font8x8` crate.
+///
+/// It contains the ASCII, Latin-1, Greek, box drawing, block and Hiragana characters.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Font8x8;
+
+impl Font for Font8x8 {
+    fn ascent(&self) -> i32 {
+        8
+    }
+
+    fn line_height(&self) -> i32 {
+        8
+    }
+
+    fn glyph(&self, c: char) -> Option<Glyph> {
+        let rows = font8x8::BASIC_FONTS
+            .get(c)
+            .or_else(|| font8x8::LATIN_FONTS.get(c))
+            .or_else(|| font8x8::GREEK_FONTS.get(c))
+            .or_else(|| font8x8::BOX_FONTS.get(c))
+            .or_else(|| font8x8::BLOCK_FONTS.get(c))
+            .or_else(|| font8x8::HIRAGANA_FONTS.get(c))?;
+        Some(Glyph {
+            width: 8,
+            height: 8,
+            x_offset: 0,
+            y_offset: -8,
+            advance: 8,
+            bitmap: Bitmap::Mono8x8(rows),
+        })
+    }
+}
+
+/// Takes the glyphs that are missing in the first font from the second font.
+///
+/// The metrics of the lines are taken from the first font.
+///
+/// # Examples
+/// ```rust
+/// // DejaVu Sans is rasterized from `fonts/DejaVuSans.ttf`
+/// let font = lcd::font::Fallback(&lcd::font::fonts::DEJAVUSANS_16, lcd::font::Font8x8);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Fallback<A, B>(pub A, pub B);
+
+impl<A: Font, B: Font> Font for Fallback<A, B> {
+    fn ascent(&self) -> i32 {
+        self.0.ascent()
+    }
+
+    fn line_height(&self) -> i32 {
+        self.0.line_height()
+    }
+
+    fn glyph(&self, c: char) -> Option<Glyph> {
+        self.0.glyph(c).or_else(|| self.1.glyph(c))
+    }
+
+    fn kerning(&self, left: char, right: char) -> i32 {
+        self.0.kerning(left, right)
+    }
+}
+
+/// Returns the glyph of the character, or a replacement glyph if the font doesn't contain it.
+///
+/// The replacement is `U+FFFD` (�), or `?` if the font doesn't contain it either.
+pub fn glyph_or_replacement<F: Font>(font: &F, c: char) -> Option<Glyph> {
+    font.glyph(c)
+        .or_else(|| font.glyph('\u{fffd}'))
+        .or_else(|| font.glyph('?'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static GLYPHS: [GlyphInfo; 2] = [
+        GlyphInfo {
+            c: 'A',
+            width: 2,
+            height: 1,
+            x_offset: 1,
+            y_offset: -1,
+            advance: 4,
+            bitmap_offset: 0,
+        },
+        GlyphInfo {
+            c: 'V',
+            width: 1,
+            height: 2,
+            x_offset: 0,
+            y_offset: -2,
+            advance: 3,
+            bitmap_offset: 2,
+        },
+    ];
+    static BITMAPS: [u8; 4] = [10, 20, 30, 40];
+    static KERNING: [(char, char, i8); 2] = [('A', 'V', -1), ('V', 'A', -2)];
+    static FONT: BitmapFont = BitmapFont::new(7, 9, &GLYPHS, &BITMAPS, &KERNING);
+
+    #[test]
+    fn bitmap_font() {
+        assert_eq!(FONT.ascent(), 7);
+        assert_eq!(FONT.line_height(), 9);
+        let a = FONT.glyph('A').unwrap();
+        assert_eq!((a.width, a.height, a.x_offset, a.y_offset), (2, 1, 1, -1));
+        assert_eq!(a.advance, 4);
+        assert_eq!(a.bitmap, Bitmap::Alpha(&[10, 20]));
+        let v = FONT.glyph('V').unwrap();
+        assert_eq!((v.coverage(0, 0), v.coverage(0, 1)), (30, 40));
+        assert_eq!(FONT.glyph('B'), None);
+
+        assert_eq!(FONT.kerning('A', 'V'), -1);
+        assert_eq!(FONT.kerning('V', 'A'), -2);
+        assert_eq!(FONT.kerning('A', 'A'), 0);
+    }
+
+    #[test]
+    fn coverage() {
+        let glyph = Font8x8.glyph('|').unwrap();
+        let column = (0..8).find(|&x| glyph.coverage(x, 0) != 0).unwrap();
+        assert_eq!(glyph.coverage(column, 0), 255);
+        assert_eq!(glyph.coverage((column + 4) % 8, 0), 0);
+        assert_eq!(Font8x8.glyph(' ').unwrap().bitmap, Bitmap::Mono8x8([0; 8]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn coverage_outside_of_glyph() {
+        FONT.glyph('A').unwrap().coverage(2, 0);
+    }
+
+    #[test]
+    fn font_8x8() {
+        for &c in &['a', 'ü', 'λ', '┼', '█', 'あ'] {
+            let glyph = Font8x8.glyph(c).unwrap();
+            assert_eq!((glyph.width, glyph.height), (8, 8), "{}", c);
+            assert_eq!((glyph.y_offset, glyph.advance), (-8, 8), "{}", c);
+        }
+        assert_eq!(Font8x8.glyph('\u{1f600}'), None);
+    }
+
+    #[test]
+    fn fallback() {
+        let font = Fallback(&FONT, Font8x8);
+        assert_eq!((font.ascent(), font.line_height()), (7, 9));
+        assert_eq!(font.glyph('A'), FONT.glyph('A'));
+        assert_eq!(font.glyph('B'), Font8x8.glyph('B'));
+        assert_eq!(font.kerning('A', 'V'), -1);
+    }
+
+    #[test]
+    fn replacement() {
+        assert_eq!(glyph_or_replacement(&FONT, 'V'), FONT.glyph('V'));
+        assert_eq!(glyph_or_replacement(&FONT, 'B'), None);
+        let replacement = glyph_or_replacement(&Font8x8, '\u{1f600}');
+        assert_eq!(replacement, Font8x8.glyph('?'));
+        let font = Fallback(&FONT, Font8x8);
+        assert_eq!(glyph_or_replacement(&font, '\u{1f600}'), Font8x8.glyph('?'));
     }
 }
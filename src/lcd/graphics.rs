@@ -0,0 +1,830 @@
+//! Drawing of lines and shapes.
+//!
+//! All functions work with any [`Framebuffer`](super::Framebuffer), including the layers of the
+//! LCD. The coordinates can be negative or outside of the display, the shapes are clipped to
+//! [`WIDTH`](super::WIDTH) × [`HEIGHT`](super::HEIGHT).
+//!
+//! # Examples
+//! ```rust
+//! use lcd::graphics::{self, Point};
+//!
+//! let mut layer_1 = lcd.layer_1().unwrap();
+//! graphics::fill_rect(&mut layer_1, Point::new(10, 10), 100, 50, Color::rgb(0, 0, 255));
+//! graphics::line_aa(&mut layer_1, Point::new(0, 0), Point::new(479, 100), Color::rgb(0, 0, 0));
+//! ```
+
+use super::dma2d::{blend_pixel, PixelBuffer};
+use super::{Color, Framebuffer, HEIGHT, WIDTH};
+use alloc::vec::Vec;
+use core::mem;
+
+/// A point on the display, which may lie outside of the visible area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Point {
+    /// The column.
+    pub x: i32,
+    /// The row.
+    pub y: i32,
+}
+
+impl Point {
+    /// Creates a point from its coordinates.
+    pub const fn new(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+}
+
+/// Draws a line from `start` to `end`, including both end points.
+///
+/// The line is clipped to the display before it is drawn, so the time doesn't depend on the
+/// length of the invisible parts.
+pub fn line<F: Framebuffer>(fb: &mut F, start: Point, end: Point, color: Color) {
+    let (start, end) = match clip_line(start, end) {
+        Some(line) => line,
+        None => return,
+    };
+
+    // Bresenham's line algorithm
+    let dx = (end.x - start.x).abs();
+    let dy = -(end.y - start.y).abs();
+    let step_x = if start.x < end.x { 1 } else { -1 };
+    let step_y = if start.y < end.y { 1 } else { -1 };
+    let mut error = dx + dy;
+    let (mut x, mut y) = (start.x, start.y);
+    loop {
+        pixel(fb, x as i32, y as i32, color);
+        if x == end.x && y == end.y {
+            break;
+        }
+        let e2 = 2 * error;
+        if e2 >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if e2 <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// Draws an anti-aliased line from `start` to `end`.
+///
+/// The alpha channel of `color` is scaled with the coverage of each pixel. If the framebuffer
+/// has a [`pixel_buffer`](Framebuffer::pixel_buffer), the line is blended with its pixels.
+/// Otherwise the pixels are overwritten, which blends the line with the lower layers of the
+/// display.
+pub fn line_aa<F: Framebuffer>(fb: &mut F, start: Point, end: Point, color: Color) {
+    // widen the clipped line by a pixel, so the partially covered pixels at the edges are kept
+    let (start, end) = match clip_line_to(start, end, -1, -1, WIDTH as i64, HEIGHT as i64) {
+        Some(line) => line,
+        None => return,
+    };
+    let buffer = fb.pixel_buffer();
+
+    // Xiaolin Wu's line algorithm with 16.16 fixed point numbers
+    let (mut start, mut end) = (start, end);
+    let steep = (end.y - start.y).abs() > (end.x - start.x).abs();
+    if steep {
+        mem::swap(&mut start.x, &mut start.y);
+        mem::swap(&mut end.x, &mut end.y);
+    }
+    if start.x > end.x {
+        mem::swap(&mut start, &mut end);
+    }
+
+    let dx = end.x - start.x;
+    let dy = end.y - start.y;
+    let gradient = if dx == 0 { 0 } else { (dy << 16) / dx };
+
+    let mut plot = |a: i64, b: i64, coverage: u32| {
+        if coverage == 0 {
+            return;
+        }
+        let alpha = (u32::from(color.alpha) * coverage / 255) as u8;
+        let color = Color { alpha, ..color };
+        if steep {
+            blend(fb, buffer, b as i32, a as i32, color);
+        } else {
+            blend(fb, buffer, a as i32, b as i32, color);
+        }
+    };
+
+    let mut intery = start.y << 16;
+    for x in start.x..=end.x {
+        let y = intery >> 16;
+        let fraction = ((intery & 0xffff) >> 8) as u32;
+        plot(x, y, 255 - fraction);
+        plot(x, y + 1, fraction);
+        intery += gradient;
+    }
+}
+
+/// Draws the outline of a rectangle.
+pub fn rect<F: Framebuffer>(fb: &mut F, top_left: Point, width: u32, height: u32, color: Color) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    let (left, top) = (i64::from(top_left.x), i64::from(top_left.y));
+    let right = left + i64::from(width) - 1;
+    let bottom = top + i64::from(height) - 1;
+    let (start, end) = (clamp_x(left), clamp_x(right));
+    horizontal_line(fb, start, end, clamp_y(top), color);
+    horizontal_line(fb, start, end, clamp_y(bottom), color);
+    for y in clamp_y(top + 1)..clamp_y(bottom) {
+        pixel(fb, clamp_x(left), y, color);
+        pixel(fb, clamp_x(right), y, color);
+    }
+}
+
+/// Draws a filled rectangle.
+///
+/// The rectangle is clipped to the display before it is drawn.
+pub fn fill_rect<F: Framebuffer>(
+    fb: &mut F,
+    top_left: Point,
+    width: u32,
+    height: u32,
+    color: Color,
+) {
+    let (left, top) = (i64::from(top_left.x), i64::from(top_left.y));
+    let right = left + i64::from(width) - 1;
+    let bottom = (top + i64::from(height) - 1).min(HEIGHT as i64 - 1);
+    for y in top.max(0)..=bottom {
+        horizontal_line(fb, clamp_x(left), clamp_x(right), y as i32, color);
+    }
+}
+
+/// Draws the outline of a rectangle with rounded corners of the given radius.
+///
+/// The radius is limited to half of the width and the height.
+pub fn rounded_rect<F: Framebuffer>(
+    fb: &mut F,
+    top_left: Point,
+    width: u32,
+    height: u32,
+    radius: u32,
+    color: Color,
+) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    let r = radius.min((width - 1) / 2).min((height - 1) / 2) as i32;
+    let left = top_left.x + r;
+    let right = top_left.x + width as i32 - 1 - r;
+    let top = top_left.y + r;
+    let bottom = top_left.y + height as i32 - 1 - r;
+
+    horizontal_line(fb, left, right, top_left.y, color);
+    horizontal_line(fb, left, right, top_left.y + height as i32 - 1, color);
+    for y in top..=bottom {
+        pixel(fb, top_left.x, y, color);
+        pixel(fb, top_left.x + width as i32 - 1, y, color);
+    }
+    circle_octants(r, |dx, dy| {
+        pixel(fb, right + dx, bottom + dy, color);
+        pixel(fb, left - dx, bottom + dy, color);
+        pixel(fb, right + dx, top - dy, color);
+        pixel(fb, left - dx, top - dy, color);
+    });
+}
+
+/// Draws a filled rectangle with rounded corners of the given radius.
+///
+/// The radius is limited to half of the width and the height.
+pub fn fill_rounded_rect<F: Framebuffer>(
+    fb: &mut F,
+    top_left: Point,
+    width: u32,
+    height: u32,
+    radius: u32,
+    color: Color,
+) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    let r = radius.min((width - 1) / 2).min((height - 1) / 2) as i32;
+    let left = top_left.x + r;
+    let right = top_left.x + width as i32 - 1 - r;
+    let top = top_left.y + r;
+    let bottom = top_left.y + height as i32 - 1 - r;
+
+    for y in top..=bottom {
+        horizontal_line(fb, top_left.x, top_left.x + width as i32 - 1, y, color);
+    }
+    circle_spans(r, |dx, dy| {
+        if dy != 0 {
+            horizontal_line(fb, left - dx, right + dx, top - dy, color);
+            horizontal_line(fb, left - dx, right + dx, bottom + dy, color);
+        }
+    });
+}
+
+/// Draws the outline of a circle.
+pub fn circle<F: Framebuffer>(fb: &mut F, center: Point, radius: u32, color: Color) {
+    circle_octants(radius as i32, |dx, dy| {
+        pixel(fb, center.x + dx, center.y + dy, color);
+        pixel(fb, center.x - dx, center.y + dy, color);
+        pixel(fb, center.x + dx, center.y - dy, color);
+        pixel(fb, center.x - dx, center.y - dy, color);
+    });
+}
+
+/// Draws a filled circle.
+pub fn fill_circle<F: Framebuffer>(fb: &mut F, center: Point, radius: u32, color: Color) {
+    circle_spans(radius as i32, |dx, dy| fill_span(fb, center, dx, dy, color));
+}
+
+/// Draws the outline of an ellipse with the horizontal radius `radius_x` and the vertical
+/// radius `radius_y`.
+pub fn ellipse<F: Framebuffer>(
+    fb: &mut F,
+    center: Point,
+    radius_x: u32,
+    radius_y: u32,
+    color: Color,
+) {
+    ellipse_quadrant(radius_x as i32, radius_y as i32, |dx, dy| {
+        pixel(fb, center.x + dx, center.y + dy, color);
+        pixel(fb, center.x - dx, center.y + dy, color);
+        pixel(fb, center.x + dx, center.y - dy, color);
+        pixel(fb, center.x - dx, center.y - dy, color);
+    });
+}
+
+/// Draws a filled ellipse with the horizontal radius `radius_x` and the vertical radius
+/// `radius_y`.
+pub fn fill_ellipse<F: Framebuffer>(
+    fb: &mut F,
+    center: Point,
+    radius_x: u32,
+    radius_y: u32,
+    color: Color,
+) {
+    let mut row = None;
+    ellipse_quadrant(radius_x as i32, radius_y as i32, |dx, dy| {
+        // the points of a row are visited one after another with increasing `dx`
+        match row {
+            Some((last_dx, last_dy)) if last_dy != dy => {
+                fill_span(fb, center, last_dx, last_dy, color)
+            }
+            _ => {}
+        }
+        row = Some((dx, dy));
+    });
+    if let Some((dx, dy)) = row {
+        fill_span(fb, center, dx, dy, color);
+    }
+}
+
+/// Draws the outline of a polygon. The last point is connected to the first point.
+pub fn polygon<F: Framebuffer>(fb: &mut F, points: &[Point], color: Color) {
+    for (i, &start) in points.iter().enumerate() {
+        let end = points[(i + 1) % points.len()];
+        line(fb, start, end, color);
+    }
+}
+
+/// Draws a filled polygon. The last point is connected to the first point.
+///
+/// Self-intersecting polygons are filled with the even-odd rule. A pixel is filled if its
+/// center lies inside of the polygon.
+pub fn fill_polygon<F: Framebuffer>(fb: &mut F, points: &[Point], color: Color) {
+    if points.is_empty() {
+        return;
+    }
+    let top = points.iter().map(|p| p.y).min().unwrap().max(0);
+    let bottom = points.iter().map(|p| p.y).max().unwrap().min(HEIGHT as i32 - 1);
+
+    let mut crossings = Vec::new();
+    for y in top..=bottom {
+        // Intersect the line through the pixel centers with all edges. The centers are at
+        // `y + 0.5`, so all coordinates are doubled to stay in integers. The products of two
+        // differences don't fit into an `i32`.
+        let center_y = 2 * i64::from(y) + 1;
+        crossings.clear();
+        for (i, &a) in points.iter().enumerate() {
+            let b = points[(i + 1) % points.len()];
+            let (a_y, b_y) = (2 * i64::from(a.y), 2 * i64::from(b.y));
+            if (a_y <= center_y) == (b_y <= center_y) {
+                continue;
+            }
+            // doubled x coordinate of the crossing
+            let (a_x, b_x) = (i64::from(a.x), i64::from(b.x));
+            let x = 2 * a_x + (center_y - a_y) * 2 * (b_x - a_x) / (b_y - a_y);
+            crossings.push(x);
+        }
+        crossings.sort_unstable();
+        for pair in crossings.chunks(2) {
+            if let [start, end] = *pair {
+                // fill the pixels whose centers `2 * x + 1` are between the crossings
+                let first = div_floor(start, 2);
+                let last = div_floor(end - 1, 2);
+                horizontal_line(fb, clamp_x(first), clamp_x(last), y, color);
+            }
+        }
+    }
+}
+
+// Sets a pixel, if it is on the display.
+fn pixel<F: Framebuffer>(fb: &mut F, x: i32, y: i32, color: Color) {
+    if x >= 0 && y >= 0 && (x as usize) < WIDTH && (y as usize) < HEIGHT {
+        fb.set_pixel(x as usize, y as usize, color);
+    }
+}
+
+// Blends `color` with the pixel of `buffer` at `x` and `y`, if it is on the display. Without a
+// buffer, the pixel is set to `color`.
+pub(super) fn blend<F: Framebuffer>(fb: &mut F, buffer: Option<PixelBuffer>, x: i32, y: i32, color: Color) {
+    if x < 0 || y < 0 || x as usize >= WIDTH || y as usize >= HEIGHT {
+        return;
+    }
+    let (x, y) = (x as usize, y as usize);
+    match buffer {
+        Some(buffer) if x < buffer.width() && y < buffer.height() => {
+            let below = buffer.get_pixel(x, y);
+            fb.set_pixel(x, y, blend_pixel(color, below, 255));
+        }
+        _ => fb.set_pixel(x, y, color),
+    }
+}
+
+// Limits a column to the range from just left of the display to just right of it.
+fn clamp_x(x: i64) -> i32 {
+    x.max(-1).min(WIDTH as i64) as i32
+}
+
+// Limits a row to the range from just above the display to just below it.
+fn clamp_y(y: i64) -> i32 {
+    y.max(-1).min(HEIGHT as i64) as i32
+}
+
+// A point with coordinates that can't overflow while clipping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Point64 {
+    x: i64,
+    y: i64,
+}
+
+// Clips a line to the display, see `clip_line_to`.
+fn clip_line(start: Point, end: Point) -> Option<(Point64, Point64)> {
+    clip_line_to(start, end, 0, 0, WIDTH as i64 - 1, HEIGHT as i64 - 1)
+}
+
+// Clips the line from `start` to `end` to the rectangle with the given inclusive bounds with the
+// Cohen–Sutherland algorithm. Returns `None` if the line lies outside of the rectangle.
+//
+// The points where the line leaves the rectangle are rounded to the nearest pixel.
+fn clip_line_to(
+    start: Point,
+    end: Point,
+    left: i64,
+    top: i64,
+    right: i64,
+    bottom: i64,
+) -> Option<(Point64, Point64)> {
+    const LEFT: u8 = 0b0001;
+    const RIGHT: u8 = 0b0010;
+    const ABOVE: u8 = 0b0100;
+    const BELOW: u8 = 0b1000;
+
+    let outcode = |p: Point64| {
+        let mut code = 0;
+        if p.x < left {
+            code |= LEFT;
+        } else if p.x > right {
+            code |= RIGHT;
+        }
+        if p.y < top {
+            code |= ABOVE;
+        } else if p.y > bottom {
+            code |= BELOW;
+        }
+        code
+    };
+    // the offset along the other axis at `distance` of `length`, rounded to the nearest pixel
+    let offset = |delta: i64, distance: i64, length: i64| {
+        let product = i128::from(delta) * i128::from(distance);
+        let length = i128::from(length);
+        let rounded = if (product < 0) == (length < 0) {
+            (product + length / 2) / length
+        } else {
+            (product - length / 2) / length
+        };
+        rounded as i64
+    };
+
+    let mut a = Point64 {
+        x: i64::from(start.x),
+        y: i64::from(start.y),
+    };
+    let mut b = Point64 {
+        x: i64::from(end.x),
+        y: i64::from(end.y),
+    };
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let (mut code_a, mut code_b) = (outcode(a), outcode(b));
+    // Each end point is moved at most twice. A line that is still outside after that passes the
+    // corner of the rectangle by less than half a pixel.
+    for _ in 0..=4 {
+        if code_a | code_b == 0 {
+            return Some((a, b));
+        } else if code_a & code_b != 0 {
+            return None;
+        }
+        // move an outside end point onto the edge of the rectangle
+        let code = if code_a != 0 { code_a } else { code_b };
+        let (x0, y0) = (i64::from(start.x), i64::from(start.y));
+        let p = if code & ABOVE != 0 {
+            Point64 {
+                x: x0 + offset(dx, top - y0, dy),
+                y: top,
+            }
+        } else if code & BELOW != 0 {
+            Point64 {
+                x: x0 + offset(dx, bottom - y0, dy),
+                y: bottom,
+            }
+        } else if code & LEFT != 0 {
+            Point64 {
+                x: left,
+                y: y0 + offset(dy, left - x0, dx),
+            }
+        } else {
+            Point64 {
+                x: right,
+                y: y0 + offset(dy, right - x0, dx),
+            }
+        };
+        if code == code_a {
+            a = p;
+            code_a = outcode(a);
+        } else {
+            b = p;
+            code_b = outcode(b);
+        }
+    }
+    None
+}
+
+// Draws the pixels from `start` to `end` (inclusive) in row `y`.
+fn horizontal_line<F: Framebuffer>(fb: &mut F, start: i32, end: i32, y: i32, color: Color) {
+    if y < 0 || y as usize >= HEIGHT {
+        return;
+    }
+    let start = start.max(0);
+    let end = end.min(WIDTH as i32 - 1);
+    for x in start..=end {
+        fb.set_pixel(x as usize, y as usize, color);
+    }
+}
+
+// Draws the symmetric horizontal spans from `center.x - dx` to `center.x + dx` in the rows
+// `center.y ± dy`.
+fn fill_span<F: Framebuffer>(fb: &mut F, center: Point, dx: i32, dy: i32, color: Color) {
+    horizontal_line(fb, center.x - dx, center.x + dx, center.y + dy, color);
+    if dy != 0 {
+        horizontal_line(fb, center.x - dx, center.x + dx, center.y - dy, color);
+    }
+}
+
+// Calls `f` with the offsets of the points of a circle in the lower right quadrant
+// (`dx >= 0`, `dy >= 0`).
+fn circle_octants(radius: i32, mut f: impl FnMut(i32, i32)) {
+    // midpoint circle algorithm
+    let (mut x, mut y) = (radius, 0);
+    let mut error = 1 - radius;
+    while x >= y {
+        f(x, y);
+        f(y, x);
+        y += 1;
+        if error < 0 {
+            error += 2 * y + 1;
+        } else {
+            x -= 1;
+            error += 2 * (y - x) + 1;
+        }
+    }
+}
+
+// Calls `f` once for each row `dy` of the lower right quadrant of a circle with the largest
+// offset `dx` of the points of `circle_octants` in that row.
+fn circle_spans(radius: i32, mut f: impl FnMut(i32, i32)) {
+    let (mut x, mut y) = (radius, 0);
+    let mut error = 1 - radius;
+    while x >= y {
+        f(x, y);
+        y += 1;
+        if error < 0 {
+            error += 2 * y + 1;
+        } else {
+            // the row `x` of the second octant ends when `x` changes
+            if x >= y {
+                f(y - 1, x);
+            }
+            x -= 1;
+            error += 2 * (y - x) + 1;
+        }
+    }
+}
+
+// Calls `f` with the offsets of the points of an ellipse in the lower right quadrant
+// (`dx >= 0`, `dy >= 0`).
+fn ellipse_quadrant(radius_x: i32, radius_y: i32, mut f: impl FnMut(i32, i32)) {
+    if radius_x == 0 || radius_y == 0 {
+        for x in 0..=radius_x {
+            for y in 0..=radius_y {
+                f(x, y);
+            }
+        }
+        return;
+    }
+
+    // midpoint ellipse algorithm
+    let (a2, b2) = (
+        i64::from(radius_x) * i64::from(radius_x),
+        i64::from(radius_y) * i64::from(radius_y),
+    );
+    let (mut x, mut y) = (0i64, i64::from(radius_y));
+
+    // region 1: the slope is flatter than -1
+    let mut d = 4 * b2 - 4 * a2 * i64::from(radius_y) + a2;
+    while b2 * x <= a2 * y {
+        f(x as i32, y as i32);
+        if d >= 0 {
+            y -= 1;
+            d -= 8 * a2 * y;
+        }
+        x += 1;
+        d += 4 * b2 * (2 * x + 1);
+    }
+
+    // region 2: the slope is steeper than -1
+    let mut d = b2 * (2 * x + 1) * (2 * x + 1) + 4 * a2 * (y - 1) * (y - 1) - 4 * a2 * b2;
+    while y >= 0 {
+        f(x as i32, y as i32);
+        if d <= 0 {
+            x += 1;
+            d += 8 * b2 * x;
+        }
+        y -= 1;
+        d += 4 * a2 * (1 - 2 * y);
+    }
+}
+
+fn div_floor(a: i64, b: i64) -> i64 {
+    let quotient = a / b;
+    if (a % b != 0) && ((a < 0) != (b < 0)) {
+        quotient - 1
+    } else {
+        quotient
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::PixelFormat;
+    use super::*;
+    use alloc::vec::Vec;
+
+    const RED: Color = Color::rgb(255, 0, 0);
+
+    // A framebuffer in memory that counts how often each pixel is set.
+    struct Pixels {
+        colors: Vec<Option<Color>>,
+        writes: Vec<u32>,
+    }
+
+    impl Pixels {
+        fn new() -> Pixels {
+            Pixels {
+                colors: vec![None; WIDTH * HEIGHT],
+                writes: vec![0; WIDTH * HEIGHT],
+            }
+        }
+
+        fn get(&self, x: usize, y: usize) -> Option<Color> {
+            self.colors[y * WIDTH + x]
+        }
+
+        fn count(&self) -> usize {
+            self.colors.iter().filter(|color| color.is_some()).count()
+        }
+
+        // Returns whether all pixels that are set in `self` are set in `other`.
+        fn is_subset_of(&self, other: &Pixels) -> bool {
+            let mut pixels = self.colors.iter().zip(&other.colors);
+            pixels.all(|(a, b)| a.is_none() || b.is_some())
+        }
+
+        fn max_writes(&self) -> u32 {
+            self.writes.iter().cloned().max().unwrap()
+        }
+    }
+
+    impl Framebuffer for Pixels {
+        fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+            self.colors[y * WIDTH + x] = Some(color);
+            self.writes[y * WIDTH + x] += 1;
+        }
+    }
+
+    fn draw(f: impl FnOnce(&mut Pixels)) -> Pixels {
+        let mut pixels = Pixels::new();
+        f(&mut pixels);
+        pixels
+    }
+
+    #[test]
+    fn lines() {
+        let (start, end) = (Point::new(2, 3), Point::new(7, 5));
+        let pixels = draw(|fb| line(fb, start, end, RED));
+        assert_eq!(pixels.count(), 6);
+        assert_eq!(pixels.get(2, 3), Some(RED));
+        assert_eq!(pixels.get(7, 5), Some(RED));
+        let reversed = draw(|fb| line(fb, end, start, RED));
+        assert_eq!(reversed.colors, pixels.colors);
+
+        // only the visible part of the diagonal is drawn
+        let pixels = draw(|fb| line(fb, Point::new(-10, -10), Point::new(10, 10), RED));
+        assert_eq!(pixels.count(), 11);
+        assert_eq!(pixels.get(0, 0), Some(RED));
+    }
+
+    #[test]
+    fn anti_aliased_lines() {
+        let pixels = draw(|fb| line_aa(fb, Point::new(0, 5), Point::new(9, 5), RED));
+        assert_eq!(pixels.count(), 10);
+        assert!((0..10).all(|x| pixels.get(x, 5) == Some(RED)));
+
+        // the coverage of the pixels between two rows is split
+        let pixels = draw(|fb| line_aa(fb, Point::new(0, 0), Point::new(4, 2), RED));
+        assert_eq!(pixels.get(1, 0), Some(Color::rgba(255, 0, 0, 127)));
+        assert_eq!(pixels.get(1, 1), Some(Color::rgba(255, 0, 0, 128)));
+        assert_eq!(pixels.get(4, 2), Some(RED));
+    }
+
+    #[test]
+    fn rectangles() {
+        let outline = draw(|fb| rect(fb, Point::new(10, 10), 10, 5, RED));
+        assert_eq!(outline.count(), 2 * 10 + 2 * 3);
+        let filled = draw(|fb| fill_rect(fb, Point::new(10, 10), 10, 5, RED));
+        assert_eq!(filled.count(), 10 * 5);
+        assert!(outline.is_subset_of(&filled));
+
+        let clipped = draw(|fb| fill_rect(fb, Point::new(-5, HEIGHT as i32 - 5), 10, 10, RED));
+        assert_eq!(clipped.count(), 5 * 5);
+        let empty = draw(|fb| {
+            rect(fb, Point::new(0, 0), 0, 5, RED);
+            fill_rect(fb, Point::new(0, 0), 5, 0, RED);
+        });
+        assert_eq!(empty.count(), 0);
+    }
+
+    #[test]
+    fn circles() {
+        let center = Point::new(100, 100);
+        for radius in 0..40 {
+            let outline = draw(|fb| circle(fb, center, radius, RED));
+            let filled = draw(|fb| fill_circle(fb, center, radius, RED));
+            assert!(outline.is_subset_of(&filled), "radius {}", radius);
+            assert_eq!(filled.max_writes(), 1, "radius {}", radius);
+
+            let r = radius as usize;
+            assert!(filled.get(100 + r, 100).is_some() && filled.get(100 + r + 1, 100).is_none());
+            assert!(filled.get(100, 100 - r).is_some() && filled.get(100, 100 - r - 1).is_none());
+        }
+
+        let filled = draw(|fb| fill_circle(fb, center, 20, RED));
+        let area = core::f32::consts::PI * 20.0 * 20.0;
+        assert!((filled.count() as f32 - area).abs() < area * 0.05);
+    }
+
+    #[test]
+    fn ellipses() {
+        let center = Point::new(100, 100);
+        let outline = draw(|fb| ellipse(fb, center, 30, 10, RED));
+        let filled = draw(|fb| fill_ellipse(fb, center, 30, 10, RED));
+        assert!(outline.is_subset_of(&filled));
+        assert_eq!(filled.max_writes(), 1);
+        let row = (0..WIDTH).filter(|&x| filled.get(x, 100).is_some());
+        assert_eq!(row.count(), 61);
+        let column = (0..HEIGHT).filter(|&y| filled.get(100, y).is_some());
+        assert_eq!(column.count(), 21);
+
+        // ellipses with a radius of zero are lines
+        let vertical = draw(|fb| fill_ellipse(fb, center, 0, 10, RED));
+        assert_eq!(vertical.count(), 21);
+        let horizontal = draw(|fb| fill_ellipse(fb, center, 10, 0, RED));
+        assert_eq!(horizontal.count(), 21);
+    }
+
+    #[test]
+    fn rounded_rectangles() {
+        let top_left = Point::new(10, 20);
+        for radius in 0..12 {
+            let outline = draw(|fb| rounded_rect(fb, top_left, 40, 20, radius, RED));
+            let filled = draw(|fb| fill_rounded_rect(fb, top_left, 40, 20, radius, RED));
+            assert!(outline.is_subset_of(&filled), "radius {}", radius);
+            assert_eq!(filled.max_writes(), 1, "radius {}", radius);
+            let corner = filled.get(10, 20).is_some();
+            assert_eq!(corner, radius == 0, "radius {}", radius);
+            assert!(filled.get(30, 20).is_some() && filled.get(30, 39).is_some());
+            assert!(filled.get(10, 30).is_some() && filled.get(49, 30).is_some());
+        }
+
+        let square = draw(|fb| fill_rounded_rect(fb, top_left, 40, 20, 0, RED));
+        assert_eq!(square.count(), 40 * 20);
+    }
+
+    #[test]
+    fn large_shapes_are_clipped() {
+        let center = Point::new(WIDTH as i32 / 2, HEIGHT as i32 / 2);
+        let screen = draw(|fb| fill_circle(fb, center, 100_000, RED));
+        assert_eq!(screen.count(), WIDTH * HEIGHT);
+        let screen = draw(|fb| fill_ellipse(fb, center, 10_000, 20_000, RED));
+        assert_eq!(screen.count(), WIDTH * HEIGHT);
+
+        let outside = draw(|fb| {
+            fill_circle(fb, Point::new(-100_000, 0), 50_000, RED);
+            fill_rounded_rect(fb, Point::new(0, -100_000), 1000, 1000, 100, RED);
+        });
+        assert_eq!(outside.count(), 0);
+    }
+
+    #[test]
+    fn polygons() {
+        let square = [
+            Point::new(0, 0),
+            Point::new(10, 0),
+            Point::new(10, 10),
+            Point::new(0, 10),
+        ];
+        let filled = draw(|fb| fill_polygon(fb, &square, RED));
+        assert_eq!(filled.count(), 10 * 10);
+        let outline = draw(|fb| polygon(fb, &square, RED));
+        assert_eq!(outline.count(), 4 * 10);
+
+        // the overlap of the two triangles is not filled with the even-odd rule
+        let bowtie = [
+            Point::new(0, 0),
+            Point::new(20, 20),
+            Point::new(20, 0),
+            Point::new(0, 20),
+        ];
+        let filled = draw(|fb| fill_polygon(fb, &bowtie, RED));
+        assert!(filled.get(2, 10).is_some() && filled.get(17, 10).is_some());
+        assert!(filled.get(10, 2).is_none() && filled.get(10, 17).is_none());
+
+        assert_eq!(draw(|fb| fill_polygon(fb, &[], RED)).count(), 0);
+    }
+
+    #[test]
+    fn extreme_coordinates() {
+        let (min, max) = (i32::min_value(), i32::max_value());
+        let diagonal = draw(|fb| line(fb, Point::new(min, min), Point::new(max, max), RED));
+        assert_eq!(diagonal.count(), HEIGHT);
+        assert!((0..HEIGHT).all(|i| diagonal.get(i, i) == Some(RED)));
+        let row = draw(|fb| line_aa(fb, Point::new(max, 10), Point::new(min, 10), RED));
+        assert_eq!(row.count(), WIDTH);
+        let outside = draw(|fb| line(fb, Point::new(min, -1), Point::new(max, -1), RED));
+        assert_eq!(outside.count(), 0);
+
+        let size = u32::max_value();
+        let screen = draw(|fb| fill_rect(fb, Point::new(min, min), size, size, RED));
+        assert_eq!(screen.count(), WIDTH * HEIGHT);
+        let outline = draw(|fb| rect(fb, Point::new(-1, -1), size, size, RED));
+        assert_eq!(outline.count(), 0);
+
+        let triangle = [
+            Point::new(min, min),
+            Point::new(max, 0),
+            Point::new(min, max),
+        ];
+        let filled = draw(|fb| fill_polygon(fb, &triangle, RED));
+        assert!(filled.get(0, 0).is_some() && filled.get(WIDTH - 1, 0).is_some());
+        assert!(filled.get(0, HEIGHT - 1).is_some());
+    }
+
+    #[test]
+    fn clipped_lines_keep_their_slope() {
+        // the visible part of a line is drawn like the line between its points on the edges
+        let clipped = draw(|fb| line(fb, Point::new(-20, 0), Point::new(20, 20), RED));
+        let visible = draw(|fb| line(fb, Point::new(0, 10), Point::new(20, 20), RED));
+        assert_eq!(clipped.colors, visible.colors);
+    }
+
+    #[test]
+    fn anti_aliased_lines_are_blended() {
+        let mut memory = vec![0xff00_00ffu32; WIDTH * HEIGHT];
+        let addr = memory.as_mut_ptr() as usize;
+        let mut buffer = unsafe {
+            PixelBuffer::from_raw_parts(addr, WIDTH, HEIGHT, PixelFormat::Argb8888)
+        };
+        line_aa(&mut buffer, Point::new(0, 0), Point::new(4, 2), RED);
+        assert_eq!(buffer.get_pixel(0, 0), RED);
+        // half of the pixel is covered by the line, the other half stays blue
+        assert_eq!(buffer.get_pixel(1, 0), Color::rgba(127, 0, 128, 255));
+        assert_eq!(buffer.get_pixel(0, 1), Color::rgb(0, 0, 255));
+    }
+}
@@ -0,0 +1,217 @@
+//! Windows bitmaps with 24 or 32 bits per pixel.
+//!
+//! Uncompressed (`BI_RGB`) and bit field (`BI_BITFIELDS`) images are supported. The fourth byte
+//! of uncompressed 32 bit images is ignored, an alpha channel is only used if the header
+//! defines an alpha mask.
+
+use super::{check_dimensions, Error, ImageFormat, ImageInfo, Input, Read};
+use alloc::vec::Vec;
+
+const INFO_HEADER_SIZE: u32 = 40;
+const V4_HEADER_SIZE: u32 = 108;
+
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+
+pub(super) struct Header {
+    width: u32,
+    height: u32,
+    top_down: bool,
+    bits_per_pixel: u16,
+    // red, green, blue and alpha masks of 32 bit pixels
+    masks: [u32; 4],
+    data_offset: u32,
+}
+
+impl Header {
+    pub(super) fn info(&self) -> ImageInfo {
+        ImageInfo {
+            format: ImageFormat::Bmp,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+pub(super) fn read_header<R: Read>(input: &mut Input<R>) -> Result<Header, Error<R::Error>> {
+    // file header
+    if input.u16_le()? != u16::from_le_bytes(*b"BM") {
+        return Err(Error::UnknownFormat);
+    }
+    input.skip(8)?; // file size and reserved
+    let data_offset = input.u32_le()?;
+
+    // info header
+    let header_size = input.u32_le()?;
+    if header_size < INFO_HEADER_SIZE {
+        return Err(Error::Unsupported); // OS/2 bitmap
+    }
+    let width = input.u32_le()? as i32;
+    let height = input.u32_le()? as i32;
+    let _planes = input.u16_le()?;
+    let bits_per_pixel = input.u16_le()?;
+    let compression = input.u32_le()?;
+    input.skip(20)?; // image size, resolution and palette sizes
+
+    let masks = match (bits_per_pixel, compression) {
+        (24, BI_RGB) => [0; 4],
+        (32, BI_RGB) => [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0],
+        (32, BI_BITFIELDS) => {
+            let mut masks = [0; 4];
+            for mask in &mut masks[..3] {
+                *mask = input.u32_le()?;
+            }
+            if header_size >= V4_HEADER_SIZE {
+                masks[3] = input.u32_le()?;
+            }
+            masks
+        }
+        _ => return Err(Error::Unsupported),
+    };
+
+    if width <= 0 || height == 0 || height == i32::min_value() {
+        return Err(Error::InvalidData);
+    }
+    // negative heights are used by images that are stored top-down
+    let top_down = height < 0;
+    let (width, height) = (width as u32, height.abs() as u32);
+    check_dimensions(width, height)?;
+    if u64::from(data_offset) < input.position {
+        return Err(Error::InvalidData);
+    }
+    Ok(Header {
+        width,
+        height,
+        top_down,
+        bits_per_pixel,
+        masks,
+        data_offset,
+    })
+}
+
+pub(super) fn decode<R, F>(
+    input: &mut Input<R>,
+    header: &Header,
+    pixel: &mut F,
+) -> Result<(), Error<R::Error>>
+where
+    R: Read,
+    F: FnMut(u32, u32, [u8; 4]),
+{
+    input.skip(u64::from(header.data_offset) - input.position)?;
+
+    let bytes_per_pixel = usize::from(header.bits_per_pixel / 8);
+    // rows are padded to a multiple of 4 bytes
+    let row_size = (header.width as usize * bytes_per_pixel + 3) & !3;
+    let mut row = Vec::new();
+    row.resize(row_size, 0);
+    let channels = [
+        Channel::new(header.masks[0]),
+        Channel::new(header.masks[1]),
+        Channel::new(header.masks[2]),
+        Channel::new(header.masks[3]),
+    ];
+
+    for i in 0..header.height {
+        input.read_exact(&mut row)?;
+        let y = if header.top_down {
+            i
+        } else {
+            header.height - 1 - i
+        };
+        for (x, bytes) in row.chunks(bytes_per_pixel).take(header.width as usize).enumerate() {
+            let color = if bytes_per_pixel == 3 {
+                [bytes[2], bytes[1], bytes[0], 255]
+            } else {
+                let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                let alpha = channels[3].extract(value).unwrap_or(255);
+                let channel = |i: usize| channels[i].extract(value).unwrap_or(0);
+                [channel(0), channel(1), channel(2), alpha]
+            };
+            pixel(x as u32, y, color);
+        }
+    }
+    Ok(())
+}
+
+// A color channel of a 32 bit pixel, defined by a bit mask.
+#[derive(Clone, Copy)]
+struct Channel {
+    mask: u32,
+    shift: u32,
+    max: u32,
+}
+
+impl Channel {
+    fn new(mask: u32) -> Channel {
+        let shift = if mask == 0 { 0 } else { mask.trailing_zeros() };
+        Channel {
+            mask,
+            shift,
+            max: mask >> shift,
+        }
+    }
+
+    // Returns the value of the channel scaled to 8 bits, or `None` if the mask is empty.
+    fn extract(&self, pixel: u32) -> Option<u8> {
+        if self.mask == 0 {
+            return None;
+        }
+        let value = (pixel & self.mask) >> self.shift;
+        Some((u64::from(value) * 255 / u64::from(self.max)) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::read_info;
+    use super::super::tests::{assert_pattern, decode_image};
+    use super::*;
+
+    const RGB: &[u8] = include_bytes!("testdata/rgb.bmp");
+
+    #[test]
+    fn bottom_up() {
+        assert_pattern(RGB, ImageFormat::Bmp, false);
+    }
+
+    #[test]
+    fn top_down_with_alpha_mask() {
+        assert_pattern(include_bytes!("testdata/rgba.bmp"), ImageFormat::Bmp, true);
+    }
+
+    #[test]
+    fn truncated() {
+        // the rows of 13 pixels are padded to 40 bytes, the padding of the last row is read too
+        for len in 0..RGB.len() {
+            assert!(decode_image(&RGB[..len]).is_err(), "{} bytes", len);
+        }
+        assert_eq!(decode_image(&RGB[..RGB.len() - 1]).err(), Some(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn unsupported() {
+        let mut data = RGB.to_vec();
+        data[28] = 16; // bits per pixel
+        assert_eq!(read_info(&data[..]), Err(Error::Unsupported));
+
+        let mut data = RGB.to_vec();
+        data[30] = 1; // RLE compression
+        assert_eq!(read_info(&data[..]), Err(Error::Unsupported));
+
+        let mut data = RGB.to_vec();
+        data[14] = 12; // OS/2 header
+        assert_eq!(read_info(&data[..]), Err(Error::Unsupported));
+    }
+
+    #[test]
+    fn invalid_header() {
+        let mut data = RGB.to_vec();
+        data[18..22].copy_from_slice(&0u32.to_le_bytes()); // width
+        assert_eq!(read_info(&data[..]), Err(Error::InvalidData));
+
+        let mut data = RGB.to_vec();
+        data[10..14].copy_from_slice(&20u32.to_le_bytes()); // data offset inside the header
+        assert_eq!(read_info(&data[..]), Err(Error::InvalidData));
+    }
+}
@@ -0,0 +1,384 @@
+//! Streaming decompression of zlib data (RFC 1950 and RFC 1951).
+//!
+//! The decompressed bytes are passed to a function as they are decoded, so only the 32 KiB
+//! window of the previous output is kept in memory. The Adler-32 checksum isn't verified.
+
+use super::Error;
+use alloc::vec::Vec;
+
+const WINDOW_SIZE: usize = 1 << 15;
+const MAX_BITS: usize = 15;
+const MAX_LITERAL_LENGTH_CODES: usize = 288;
+const MAX_DISTANCE_CODES: usize = 30;
+const END_OF_BLOCK: u16 = 256;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// The order of the code length code lengths in a dynamic block header.
+const CODE_LENGTH_ORDER: [usize; 19] =
+    [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Decompresses a zlib stream. `input` returns the next byte of the compressed data and `output`
+/// receives the decompressed bytes.
+pub(super) fn decompress<E, I, O>(input: I, output: O) -> Result<(), Error<E>>
+where
+    I: FnMut() -> Result<u8, Error<E>>,
+    O: FnMut(u8) -> Result<(), Error<E>>,
+{
+    let mut decoder = Decoder {
+        input,
+        output,
+        bits: 0,
+        bit_count: 0,
+        window: Vec::new(),
+        position: 0,
+    };
+    decoder.window.resize(WINDOW_SIZE, 0);
+
+    let method = decoder.byte()?;
+    let flags = decoder.byte()?;
+    let header = u16::from(method) << 8 | u16::from(flags);
+    if method & 0x0f != 8 || method >> 4 > 7 || header % 31 != 0 {
+        return Err(Error::InvalidData); // not deflate or invalid check bits
+    }
+    if flags & 0x20 != 0 {
+        return Err(Error::Unsupported); // preset dictionary
+    }
+
+    loop {
+        let last = decoder.bits(1)? == 1;
+        match decoder.bits(2)? {
+            0 => decoder.stored_block()?,
+            1 => {
+                let (literal_length, distance) = fixed_codes();
+                decoder.compressed_block(&literal_length, &distance)?;
+            }
+            2 => {
+                let (literal_length, distance) = decoder.dynamic_codes()?;
+                decoder.compressed_block(&literal_length, &distance)?;
+            }
+            _ => return Err(Error::InvalidData),
+        }
+        if last {
+            return Ok(());
+        }
+    }
+}
+
+struct Decoder<I, O> {
+    input: I,
+    output: O,
+    bits: u32,
+    bit_count: u32,
+    window: Vec<u8>,
+    // the number of decompressed bytes
+    position: usize,
+}
+
+impl<E, I, O> Decoder<I, O>
+where
+    I: FnMut() -> Result<u8, Error<E>>,
+    O: FnMut(u8) -> Result<(), Error<E>>,
+{
+    fn byte(&mut self) -> Result<u8, Error<E>> {
+        (self.input)()
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, Error<E>> {
+        while self.bit_count < count {
+            self.bits |= u32::from(self.byte()?) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = self.bits & ((1 << count) - 1);
+        self.bits >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    fn emit(&mut self, byte: u8) -> Result<(), Error<E>> {
+        self.window[self.position % WINDOW_SIZE] = byte;
+        self.position += 1;
+        (self.output)(byte)
+    }
+
+    fn stored_block(&mut self) -> Result<(), Error<E>> {
+        // skip to the byte boundary
+        self.bits = 0;
+        self.bit_count = 0;
+        let length = u16::from(self.byte()?) | u16::from(self.byte()?) << 8;
+        let inverted = u16::from(self.byte()?) | u16::from(self.byte()?) << 8;
+        if length != !inverted {
+            return Err(Error::InvalidData);
+        }
+        for _ in 0..length {
+            let byte = self.byte()?;
+            self.emit(byte)?;
+        }
+        Ok(())
+    }
+
+    fn compressed_block(
+        &mut self,
+        literal_length: &Huffman,
+        distance: &Huffman,
+    ) -> Result<(), Error<E>> {
+        loop {
+            let symbol = self.symbol(literal_length)?;
+            if symbol < END_OF_BLOCK {
+                self.emit(symbol as u8)?;
+                continue;
+            } else if symbol == END_OF_BLOCK {
+                return Ok(());
+            }
+
+            let index = usize::from(symbol - END_OF_BLOCK - 1);
+            if index >= LENGTH_BASE.len() {
+                return Err(Error::InvalidData);
+            }
+            let length = usize::from(LENGTH_BASE[index])
+                + self.bits(u32::from(LENGTH_EXTRA[index]))? as usize;
+
+            let index = usize::from(self.symbol(distance)?);
+            if index >= DISTANCE_BASE.len() {
+                return Err(Error::InvalidData);
+            }
+            let distance = usize::from(DISTANCE_BASE[index])
+                + self.bits(u32::from(DISTANCE_EXTRA[index]))? as usize;
+            if distance > self.position {
+                return Err(Error::InvalidData);
+            }
+
+            for _ in 0..length {
+                let byte = self.window[(self.position - distance) % WINDOW_SIZE];
+                self.emit(byte)?;
+            }
+        }
+    }
+
+    fn dynamic_codes(&mut self) -> Result<(Huffman, Huffman), Error<E>> {
+        let literal_length_count = self.bits(5)? as usize + 257;
+        let distance_count = self.bits(5)? as usize + 1;
+        let code_length_count = self.bits(4)? as usize + 4;
+        if literal_length_count > MAX_LITERAL_LENGTH_CODES || distance_count > MAX_DISTANCE_CODES {
+            return Err(Error::InvalidData);
+        }
+
+        let mut lengths = [0u8; MAX_LITERAL_LENGTH_CODES + MAX_DISTANCE_CODES];
+        for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+            lengths[index] = self.bits(3)? as u8;
+        }
+        let code_length = Huffman::new(&lengths[..CODE_LENGTH_ORDER.len()], false)
+            .ok_or(Error::InvalidData)?;
+
+        // the literal/length and distance code lengths are one sequence that can contain runs
+        let total = literal_length_count + distance_count;
+        let mut lengths = [0u8; MAX_LITERAL_LENGTH_CODES + MAX_DISTANCE_CODES];
+        let mut i = 0;
+        while i < total {
+            let symbol = self.symbol(&code_length)?;
+            let (length, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 if i > 0 => (lengths[i - 1], 3 + self.bits(2)?),
+                17 => (0, 3 + self.bits(3)?),
+                18 => (0, 11 + self.bits(7)?),
+                _ => return Err(Error::InvalidData),
+            };
+            let repeat = repeat as usize;
+            if i + repeat > total {
+                return Err(Error::InvalidData);
+            }
+            for length_slot in &mut lengths[i..i + repeat] {
+                *length_slot = length;
+            }
+            i += repeat;
+        }
+        if lengths[usize::from(END_OF_BLOCK)] == 0 {
+            return Err(Error::InvalidData);
+        }
+
+        let literal_length =
+            Huffman::new(&lengths[..literal_length_count], false).ok_or(Error::InvalidData)?;
+        let distance =
+            Huffman::new(&lengths[literal_length_count..total], true).ok_or(Error::InvalidData)?;
+        Ok((literal_length, distance))
+    }
+
+    // Decodes a symbol bit by bit with the canonical code.
+    fn symbol(&mut self, code: &Huffman) -> Result<u16, Error<E>> {
+        let mut value = 0i32; // the bits read so far
+        let mut first = 0i32; // the first code of the current length
+        let mut index = 0i32; // the index of the first code of the current length in `symbols`
+        for &count in &code.counts[1..] {
+            value |= self.bits(1)? as i32;
+            let count = i32::from(count);
+            if value - first < count {
+                return Ok(code.symbols[(index + value - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            value <<= 1;
+        }
+        Err(Error::InvalidData)
+    }
+}
+
+// A canonical Huffman code, defined by the number of codes of each length and the symbols
+// ordered by their codes.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: [u16; MAX_LITERAL_LENGTH_CODES],
+}
+
+impl Huffman {
+    // Creates the code from the code lengths of the symbols. Returns `None` if the lengths are
+    // over-subscribed or incomplete. Incomplete codes are allowed for distances, which may
+    // consist of a single code.
+    fn new(lengths: &[u8], allow_incomplete: bool) -> Option<Huffman> {
+        let mut code = Huffman {
+            counts: [0; MAX_BITS + 1],
+            symbols: [0; MAX_LITERAL_LENGTH_CODES],
+        };
+        for &length in lengths {
+            code.counts[usize::from(length)] += 1;
+        }
+        code.counts[0] = 0;
+
+        let mut left = 1i32;
+        for &count in &code.counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return None;
+            }
+        }
+        if left > 0 && !allow_incomplete {
+            return None;
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + code.counts[length];
+        }
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                let offset = &mut offsets[usize::from(length)];
+                code.symbols[usize::from(*offset)] = symbol as u16;
+                *offset += 1;
+            }
+        }
+        Some(code)
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; MAX_LITERAL_LENGTH_CODES];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    let literal_length = Huffman::new(&lengths, false).unwrap();
+    let distance = Huffman::new(&[5; MAX_DISTANCE_CODES], true).unwrap();
+    (literal_length, distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inflate(data: &[u8]) -> Result<Vec<u8>, Error<()>> {
+        let mut input = data.iter();
+        let mut output = Vec::new();
+        decompress(
+            || input.next().cloned().ok_or(Error::UnexpectedEnd),
+            |byte| {
+                output.push(byte);
+                Ok(())
+            },
+        )?;
+        Ok(output)
+    }
+
+    const STORED: [u8; 24] = [
+        0x78, 0x01, 0x01, 0x0d, 0x00, 0xf2, 0xff, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x2c, 0x20, 0x77,
+        0x6f, 0x72, 0x6c, 0x64, 0x21, 0x20, 0x5e, 0x04, 0x8a,
+    ];
+
+    #[test]
+    fn stored_block() {
+        assert_eq!(inflate(&STORED).unwrap(), b"Hello, world!");
+    }
+
+    #[test]
+    fn fixed_codes() {
+        let data = [
+            0x78, 0x01, 0x4b, 0x4c, 0x4a, 0x4e, 0x84, 0x21, 0x85, 0x8c, 0xd4, 0x9c, 0x9c, 0x7c,
+            0x08, 0x09, 0x00, 0x70, 0x12, 0x09, 0x01,
+        ];
+        assert_eq!(inflate(&data).unwrap(), &b"abcabcabcabc hello hello"[..]);
+    }
+
+    #[test]
+    fn dynamic_codes_and_long_distances() {
+        // 1000 random letters, 31000 zeros and the letters 41 times, which needs the whole
+        // window and wraps around it
+        let mut state = 1u32;
+        let letters: Vec<u8> = (0..1000)
+            .map(|_| {
+                state = (state.wrapping_mul(1_103_515_245).wrapping_add(12345)) & 0x7fff_ffff;
+                b'a' + ((state >> 16) % 26) as u8
+            })
+            .collect();
+        let mut expected = letters.clone();
+        expected.resize(32_000, 0);
+        for _ in 0..40 {
+            expected.extend_from_slice(&letters);
+        }
+
+        let data = include_bytes!("testdata/window.zlib");
+        assert_eq!((data[2] >> 1) & 0b11, 2); // the first block uses dynamic codes
+        assert_eq!(inflate(data).unwrap(), expected);
+
+        // the Adler-32 checksum isn't read
+        for len in 0..data.len() - 4 {
+            assert_eq!(inflate(&data[..len]), Err(Error::UnexpectedEnd), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert_eq!(inflate(&[0x78, 0x02]), Err(Error::InvalidData)); // check bits
+        assert_eq!(inflate(&[0x79, 0x1e]), Err(Error::InvalidData)); // not deflate
+        assert_eq!(inflate(&[0x78, 0x20]), Err(Error::Unsupported)); // preset dictionary
+        assert_eq!(inflate(&[0x78]), Err(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn invalid_blocks() {
+        // block type 3
+        assert_eq!(inflate(&[0x78, 0x01, 0x07]), Err(Error::InvalidData));
+
+        // the inverted length of a stored block doesn't match
+        let mut data = STORED;
+        data[5] = 0xf3;
+        assert_eq!(inflate(&data), Err(Error::InvalidData));
+
+        // a fixed block that starts with a match of distance 1
+        assert_eq!(inflate(&[0x78, 0x01, 0x03, 0x02, 0x00]), Err(Error::InvalidData));
+    }
+}
@@ -0,0 +1,468 @@
+//! Decoders for BMP, PNG and QOI images.
+//!
+//! This module only depends on `core` and `alloc`, so the build script uses it to convert the
+//! assets and it can be tested on the host.
+
+use alloc::vec::Vec;
+use core::convert::Infallible;
+
+mod bmp;
+mod inflate;
+mod png;
+mod qoi;
+
+/// The largest width and height of an image that is decoded.
+pub const MAX_DIMENSION: u32 = 1 << 14;
+
+/// A source of bytes, e.g. a byte slice or a file.
+pub trait Read {
+    /// The error that can occur while reading.
+    type Error;
+
+    /// Reads bytes into `buf` and returns the number of bytes read. Returns 0 only at the end of
+    /// the data.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error>;
+}
+
+impl<'a> Read for &'a [u8] {
+    type Error = Infallible;
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+        let len = buf.len().min(self.len());
+        let (data, rest) = self.split_at(len);
+        buf[..len].copy_from_slice(data);
+        *self = rest;
+        Ok(len)
+    }
+}
+
+/// Errors that can occur while decoding an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Reading the image data failed.
+    Read(E),
+    /// The data ended before the image was complete.
+    UnexpectedEnd,
+    /// The data doesn't start with the signature of a supported format.
+    UnknownFormat,
+    /// The image uses a feature of the format that isn't supported, or it is larger than
+    /// `MAX_DIMENSION`.
+    Unsupported,
+    /// The image data is invalid.
+    InvalidData,
+}
+
+/// The file format of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Windows bitmap with 24 or 32 bits per pixel.
+    Bmp,
+    /// Portable Network Graphics.
+    Png,
+    /// Quite OK Image format.
+    Qoi,
+}
+
+impl ImageFormat {
+    /// Detects the format from the first bytes of the image data.
+    pub fn detect(header: &[u8]) -> Option<ImageFormat> {
+        if header.starts_with(b"BM") {
+            Some(ImageFormat::Bmp)
+        } else if header.starts_with(png::SIGNATURE) {
+            Some(ImageFormat::Png)
+        } else if header.starts_with(qoi::MAGIC) {
+            Some(ImageFormat::Qoi)
+        } else {
+            None
+        }
+    }
+}
+
+/// The format and the size of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    /// The file format.
+    pub format: ImageFormat,
+    /// The width in pixels.
+    pub width: u32,
+    /// The height in pixels.
+    pub height: u32,
+}
+
+/// Reads the format and the size of an image without decoding its pixels.
+pub fn read_info<R: Read>(reader: R) -> Result<ImageInfo, Error<R::Error>> {
+    let mut input = Input::new(reader);
+    match detect_format(&mut input)? {
+        ImageFormat::Bmp => bmp::read_header(&mut input).map(|header| header.info()),
+        ImageFormat::Png => png::read_header(&mut input).map(|header| header.info()),
+        ImageFormat::Qoi => qoi::read_header(&mut input).map(|header| header.info()),
+    }
+}
+
+/// Decodes an image and passes the coordinates and the RGBA color of each pixel to `pixel`.
+///
+/// The pixels are not passed in a particular order, e.g. BMP images are stored bottom-up.
+pub fn decode<R, F>(reader: R, mut pixel: F) -> Result<ImageInfo, Error<R::Error>>
+where
+    R: Read,
+    F: FnMut(u32, u32, [u8; 4]),
+{
+    let mut input = Input::new(reader);
+    match detect_format(&mut input)? {
+        ImageFormat::Bmp => {
+            let header = bmp::read_header(&mut input)?;
+            bmp::decode(&mut input, &header, &mut pixel)?;
+            Ok(header.info())
+        }
+        ImageFormat::Png => {
+            let header = png::read_header(&mut input)?;
+            png::decode(&mut input, &header, &mut pixel)?;
+            Ok(header.info())
+        }
+        ImageFormat::Qoi => {
+            let header = qoi::read_header(&mut input)?;
+            qoi::decode(&mut input, &header, &mut pixel)?;
+            Ok(header.info())
+        }
+    }
+}
+
+/// The pixel formats that [`convert`](convert) produces. The names match the variants of
+/// `lcd::PixelFormat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawFormat {
+    /// The blue, green, red and alpha bytes of each pixel.
+    Argb8888,
+    /// The luminance and the alpha byte of each pixel.
+    Al88,
+}
+
+impl RawFormat {
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            RawFormat::Argb8888 => 4,
+            RawFormat::Al88 => 2,
+        }
+    }
+}
+
+/// Decodes an image and converts its pixels to `format`. The pixels are stored line by line
+/// without padding.
+///
+/// The build script uses this function to convert the assets.
+#[allow(dead_code)]
+pub fn convert(data: &[u8], format: RawFormat) -> Result<(ImageInfo, Vec<u8>), Error<Infallible>> {
+    let info = read_info(data)?;
+    let (width, height) = (info.width as usize, info.height as usize);
+    let bytes_per_pixel = format.bytes_per_pixel();
+    let mut pixels = Vec::new();
+    pixels.resize(width * height * bytes_per_pixel, 0);
+    decode(data, |x, y, [red, green, blue, alpha]| {
+        let offset = (y as usize * width + x as usize) * bytes_per_pixel;
+        match format {
+            RawFormat::Argb8888 => {
+                pixels[offset..offset + 4].copy_from_slice(&[blue, green, red, alpha]);
+            }
+            RawFormat::Al88 => {
+                pixels[offset..offset + 2].copy_from_slice(&[luminance(red, green, blue), alpha]);
+            }
+        }
+    })?;
+    Ok((info, pixels))
+}
+
+/// Returns the color of entry `index` of the color lookup table that [`RawFormat::Al88`]
+/// images are converted for.
+///
+/// The pixels store their luminance as index, so the table is a gray ramp.
+#[allow(dead_code)]
+pub fn gray_ramp(index: u8) -> [u8; 3] {
+    [index; 3]
+}
+
+fn luminance(red: u8, green: u8, blue: u8) -> u8 {
+    ((299 * u32::from(red) + 587 * u32::from(green) + 114 * u32::from(blue)) / 1000) as u8
+}
+
+// Peeks at the signature. The signature isn't consumed, the decoders check it again.
+fn detect_format<R: Read>(input: &mut Input<R>) -> Result<ImageFormat, Error<R::Error>> {
+    let header = input.peek(png::SIGNATURE.len())?;
+    ImageFormat::detect(header).ok_or(Error::UnknownFormat)
+}
+
+fn check_dimensions<E>(width: u32, height: u32) -> Result<(), Error<E>> {
+    if width == 0 || height == 0 {
+        Err(Error::InvalidData)
+    } else if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        Err(Error::Unsupported)
+    } else {
+        Ok(())
+    }
+}
+
+const INPUT_BUFFER_SIZE: usize = 512;
+
+// A buffered reader with helpers for parsing.
+struct Input<R> {
+    reader: R,
+    buffer: [u8; INPUT_BUFFER_SIZE],
+    start: usize,
+    end: usize,
+    // the number of bytes consumed from the data
+    position: u64,
+}
+
+impl<R: Read> Input<R> {
+    fn new(reader: R) -> Self {
+        Input {
+            reader,
+            buffer: [0; INPUT_BUFFER_SIZE],
+            start: 0,
+            end: 0,
+            position: 0,
+        }
+    }
+
+    // Returns the next `len` bytes without consuming them, or less at the end of the data.
+    fn peek(&mut self, len: usize) -> Result<&[u8], Error<R::Error>> {
+        assert!(len <= INPUT_BUFFER_SIZE);
+        if self.end - self.start < len {
+            for i in self.start..self.end {
+                self.buffer[i - self.start] = self.buffer[i];
+            }
+            self.end -= self.start;
+            self.start = 0;
+            while self.end < len {
+                match self.reader.read(&mut self.buffer[self.end..]) {
+                    Ok(0) => break,
+                    Ok(read) => self.end += read,
+                    Err(err) => return Err(Error::Read(err)),
+                }
+            }
+        }
+        let end = self.end.min(self.start + len);
+        Ok(&self.buffer[self.start..end])
+    }
+
+    fn byte(&mut self) -> Result<u8, Error<R::Error>> {
+        if self.start == self.end {
+            self.start = 0;
+            self.end = 0;
+            match self.reader.read(&mut self.buffer) {
+                Ok(0) => return Err(Error::UnexpectedEnd),
+                Ok(read) => self.end = read,
+                Err(err) => return Err(Error::Read(err)),
+            }
+        }
+        let byte = self.buffer[self.start];
+        self.start += 1;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error<R::Error>> {
+        for byte in buf {
+            *byte = self.byte()?;
+        }
+        Ok(())
+    }
+
+    fn skip(&mut self, len: u64) -> Result<(), Error<R::Error>> {
+        for _ in 0..len {
+            self.byte()?;
+        }
+        Ok(())
+    }
+
+    fn u16_le(&mut self) -> Result<u16, Error<R::Error>> {
+        let mut bytes = [0; 2];
+        self.read_exact(&mut bytes)?;
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn u16_be(&mut self) -> Result<u16, Error<R::Error>> {
+        let mut bytes = [0; 2];
+        self.read_exact(&mut bytes)?;
+        Ok(u16::from_be_bytes(bytes))
+    }
+
+    fn u32_le(&mut self) -> Result<u32, Error<R::Error>> {
+        let mut bytes = [0; 4];
+        self.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u32_be(&mut self) -> Result<u32, Error<R::Error>> {
+        let mut bytes = [0; 4];
+        self.read_exact(&mut bytes)?;
+        Ok(u32::from_be_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The fixtures in `testdata` are 13x9 pixels. The true color images show the pattern below,
+    // images without alpha channel are opaque.
+    pub(super) const WIDTH: u32 = 13;
+    pub(super) const HEIGHT: u32 = 9;
+
+    pub(super) fn pattern(x: u32, y: u32) -> [u8; 4] {
+        if y % 4 == 3 || x == WIDTH - 1 {
+            [10, 20, 30, 200]
+        } else {
+            [(x + y * 20) as u8, (y * 5) as u8, (x * y) as u8, (255 - y * 4) as u8]
+        }
+    }
+
+    // Decodes an image and checks that every pixel is passed exactly once.
+    pub(super) fn decode_image(
+        data: &[u8],
+    ) -> Result<(ImageInfo, Vec<[u8; 4]>), Error<Infallible>> {
+        let info = read_info(data)?;
+        let mut pixels = vec![None; (info.width * info.height) as usize];
+        let decoded = decode(data, |x, y, color| {
+            assert!(x < info.width && y < info.height);
+            let pixel = &mut pixels[(y * info.width + x) as usize];
+            assert_eq!(*pixel, None, "pixel ({}, {}) decoded twice", x, y);
+            *pixel = Some(color);
+        })?;
+        assert_eq!(decoded, info);
+        let pixels = pixels.into_iter().map(|pixel| pixel.expect("pixel missing"));
+        Ok((info, pixels.collect()))
+    }
+
+    // Decodes an image of the pattern and compares the pixels.
+    pub(super) fn assert_pattern(data: &[u8], format: ImageFormat, alpha: bool) {
+        let (info, pixels) = decode_image(data).unwrap();
+        assert_eq!(info, ImageInfo { format, width: WIDTH, height: HEIGHT });
+        for (i, &color) in pixels.iter().enumerate() {
+            let (x, y) = (i as u32 % WIDTH, i as u32 / WIDTH);
+            let mut expected = pattern(x, y);
+            if !alpha {
+                expected[3] = 255;
+            }
+            assert_eq!(color, expected, "pixel ({}, {})", x, y);
+        }
+    }
+
+    const FIXTURES: [&[u8]; 8] = [
+        include_bytes!("testdata/rgba.png"),
+        include_bytes!("testdata/rgba_interlaced.png"),
+        include_bytes!("testdata/rgb16.png"),
+        include_bytes!("testdata/gray2.png"),
+        include_bytes!("testdata/palette4.png"),
+        include_bytes!("testdata/rgb.bmp"),
+        include_bytes!("testdata/rgba.bmp"),
+        include_bytes!("testdata/rgba.qoi"),
+    ];
+
+    // Returns a single byte per call.
+    struct Bytewise<'a>(&'a [u8]);
+
+    impl<'a> Read for Bytewise<'a> {
+        type Error = Infallible;
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    struct Failing;
+
+    impl Read for Failing {
+        type Error = ();
+
+        fn read(&mut self, _buf: &mut [u8]) -> Result<usize, ()> {
+            Err(())
+        }
+    }
+
+    #[test]
+    fn formats_are_detected() {
+        let formats = FIXTURES.iter().map(|data| ImageFormat::detect(data).unwrap());
+        let formats: Vec<_> = formats.collect();
+        assert_eq!(formats[..5], [ImageFormat::Png; 5]);
+        assert_eq!(formats[5..], [ImageFormat::Bmp, ImageFormat::Bmp, ImageFormat::Qoi]);
+        assert_eq!(ImageFormat::detect(b"GIF89a"), None);
+
+        assert_eq!(read_info(&b"GIF89a"[..]), Err(Error::UnknownFormat));
+        assert_eq!(read_info(&b""[..]), Err(Error::UnknownFormat));
+        assert_eq!(decode(Failing, |_, _, _| {}), Err(Error::Read(())));
+    }
+
+    #[test]
+    fn short_reads() {
+        for data in FIXTURES.iter() {
+            let mut pixels = Vec::new();
+            let info = decode(Bytewise(data), |x, y, color| pixels.push((x, y, color)));
+            let mut expected = Vec::new();
+            let expected_info = decode(*data, |x, y, color| expected.push((x, y, color)));
+            assert_eq!(info, expected_info);
+            assert_eq!(pixels, expected);
+        }
+    }
+
+    #[test]
+    fn corrupt_data() {
+        // flipping a byte may produce a valid image, but mustn't panic or produce pixels
+        // outside of the image
+        for data in FIXTURES.iter() {
+            for i in 0..data.len() {
+                let mut corrupt = data.to_vec();
+                corrupt[i] ^= 0xff;
+                let info = read_info(&corrupt[..]);
+                let _ = decode(&corrupt[..], |x, y, _| {
+                    let info = info.unwrap();
+                    assert!(x < info.width && y < info.height);
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn dimensions() {
+        assert_eq!(check_dimensions::<()>(0, 1), Err(Error::InvalidData));
+        assert_eq!(check_dimensions::<()>(1, 0), Err(Error::InvalidData));
+        assert_eq!(check_dimensions::<()>(MAX_DIMENSION + 1, 1), Err(Error::Unsupported));
+        assert_eq!(check_dimensions::<()>(MAX_DIMENSION, MAX_DIMENSION), Ok(()));
+    }
+
+    #[test]
+    fn convert_assets() {
+        let data = include_bytes!("testdata/rgba.qoi");
+        let (info, pixels) = convert(data, RawFormat::Argb8888).unwrap();
+        assert_eq!((info.width, info.height), (WIDTH, HEIGHT));
+        assert_eq!(pixels.len(), (WIDTH * HEIGHT * 4) as usize);
+        for (i, pixel) in pixels.chunks(4).enumerate() {
+            let [red, green, blue, alpha] = pattern(i as u32 % WIDTH, i as u32 / WIDTH);
+            assert_eq!(pixel, [blue, green, red, alpha]);
+        }
+
+        let (_, pixels) = convert(data, RawFormat::Al88).unwrap();
+        assert_eq!(pixels.len(), (WIDTH * HEIGHT * 2) as usize);
+        assert_eq!(pixels[..2], [0, 255]);
+        // (299 * 10 + 587 * 20 + 114 * 30) / 1000
+        assert_eq!(pixels[(WIDTH as usize - 1) * 2..][..2], [18, 200]);
+
+        assert_eq!(convert(b"GIF89a", RawFormat::Argb8888), Err(Error::UnknownFormat));
+    }
+
+    #[test]
+    fn al88_shows_the_luminance_with_the_gray_ramp() {
+        let data = include_bytes!("testdata/rgba.qoi");
+        let (_, pixels) = convert(data, RawFormat::Al88).unwrap();
+        for (i, pixel) in pixels.chunks(2).enumerate() {
+            let [red, green, blue, alpha] = pattern(i as u32 % WIDTH, i as u32 / WIDTH);
+            let [r, g, b] = gray_ramp(pixel[0]);
+            // the lookup table turns the index back into a gray of the same luminance
+            assert_eq!((r, r), (g, b));
+            assert_eq!(luminance(r, g, b), luminance(red, green, blue));
+            assert_eq!(pixel[1], alpha);
+        }
+        assert_eq!(gray_ramp(0), [0, 0, 0]);
+        assert_eq!(gray_ramp(255), [255, 255, 255]);
+    }
+}
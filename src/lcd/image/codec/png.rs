@@ -0,0 +1,489 @@
+//! Portable Network Graphics.
+//!
+//! All color types and bit depths, transparency (`tRNS`) and Adam7 interlacing are supported.
+//! The image data is decompressed while it is read, so only two lines of the image are kept in
+//! memory. Chunk checksums aren't verified.
+
+use super::{check_dimensions, inflate, Error, ImageFormat, ImageInfo, Input, Read};
+use alloc::vec::Vec;
+
+pub(super) const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+const GRAYSCALE: u8 = 0;
+const TRUECOLOR: u8 = 2;
+const INDEXED: u8 = 3;
+const GRAYSCALE_ALPHA: u8 = 4;
+const TRUECOLOR_ALPHA: u8 = 6;
+
+// The first column, first row, column step and row step of the Adam7 passes.
+const ADAM7_PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+const SINGLE_PASS: [(u32, u32, u32, u32); 1] = [(0, 0, 1, 1)];
+
+pub(super) struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    pub(super) fn info(&self) -> ImageInfo {
+        ImageInfo {
+            format: ImageFormat::Png,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            TRUECOLOR => 3,
+            GRAYSCALE_ALPHA => 2,
+            TRUECOLOR_ALPHA => 4,
+            _ => 1,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * usize::from(self.bit_depth)
+    }
+
+    // The number of bytes of a line with `width` pixels, without the filter type.
+    fn line_size(&self, width: u32) -> usize {
+        (width as usize * self.bits_per_pixel() + 7) / 8
+    }
+}
+
+pub(super) fn read_header<R: Read>(input: &mut Input<R>) -> Result<Header, Error<R::Error>> {
+    let mut signature = [0; 8];
+    input.read_exact(&mut signature)?;
+    if signature != SIGNATURE {
+        return Err(Error::UnknownFormat);
+    }
+    let (length, chunk_type) = chunk_header(input)?;
+    if &chunk_type != b"IHDR" || length != 13 {
+        return Err(Error::InvalidData);
+    }
+
+    let width = input.u32_be()?;
+    let height = input.u32_be()?;
+    let bit_depth = input.byte()?;
+    let color_type = input.byte()?;
+    let compression = input.byte()?;
+    let filter = input.byte()?;
+    let interlace = input.byte()?;
+    input.skip(4)?; // CRC
+
+    let valid_depth = match color_type {
+        GRAYSCALE => [1, 2, 4, 8, 16].contains(&bit_depth),
+        INDEXED => [1, 2, 4, 8].contains(&bit_depth),
+        TRUECOLOR | GRAYSCALE_ALPHA | TRUECOLOR_ALPHA => bit_depth == 8 || bit_depth == 16,
+        _ => false,
+    };
+    if !valid_depth || compression != 0 || filter != 0 || interlace > 1 {
+        return Err(Error::InvalidData);
+    }
+    check_dimensions(width, height)?;
+    Ok(Header {
+        width,
+        height,
+        bit_depth,
+        color_type,
+        interlaced: interlace == 1,
+    })
+}
+
+fn chunk_header<R: Read>(input: &mut Input<R>) -> Result<(u32, [u8; 4]), Error<R::Error>> {
+    let length = input.u32_be()?;
+    let mut chunk_type = [0; 4];
+    input.read_exact(&mut chunk_type)?;
+    Ok((length, chunk_type))
+}
+
+// The palette and the transparency information of the image.
+struct Colors {
+    palette: Vec<[u8; 4]>,
+    // the 16 bit samples of the transparent gray or RGB color
+    transparent: Option<[u16; 3]>,
+}
+
+pub(super) fn decode<R, F>(
+    input: &mut Input<R>,
+    header: &Header,
+    pixel: &mut F,
+) -> Result<(), Error<R::Error>>
+where
+    R: Read,
+    F: FnMut(u32, u32, [u8; 4]),
+{
+    let mut colors = Colors {
+        palette: Vec::new(),
+        transparent: None,
+    };
+
+    // read the chunks before the image data
+    let first_data_length = loop {
+        let (length, chunk_type) = chunk_header(input)?;
+        match &chunk_type {
+            b"IDAT" => break length,
+            b"PLTE" => {
+                if length % 3 != 0 || length > 256 * 3 {
+                    return Err(Error::InvalidData);
+                }
+                for _ in 0..length / 3 {
+                    let mut entry = [0, 0, 0, 255];
+                    input.read_exact(&mut entry[..3])?;
+                    colors.palette.push(entry);
+                }
+            }
+            b"tRNS" => match header.color_type {
+                INDEXED => {
+                    for i in 0..length as usize {
+                        let alpha = input.byte()?;
+                        let entry = colors.palette.get_mut(i).ok_or(Error::InvalidData)?;
+                        entry[3] = alpha;
+                    }
+                }
+                GRAYSCALE if length == 2 => {
+                    let gray = input.u16_be()?;
+                    colors.transparent = Some([gray, gray, gray]);
+                }
+                TRUECOLOR if length == 6 => {
+                    let (red, green, blue) = (input.u16_be()?, input.u16_be()?, input.u16_be()?);
+                    colors.transparent = Some([red, green, blue]);
+                }
+                _ => return Err(Error::InvalidData),
+            },
+            b"IEND" => return Err(Error::UnexpectedEnd),
+            _ => input.skip(u64::from(length))?,
+        }
+        input.skip(4)?; // CRC
+    };
+    if header.color_type == INDEXED && colors.palette.is_empty() {
+        return Err(Error::InvalidData);
+    }
+
+    let passes: &[(u32, u32, u32, u32)] = if header.interlaced {
+        &ADAM7_PASSES
+    } else {
+        &SINGLE_PASS
+    };
+    let mut lines = Lines::new(header, &colors, passes, pixel);
+
+    // the compressed data can be split into several consecutive IDAT chunks
+    let mut remaining = first_data_length;
+    let next_byte = || {
+        while remaining == 0 {
+            input.skip(4)?; // CRC
+            let (length, chunk_type) = chunk_header(input)?;
+            if &chunk_type != b"IDAT" {
+                return Err(Error::UnexpectedEnd);
+            }
+            remaining = length;
+        }
+        remaining -= 1;
+        input.byte()
+    };
+    inflate::decompress(next_byte, |byte| lines.push(byte))?;
+
+    if lines.is_complete() {
+        Ok(())
+    } else {
+        Err(Error::UnexpectedEnd)
+    }
+}
+
+// Collects the decompressed bytes into lines, reverses the filters and passes the pixels on.
+struct Lines<'a, F> {
+    header: &'a Header,
+    colors: &'a Colors,
+    passes: &'a [(u32, u32, u32, u32)],
+    pixel: &'a mut F,
+    pass: usize,
+    // the size and the number of lines of the current pass
+    line_size: usize,
+    pass_width: u32,
+    pass_height: u32,
+    y: u32,
+    // the filter type followed by the line
+    current: Vec<u8>,
+    previous: Vec<u8>,
+    filled: usize,
+}
+
+impl<'a, F: FnMut(u32, u32, [u8; 4])> Lines<'a, F> {
+    fn new(
+        header: &'a Header,
+        colors: &'a Colors,
+        passes: &'a [(u32, u32, u32, u32)],
+        pixel: &'a mut F,
+    ) -> Self {
+        let max_line_size = header.line_size(header.width) + 1;
+        let mut lines = Lines {
+            header,
+            colors,
+            passes,
+            pixel,
+            pass: 0,
+            line_size: 0,
+            pass_width: 0,
+            pass_height: 0,
+            y: 0,
+            current: Vec::new(),
+            previous: Vec::new(),
+            filled: 0,
+        };
+        lines.current.resize(max_line_size, 0);
+        lines.previous.resize(max_line_size, 0);
+        lines.start_pass();
+        lines
+    }
+
+    // Starts the current pass or the next pass that isn't empty.
+    fn start_pass(&mut self) {
+        while let Some(&(x0, y0, dx, dy)) = self.passes.get(self.pass) {
+            self.pass_width = (self.header.width + dx - 1 - x0) / dx;
+            self.pass_height = (self.header.height + dy - 1 - y0) / dy;
+            if self.header.width > x0 && self.header.height > y0 {
+                self.line_size = self.header.line_size(self.pass_width) + 1;
+                self.y = 0;
+                self.filled = 0;
+                // the first line of a pass is filtered against zeros
+                for byte in &mut self.previous {
+                    *byte = 0;
+                }
+                return;
+            }
+            self.pass += 1;
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.pass >= self.passes.len()
+    }
+
+    fn push<E>(&mut self, byte: u8) -> Result<(), Error<E>> {
+        if self.is_complete() {
+            return Ok(()); // ignore data after the image
+        }
+        self.current[self.filled] = byte;
+        self.filled += 1;
+        if self.filled < self.line_size {
+            return Ok(());
+        }
+
+        self.unfilter()?;
+        self.emit_line()?;
+        self.filled = 0;
+        self.y += 1;
+        core::mem::swap(&mut self.current, &mut self.previous);
+        if self.y == self.pass_height {
+            self.pass += 1;
+            self.start_pass();
+        }
+        Ok(())
+    }
+
+    fn unfilter<E>(&mut self) -> Result<(), Error<E>> {
+        // filters work on bytes of complete pixels, but at least on single bytes
+        let bpp = (self.header.bits_per_pixel() + 7) / 8;
+        let filter = self.current[0];
+        let line = &mut self.current[1..self.line_size];
+        let previous = &self.previous[1..self.line_size];
+        for i in 0..line.len() {
+            let a = if i >= bpp { line[i - bpp] } else { 0 };
+            let b = previous[i];
+            let c = if i >= bpp { previous[i - bpp] } else { 0 };
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((u16::from(a) + u16::from(b)) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(Error::InvalidData),
+            };
+            line[i] = line[i].wrapping_add(predictor);
+        }
+        Ok(())
+    }
+
+    fn emit_line<E>(&mut self) -> Result<(), Error<E>> {
+        let (x0, y0, dx, dy) = self.passes[self.pass];
+        let y = y0 + self.y * dy;
+        for i in 0..self.pass_width {
+            let color = self.color(i as usize)?;
+            (self.pixel)(x0 + i * dx, y, color);
+        }
+        Ok(())
+    }
+
+    // Returns the color of the pixel at index `i` of the current line.
+    fn color<E>(&self, i: usize) -> Result<[u8; 4], Error<E>> {
+        let line = &self.current[1..self.line_size];
+        let depth = usize::from(self.header.bit_depth);
+        let channels = self.header.channels();
+        // returns the 16 bit value of a sample and the value scaled to 8 bit
+        let sample = |channel: usize| -> (u16, u8) {
+            let index = i * channels + channel;
+            match depth {
+                16 => {
+                    let value = u16::from(line[2 * index]) << 8 | u16::from(line[2 * index + 1]);
+                    (value, line[2 * index])
+                }
+                8 => (u16::from(line[index]), line[index]),
+                _ => {
+                    let bit = index * depth;
+                    let shift = 8 - depth - bit % 8;
+                    let value = (line[bit / 8] >> shift) & ((1 << depth) - 1);
+                    // replicate the bits, e.g. 0b01 becomes 0b0101_0101
+                    (u16::from(value), value * (255 / ((1 << depth) - 1)))
+                }
+            }
+        };
+
+        let color = match self.header.color_type {
+            GRAYSCALE | TRUECOLOR => {
+                let (raw, color) = if self.header.color_type == GRAYSCALE {
+                    let (raw, gray) = sample(0);
+                    ([raw; 3], [gray; 3])
+                } else {
+                    let (red, green, blue) = (sample(0), sample(1), sample(2));
+                    ([red.0, green.0, blue.0], [red.1, green.1, blue.1])
+                };
+                let alpha = if self.colors.transparent == Some(raw) { 0 } else { 255 };
+                [color[0], color[1], color[2], alpha]
+            }
+            INDEXED => {
+                let (index, _) = sample(0);
+                *self
+                    .colors
+                    .palette
+                    .get(usize::from(index))
+                    .ok_or(Error::InvalidData)?
+            }
+            GRAYSCALE_ALPHA => {
+                let (gray, alpha) = (sample(0).1, sample(1).1);
+                [gray, gray, gray, alpha]
+            }
+            _ => [sample(0).1, sample(1).1, sample(2).1, sample(3).1],
+        };
+        Ok(color)
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let pa = (p - i16::from(a)).abs();
+    let pb = (p - i16::from(b)).abs();
+    let pc = (p - i16::from(c)).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{assert_pattern, decode_image, WIDTH};
+    use super::super::{read_info, MAX_DIMENSION};
+    use super::*;
+
+    const RGBA: &[u8] = include_bytes!("testdata/rgba.png");
+
+    #[test]
+    fn true_color() {
+        assert_pattern(RGBA, ImageFormat::Png, true);
+        assert_pattern(include_bytes!("testdata/rgb16.png"), ImageFormat::Png, false);
+    }
+
+    #[test]
+    fn interlaced() {
+        assert_pattern(include_bytes!("testdata/rgba_interlaced.png"), ImageFormat::Png, true);
+    }
+
+    #[test]
+    fn gray_with_transparent_level() {
+        let (_, pixels) = decode_image(include_bytes!("testdata/gray2.png")).unwrap();
+        for (i, &color) in pixels.iter().enumerate() {
+            let (x, y) = (i as u32 % WIDTH, i as u32 / WIDTH);
+            let level = ((x + y) % 4) as u8;
+            let alpha = if level == 3 { 0 } else { 255 };
+            assert_eq!(color, [level * 0x55, level * 0x55, level * 0x55, alpha]);
+        }
+    }
+
+    #[test]
+    fn palette() {
+        let (_, pixels) = decode_image(include_bytes!("testdata/palette4.png")).unwrap();
+        for (i, &color) in pixels.iter().enumerate() {
+            let (x, y) = (i as u32 % WIDTH, i as u32 / WIDTH);
+            let index = ((x + 2 * y) % 16) as u8;
+            // only the first 8 entries have an alpha value
+            let alpha = if index < 8 { index * 32 } else { 255 };
+            assert_eq!(color, [index * 16, 255 - index * 16, index * 8, alpha]);
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        // the IEND chunk, the CRC of the last IDAT chunk and the Adler-32 checksum aren't read
+        for len in 0..RGBA.len() - 20 {
+            assert!(decode_image(&RGBA[..len]).is_err(), "{} bytes", len);
+        }
+        assert_eq!(decode_image(&RGBA[..100]).err(), Some(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn invalid_header() {
+        let mut data = RGBA.to_vec();
+        data[24] = 7; // bit depth
+        assert_eq!(read_info(&data[..]), Err(Error::InvalidData));
+
+        let mut data = RGBA.to_vec();
+        data[12..16].copy_from_slice(b"IDAT");
+        assert_eq!(read_info(&data[..]), Err(Error::InvalidData));
+
+        let mut data = RGBA.to_vec();
+        data[16..20].copy_from_slice(&(MAX_DIMENSION + 1).to_be_bytes());
+        assert_eq!(read_info(&data[..]), Err(Error::Unsupported));
+        assert_eq!(read_info(&data[..12]), Err(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn invalid_filter() {
+        // an uncompressed 1x1 gray image with filter type 5
+        let mut data = SIGNATURE.to_vec();
+        let ihdr = [0, 0, 0, 13, b'I', b'H', b'D', b'R', 0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0];
+        data.extend_from_slice(&ihdr);
+        data.extend_from_slice(&[0; 4]); // CRC
+        data.extend_from_slice(&[0, 0, 0, 9, b'I', b'D', b'A', b'T']);
+        data.extend_from_slice(&[0x78, 0x01, 0x01, 0x02, 0x00, 0xfd, 0xff, 5, 42]);
+        assert_eq!(decode_image(&data), Err(Error::InvalidData));
+
+        let last = data.len() - 2;
+        data[last] = 0; // no filter
+        let (_, pixels) = decode_image(&data).unwrap();
+        assert_eq!(pixels, [[42, 42, 42, 255]]);
+    }
+
+    #[test]
+    fn missing_image_data() {
+        // the IDAT chunks replaced by IEND
+        let mut data = SIGNATURE.to_vec();
+        data.extend_from_slice(&RGBA[8..33]);
+        data.extend_from_slice(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+        assert_eq!(decode_image(&data), Err(Error::UnexpectedEnd));
+    }
+}
@@ -0,0 +1,143 @@
+//! Quite OK Image format, see <https://qoiformat.org/qoi-specification.pdf>.
+
+use super::{check_dimensions, Error, ImageFormat, ImageInfo, Input, Read};
+
+pub(super) const MAGIC: &[u8] = b"qoif";
+
+const OP_RGB: u8 = 0xfe;
+const OP_RGBA: u8 = 0xff;
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_MASK: u8 = 0xc0;
+
+pub(super) struct Header {
+    width: u32,
+    height: u32,
+}
+
+impl Header {
+    pub(super) fn info(&self) -> ImageInfo {
+        ImageInfo {
+            format: ImageFormat::Qoi,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+pub(super) fn read_header<R: Read>(input: &mut Input<R>) -> Result<Header, Error<R::Error>> {
+    let mut magic = [0; 4];
+    input.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(Error::UnknownFormat);
+    }
+    let width = input.u32_be()?;
+    let height = input.u32_be()?;
+    let channels = input.byte()?;
+    let _colorspace = input.byte()?;
+    if channels != 3 && channels != 4 {
+        return Err(Error::InvalidData);
+    }
+    check_dimensions(width, height)?;
+    Ok(Header { width, height })
+}
+
+pub(super) fn decode<R, F>(
+    input: &mut Input<R>,
+    header: &Header,
+    pixel: &mut F,
+) -> Result<(), Error<R::Error>>
+where
+    R: Read,
+    F: FnMut(u32, u32, [u8; 4]),
+{
+    let mut index = [[0u8; 4]; 64];
+    let mut color = [0, 0, 0, 255];
+    let mut run = 0;
+
+    for y in 0..header.height {
+        for x in 0..header.width {
+            if run > 0 {
+                run -= 1;
+            } else {
+                let op = input.byte()?;
+                match op {
+                    OP_RGB => input.read_exact(&mut color[..3])?,
+                    OP_RGBA => input.read_exact(&mut color)?,
+                    _ => match op & OP_MASK {
+                        OP_INDEX => color = index[usize::from(op)],
+                        OP_DIFF => {
+                            color[0] = color[0].wrapping_add((op >> 4) & 0x03).wrapping_sub(2);
+                            color[1] = color[1].wrapping_add((op >> 2) & 0x03).wrapping_sub(2);
+                            color[2] = color[2].wrapping_add(op & 0x03).wrapping_sub(2);
+                        }
+                        OP_LUMA => {
+                            let green_diff = (op & 0x3f).wrapping_sub(32);
+                            let next = input.byte()?;
+                            let red_diff = green_diff.wrapping_add(next >> 4).wrapping_sub(8);
+                            let blue_diff = green_diff.wrapping_add(next & 0x0f).wrapping_sub(8);
+                            color[0] = color[0].wrapping_add(red_diff);
+                            color[1] = color[1].wrapping_add(green_diff);
+                            color[2] = color[2].wrapping_add(blue_diff);
+                        }
+                        _ => run = op & 0x3f, // OP_RUN, the current pixel is the first of the run
+                    },
+                }
+                index[hash(color)] = color;
+            }
+            pixel(x, y, color);
+        }
+    }
+    Ok(())
+}
+
+fn hash(color: [u8; 4]) -> usize {
+    let [r, g, b, a] = color;
+    (usize::from(r) * 3 + usize::from(g) * 5 + usize::from(b) * 7 + usize::from(a) * 11) % 64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::read_info;
+    use super::super::tests::{assert_pattern, decode_image};
+    use super::*;
+
+    // uses all operations of the format
+    const RGBA: &[u8] = include_bytes!("testdata/rgba.qoi");
+
+    #[test]
+    fn decode_pattern() {
+        assert_pattern(RGBA, ImageFormat::Qoi, true);
+    }
+
+    #[test]
+    fn truncated() {
+        // the end marker isn't read
+        for len in 0..RGBA.len() - 8 {
+            assert!(decode_image(&RGBA[..len]).is_err(), "{} bytes", len);
+        }
+        assert_eq!(decode_image(&RGBA[..100]).err(), Some(Error::UnexpectedEnd));
+    }
+
+    #[test]
+    fn invalid_header() {
+        let mut data = RGBA.to_vec();
+        data[12] = 5; // channels
+        assert_eq!(read_info(&data[..]), Err(Error::InvalidData));
+
+        let mut data = RGBA.to_vec();
+        data[4..8].copy_from_slice(&0u32.to_be_bytes()); // width
+        assert_eq!(read_info(&data[..]), Err(Error::InvalidData));
+    }
+
+    #[test]
+    fn run_across_lines() {
+        // a 2x2 image with a run of 3 pixels after the first one
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 2, 4, 0]);
+        data.extend_from_slice(&[OP_RGBA, 1, 2, 3, 4, 0xc0 | 2]);
+        let (_, pixels) = decode_image(&data).unwrap();
+        assert_eq!(pixels, [[1, 2, 3, 4]; 4]);
+    }
+}
@@ -0,0 +1,169 @@
+//! Decoding BMP, PNG and QOI images and drawing them on the layers.
+//!
+//! Images are read through the [`Read`](Read) trait, which is implemented for byte slices and
+//! for files on the SD card (see [`FileReader`](FileReader)). The format is detected from the
+//! image data. The decoders don't access the hardware, so they can be tested on the host.
+//!
+//! Decoding takes time and memory, so images that are known at compile time can be converted
+//! into the pixel format of a layer by the build script instead. Every `assets/<name>.bmp`,
+//! `assets/<name>.png` or `assets/<name>.qoi` file of the crate is converted to ARGB8888 and is
+//! available as `lcd::image::assets::<NAME>`. If an `assets/<name>.format` file contains `al88`,
+//! the image is converted to AL88 with the luminance of the pixels as index into the gray ramp
+//! of [`color_lookup_table`](color_lookup_table), which `lcd::init` loads for both layers.
+//! `lcd::stdout` replaces the table of its layer with the terminal palette, so AL88 images
+//! belong on the other layer then. These [`RawImage`](RawImage)s can be blitted directly, e.g.
+//! with the DMA2D.
+//!
+//! # Examples
+//! ```rust
+//! // decode an image from a byte slice
+//! let region = lcd::Rect::new(10, 10, 100, 100);
+//! lcd::image::draw(&mut layer_1, &include_bytes!("logo.png")[..], region)
+//!     .expect("invalid image");
+//!
+//! // decode an image from the SD card
+//! let mut file = fs.open("/images/photo.bmp").expect("File not found");
+//! let reader = lcd::image::FileReader::new(&mut fs, &mut file);
+//! lcd::image::draw(&mut layer_1, reader, lcd::Rect::new(0, 0, lcd::WIDTH, lcd::HEIGHT))
+//!     .expect("invalid image");
+//!
+//! // blit an image that was converted at build time (`assets/icon.png`)
+//! let icon = &lcd::image::assets::ICON;
+//! layer_1.blit(&mut dma2d, icon.pixel_buffer(), 200, 100).expect("blit failed");
+//! ```
+
+pub use self::codec::{read_info, Error, ImageFormat, ImageInfo, Read, MAX_DIMENSION};
+
+use super::{Color, Framebuffer, PixelBuffer, PixelFormat, Rect, HEIGHT, WIDTH};
+use crate::sd::{fs, BlockDevice};
+
+mod codec;
+
+/// The images that were converted at build time.
+pub mod assets {
+    include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+}
+
+/// Returns the color of entry `index` of the color lookup table that the AL88
+/// [`assets`](assets) are converted for.
+///
+/// The assets store the luminance of their pixels as index, so the table is a gray ramp.
+pub fn color_lookup_table(index: u8) -> Color {
+    let [red, green, blue] = codec::gray_ramp(index);
+    Color::rgb(red, green, blue)
+}
+
+/// Decodes an image and passes the coordinates and the color of each pixel to `pixel`.
+///
+/// The pixels are not passed in a particular order.
+///
+/// # Errors
+///
+/// Returns an error if the data can't be read or is no valid image of a supported format. The
+/// pixels that were decoded before the error occurred are passed to `pixel`.
+pub fn decode<R, F>(reader: R, mut pixel: F) -> Result<ImageInfo, Error<R::Error>>
+where
+    R: Read,
+    F: FnMut(usize, usize, Color),
+{
+    codec::decode(reader, |x, y, [red, green, blue, alpha]| {
+        pixel(x as usize, y as usize, Color::rgba(red, green, blue, alpha));
+    })
+}
+
+/// Decodes an image and draws it with its top left corner at the top left corner of `region`.
+///
+/// The image is clipped to the region and to the display. The pixels replace the pixels of the
+/// framebuffer, including their alpha channel.
+///
+/// # Errors
+///
+/// Returns an error if the data can't be read or is no valid image of a supported format. The
+/// part of the image that was decoded before the error occurred is drawn.
+pub fn draw<T, R>(fb: &mut T, reader: R, region: Rect) -> Result<ImageInfo, Error<R::Error>>
+where
+    T: Framebuffer,
+    R: Read,
+{
+    let clip = region.intersection(Rect::new(0, 0, WIDTH, HEIGHT));
+    decode(reader, |x, y, color| {
+        let (x, y) = (region.x + x, region.y + y);
+        if x >= clip.x && y >= clip.y && x < clip.x + clip.width && y < clip.y + clip.height {
+            fb.set_pixel(x, y, color);
+        }
+    })
+}
+
+/// Reads an image from a file on the SD card.
+pub struct FileReader<'a, D: BlockDevice> {
+    fs: &'a mut fs::FileSystem<D>,
+    file: &'a mut fs::File,
+}
+
+impl<'a, D: BlockDevice> FileReader<'a, D> {
+    /// Creates a reader that reads from the current position of the file.
+    pub fn new(fs: &'a mut fs::FileSystem<D>, file: &'a mut fs::File) -> Self {
+        FileReader { fs, file }
+    }
+}
+
+impl<'a, D: BlockDevice> Read for FileReader<'a, D> {
+    type Error = fs::Error<D::Error>;
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.fs.read(self.file, buf)
+    }
+}
+
+/// An image in the pixel format of a layer, which was converted at build time.
+#[derive(Debug, Clone, Copy)]
+pub struct RawImage {
+    width: usize,
+    height: usize,
+    format: PixelFormat,
+    data: &'static [u8],
+}
+
+impl RawImage {
+    /// Creates an image from its pixels, which are stored line by line without padding.
+    pub const fn new(
+        width: usize,
+        height: usize,
+        format: PixelFormat,
+        data: &'static [u8],
+    ) -> RawImage {
+        RawImage {
+            width,
+            height,
+            format,
+            data,
+        }
+    }
+
+    /// Returns the width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the pixel format of the image.
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Returns the pixels as source for [`Layer::blit`](super::Layer::blit) and
+    /// [`Layer::blend`](super::Layer::blend).
+    ///
+    /// The data is usually stored in the flash memory, so the buffer must not be used as
+    /// destination.
+    pub fn pixel_buffer(&self) -> PixelBuffer {
+        let bytes_per_pixel = self.format.bytes_per_pixel();
+        assert!(self.data.len() >= self.width * self.height * bytes_per_pixel);
+        let addr = self.data.as_ptr() as usize;
+        unsafe { PixelBuffer::from_raw_parts(addr, self.width, self.height, self.format) }
+    }
+}
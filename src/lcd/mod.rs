@@ -4,16 +4,34 @@
 //! with an uniform color.
 
 pub use self::color::Color;
+pub use self::config::{BlendingFactor, LayerConfig};
+pub use self::dma2d::{Blitter, Dma2d, PixelBuffer, Software};
 pub use self::init::init;
+pub use self::pixel_format::PixelFormat;
 pub use self::stdout::init as init_stdout;
+pub use self::vsync::WaitForVsync;
 
-use core::{fmt, ptr};
+use self::font::Font8x8;
+use arrayvec::ArrayVec;
+use core::{fmt, mem, ptr};
+use futures::Stream;
 use stm32f7::stm32f7x6::LTDC;
 
 #[macro_use]
 pub mod stdout;
 mod color;
+pub mod config;
+pub mod dma2d;
+#[cfg(feature = "embedded-graphics")]
+pub mod draw_target;
+pub mod font;
+pub mod graphics;
+pub mod image;
 mod init;
+mod pixel_format;
+pub mod terminal;
+pub mod text;
+mod vsync;
 
 /// The height of the display in pixels.
 pub const HEIGHT: usize = 272;
@@ -35,12 +53,128 @@ pub const SDRAM_START: usize = 0xC000_0000;
 pub const LAYER_1_START: usize = SDRAM_START;
 /// Start address of the layer 2 framebuffer.
 pub const LAYER_2_START: usize = SDRAM_START + LAYER_1_LENGTH;
+/// The length of a back buffer in bytes, which is large enough for all pixel formats.
+pub const BACK_BUFFER_LENGTH: usize = HEIGHT * WIDTH * 4;
+/// Start address of the two back buffers of layer 1.
+pub const LAYER_1_BACK_BUFFERS_START: usize = LAYER_2_START + LAYER_2_LENGTH;
+/// Start address of the two back buffers of layer 2.
+pub const LAYER_2_BACK_BUFFERS_START: usize = LAYER_1_BACK_BUFFERS_START + 2 * BACK_BUFFER_LENGTH;
+/// End address of the memory used by the LCD. The SDRAM after this address is unused.
+pub const LCD_MEMORY_END: usize = LAYER_2_BACK_BUFFERS_START + 2 * BACK_BUFFER_LENGTH;
+
+// The area of the whole display.
+const SCREEN: Rect = Rect::new(0, 0, WIDTH, HEIGHT);
+
+// Transparent, or black in pixel formats without alpha channel.
+const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+
+// The line at the end of the active display area, where the vertical blanking period starts.
+const VSYNC_LINE: u16 = (HEIGHT + 10 + 2) as u16;
+
+/// Identifies one of the two layers of the LCD controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerId {
+    /// Layer 1, which is below layer 2.
+    Layer1,
+    /// Layer 2, which is blended on top of layer 1.
+    Layer2,
+}
+
+/// The number of framebuffers of a layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Buffering {
+    /// The layer draws directly into the displayed framebuffer.
+    Single,
+    /// The layer draws into a back buffer, which is displayed by `Lcd::swap_buffers`.
+    Double,
+    /// Like `Double`, but with a second back buffer, so that drawing can continue while a swap
+    /// is waiting for the vertical blanking period.
+    Triple,
+}
+
+impl Buffering {
+    fn back_buffers(self) -> usize {
+        match self {
+            Buffering::Single => 0,
+            Buffering::Double => 1,
+            Buffering::Triple => 2,
+        }
+    }
+}
+
+/// A rectangular area of pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    /// The column of the top left corner.
+    pub x: usize,
+    /// The row of the top left corner.
+    pub y: usize,
+    /// The number of columns.
+    pub width: usize,
+    /// The number of rows.
+    pub height: usize,
+}
+
+impl Rect {
+    /// Creates a rectangle from its top left corner and its size.
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the area that is covered by both rectangles.
+    ///
+    /// If the rectangles don't overlap, the returned rectangle is empty.
+    pub fn intersection(self, other: Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        Rect {
+            x,
+            y,
+            width: right.saturating_sub(x),
+            height: bottom.saturating_sub(y),
+        }
+    }
+
+    /// Returns the smallest rectangle that covers both rectangles.
+    ///
+    /// Empty rectangles are ignored.
+    pub fn union(self, other: Rect) -> Rect {
+        if self.is_empty() {
+            return other;
+        } else if other.is_empty() {
+            return self;
+        }
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Rect::new(x, y, right - x, bottom - y)
+    }
+
+    /// Returns whether the pixel at the given coordinates lies in the rectangle.
+    pub fn contains(self, x: usize, y: usize) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    /// Returns whether the rectangle contains no pixels.
+    pub fn is_empty(self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
 
 /// Represents the LCD and provides methods to access both layers.
 pub struct Lcd<'a> {
     controller: &'a mut LTDC,
     layer_1_in_use: bool,
     layer_2_in_use: bool,
+    layer_configs: [LayerConfig; 2],
 }
 
 impl<'a> Lcd<'a> {
@@ -49,6 +183,10 @@ impl<'a> Lcd<'a> {
             controller: ltdc,
             layer_1_in_use: false,
             layer_2_in_use: false,
+            layer_configs: [
+                LayerConfig::new(PixelFormat::Argb8888),
+                LayerConfig::new(PixelFormat::Al88),
+            ],
         }
     }
 
@@ -59,45 +197,236 @@ impl<'a> Lcd<'a> {
             .modify(|_, w| unsafe { w.bc().bits(color.to_rgb()) });
     }
 
-    /// Sets the color `i` in the lookup table for layer 2
-    pub fn set_color_lookup_table(&mut self, i: u8, color: Color) {
-        self.controller
-            .l2clutwr
-            .write(|w| unsafe { w
-                .clutadd().bits(i)
-                .red().bits(color.red)
-                .green().bits(color.green)
-                .blue().bits(color.blue)
-            });
+    /// Sets the color `i` in the lookup table of the layer.
+    ///
+    /// The lookup table is used by the luminance pixel formats, where the luminance is the index
+    /// of the color.
+    pub fn set_color_lookup_table(&mut self, id: LayerId, i: u8, color: Color) {
+        match id {
+            LayerId::Layer1 => self
+                .controller
+                .l1clutwr
+                .write(|w| unsafe { w
+                    .clutadd().bits(i)
+                    .red().bits(color.red)
+                    .green().bits(color.green)
+                    .blue().bits(color.blue)
+                }),
+            LayerId::Layer2 => self
+                .controller
+                .l2clutwr
+                .write(|w| unsafe { w
+                    .clutadd().bits(i)
+                    .red().bits(color.red)
+                    .green().bits(color.green)
+                    .blue().bits(color.blue)
+                }),
+        }
     }
 
     /// Returns a reference to layer 1.
+    ///
+    /// Each layer can only be taken once, later calls return `None`.
     pub fn layer_1(&mut self) -> Option<Layer<FramebufferArgb8888>> {
+        self.layer_1_buffered(Buffering::Single, &mut Software)
+    }
+
+    /// Returns a reference to layer 2.
+    ///
+    /// Each layer can only be taken once, later calls return `None`.
+    pub fn layer_2(&mut self) -> Option<Layer<FramebufferAl88>> {
+        self.layer_2_buffered(Buffering::Single, &mut Software)
+    }
+
+    /// Returns a reference to layer 1, which draws into back buffers in the SDRAM.
+    ///
+    /// The back buffers are cleared with `blitter`. See [`swap_buffers`](Lcd::swap_buffers) for
+    /// more information.
+    pub fn layer_1_buffered<B: Blitter>(
+        &mut self,
+        buffering: Buffering,
+        blitter: &mut B,
+    ) -> Option<Layer<FramebufferArgb8888>> {
         if self.layer_1_in_use {
             None
         } else {
-            Some(Layer {
-                framebuffer: FramebufferArgb8888::new(LAYER_1_START),
-            })
+            self.layer_1_in_use = true;
+            let back_buffers = (0..buffering.back_buffers())
+                .map(|i| LAYER_1_BACK_BUFFERS_START + i * BACK_BUFFER_LENGTH)
+                .map(FramebufferArgb8888::new);
+            Some(Layer::new(
+                LayerId::Layer1,
+                FramebufferArgb8888::new(LAYER_1_START),
+                back_buffers,
+                blitter,
+            ))
         }
     }
 
-    /// Returns a reference to layer 2.
-    pub fn layer_2(&mut self) -> Option<Layer<FramebufferAl88>> {
+    /// Returns a reference to layer 2, which draws into back buffers in the SDRAM.
+    ///
+    /// The back buffers are cleared with `blitter`. See [`swap_buffers`](Lcd::swap_buffers) for
+    /// more information.
+    pub fn layer_2_buffered<B: Blitter>(
+        &mut self,
+        buffering: Buffering,
+        blitter: &mut B,
+    ) -> Option<Layer<FramebufferAl88>> {
         if self.layer_2_in_use {
             None
         } else {
-            Some(Layer {
-                framebuffer: FramebufferAl88::new(LAYER_2_START),
-            })
+            self.layer_2_in_use = true;
+            let back_buffers = (0..buffering.back_buffers())
+                .map(|i| LAYER_2_BACK_BUFFERS_START + i * BACK_BUFFER_LENGTH)
+                .map(FramebufferAl88::new);
+            Some(Layer::new(
+                LayerId::Layer2,
+                FramebufferAl88::new(LAYER_2_START),
+                back_buffers,
+                blitter,
+            ))
         }
     }
+
+    /// Displays the back buffer the layer has drawn into and lets the layer draw into the next
+    /// buffer.
+    ///
+    /// The framebuffer address is updated in the shadow registers of the LTDC, which are
+    /// reloaded in the next vertical blanking period, so the displayed frame doesn't tear. If
+    /// the previous swap of this layer is still waiting for the vertical blanking period, this
+    /// function blocks until it is done, use [`try_swap_buffers`](Lcd::try_swap_buffers) to
+    /// avoid this.
+    ///
+    /// With double buffering, the buffer that is drawn into next is still displayed until the
+    /// vertical blanking period, so await `wait_for_vsync` before drawing into it. With triple
+    /// buffering, drawing can continue immediately. Layers with a single buffer are not
+    /// changed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let mut layer_1 = lcd.layer_1_buffered(lcd::Buffering::Double, &mut dma2d).unwrap();
+    /// loop {
+    ///     draw_frame(&mut layer_1);
+    ///     lcd.swap_buffers(&mut layer_1);
+    ///     await!(lcd.wait_for_vsync(&mut ltdc_stream));
+    /// }
+    /// ```
+    pub fn swap_buffers<T: Framebuffer>(&mut self, layer: &mut Layer<T>) {
+        while self.try_swap_buffers(layer).is_err() {}
+    }
+
+    /// Like [`swap_buffers`](Lcd::swap_buffers), but returns an error instead of blocking if the
+    /// previous swap of this layer is still waiting for the vertical blanking period.
+    ///
+    /// The swaps of both layers can wait for the same vertical blanking period.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let mut layer_1 = lcd.layer_1_buffered(lcd::Buffering::Triple, &mut dma2d).unwrap();
+    /// loop {
+    ///     draw_frame(&mut layer_1);
+    ///     while lcd.try_swap_buffers(&mut layer_1).is_err() {
+    ///         await!(lcd.wait_for_vsync(&mut ltdc_stream));
+    ///     }
+    /// }
+    /// ```
+    pub fn try_swap_buffers<T: Framebuffer>(
+        &mut self,
+        layer: &mut Layer<T>,
+    ) -> Result<(), SwapPending> {
+        if layer.back_buffers.is_empty() {
+            return Ok(());
+        }
+        if layer.swapped && self.swap_pending() {
+            return Err(SwapPending);
+        }
+
+        let addr = match layer.framebuffer.pixel_buffer() {
+            Some(buffer) => buffer.addr() as u32,
+            None => return Ok(()),
+        };
+        match layer.id {
+            LayerId::Layer1 => self
+                .controller
+                .l1cfbar
+                .modify(|_, w| unsafe { w.cfbadd().bits(addr) }),
+            LayerId::Layer2 => self
+                .controller
+                .l2cfbar
+                .modify(|_, w| unsafe { w.cfbadd().bits(addr) }),
+        }
+        self.controller.srcr.modify(|_, w| w.vbr().set_bit()); // VERTICAL_BLANKING_RELOAD
+
+        // the oldest buffer is not displayed anymore after the reload
+        let next = layer.back_buffers.remove(0);
+        let displayed = mem::replace(&mut layer.framebuffer, next);
+        layer.back_buffers.push(displayed);
+        layer.swapped = true;
+        Ok(())
+    }
+
+    /// Returns whether a buffer swap of any layer is waiting for the vertical blanking period.
+    pub fn swap_pending(&self) -> bool {
+        self.controller.srcr.read().vbr().bit_is_set()
+    }
+
+    /// Returns a future that resolves at the start of the next vertical blanking period, after
+    /// pending buffer swaps are done.
+    ///
+    /// The future is woken by the passed stream, which must be fed by the LTDC interrupt.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let (ltdc_sink, ltdc_stream) = mpsc::unbounded();
+    /// interrupt_table
+    ///     .register(InterruptRequest::LTDC, Priority::P1, move || {
+    ///         lcd::mask_line_interrupt();
+    ///         ltdc_sink
+    ///             .unbounded_send(())
+    ///             .expect("sending on ltdc channel failed");
+    ///     })
+    ///     .expect("registering ltdc interrupt failed");
+    ///
+    /// // in an async task
+    /// await!(lcd.wait_for_vsync(&mut ltdc_stream));
+    /// ```
+    pub fn wait_for_vsync<S>(&mut self, line_interrupts: S) -> WaitForVsync<S>
+    where
+        S: Stream<Item = ()> + Unpin,
+    {
+        WaitForVsync::new(self.controller, line_interrupts)
+    }
+}
+
+/// The error returned by [`Lcd::try_swap_buffers`](Lcd::try_swap_buffers) if the previous swap
+/// of the layer is still waiting for the vertical blanking period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapPending;
+
+/// Disables and acknowledges the LTDC line interrupt.
+///
+/// This function must be called from the LTDC interrupt handler, see
+/// [`Lcd::wait_for_vsync`](Lcd::wait_for_vsync). The line interrupt is enabled again by the next
+/// `wait_for_vsync` future.
+pub fn mask_line_interrupt() {
+    // IER and ICR are not touched by the future while the interrupt is enabled.
+    let ltdc = unsafe { &*LTDC::ptr() };
+    ltdc.ier.modify(|_, w| w.lie().clear_bit());
+    ltdc.icr.write(|w| w.clif().set_bit());
 }
 
 /// Represents a buffer of pixels.
 pub trait Framebuffer {
     /// Set the pixel at the specified coordinates to the specified color.
     fn set_pixel(&mut self, x: usize, y: usize, color: Color);
+
+    /// Returns the memory of the framebuffer, which is used for accelerated drawing.
+    ///
+    /// Framebuffers that are not in memory return `None`, which is the default. Drawing on
+    /// them falls back to [`set_pixel`](Framebuffer::set_pixel).
+    fn pixel_buffer(&mut self) -> Option<PixelBuffer> {
+        None
+    }
 }
 
 /// A framebuffer in the ARGB8888 format.
@@ -119,6 +448,13 @@ impl Framebuffer for FramebufferArgb8888 {
         let pixel_ptr = (self.base_addr + pixel * LAYER_1_OCTETS_PER_PIXEL) as *mut u32;
         unsafe { ptr::write_volatile(pixel_ptr, color.to_argb8888()) };
     }
+
+    fn pixel_buffer(&mut self) -> Option<PixelBuffer> {
+        let buffer = unsafe {
+            PixelBuffer::from_raw_parts(self.base_addr, WIDTH, HEIGHT, PixelFormat::Argb8888)
+        };
+        Some(buffer)
+    }
 }
 
 /// A framebuffer in the AL88 format.
@@ -141,63 +477,91 @@ impl Framebuffer for FramebufferAl88 {
         let pixel_ptr = (self.base_addr + pixel * LAYER_2_OCTETS_PER_PIXEL) as *mut u16;
         unsafe { ptr::write_volatile(pixel_ptr, u16::from(color.alpha) << 8 | u16::from(color.red)) };
     }
+
+    fn pixel_buffer(&mut self) -> Option<PixelBuffer> {
+        let buffer = unsafe {
+            PixelBuffer::from_raw_parts(self.base_addr, WIDTH, HEIGHT, PixelFormat::Al88)
+        };
+        Some(buffer)
+    }
 }
 
 /// Represents a layer of the LCD controller.
 pub struct Layer<T> {
+    id: LayerId,
+    /// The buffer that is drawn into.
     framebuffer: T,
+    /// The other buffers, the last one is displayed.
+    back_buffers: ArrayVec<[T; 2]>,
+    /// Whether the buffers were swapped, the swap may still wait for the vertical blanking.
+    swapped: bool,
 }
 
 impl<T: Framebuffer> Layer<T> {
+    fn new<B: Blitter>(
+        id: LayerId,
+        framebuffer: T,
+        back_buffers: impl Iterator<Item = T>,
+        blitter: &mut B,
+    ) -> Self {
+        let mut layer = Layer {
+            id,
+            framebuffer,
+            back_buffers: ArrayVec::new(),
+            swapped: false,
+        };
+        for mut back_buffer in back_buffers {
+            let _ = fill(&mut back_buffer, blitter, SCREEN, TRANSPARENT);
+            // draw into a back buffer, the displayed buffer stays the last one
+            mem::swap(&mut layer.framebuffer, &mut back_buffer);
+            layer.back_buffers.insert(0, back_buffer);
+        }
+        layer
+    }
+
+    /// Returns which layer of the LCD controller this is.
+    pub fn id(&self) -> LayerId {
+        self.id
+    }
+
     /// Fill the layer with horizontal stripes.
     ///
     /// Useful for testing.
-    pub fn horizontal_stripes(&mut self) {
+    pub fn horizontal_stripes<B: Blitter>(&mut self, blitter: &mut B) {
         let colors = [
             0xff_ff_ff, 0xcc_cc_cc, 0x99_99_99, 0x66_66_66, 0x33_33_33, 0x00_00_00, 0xff_00_00, 0x00_00_ff,
         ];
 
         // horizontal stripes
-        for i in 0..HEIGHT {
-            for j in 0..WIDTH {
-                self.framebuffer.set_pixel(
-                    j,
-                    i,
-                    Color::from_rgb888(colors[(i / 10) % colors.len()]),
-                );
-            }
+        for (i, y) in (0..HEIGHT).step_by(10).enumerate() {
+            let stripe = Rect::new(0, y, WIDTH, 10);
+            let color = Color::from_rgb888(colors[i % colors.len()]);
+            let _ = fill(&mut self.framebuffer, blitter, stripe, color);
         }
     }
 
     /// Fill the layer with vertical stripes.
     ///
     /// Useful for testing.
-    pub fn vertical_stripes(&mut self) {
+    pub fn vertical_stripes<B: Blitter>(&mut self, blitter: &mut B) {
         let colors = [
             0xcc_cc_cc, 0x99_99_99, 0x66_66_66, 0x33_33_33, 0x00_00_00, 0xff_00_00, 0x00_00_ff, 0xff_ff_ff,
         ];
 
         // vertical stripes
-        for i in 0..HEIGHT {
-            for j in 0..WIDTH {
-                self.framebuffer.set_pixel(
-                    j,
-                    i,
-                    Color::from_rgb888(colors[(j / 10) % colors.len()]),
-                );
-            }
+        for (i, x) in (0..WIDTH).step_by(10).enumerate() {
+            let stripe = Rect::new(x, 0, 10, HEIGHT);
+            let color = Color::from_rgb888(colors[i % colors.len()]);
+            let _ = fill(&mut self.framebuffer, blitter, stripe, color);
         }
     }
 
     /// Clear all pixels.
     ///
     /// This method sets each pixel to transparent or black, depending on the framebuffer format.
-    pub fn clear(&mut self) {
-        for i in 0..HEIGHT {
-            for j in 0..WIDTH {
-                self.framebuffer.set_pixel(j, i, Color::from_argb8888(0));
-            }
-        }
+    /// Pass a [`Dma2d`](Dma2d) to clear the layer in hardware.
+    pub fn clear<B: Blitter>(&mut self, blitter: &mut B) {
+        let _ = fill(&mut self.framebuffer, blitter, SCREEN, TRANSPARENT);
     }
 
     /// Sets the pixel at the specified coordinates to white.
@@ -213,6 +577,106 @@ impl<T: Framebuffer> Layer<T> {
         self.framebuffer.set_pixel(x, y, color);
     }
 
+    /// Sets all pixels in `rect` to `color`. The rectangle is clipped to the layer.
+    ///
+    /// If the framebuffer has no [`pixel_buffer`](Framebuffer::pixel_buffer), the pixels are set
+    /// one by one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blitter fails, see [`dma2d::Error`](dma2d::Error).
+    pub fn fill_rect<B: Blitter>(
+        &mut self,
+        blitter: &mut B,
+        rect: Rect,
+        color: Color,
+    ) -> Result<(), dma2d::Error> {
+        fill(&mut self.framebuffer, blitter, rect, color)
+    }
+
+    /// Copies the pixels in `src` to the area starting at `x` and `y`. Both areas are clipped
+    /// to the layer.
+    ///
+    /// The areas may only overlap if the destination is above the source, or on the same
+    /// lines and left of the source.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blitter fails or the framebuffer has no
+    /// [`pixel_buffer`](Framebuffer::pixel_buffer), see [`dma2d::Error`](dma2d::Error).
+    pub fn copy_rect<B: Blitter>(
+        &mut self,
+        blitter: &mut B,
+        src: Rect,
+        x: usize,
+        y: usize,
+    ) -> Result<(), dma2d::Error> {
+        let buffer = self
+            .framebuffer
+            .pixel_buffer()
+            .ok_or(dma2d::Error::NoPixelBuffer)?;
+        let dst = Rect::new(x, y, src.width, src.height);
+        blitter.copy(buffer.sub_buffer(src), buffer.sub_buffer(dst))
+    }
+
+    /// Copies the pixels of `src` to the area starting at `x` and `y` and converts them to the
+    /// pixel format of the layer. The area is clipped to the layer.
+    ///
+    /// If the framebuffer has no [`pixel_buffer`](Framebuffer::pixel_buffer), the pixels are set
+    /// one by one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blitter fails, see [`dma2d::Error`](dma2d::Error).
+    pub fn blit<B: Blitter>(
+        &mut self,
+        blitter: &mut B,
+        src: PixelBuffer,
+        x: usize,
+        y: usize,
+    ) -> Result<(), dma2d::Error> {
+        let dst = Rect::new(x, y, src.width(), src.height());
+        match self.framebuffer.pixel_buffer() {
+            Some(buffer) => blitter.copy(src, buffer.sub_buffer(dst)),
+            None => {
+                let dst = dst.intersection(SCREEN);
+                for row in 0..dst.height {
+                    for column in 0..dst.width {
+                        let color = src.get_pixel(column, row);
+                        let (x, y) = (dst.x + column, dst.y + row);
+                        self.framebuffer.set_pixel(x, y, color);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Blends the pixels of `fg` on top of the area starting at `x` and `y`. The area is clipped
+    /// to the layer.
+    ///
+    /// The alpha channel of the foreground pixels is multiplied with `alpha`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the blitter fails or the framebuffer has no
+    /// [`pixel_buffer`](Framebuffer::pixel_buffer), see [`dma2d::Error`](dma2d::Error).
+    pub fn blend<B: Blitter>(
+        &mut self,
+        blitter: &mut B,
+        fg: PixelBuffer,
+        x: usize,
+        y: usize,
+        alpha: u8,
+    ) -> Result<(), dma2d::Error> {
+        let buffer = self
+            .framebuffer
+            .pixel_buffer()
+            .ok_or(dma2d::Error::NoPixelBuffer)?;
+        let dst = Rect::new(x, y, fg.width(), fg.height());
+        blitter.blend(fg, buffer.sub_buffer(dst), alpha)
+    }
+
     /// Creates a text writer on this layer.
     pub fn text_writer(&mut self) -> TextWriter<T> {
         TextWriter {
@@ -223,6 +687,37 @@ impl<T: Framebuffer> Layer<T> {
     }
 }
 
+impl<T: Framebuffer> Framebuffer for Layer<T> {
+    fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        self.framebuffer.set_pixel(x, y, color);
+    }
+
+    fn pixel_buffer(&mut self) -> Option<PixelBuffer> {
+        self.framebuffer.pixel_buffer()
+    }
+}
+
+// Fills `rect` with the blitter, or pixel by pixel if the framebuffer is not in memory.
+fn fill<T: Framebuffer, B: Blitter>(
+    framebuffer: &mut T,
+    blitter: &mut B,
+    rect: Rect,
+    color: Color,
+) -> Result<(), dma2d::Error> {
+    match framebuffer.pixel_buffer() {
+        Some(buffer) => blitter.fill(buffer.sub_buffer(rect), color),
+        None => {
+            let rect = rect.intersection(SCREEN);
+            for y in rect.y..rect.y + rect.height {
+                for x in rect.x..rect.x + rect.width {
+                    framebuffer.set_pixel(x, y, color);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
 /// Allows to print audio data.
 pub struct AudioWriter {
     next_pixel: usize,
@@ -311,17 +806,15 @@ impl<'a, T: Framebuffer> TextWriter<'a, T> {
         self.x_pos = 0;
     }
     /// Erases all text on the screen
-    pub fn clear(&mut self) {
+    pub fn clear<B: Blitter>(&mut self, blitter: &mut B) {
         self.x_pos = 0;
         self.y_pos = 0;
-        self.layer.clear();
+        self.layer.clear(blitter);
     }
 }
 
 impl<'a, T: Framebuffer> fmt::Write for TextWriter<'a, T> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        use font8x8::UnicodeFonts;
-
         for c in s.chars() {
             if c == '\n' {
                 self.newline();
@@ -330,35 +823,169 @@ impl<'a, T: Framebuffer> fmt::Write for TextWriter<'a, T> {
                 self.carriage_return();
                 continue;
             }
-            match c {
-                ' '..='~' => {
-                    if self.x_pos >= WIDTH {
-                        self.newline();
-                    }
-                    if self.y_pos >= HEIGHT {
-                        self.clear();
-                    }
-                    let rendered = font8x8::BASIC_FONTS
-                        .get(c)
-                        .expect("character not found in basic font");
-                    for (y, byte) in rendered.iter().enumerate() {
-                        for (x, bit) in (0..8).enumerate() {
-                            let alpha = if *byte & (1 << bit) == 0 { 0 } else { 255 };
-                            let color = Color {
-                                red: 255,
-                                green: 255,
-                                blue: 255,
-                                alpha,
-                            };
-                            self.layer
-                                .print_point_color_at(self.x_pos + x, self.y_pos + y, color);
-                        }
-                    }
+            // characters without glyph are replaced by `?`
+            let glyph = match font::glyph_or_replacement(&Font8x8, c) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            if self.x_pos >= WIDTH {
+                self.newline();
+            }
+            if self.y_pos >= HEIGHT {
+                // `fmt::Write` can't pass a blitter
+                self.clear(&mut Software);
+            }
+            for y in 0..glyph.height {
+                for x in 0..glyph.width {
+                    let color = Color {
+                        red: 255,
+                        green: 255,
+                        blue: 255,
+                        alpha: glyph.coverage(x, y),
+                    };
+                    self.layer.print_point_color_at(
+                        self.x_pos + usize::from(x),
+                        self.y_pos + usize::from(y),
+                        color,
+                    );
                 }
-                _ => panic!("unprintable character"),
             }
             self.x_pos += 8;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use core::iter;
+
+    // A framebuffer that is not in memory, like a remote display.
+    struct Pixels(Vec<Color>);
+
+    impl Framebuffer for Pixels {
+        fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+            self.0[y * WIDTH + x] = color;
+        }
+    }
+
+    fn pixels_layer() -> Layer<Pixels> {
+        let pixels = Pixels(vec![Color::rgb(1, 2, 3); WIDTH * HEIGHT]);
+        Layer::new(LayerId::Layer1, pixels, iter::empty(), &mut Software)
+    }
+
+    fn memory_layer(memory: &mut Vec<u32>) -> Layer<PixelBuffer> {
+        memory.resize(WIDTH * HEIGHT, 0x0102_0304);
+        let addr = memory.as_mut_ptr() as usize;
+        let buffer =
+            unsafe { PixelBuffer::from_raw_parts(addr, WIDTH, HEIGHT, PixelFormat::Argb8888) };
+        Layer::new(LayerId::Layer2, buffer, iter::empty(), &mut Software)
+    }
+
+    // Checks that the software renderer and the fallback for framebuffers without pixel buffer
+    // draw the same pixels.
+    fn assert_same_pixels(memory: &mut Layer<PixelBuffer>, pixels: &Layer<Pixels>) {
+        let buffer = memory.pixel_buffer().unwrap();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let expected = pixels.framebuffer.0[y * WIDTH + x];
+                assert_eq!(buffer.get_pixel(x, y), expected, "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn stripes_and_clear() {
+        let mut memory = Vec::new();
+        let mut memory_layer = memory_layer(&mut memory);
+        let mut pixels_layer = pixels_layer();
+
+        memory_layer.horizontal_stripes(&mut Software);
+        pixels_layer.horizontal_stripes(&mut Software);
+        let pixels = &pixels_layer.framebuffer.0;
+        assert_eq!(pixels[9 * WIDTH], Color::from_rgb888(0xff_ff_ff));
+        assert_eq!(pixels[10 * WIDTH], Color::from_rgb888(0xcc_cc_cc));
+        assert_same_pixels(&mut memory_layer, &pixels_layer);
+
+        memory_layer.vertical_stripes(&mut Software);
+        pixels_layer.vertical_stripes(&mut Software);
+        let pixels = &pixels_layer.framebuffer.0;
+        assert_eq!(pixels[WIDTH - 1], Color::from_rgb888(0xff_ff_ff));
+        assert_same_pixels(&mut memory_layer, &pixels_layer);
+
+        memory_layer.clear(&mut Software);
+        pixels_layer.clear(&mut Software);
+        assert!(memory.iter().all(|&pixel| pixel == 0));
+        let pixels = &pixels_layer.framebuffer.0;
+        assert!(pixels.iter().all(|&color| color == TRANSPARENT));
+    }
+
+    #[test]
+    fn fill_rect_is_clipped() {
+        let mut memory = Vec::new();
+        let mut memory_layer = memory_layer(&mut memory);
+        let mut pixels_layer = pixels_layer();
+        memory_layer.clear(&mut Software);
+        pixels_layer.clear(&mut Software);
+
+        let rect = Rect::new(WIDTH - 2, HEIGHT - 3, 10, 10);
+        let color = Color::rgb(255, 0, 0);
+        memory_layer.fill_rect(&mut Software, rect, color).unwrap();
+        pixels_layer.fill_rect(&mut Software, rect, color).unwrap();
+        assert_same_pixels(&mut memory_layer, &pixels_layer);
+        let filled = pixels_layer.framebuffer.0.iter().filter(|&&c| c == color);
+        assert_eq!(filled.count(), 2 * 3);
+    }
+
+    #[test]
+    fn blit_without_pixel_buffer() {
+        let mut src_memory = vec![0xff00_00ffu32; 4 * 2];
+        src_memory[1] = 0xff00_ff00;
+        let addr = src_memory.as_mut_ptr() as usize;
+        let src = unsafe { PixelBuffer::from_raw_parts(addr, 4, 2, PixelFormat::Argb8888) };
+
+        let mut memory = Vec::new();
+        let mut memory_layer = memory_layer(&mut memory);
+        let mut pixels_layer = pixels_layer();
+        memory_layer.clear(&mut Software);
+        pixels_layer.clear(&mut Software);
+        for &(x, y) in &[(10, 20), (WIDTH - 3, HEIGHT - 1)] {
+            memory_layer.blit(&mut Software, src, x, y).unwrap();
+            pixels_layer.blit(&mut Software, src, x, y).unwrap();
+        }
+        assert_same_pixels(&mut memory_layer, &pixels_layer);
+        let pixels = &pixels_layer.framebuffer.0;
+        assert_eq!(pixels[20 * WIDTH + 11], Color::rgb(0, 255, 0));
+    }
+
+    #[test]
+    fn copy_and_blend_need_pixel_buffer() {
+        let mut memory = vec![0u32; 1];
+        let addr = memory.as_mut_ptr() as usize;
+        let src = unsafe { PixelBuffer::from_raw_parts(addr, 1, 1, PixelFormat::Argb8888) };
+
+        let mut layer = pixels_layer();
+        let rect = Rect::new(0, 0, 10, 10);
+        let error = Err(dma2d::Error::NoPixelBuffer);
+        assert_eq!(layer.copy_rect(&mut Software, rect, 20, 0), error);
+        assert_eq!(layer.blend(&mut Software, src, 0, 0, 255), error);
+    }
+
+    #[test]
+    fn back_buffers_are_cleared() {
+        let mut memory = vec![0xffff_ffffu32; 3 * 2];
+        let addr = memory.as_mut_ptr() as usize;
+        let buffers = (0..3).map(|i| unsafe {
+            PixelBuffer::from_raw_parts(addr + i * 2 * 4, 2, 1, PixelFormat::Argb8888)
+        });
+        let mut buffers = buffers.collect::<Vec<_>>().into_iter();
+        let displayed = buffers.next().unwrap();
+        let layer = Layer::new(LayerId::Layer1, displayed, buffers, &mut Software);
+
+        assert_eq!(memory, [0xffff_ffff, 0xffff_ffff, 0, 0, 0, 0]);
+        assert_eq!(layer.back_buffers.len(), 2);
+        assert_eq!(layer.back_buffers[1], displayed);
+    }
+}
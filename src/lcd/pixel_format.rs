@@ -0,0 +1,137 @@
+//! The pixel formats supported by the LCD controller and the DMA2D.
+
+use super::Color;
+
+/// The format of the pixels in a buffer.
+///
+/// The luminance formats (`L8`, `Al44`, `Al88`) store an index into a color lookup table
+/// instead of a color. Like the [`FramebufferAl88`](super::FramebufferAl88), the red channel of
+/// a [`Color`](Color) is used as index when encoding, and the index is returned as gray value
+/// when decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 8 bits for alpha, red, green and blue, 32 bits per pixel.
+    Argb8888,
+    /// 8 bits for red, green and blue, 24 bits per pixel.
+    Rgb888,
+    /// 5 bits for red and blue and 6 bits for green, 16 bits per pixel.
+    Rgb565,
+    /// 1 bit for alpha and 5 bits for red, green and blue, 16 bits per pixel.
+    Argb1555,
+    /// 4 bits for alpha, red, green and blue, 16 bits per pixel.
+    Argb4444,
+    /// An 8 bit lookup table index, 8 bits per pixel.
+    L8,
+    /// 4 bits for alpha and a 4 bit lookup table index, 8 bits per pixel.
+    Al44,
+    /// 8 bits for alpha and an 8 bit lookup table index, 16 bits per pixel.
+    Al88,
+}
+
+impl PixelFormat {
+    /// Returns the number of bytes that are used to store a pixel.
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Argb8888 => 4,
+            PixelFormat::Rgb888 => 3,
+            PixelFormat::Rgb565
+            | PixelFormat::Argb1555
+            | PixelFormat::Argb4444
+            | PixelFormat::Al88 => 2,
+            PixelFormat::L8 | PixelFormat::Al44 => 1,
+        }
+    }
+
+    /// Returns whether the format stores lookup table indices instead of colors.
+    pub fn is_luminance(self) -> bool {
+        match self {
+            PixelFormat::L8 | PixelFormat::Al44 | PixelFormat::Al88 => true,
+            _ => false,
+        }
+    }
+
+    /// Encodes the color as raw pixel value. The lower bits of each channel are truncated.
+    pub fn encode(self, color: Color) -> u32 {
+        let (a, r, g, b) = (
+            u32::from(color.alpha),
+            u32::from(color.red),
+            u32::from(color.green),
+            u32::from(color.blue),
+        );
+        match self {
+            PixelFormat::Argb8888 => color.to_argb8888(),
+            PixelFormat::Rgb888 => color.to_rgb888(),
+            PixelFormat::Rgb565 => (r >> 3) << 11 | (g >> 2) << 5 | b >> 3,
+            PixelFormat::Argb1555 => u32::from(color.to_argb1555()),
+            PixelFormat::Argb4444 => (a >> 4) << 12 | (r >> 4) << 8 | (g >> 4) << 4 | b >> 4,
+            PixelFormat::L8 => r,
+            PixelFormat::Al44 => (a >> 4) << 4 | (r & 0xf),
+            PixelFormat::Al88 => a << 8 | r,
+        }
+    }
+
+    /// Decodes a raw pixel value.
+    ///
+    /// Channels with less than 8 bits are expanded by repeating their upper bits, like the DMA2D
+    /// does, so that e.g. `0x1f` becomes `0xff`. Formats without alpha channel are opaque.
+    pub fn decode(self, raw: u32) -> Color {
+        match self {
+            PixelFormat::Argb8888 => Color::from_argb8888(raw),
+            PixelFormat::Rgb888 => Color::from_argb8888(0xff00_0000 | raw),
+            PixelFormat::Rgb565 => Color::rgb(
+                expand(raw >> 11, 5),
+                expand(raw >> 5, 6),
+                expand(raw, 5),
+            ),
+            PixelFormat::Argb1555 => Color::rgba(
+                expand(raw >> 10, 5),
+                expand(raw >> 5, 5),
+                expand(raw, 5),
+                expand(raw >> 15, 1),
+            ),
+            PixelFormat::Argb4444 => Color::rgba(
+                expand(raw >> 8, 4),
+                expand(raw >> 4, 4),
+                expand(raw, 4),
+                expand(raw >> 12, 4),
+            ),
+            PixelFormat::L8 => gray(raw as u8, 0xff),
+            PixelFormat::Al44 => gray(raw as u8 & 0xf, expand(raw >> 4, 4)),
+            PixelFormat::Al88 => gray(raw as u8, (raw >> 8) as u8),
+        }
+    }
+
+    /// The color mode value of the format in the LTDC and DMA2D registers.
+    pub(super) fn color_mode(self) -> u8 {
+        match self {
+            PixelFormat::Argb8888 => 0b000,
+            PixelFormat::Rgb888 => 0b001,
+            PixelFormat::Rgb565 => 0b010,
+            PixelFormat::Argb1555 => 0b011,
+            PixelFormat::Argb4444 => 0b100,
+            PixelFormat::L8 => 0b101,
+            PixelFormat::Al44 => 0b110,
+            PixelFormat::Al88 => 0b111,
+        }
+    }
+}
+
+// Expands the lowest `bits` bits of `value` to 8 bits.
+fn expand(value: u32, bits: u32) -> u8 {
+    let value = value & ((1 << bits) - 1);
+    let mut result = 0;
+    let mut shift = 8i32 - bits as i32;
+    while shift > -(bits as i32) {
+        result |= if shift >= 0 {
+            value << shift
+        } else {
+            value >> -shift
+        };
+        shift -= bits as i32;
+    }
+    result as u8
+}
+
+fn gray(index: u8, alpha: u8) -> Color {
+    Color::rgba(index, index, index, alpha)
+}
@@ -1,25 +1,35 @@
 //! Initialize a LCD layer as standard output.
+//!
+//! The output is shown by a [`Terminal`](super::terminal::Terminal), so it can contain ANSI
+//! escape sequences, e.g. for colors.
 
-use super::{FramebufferAl88, Layer, TextWriter};
+use super::terminal::{self, Terminal};
+use super::{FramebufferAl88, Layer, Lcd};
 use core::fmt;
 use cortex_m::interrupt;
 use spin::Mutex;
 
 static STDOUT: Stdout = Stdout(Mutex::new(None));
 
-struct Stdout<'a>(Mutex<Option<TextWriter<'a, FramebufferAl88>>>);
+struct Console {
+    terminal: Terminal,
+    layer: Layer<FramebufferAl88>,
+}
+
+struct Stdout(Mutex<Option<Console>>);
 
-impl<'a> Stdout<'a> {
-    fn with(&self, f: impl FnOnce(&mut Option<TextWriter<'a, FramebufferAl88>>)) {
+impl Stdout {
+    fn with(&self, f: impl FnOnce(&mut Option<Console>)) {
         interrupt::free(|_| f(&mut self.0.lock()))
     }
 }
 
-/// Erases the stdout output on the screen
+/// Erases the stdout output on the screen and in the scrollback buffer.
 pub fn clear() {
     STDOUT.with(|stdout| {
-        if let Some(ref mut stdout) = *stdout {
-            stdout.clear();
+        if let Some(ref mut console) = *stdout {
+            console.terminal.clear();
+            console.terminal.render(&mut console.layer);
         }
     });
 }
@@ -27,13 +37,39 @@ pub fn clear() {
 /// Initialize the passed layer as standard output.
 ///
 /// Subsequent calls to [`print`](print) or [`println!`](println!) will then print
-/// to the layer.
-pub fn init(layer: Layer<FramebufferAl88>) {
-    static mut LAYER: Option<Layer<FramebufferAl88>> = None;
+/// to the layer. Lines that are scrolled off the screen are discarded, use
+/// [`init_with_scrollback`](init_with_scrollback) to keep them.
+///
+/// The terminal writes the colors as indices of its [`palette`](terminal::palette), so the
+/// palette replaces the gray ramp that `lcd::init` loads into the color lookup table of the
+/// layer. The cells of the terminal are
+/// allocated on the heap, so the allocator must be initialized first.
+pub fn init(lcd: &mut Lcd, layer: Layer<FramebufferAl88>) {
+    init_with_scrollback(lcd, layer, 0);
+}
 
+/// Initialize the passed layer as standard output that keeps up to `scrollback_lines` lines
+/// that were scrolled off the screen.
+///
+/// Like [`init`](init), this loads the palette of the terminal into the color lookup table of
+/// the layer. The terminal and the scrollback buffer are allocated on the heap, so the
+/// allocator must be initialized first. Use [`scroll_view`](scroll_view) to show the
+/// scrollback buffer.
+pub fn init_with_scrollback(
+    lcd: &mut Lcd,
+    layer: Layer<FramebufferAl88>,
+    scrollback_lines: usize,
+) {
+    for i in 0..=255 {
+        lcd.set_color_lookup_table(layer.id(), i, terminal::palette(i));
+    }
     STDOUT.with(|stdout| {
-        let layer = unsafe { LAYER.get_or_insert_with(|| layer) };
-        *stdout = Some(layer.text_writer());
+        let mut console = Console {
+            terminal: Terminal::new(scrollback_lines),
+            layer,
+        };
+        console.terminal.render(&mut console.layer);
+        *stdout = Some(console);
     });
 }
 
@@ -66,10 +102,35 @@ macro_rules! print {
 /// Panics if the standard output is not yet initialized.
 pub fn print(args: fmt::Arguments) {
     use core::fmt::Write;
+    with_console(|console| console.terminal.write_fmt(args).unwrap());
+}
+
+/// Prints UTF-8 encoded bytes to the standard output, e.g. the output of a serial console.
+///
+/// Multi-byte characters may be split across calls.
+///
+/// Panics if the standard output is not yet initialized.
+pub fn write_bytes(bytes: &[u8]) {
+    with_console(|console| console.terminal.write_bytes(bytes));
+}
+
+/// Scrolls the standard output into the scrollback buffer.
+///
+/// Positive values show older lines, negative values newer lines. The view returns to the
+/// bottom when new output is printed.
+///
+/// Panics if the standard output is not yet initialized.
+pub fn scroll_view(lines: isize) {
+    with_console(|console| console.terminal.scroll_view(lines));
+}
+
+// Runs the function on the console and draws the changes.
+fn with_console(f: impl FnOnce(&mut Console)) {
     let mut uninitialized = false;
     STDOUT.with(|stdout| {
-        if let Some(ref mut stdout) = *stdout {
-            stdout.write_fmt(args).unwrap();
+        if let Some(ref mut console) = *stdout {
+            f(console);
+            console.terminal.render(&mut console.layer);
         } else {
             uninitialized = true;
         }
@@ -0,0 +1,1165 @@
+//! A terminal emulator that understands a subset of the ANSI/VT100 escape sequences.
+//!
+//! The terminal keeps a grid of character cells with the 8x8 font, so the whole display has
+//! [`COLUMNS`](COLUMNS) x [`ROWS`](ROWS) cells. Output that reaches the bottom of the display
+//! scrolls the grid up, and the lines that leave the display are kept in a scrollback buffer
+//! of configurable size.
+//!
+//! The following control characters and escape sequences are supported:
+//!
+//! - Backspace, horizontal tab (tab stops every 8 columns), carriage return and line feed. A
+//!   line feed also returns the cursor to the first column, like the output of `println!`
+//!   expects.
+//! - `ESC 7` / `ESC 8` (save/restore cursor), `ESC D` (index), `ESC E` (next line), `ESC M`
+//!   (reverse index) and `ESC c` (reset).
+//! - Cursor movement: `CSI n A/B/C/D/E/F/G/d`, `CSI row;col H/f`, `CSI s/u` and
+//!   `CSI ?25 h/l` (show/hide cursor).
+//! - Erasing: `CSI n J` (screen, `3` also erases the scrollback), `CSI n K` (line) and
+//!   `CSI n X/P/@` (erase, delete and insert characters).
+//! - Scrolling: `CSI n S/T`, `CSI n L/M` (insert and delete lines) and `CSI top;bottom r`
+//!   (scrolling region).
+//! - Graphic rendition (`CSI ... m`): bold, underline, reverse, the 16 standard colors, the
+//!   256 color palette (`38;5;n`) and true color (`38;2;r;g;b`).
+//!
+//! Operating system commands (`ESC ]`) and character set selections are ignored.
+//!
+//! The terminal only draws the cells that changed since the last call of
+//! [`render`](Terminal::render). On layers with a luminance pixel format, the colors are
+//! written as indices into the color lookup table, so the layer's table must contain the
+//! [`palette`](palette). [`lcd::stdout`](super::stdout) loads it when it is initialized.
+//!
+//! # Examples
+//! ```rust
+//! use core::fmt::Write;
+//!
+//! let mut terminal = lcd::terminal::Terminal::new(100);
+//! writeln!(terminal, "\x1b[1;31merror:\x1b[0m file not found").unwrap();
+//! terminal.render(&mut layer_2);
+//! ```
+
+use super::font::{self, Font8x8};
+use super::{Color, Framebuffer, HEIGHT, WIDTH};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use bitflags::bitflags;
+use core::{fmt, str};
+
+/// The number of character columns.
+pub const COLUMNS: usize = WIDTH / CELL_SIZE;
+/// The number of character rows.
+pub const ROWS: usize = HEIGHT / CELL_SIZE;
+
+const CELL_SIZE: usize = 8;
+const TAB_WIDTH: usize = 8;
+const MAX_PARAMS: usize = 16;
+
+/// The color of a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellColor {
+    /// The default color, which is white for the foreground and transparent for the
+    /// background.
+    Default,
+    /// A color of the 256 color [`palette`](palette).
+    Indexed(u8),
+    /// A true color with red, green and blue channels.
+    Rgb(u8, u8, u8),
+}
+
+bitflags! {
+    /// The graphic rendition of a cell.
+    pub struct Attributes: u8 {
+        /// Uses the bright variant of the 8 standard foreground colors.
+        const BOLD      = 0x01;
+        /// Draws a line below the character.
+        const UNDERLINE = 0x02;
+        /// Swaps the foreground and the background color.
+        const REVERSE   = 0x04;
+    }
+}
+
+/// A character cell of the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    /// The character.
+    pub c: char,
+    /// The color of the character.
+    pub foreground: CellColor,
+    /// The color behind the character.
+    pub background: CellColor,
+    /// The graphic rendition.
+    pub attributes: Attributes,
+}
+
+impl Cell {
+    const BLANK: Cell = Cell {
+        c: ' ',
+        foreground: CellColor::Default,
+        background: CellColor::Default,
+        attributes: Attributes { bits: 0 },
+    };
+
+    // An empty cell with the colors of the style, which is used for erasing.
+    fn blank(style: Cell) -> Cell {
+        Cell {
+            background: style.background,
+            ..Cell::BLANK
+        }
+    }
+}
+
+type Row = [Cell; COLUMNS];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    // the flag is set for private sequences like `CSI ? 25 h`
+    Csi { private: bool },
+    Osc,
+    Charset,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SavedCursor {
+    row: usize,
+    column: usize,
+    style: Cell,
+}
+
+/// A terminal emulator that renders into a framebuffer.
+///
+/// Text is written through the [`fmt::Write`](core::fmt::Write) implementation or through
+/// [`write_bytes`](Terminal::write_bytes), which is useful for the output of a serial console.
+/// Both only update the cells, the changes are drawn by [`render`](Terminal::render).
+pub struct Terminal {
+    // the grid is too large to be moved around on the stack, so it is on the heap
+    cells: Box<[Row]>,
+    dirty: Box<[[bool; COLUMNS]]>,
+    row: usize,
+    column: usize,
+    // set when a character was written into the last column, the next character wraps
+    wrap_pending: bool,
+    // the foreground, background and attributes for new characters
+    style: Cell,
+    saved_cursor: SavedCursor,
+    scroll_top: usize,
+    scroll_bottom: usize,
+    cursor_visible: bool,
+    rendered_cursor: Option<(usize, usize)>,
+    state: State,
+    params: [u16; MAX_PARAMS],
+    param_count: usize,
+    utf8_buffer: [u8; 4],
+    utf8_length: usize,
+    utf8_expected: usize,
+    scrollback: Vec<Row>,
+    scrollback_capacity: usize,
+    // index of the oldest line when the scrollback buffer is full
+    scrollback_start: usize,
+    view_offset: usize,
+}
+
+impl Terminal {
+    /// Creates an empty terminal that keeps up to `scrollback_lines` lines that were scrolled
+    /// off the display.
+    ///
+    /// The cells and the scrollback buffer are allocated on the heap, so the allocator must be
+    /// initialized first.
+    pub fn new(scrollback_lines: usize) -> Terminal {
+        Terminal {
+            cells: vec![[Cell::BLANK; COLUMNS]; ROWS].into_boxed_slice(),
+            dirty: vec![[true; COLUMNS]; ROWS].into_boxed_slice(),
+            row: 0,
+            column: 0,
+            wrap_pending: false,
+            style: Cell::BLANK,
+            saved_cursor: SavedCursor {
+                row: 0,
+                column: 0,
+                style: Cell::BLANK,
+            },
+            scroll_top: 0,
+            scroll_bottom: ROWS - 1,
+            cursor_visible: false,
+            rendered_cursor: None,
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            param_count: 0,
+            utf8_buffer: [0; 4],
+            utf8_length: 0,
+            utf8_expected: 0,
+            scrollback: Vec::new(),
+            scrollback_capacity: scrollback_lines,
+            scrollback_start: 0,
+            view_offset: 0,
+        }
+    }
+
+    /// Returns the cursor position as `(column, row)`.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.column, self.row)
+    }
+
+    /// Returns the cell at the specified position of the display, ignoring the scrollback.
+    pub fn cell(&self, column: usize, row: usize) -> Cell {
+        self.cells[row][column]
+    }
+
+    /// Returns the number of lines in the scrollback buffer.
+    pub fn scrollback_len(&self) -> usize {
+        self.scrollback.len()
+    }
+
+    /// Erases the display and the scrollback buffer and resets the terminal state.
+    pub fn clear(&mut self) {
+        for row in self.cells.iter_mut() {
+            for cell in row.iter_mut() {
+                *cell = Cell::BLANK;
+            }
+        }
+        self.mark_dirty(0, ROWS - 1);
+        self.row = 0;
+        self.column = 0;
+        self.wrap_pending = false;
+        self.style = Cell::BLANK;
+        self.saved_cursor = SavedCursor {
+            row: 0,
+            column: 0,
+            style: Cell::BLANK,
+        };
+        self.scroll_top = 0;
+        self.scroll_bottom = ROWS - 1;
+        self.cursor_visible = false;
+        self.state = State::Ground;
+        self.param_count = 0;
+        self.utf8_length = 0;
+        self.utf8_expected = 0;
+        self.scrollback.clear();
+        self.scrollback_start = 0;
+        self.view_offset = 0;
+    }
+
+    /// Scrolls the view into the scrollback buffer.
+    ///
+    /// Positive values show older lines, negative values newer lines. The view returns to the
+    /// bottom when new output is written.
+    pub fn scroll_view(&mut self, lines: isize) {
+        let offset = if lines >= 0 {
+            self.view_offset.saturating_add(lines as usize)
+        } else {
+            self.view_offset.saturating_sub(lines.wrapping_neg() as usize)
+        };
+        self.set_view_offset(offset.min(self.scrollback.len()));
+    }
+
+    /// Returns how many lines the view is scrolled into the scrollback buffer.
+    pub fn view_offset(&self) -> usize {
+        self.view_offset
+    }
+
+    /// Processes UTF-8 encoded output, for example from a serial console.
+    ///
+    /// Multi-byte characters may be split across calls. Invalid sequences are shown as the
+    /// replacement character.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_byte(byte);
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        if self.utf8_expected > 0 {
+            if byte & 0xc0 == 0x80 {
+                self.utf8_buffer[self.utf8_length] = byte;
+                self.utf8_length += 1;
+                if self.utf8_length == self.utf8_expected {
+                    self.utf8_expected = 0;
+                    let buffer = self.utf8_buffer;
+                    match str::from_utf8(&buffer[..self.utf8_length]) {
+                        Ok(s) => s.chars().for_each(|c| self.process(c)),
+                        Err(_) => self.process('\u{fffd}'),
+                    }
+                }
+                return;
+            }
+            // the sequence is incomplete
+            self.utf8_expected = 0;
+            self.process('\u{fffd}');
+        }
+
+        let length = match byte {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 0,
+        };
+        match length {
+            0 => self.process('\u{fffd}'),
+            1 => self.process(char::from(byte)),
+            _ => {
+                self.utf8_buffer[0] = byte;
+                self.utf8_length = 1;
+                self.utf8_expected = length;
+            }
+        }
+    }
+
+    /// Processes a single character of output.
+    pub fn process(&mut self, c: char) {
+        self.set_view_offset(0);
+        match self.state {
+            State::Ground => self.process_ground(c),
+            State::Escape => self.process_escape(c),
+            State::Csi { private } => self.process_csi(c, private),
+            State::Osc => match c {
+                '\x07' => self.state = State::Ground,
+                '\x1b' => self.state = State::Escape, // string terminator `ESC \`
+                _ => {}
+            },
+            State::Charset => self.state = State::Ground,
+        }
+    }
+
+    fn process_ground(&mut self, c: char) {
+        match c {
+            '\x08' => {
+                self.column = self.column.saturating_sub(1);
+                self.wrap_pending = false;
+            }
+            '\t' => {
+                self.column = ((self.column / TAB_WIDTH + 1) * TAB_WIDTH).min(COLUMNS - 1);
+                self.wrap_pending = false;
+            }
+            '\n' | '\x0b' | '\x0c' => {
+                self.line_feed();
+                self.column = 0;
+            }
+            '\r' => {
+                self.column = 0;
+                self.wrap_pending = false;
+            }
+            '\x1b' => self.state = State::Escape,
+            c if c.is_control() => {}
+            c => self.put_char(c),
+        }
+    }
+
+    fn process_escape(&mut self, c: char) {
+        self.state = State::Ground;
+        match c {
+            '[' => {
+                self.params = [0; MAX_PARAMS];
+                self.param_count = 0;
+                self.state = State::Csi { private: false };
+            }
+            ']' => self.state = State::Osc,
+            '(' | ')' | '*' | '+' => self.state = State::Charset,
+            '7' => self.save_cursor(),
+            '8' => self.restore_cursor(),
+            'D' => self.line_feed(),
+            'E' => {
+                self.line_feed();
+                self.column = 0;
+            }
+            'M' => self.reverse_index(),
+            'c' => self.clear(),
+            _ => {}
+        }
+    }
+
+    fn process_csi(&mut self, c: char, private: bool) {
+        match c {
+            '0'..='9' => {
+                let digit = c as u16 - '0' as u16;
+                let index = self.param_count.max(1) - 1;
+                self.param_count = self.param_count.max(1);
+                if index < MAX_PARAMS {
+                    let param = &mut self.params[index];
+                    *param = param.saturating_mul(10).saturating_add(digit);
+                }
+            }
+            ';' => self.param_count = self.param_count.max(1) + 1,
+            '?' => self.state = State::Csi { private: true },
+            '\x1b' => self.state = State::Escape,
+            '\x18' | '\x1a' => self.state = State::Ground, // cancel
+            '\x40'..='\x7e' => {
+                self.state = State::Ground;
+                self.execute_csi(c, private);
+            }
+            _ => {} // intermediate bytes
+        }
+    }
+
+    // Returns the parameter at `index`, or `default` if it is missing or 0.
+    fn param(&self, index: usize, default: u16) -> usize {
+        match self.params.get(index) {
+            Some(&param) if index < self.param_count && param != 0 => usize::from(param),
+            _ => usize::from(default),
+        }
+    }
+
+    fn execute_csi(&mut self, c: char, private: bool) {
+        if private {
+            match (c, self.param(0, 0)) {
+                ('h', 25) => self.cursor_visible = true,
+                ('l', 25) => self.cursor_visible = false,
+                _ => {}
+            }
+            return;
+        }
+
+        let n = self.param(0, 1);
+        self.wrap_pending = false;
+        match c {
+            'A' => self.row = self.row.saturating_sub(n).max(self.upper_limit()),
+            'B' => self.row = (self.row + n).min(self.lower_limit()),
+            'C' => self.column = (self.column + n).min(COLUMNS - 1),
+            'D' => self.column = self.column.saturating_sub(n),
+            'E' => {
+                self.row = (self.row + n).min(self.lower_limit());
+                self.column = 0;
+            }
+            'F' => {
+                self.row = self.row.saturating_sub(n).max(self.upper_limit());
+                self.column = 0;
+            }
+            'G' => self.column = n.min(COLUMNS) - 1,
+            'd' => self.row = n.min(ROWS) - 1,
+            'H' | 'f' => {
+                self.row = n.min(ROWS) - 1;
+                self.column = self.param(1, 1).min(COLUMNS) - 1;
+            }
+            'J' => match self.param(0, 0) {
+                0 => {
+                    self.erase_line(self.row, self.column, COLUMNS);
+                    (self.row + 1..ROWS).for_each(|row| self.erase_line(row, 0, COLUMNS));
+                }
+                1 => {
+                    (0..self.row).for_each(|row| self.erase_line(row, 0, COLUMNS));
+                    self.erase_line(self.row, 0, self.column + 1);
+                }
+                2 => (0..ROWS).for_each(|row| self.erase_line(row, 0, COLUMNS)),
+                3 => {
+                    (0..ROWS).for_each(|row| self.erase_line(row, 0, COLUMNS));
+                    self.scrollback.clear();
+                    self.scrollback_start = 0;
+                }
+                _ => {}
+            },
+            'K' => match self.param(0, 0) {
+                0 => self.erase_line(self.row, self.column, COLUMNS),
+                1 => self.erase_line(self.row, 0, self.column + 1),
+                2 => self.erase_line(self.row, 0, COLUMNS),
+                _ => {}
+            },
+            'X' => self.erase_line(self.row, self.column, (self.column + n).min(COLUMNS)),
+            'P' => self.shift_characters(n, false),
+            '@' => self.shift_characters(n, true),
+            'S' => self.scroll_up(n),
+            'T' => self.scroll_down(n),
+            'L' | 'M' if self.row >= self.scroll_top && self.row <= self.scroll_bottom => {
+                // move the top of the scrolling region to the cursor for the duration
+                let top = self.scroll_top;
+                self.scroll_top = self.row;
+                if c == 'L' {
+                    self.scroll_down(n);
+                } else {
+                    self.scroll_up(n);
+                }
+                self.scroll_top = top;
+                self.column = 0;
+            }
+            'm' => self.select_graphic_rendition(),
+            'r' => {
+                let top = self.param(0, 1) - 1;
+                let bottom = self.param(1, ROWS as u16).min(ROWS) - 1;
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.row = 0;
+                    self.column = 0;
+                }
+            }
+            's' => self.save_cursor(),
+            'u' => self.restore_cursor(),
+            _ => {}
+        }
+    }
+
+    fn select_graphic_rendition(&mut self) {
+        let count = self.param_count.max(1).min(MAX_PARAMS);
+        let mut i = 0;
+        while i < count {
+            let param = self.params[i];
+            match param {
+                0 => self.style = Cell::BLANK,
+                1 => self.style.attributes.insert(Attributes::BOLD),
+                4 => self.style.attributes.insert(Attributes::UNDERLINE),
+                7 => self.style.attributes.insert(Attributes::REVERSE),
+                22 => self.style.attributes.remove(Attributes::BOLD),
+                24 => self.style.attributes.remove(Attributes::UNDERLINE),
+                27 => self.style.attributes.remove(Attributes::REVERSE),
+                30..=37 => self.style.foreground = CellColor::Indexed(param as u8 - 30),
+                39 => self.style.foreground = CellColor::Default,
+                40..=47 => self.style.background = CellColor::Indexed(param as u8 - 40),
+                49 => self.style.background = CellColor::Default,
+                90..=97 => self.style.foreground = CellColor::Indexed(param as u8 - 90 + 8),
+                100..=107 => self.style.background = CellColor::Indexed(param as u8 - 100 + 8),
+                38 | 48 => {
+                    let (color, length) = self.extended_color(i + 1, count);
+                    if let Some(color) = color {
+                        if param == 38 {
+                            self.style.foreground = color;
+                        } else {
+                            self.style.background = color;
+                        }
+                    }
+                    i += length;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    // Parses the `5;n` and `2;r;g;b` parameters of an extended color. Returns the color and the
+    // number of parameters that belong to it.
+    fn extended_color(&self, start: usize, count: usize) -> (Option<CellColor>, usize) {
+        let params = &self.params[start.min(count)..count];
+        let channel = |i: usize| params[i].min(255) as u8;
+        match params.first().cloned() {
+            Some(5) if params.len() >= 2 => (Some(CellColor::Indexed(channel(1))), 2),
+            Some(2) if params.len() >= 4 => {
+                let color = CellColor::Rgb(channel(1), channel(2), channel(3));
+                (Some(color), 4)
+            }
+            _ => (None, params.len()),
+        }
+    }
+
+    fn put_char(&mut self, c: char) {
+        if self.wrap_pending {
+            self.wrap_pending = false;
+            self.line_feed();
+            self.column = 0;
+        }
+        let (row, column) = (self.row, self.column);
+        self.set_cell(row, column, Cell { c, ..self.style });
+        if column == COLUMNS - 1 {
+            self.wrap_pending = true;
+        } else {
+            self.column += 1;
+        }
+    }
+
+    fn set_cell(&mut self, row: usize, column: usize, cell: Cell) {
+        if self.cells[row][column] != cell {
+            self.cells[row][column] = cell;
+            self.dirty[row][column] = true;
+        }
+    }
+
+    fn erase_line(&mut self, row: usize, start: usize, end: usize) {
+        let blank = Cell::blank(self.style);
+        for column in start..end {
+            self.set_cell(row, column, blank);
+        }
+    }
+
+    // Deletes or inserts `n` characters at the cursor and moves the rest of the line.
+    fn shift_characters(&mut self, n: usize, insert: bool) {
+        let (row, column) = (self.row, self.column);
+        let n = n.min(COLUMNS - column);
+        let mut line = self.cells[row];
+        if insert {
+            line[column..].rotate_right(n);
+            line[column..column + n]
+                .iter_mut()
+                .for_each(|cell| *cell = Cell::blank(self.style));
+        } else {
+            line[column..].rotate_left(n);
+            line[COLUMNS - n..]
+                .iter_mut()
+                .for_each(|cell| *cell = Cell::blank(self.style));
+        }
+        for (column, &cell) in line.iter().enumerate() {
+            self.set_cell(row, column, cell);
+        }
+    }
+
+    // The cursor can't be moved out of the scrolling region with the cursor movement sequences.
+    fn upper_limit(&self) -> usize {
+        if self.row >= self.scroll_top {
+            self.scroll_top
+        } else {
+            0
+        }
+    }
+
+    fn lower_limit(&self) -> usize {
+        if self.row <= self.scroll_bottom {
+            self.scroll_bottom
+        } else {
+            ROWS - 1
+        }
+    }
+
+    fn line_feed(&mut self) {
+        self.wrap_pending = false;
+        if self.row == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.row < ROWS - 1 {
+            self.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.wrap_pending = false;
+        if self.row == self.scroll_top {
+            self.scroll_down(1);
+        } else if self.row > 0 {
+            self.row -= 1;
+        }
+    }
+
+    // Moves the lines of the scrolling region up. Lines that leave the top of the display are
+    // moved into the scrollback buffer.
+    fn scroll_up(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let n = n.min(bottom - top + 1);
+        if top == 0 {
+            for row in 0..n {
+                let line = self.cells[row];
+                self.push_scrollback(line);
+            }
+        }
+        self.cells[top..=bottom].rotate_left(n);
+        for row in bottom + 1 - n..=bottom {
+            self.cells[row] = [Cell::blank(self.style); COLUMNS];
+        }
+        self.mark_dirty(top, bottom);
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let n = n.min(bottom - top + 1);
+        self.cells[top..=bottom].rotate_right(n);
+        for row in top..top + n {
+            self.cells[row] = [Cell::blank(self.style); COLUMNS];
+        }
+        self.mark_dirty(top, bottom);
+    }
+
+    fn push_scrollback(&mut self, line: Row) {
+        if self.scrollback_capacity == 0 {
+            return;
+        }
+        if self.scrollback.len() < self.scrollback_capacity {
+            self.scrollback.push(line);
+        } else {
+            self.scrollback[self.scrollback_start] = line;
+            self.scrollback_start = (self.scrollback_start + 1) % self.scrollback_capacity;
+        }
+    }
+
+    // Returns a line of the scrollback buffer, 0 is the oldest line.
+    fn scrollback_line(&self, index: usize) -> &Row {
+        &self.scrollback[(self.scrollback_start + index) % self.scrollback.len()]
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = SavedCursor {
+            row: self.row,
+            column: self.column,
+            style: self.style,
+        };
+    }
+
+    fn restore_cursor(&mut self) {
+        self.row = self.saved_cursor.row;
+        self.column = self.saved_cursor.column;
+        self.style = self.saved_cursor.style;
+        self.wrap_pending = false;
+    }
+
+    fn set_view_offset(&mut self, offset: usize) {
+        if self.view_offset != offset {
+            self.view_offset = offset;
+            self.mark_dirty(0, ROWS - 1);
+        }
+    }
+
+    fn mark_dirty(&mut self, top: usize, bottom: usize) {
+        for row in &mut self.dirty[top..=bottom] {
+            *row = [true; COLUMNS];
+        }
+    }
+
+    /// Draws the cells that changed since the last call.
+    ///
+    /// The framebuffer must cover the whole display, and nothing else should draw into the
+    /// area of the terminal. Framebuffers without a pixel buffer in a luminance format get RGB
+    /// colors instead of color lookup table indices.
+    pub fn render<F: Framebuffer>(&mut self, framebuffer: &mut F) {
+        let indexed = framebuffer
+            .pixel_buffer()
+            .map_or(false, |buffer| buffer.format().is_luminance());
+
+        let cursor = if self.cursor_visible && self.view_offset == 0 {
+            Some((self.row, self.column))
+        } else {
+            None
+        };
+        if cursor != self.rendered_cursor {
+            for &(row, column) in self.rendered_cursor.iter().chain(cursor.iter()) {
+                self.dirty[row][column] = true;
+            }
+            self.rendered_cursor = cursor;
+        }
+
+        for row in 0..ROWS {
+            for column in 0..COLUMNS {
+                if !self.dirty[row][column] {
+                    continue;
+                }
+                self.dirty[row][column] = false;
+                let mut cell = if row < self.view_offset {
+                    let index = self.scrollback.len() - self.view_offset + row;
+                    self.scrollback_line(index)[column]
+                } else {
+                    self.cells[row - self.view_offset][column]
+                };
+                if cursor == Some((row, column)) {
+                    cell.attributes.toggle(Attributes::REVERSE);
+                }
+                draw_cell(framebuffer, &cell, row, column, indexed);
+            }
+        }
+    }
+}
+
+impl fmt::Write for Terminal {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.process(c);
+        }
+        Ok(())
+    }
+}
+
+fn draw_cell<F: Framebuffer>(fb: &mut F, cell: &Cell, row: usize, column: usize, indexed: bool) {
+    let mut foreground = cell.foreground;
+    let mut background = cell.background;
+    if cell.attributes.contains(Attributes::BOLD) {
+        if let CellColor::Indexed(index @ 0..=7) = foreground {
+            foreground = CellColor::Indexed(index + 8);
+        }
+    }
+    if cell.attributes.contains(Attributes::REVERSE) {
+        let new_foreground = match background {
+            CellColor::Default => CellColor::Indexed(0),
+            color => color,
+        };
+        background = match foreground {
+            CellColor::Default => CellColor::Indexed(15),
+            color => color,
+        };
+        foreground = new_foreground;
+    }
+    let foreground = match foreground {
+        CellColor::Default => cell_color(CellColor::Indexed(15), indexed),
+        color => cell_color(color, indexed),
+    };
+    let background = match background {
+        CellColor::Default => Color::rgba(0, 0, 0, 0),
+        color => cell_color(color, indexed),
+    };
+
+    let glyph = font::glyph_or_replacement(&Font8x8, cell.c);
+    let underline = cell.attributes.contains(Attributes::UNDERLINE);
+    for y in 0..CELL_SIZE {
+        for x in 0..CELL_SIZE {
+            // all glyphs of the 8x8 font fill the whole cell
+            let covered = glyph.map_or(false, |glyph| glyph.coverage(x as u16, y as u16) != 0);
+            let color = if covered || (underline && y == CELL_SIZE - 1) {
+                foreground
+            } else {
+                background
+            };
+            fb.set_pixel(column * CELL_SIZE + x, row * CELL_SIZE + y, color);
+        }
+    }
+}
+
+// Converts a cell color to the pixel color. For luminance formats, the color is replaced by the
+// index of the palette color, which the framebuffer writes as luminance.
+fn cell_color(color: CellColor, indexed: bool) -> Color {
+    match (color, indexed) {
+        (CellColor::Indexed(index), true) => Color::rgb(index, index, index),
+        (CellColor::Rgb(red, green, blue), true) => {
+            let index = nearest_palette_index(red, green, blue);
+            Color::rgb(index, index, index)
+        }
+        (CellColor::Indexed(index), false) => palette(index),
+        (CellColor::Rgb(red, green, blue), false) => Color::rgb(red, green, blue),
+        (CellColor::Default, _) => unreachable!(),
+    }
+}
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Returns the color of the 256 color palette of xterm.
+///
+/// The first 16 colors are the standard and the bright colors, followed by a 6x6x6 color cube
+/// and 24 shades of gray.
+pub fn palette(index: u8) -> Color {
+    const STANDARD: [u32; 16] = [
+        0x00_0000, 0xcd_0000, 0x00_cd00, 0xcd_cd00, 0x00_00ee, 0xcd_00cd, 0x00_cdcd, 0xe5_e5e5,
+        0x7f_7f7f, 0xff_0000, 0x00_ff00, 0xff_ff00, 0x5c_5cff, 0xff_00ff, 0x00_ffff, 0xff_ffff,
+    ];
+    match index {
+        0..=15 => Color::from_rgb888(STANDARD[usize::from(index)]),
+        16..=231 => {
+            let i = index - 16;
+            let level = |value: u8| CUBE_LEVELS[usize::from(value)];
+            Color::rgb(level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            Color::rgb(gray, gray, gray)
+        }
+    }
+}
+
+// Returns the index of the color cube or gray ramp color that is closest to the color.
+fn nearest_palette_index(red: u8, green: u8, blue: u8) -> u8 {
+    let cube_index = |value: u8| match value {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (value - 35) / 40,
+    };
+    let (r, g, b) = (cube_index(red), cube_index(green), cube_index(blue));
+    let cube = 16 + 36 * r + 6 * g + b;
+
+    let average = (u16::from(red) + u16::from(green) + u16::from(blue)) / 3;
+    let gray = (average.saturating_sub(3) / 10).min(23) as u8;
+
+    let distance = |color: Color| {
+        let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+        d(color.red, red) + d(color.green, green) + d(color.blue, blue)
+    };
+    if distance(palette(232 + gray)) < distance(palette(cube)) {
+        232 + gray
+    } else {
+        cube
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{PixelBuffer, PixelFormat};
+    use super::*;
+    use alloc::string::String;
+    use core::fmt::Write;
+
+    fn terminal(output: &str) -> Terminal {
+        let mut terminal = Terminal::new(0);
+        terminal.write_str(output).unwrap();
+        terminal
+    }
+
+    // Returns the characters of a row without the trailing spaces.
+    fn line(terminal: &Terminal, row: usize) -> String {
+        let line: String = (0..COLUMNS)
+            .map(|column| terminal.cell(column, row).c)
+            .collect();
+        line.trim_end().into()
+    }
+
+    // A pixel buffer that covers the whole display.
+    struct Screen {
+        buffer: PixelBuffer,
+        _memory: Vec<u32>,
+    }
+
+    impl Screen {
+        fn new(format: PixelFormat) -> Screen {
+            let mut memory = vec![0; WIDTH * HEIGHT];
+            let addr = memory.as_mut_ptr() as usize;
+            let buffer = unsafe { PixelBuffer::from_raw_parts(addr, WIDTH, HEIGHT, format) };
+            Screen {
+                buffer,
+                _memory: memory,
+            }
+        }
+
+        fn rendered(terminal: &mut Terminal, format: PixelFormat) -> Screen {
+            let mut screen = Screen::new(format);
+            terminal.render(&mut screen.buffer);
+            screen
+        }
+
+        fn row(&self, row: usize) -> Vec<Color> {
+            let ys = row * CELL_SIZE..(row + 1) * CELL_SIZE;
+            ys.flat_map(|y| (0..WIDTH).map(move |x| (x, y)))
+                .map(|(x, y)| self.buffer.get_pixel(x, y))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn cursor_movement() {
+        let mut terminal = terminal("\x1b[5;10H");
+        assert_eq!(terminal.cursor(), (9, 4));
+        for &(sequence, cursor) in &[
+            ("\x1b[2A", (9, 2)),
+            ("\x1b[3C", (12, 2)),
+            ("\x1b[20D", (0, 2)),
+            ("\x1b[B", (0, 3)),
+            ("\x1b[7G", (6, 3)),
+            ("\x1b[2d", (6, 1)),
+            ("\x1b[s\x1b[H\x1b[u", (6, 1)),
+            ("\x1b7\x1b[9;9H\x1b8", (6, 1)),
+            ("\x1b[2E", (0, 3)),
+            ("ab\x1b[F", (0, 2)),
+            ("ab\x08", (1, 2)),
+            ("\t", (8, 2)),
+            ("\r", (0, 2)),
+            ("\x1b[999;999f", (COLUMNS - 1, ROWS - 1)),
+            ("\x1b[0;0H", (0, 0)),
+            ("\x1b[99A\x1b[99D", (0, 0)),
+        ] {
+            terminal.write_str(sequence).unwrap();
+            assert_eq!(terminal.cursor(), cursor, "{:?}", sequence);
+        }
+    }
+
+    #[test]
+    fn line_wrap() {
+        let mut text = String::new();
+        (0..=COLUMNS).for_each(|i| text.push(char::from(b'a' + (i % 26) as u8)));
+        let mut terminal = terminal(&text[..COLUMNS]);
+        // the cursor stays in the last column until the next character is written
+        assert_eq!(terminal.cursor(), (COLUMNS - 1, 0));
+        terminal.write_str(&text[COLUMNS..]).unwrap();
+        assert_eq!(line(&terminal, 0), &text[..COLUMNS]);
+        assert_eq!(line(&terminal, 1), &text[COLUMNS..]);
+        assert_eq!(terminal.cursor(), (1, 1));
+    }
+
+    #[test]
+    fn erase() {
+        let mut terminal = terminal("abcdef\nghijkl\nmnopqr");
+        terminal.write_str("\x1b[2;3H\x1b[1K").unwrap();
+        assert_eq!(line(&terminal, 1), "   jkl");
+        terminal.write_str("\x1b[5G\x1b[K").unwrap();
+        assert_eq!(line(&terminal, 1), "   j");
+        terminal.write_str("\x1b[3;2H\x1b[J").unwrap();
+        assert_eq!(
+            [line(&terminal, 0), line(&terminal, 1), line(&terminal, 2)],
+            ["abcdef", "   j", "m"]
+        );
+        terminal.write_str("\x1b[1;2H\x1b[2X").unwrap();
+        assert_eq!(line(&terminal, 0), "a  def");
+        terminal.write_str("\x1b[2P").unwrap();
+        assert_eq!(line(&terminal, 0), "adef");
+        terminal.write_str("\x1b[3@").unwrap();
+        assert_eq!(line(&terminal, 0), "a   def");
+        terminal.write_str("\x1b[2;1H\x1b[1J").unwrap();
+        assert_eq!([line(&terminal, 0), line(&terminal, 1)], ["", "   j"]);
+        terminal.write_str("\x1b[2J").unwrap();
+        assert!((0..ROWS).all(|row| line(&terminal, row).is_empty()));
+        // erasing doesn't move the cursor
+        assert_eq!(terminal.cursor(), (0, 1));
+    }
+
+    #[test]
+    fn erase_with_background_color() {
+        let terminal = terminal("abc\r\x1b[1;44m\x1b[K");
+        let expected = Cell {
+            background: CellColor::Indexed(4),
+            ..Cell::BLANK
+        };
+        assert!((0..COLUMNS).all(|column| terminal.cell(column, 0) == expected));
+    }
+
+    #[test]
+    fn graphic_rendition() {
+        let terminal = terminal(concat!(
+            "\x1b[1;4;31;42mA",
+            "\x1b[22;24;39;49mB",
+            "\x1b[38;5;200;48;2;1;2;300mC",
+            "\x1b[0;7mD",
+            "\x1b[mE",
+            "\x1b[94;103mF",
+            "\x1b[38;5mG",
+        ));
+        let cell = |column| terminal.cell(column, 0);
+        assert_eq!(
+            cell(0),
+            Cell {
+                c: 'A',
+                foreground: CellColor::Indexed(1),
+                background: CellColor::Indexed(2),
+                attributes: Attributes::BOLD | Attributes::UNDERLINE,
+            }
+        );
+        assert_eq!(
+            cell(1),
+            Cell {
+                c: 'B',
+                ..Cell::BLANK
+            }
+        );
+        assert_eq!(cell(2).foreground, CellColor::Indexed(200));
+        assert_eq!(cell(2).background, CellColor::Rgb(1, 2, 255));
+        assert_eq!(
+            cell(3),
+            Cell {
+                c: 'D',
+                attributes: Attributes::REVERSE,
+                ..Cell::BLANK
+            }
+        );
+        assert_eq!(
+            cell(4),
+            Cell {
+                c: 'E',
+                ..Cell::BLANK
+            }
+        );
+        assert_eq!(cell(5).foreground, CellColor::Indexed(12));
+        assert_eq!(cell(5).background, CellColor::Indexed(11));
+        // incomplete extended colors are ignored
+        assert_eq!(cell(6), Cell { c: 'G', ..cell(5) });
+    }
+
+    #[test]
+    fn scrolling_region() {
+        let mut terminal = Terminal::new(10);
+        terminal.write_str("0\n1\n2\n3\n4").unwrap();
+        let lines = |terminal: &Terminal| -> Vec<String> {
+            (0..5).map(|row| line(terminal, row)).collect()
+        };
+
+        // setting the region moves the cursor home
+        terminal.write_str("\x1b[2;4r").unwrap();
+        assert_eq!(terminal.cursor(), (0, 0));
+        terminal.write_str("\x1b[4;1H\n").unwrap();
+        assert_eq!(lines(&terminal), ["0", "2", "3", "", "4"]);
+        assert_eq!(terminal.cursor(), (0, 3));
+        terminal.write_str("\x1b[2;1H\x1bM").unwrap();
+        assert_eq!(lines(&terminal), ["0", "", "2", "3", "4"]);
+        terminal.write_str("\x1b[3;1H\x1b[M").unwrap();
+        assert_eq!(lines(&terminal), ["0", "", "3", "", "4"]);
+        terminal.write_str("\x1b[L").unwrap();
+        assert_eq!(lines(&terminal), ["0", "", "", "3", "4"]);
+        terminal.write_str("\x1b[2T").unwrap();
+        assert_eq!(lines(&terminal), ["0", "", "", "", "4"]);
+        // the cursor can't leave the region with relative movements
+        terminal.write_str("\x1b[3;1H\x1b[9A").unwrap();
+        assert_eq!(terminal.cursor(), (0, 1));
+        // lines that leave a region below the top of the display are not kept
+        terminal.write_str("x\x1b[9S").unwrap();
+        assert_eq!(lines(&terminal), ["0", "", "", "", "4"]);
+        assert_eq!(terminal.scrollback_len(), 0);
+
+        terminal.write_str("\x1b[r\x1b[S").unwrap();
+        assert_eq!(lines(&terminal), ["", "", "", "4", ""]);
+        assert_eq!(terminal.scrollback_len(), 1);
+    }
+
+    #[test]
+    fn split_utf8_sequences() {
+        let mut terminal = Terminal::new(0);
+        terminal.write_bytes(&[b'a', 0xc3]);
+        terminal.write_bytes(&[0xbc]);
+        terminal.write_bytes(&[0xe2]);
+        terminal.write_bytes(&[0x82]);
+        terminal.write_bytes(&[0xac, 0xf0, 0x9f, 0x98]);
+        terminal.write_bytes(&[0x80]);
+        assert_eq!(line(&terminal, 0), "aü€\u{1f600}");
+        // invalid bytes and incomplete sequences are replaced
+        terminal.write_bytes(&[0xff, 0xc3, b'x', 0xed, 0xa0, 0x80]);
+        assert_eq!(
+            line(&terminal, 0),
+            "aü€\u{1f600}\u{fffd}\u{fffd}x\u{fffd}"
+        );
+    }
+
+    #[test]
+    fn scrollback() {
+        let mut terminal = Terminal::new(3);
+        for i in 0..ROWS + 5 {
+            writeln!(terminal, "line {}", i).unwrap();
+        }
+        // 6 lines left the display, the oldest of them were discarded
+        assert_eq!(terminal.scrollback_len(), 3);
+        assert_eq!(line(&terminal, 0), "line 6");
+        assert_eq!(terminal.cursor(), (0, ROWS - 1));
+        let scrollback: Vec<String> = (0..3)
+            .map(|i| {
+                terminal
+                    .scrollback_line(i)
+                    .iter()
+                    .map(|cell| cell.c)
+                    .collect()
+            })
+            .map(|line: String| line.trim_end().into())
+            .collect();
+        assert_eq!(scrollback, ["line 3", "line 4", "line 5"]);
+
+        terminal.scroll_view(-1);
+        assert_eq!(terminal.view_offset(), 0);
+        terminal.scroll_view(10);
+        assert_eq!(terminal.view_offset(), 3);
+        terminal.scroll_view(-1);
+        assert_eq!(terminal.view_offset(), 2);
+
+        // the view shows the scrollback lines above the display
+        let mut expected = self::terminal("line 4\nline 5\nline 6");
+        let expected = Screen::rendered(&mut expected, PixelFormat::Argb8888);
+        let screen = Screen::rendered(&mut terminal, PixelFormat::Argb8888);
+        for row in 0..3 {
+            assert!(screen.row(row) == expected.row(row), "row {}", row);
+        }
+
+        // new output returns to the bottom
+        terminal.write_str("x").unwrap();
+        assert_eq!(terminal.view_offset(), 0);
+        terminal.write_str("\x1b[3J").unwrap();
+        assert_eq!(terminal.scrollback_len(), 0);
+        terminal.scroll_view(1);
+        assert_eq!(terminal.view_offset(), 0);
+    }
+
+    #[test]
+    fn render_palette_indices() {
+        let mut terminal = terminal("\x1b[31;48;2;0;0;250mA");
+        let screen = Screen::rendered(&mut terminal, PixelFormat::Al88);
+        let colors = screen.row(0);
+        // the luminance is the palette index
+        assert!(colors.contains(&Color::rgb(1, 1, 1)));
+        assert!(colors.contains(&Color::rgb(21, 21, 21)));
+        assert_eq!(palette(21), Color::rgb(0, 0, 255));
+
+        let screen = Screen::rendered(&mut self::terminal("\x1b[31mA"), PixelFormat::Argb8888);
+        assert!(screen.row(0).contains(&palette(1)));
+        // the default background is transparent
+        assert_eq!(screen.buffer.get_pixel(WIDTH - 1, 0).alpha, 0);
+    }
+
+    #[test]
+    fn nearest_palette_colors() {
+        for index in 16..=255 {
+            let color = palette(index);
+            assert_eq!(
+                nearest_palette_index(color.red, color.green, color.blue),
+                index
+            );
+        }
+        assert_eq!(nearest_palette_index(250, 5, 5), 196);
+        assert_eq!(nearest_palette_index(120, 121, 119), 243);
+    }
+}
@@ -0,0 +1,338 @@
+//! Layout and anti-aliased rendering of text.
+//!
+//! The text is drawn with any [`Font`](super::font::Font). Characters that are missing in the
+//! font are replaced, see [`font::glyph_or_replacement`](super::font::glyph_or_replacement).
+//!
+//! The coverage of the glyph pixels is multiplied into the alpha channel of the text color. Like
+//! the anti-aliased lines of the [`graphics`](super::graphics) module, the text is blended with
+//! the pixels of framebuffers that have a [`pixel_buffer`](super::Framebuffer::pixel_buffer),
+//! and with the layers below otherwise.
+//!
+//! # Examples
+//! ```rust
+//! use lcd::{font, text};
+//!
+//! let font = font::Fallback(&font::fonts::DEJAVUSANS_16, font::Font8x8);
+//! let bounds = lcd::Rect::new(10, 10, 200, 100);
+//! text::draw_text_box(
+//!     &mut layer_1,
+//!     &font,
+//!     "Grüße! This text is wrapped at the edge of the box.",
+//!     bounds,
+//!     text::Alignment::Center,
+//!     Color::rgb(255, 255, 255),
+//! );
+//! ```
+
+use super::font::{self, Font, Glyph};
+use super::graphics::{self, Point};
+use super::{Color, Framebuffer, PixelBuffer, Rect, HEIGHT, WIDTH};
+use alloc::vec::Vec;
+
+/// The horizontal alignment of the lines in a box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    /// Lines start at the left edge.
+    Left,
+    /// Lines are centered.
+    Center,
+    /// Lines end at the right edge.
+    Right,
+}
+
+/// Returns the width of the text in pixels, if it is drawn on a single line.
+pub fn text_width<F: Font>(font: &F, text: &str) -> i32 {
+    let mut width = 0;
+    let mut previous = None;
+    for c in text.chars() {
+        if let Some(glyph) = font::glyph_or_replacement(font, c) {
+            if let Some(previous) = previous {
+                width += font.kerning(previous, c);
+            }
+            width += i32::from(glyph.advance);
+        }
+        previous = Some(c);
+    }
+    width
+}
+
+/// Splits the text into lines that are at most `max_width` pixels wide.
+///
+/// Lines are broken at newlines and at spaces. Words that are wider than `max_width` are broken
+/// between characters. The spaces at line breaks are removed.
+pub fn wrap_lines<'t, F: Font>(font: &F, text: &'t str, max_width: i32) -> Vec<&'t str> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let paragraph = paragraph.trim_end_matches('\r');
+        let mut line_start = 0;
+        let mut line_end = 0;
+        for (word_start, word) in words(paragraph) {
+            let word_end = word_start + word.len();
+            if line_end == line_start {
+                line_start = word_start;
+            } else if text_width(font, &paragraph[line_start..word_end]) > max_width {
+                lines.push(&paragraph[line_start..line_end]);
+                line_start = word_start;
+            }
+            // break words that don't fit into a line on their own
+            while text_width(font, &paragraph[line_start..word_end]) > max_width {
+                let split = split_index(font, &paragraph[line_start..word_end], max_width);
+                if line_start + split == word_end {
+                    break; // a single character that is wider than the line
+                }
+                lines.push(&paragraph[line_start..line_start + split]);
+                line_start += split;
+            }
+            line_end = word_end;
+        }
+        lines.push(&paragraph[line_start..line_end]);
+    }
+    lines
+}
+
+// Returns the words of the text and their byte offsets.
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split(' ')
+        .scan(0, |offset, word| {
+            let start = *offset;
+            *offset += word.len() + 1;
+            Some((start, word))
+        })
+        .filter(|(_, word)| !word.is_empty())
+}
+
+// Returns the byte index of the first character that doesn't fit into `max_width`, but at
+// least the length of the first character.
+fn split_index<F: Font>(font: &F, text: &str, max_width: i32) -> usize {
+    let mut indices = text.char_indices().skip(1);
+    let first = indices.next().map(|(i, _)| i).unwrap_or_else(|| text.len());
+    indices
+        .map(|(i, _)| i)
+        .take_while(|&i| text_width(font, &text[..i]) <= max_width)
+        .last()
+        .unwrap_or(first)
+}
+
+/// Draws a line of text. `position` is the top left corner of the line.
+///
+/// Returns the horizontal position after the text.
+pub fn draw_text<T, F>(fb: &mut T, font: &F, text: &str, position: Point, color: Color) -> i32
+where
+    T: Framebuffer,
+    F: Font,
+{
+    let clip = Rect::new(0, 0, WIDTH, HEIGHT);
+    draw_line(fb, font, text, position, color, clip)
+}
+
+/// Draws text into a box, wrapped at its edges and aligned horizontally.
+///
+/// The text is clipped to the box. Returns the height of the wrapped text in pixels, which is
+/// larger than the height of the box if the text doesn't fit.
+pub fn draw_text_box<T, F>(
+    fb: &mut T,
+    font: &F,
+    text: &str,
+    bounds: Rect,
+    alignment: Alignment,
+    color: Color,
+) -> i32
+where
+    T: Framebuffer,
+    F: Font,
+{
+    let clip = bounds.intersection(Rect::new(0, 0, WIDTH, HEIGHT));
+    let lines = wrap_lines(font, text, bounds.width as i32);
+    for (i, line) in lines.iter().enumerate() {
+        let y = bounds.y as i32 + i as i32 * font.line_height();
+        if y >= (bounds.y + bounds.height) as i32 {
+            break;
+        }
+        let free_space = bounds.width as i32 - text_width(font, line);
+        let x = bounds.x as i32
+            + match alignment {
+                Alignment::Left => 0,
+                Alignment::Center => free_space / 2,
+                Alignment::Right => free_space,
+            };
+        draw_line(fb, font, line, Point::new(x, y), color, clip);
+    }
+    lines.len() as i32 * font.line_height()
+}
+
+fn draw_line<T, F>(
+    fb: &mut T,
+    font: &F,
+    text: &str,
+    position: Point,
+    color: Color,
+    clip: Rect,
+) -> i32
+where
+    T: Framebuffer,
+    F: Font,
+{
+    let baseline = position.y + font.ascent();
+    let buffer = fb.pixel_buffer();
+    let mut x = position.x;
+    let mut previous = None;
+    for c in text.chars() {
+        if let Some(glyph) = font::glyph_or_replacement(font, c) {
+            if let Some(previous) = previous {
+                x += font.kerning(previous, c);
+            }
+            let origin = Point::new(x, baseline);
+            draw_glyph(fb, buffer, &glyph, origin, color, clip);
+            x += i32::from(glyph.advance);
+        }
+        previous = Some(c);
+    }
+    x
+}
+
+// Draws the glyph with the pen at `origin`, which is on the baseline, and blends it with the
+// pixels of `buffer`.
+fn draw_glyph<T>(
+    fb: &mut T,
+    buffer: Option<PixelBuffer>,
+    glyph: &Glyph,
+    origin: Point,
+    color: Color,
+    clip: Rect,
+) where
+    T: Framebuffer,
+{
+    let left = origin.x + i32::from(glyph.x_offset);
+    let top = origin.y + i32::from(glyph.y_offset);
+    for y in 0..glyph.height {
+        for x in 0..glyph.width {
+            let coverage = glyph.coverage(x, y);
+            let (px, py) = (left + i32::from(x), top + i32::from(y));
+            if coverage == 0 || !contains(clip, px, py) {
+                continue;
+            }
+            let alpha = (u32::from(color.alpha) * u32::from(coverage) / 255) as u8;
+            graphics::blend(fb, buffer, px, py, Color { alpha, ..color });
+        }
+    }
+}
+
+fn contains(rect: Rect, x: i32, y: i32) -> bool {
+    x >= rect.x as i32
+        && y >= rect.y as i32
+        && x < (rect.x + rect.width) as i32
+        && y < (rect.y + rect.height) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::font::Bitmap;
+    use super::super::PixelFormat;
+    use super::*;
+
+    static COVERED: [u8; 16] = [255; 16];
+
+    // A monospace font with 5 pixels wide ASCII letters, digits, spaces and question marks, and
+    // a kerning pair.
+    struct TestFont;
+
+    impl Font for TestFont {
+        fn ascent(&self) -> i32 {
+            8
+        }
+
+        fn line_height(&self) -> i32 {
+            10
+        }
+
+        fn glyph(&self, c: char) -> Option<Glyph> {
+            if !c.is_ascii_alphanumeric() && c != ' ' && c != '?' {
+                return None;
+            }
+            Some(Glyph {
+                width: 4,
+                height: 4,
+                x_offset: 0,
+                y_offset: -4,
+                advance: 5,
+                bitmap: Bitmap::Alpha(&COVERED),
+            })
+        }
+
+        fn kerning(&self, left: char, right: char) -> i32 {
+            if (left, right) == ('A', 'V') {
+                -2
+            } else {
+                0
+            }
+        }
+    }
+
+    #[test]
+    fn width() {
+        assert_eq!(text_width(&TestFont, ""), 0);
+        assert_eq!(text_width(&TestFont, "ab c"), 20);
+        assert_eq!(text_width(&TestFont, "AV"), 8);
+        assert_eq!(text_width(&TestFont, "VA"), 10);
+        // missing characters are replaced by `?`
+        assert_eq!(text_width(&TestFont, "ä\u{1f600}"), 10);
+    }
+
+    #[test]
+    fn split_between_characters() {
+        assert_eq!(split_index(&TestFont, "abcd", 12), 2);
+        assert_eq!(split_index(&TestFont, "abcd", 15), 3);
+        // at least one character, even if it doesn't fit
+        assert_eq!(split_index(&TestFont, "abcd", 3), 1);
+        assert_eq!(split_index(&TestFont, "", 3), 0);
+        // indices are on character boundaries
+        assert_eq!(split_index(&TestFont, "äöü", 10), 4);
+        assert_eq!(split_index(&TestFont, "äöü", 0), 2);
+    }
+
+    #[test]
+    fn wrap_at_spaces() {
+        assert_eq!(wrap_lines(&TestFont, "ab cd", 10), ["ab", "cd"]);
+        assert_eq!(wrap_lines(&TestFont, "ab cd", 25), ["ab cd"]);
+        assert_eq!(
+            wrap_lines(&TestFont, "one two three", 20),
+            ["one", "two", "thre", "e"]
+        );
+        // spaces inside of a line are kept
+        assert_eq!(wrap_lines(&TestFont, "a  b   c", 20), ["a  b", "c"]);
+        assert_eq!(wrap_lines(&TestFont, "", 20), [""]);
+    }
+
+    #[test]
+    fn wrap_at_newlines() {
+        assert_eq!(wrap_lines(&TestFont, "a b\r\nc", 100), ["a b", "c"]);
+        assert_eq!(wrap_lines(&TestFont, "a\n\nb\n", 100), ["a", "", "b", ""]);
+    }
+
+    #[test]
+    fn wrap_long_words() {
+        assert_eq!(
+            wrap_lines(&TestFont, "abcdefg hi", 15),
+            ["abc", "def", "g", "hi"]
+        );
+        // characters that are wider than the line get a line of their own
+        assert_eq!(wrap_lines(&TestFont, "abc", 3), ["a", "b", "c"]);
+        assert_eq!(wrap_lines(&TestFont, "äöü", 10), ["äö", "ü"]);
+    }
+
+    #[test]
+    fn glyphs_are_blended() {
+        let mut memory = vec![0xff00_00ffu32; WIDTH * HEIGHT];
+        let addr = memory.as_mut_ptr() as usize;
+        let mut buffer =
+            unsafe { PixelBuffer::from_raw_parts(addr, WIDTH, HEIGHT, PixelFormat::Argb8888) };
+        let color = Color::rgba(255, 0, 0, 128);
+        let end = draw_text(&mut buffer, &TestFont, "a", Point::new(0, 0), color);
+        assert_eq!(end, 5);
+        // the glyph covers the rows 4 to 7 below the top of the line
+        assert_eq!(buffer.get_pixel(0, 4), Color::rgba(128, 0, 127, 255));
+        assert_eq!(buffer.get_pixel(3, 7), Color::rgba(128, 0, 127, 255));
+        assert_eq!(buffer.get_pixel(0, 3), Color::rgb(0, 0, 255));
+        assert_eq!(buffer.get_pixel(4, 4), Color::rgb(0, 0, 255));
+    }
+}
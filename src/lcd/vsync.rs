@@ -0,0 +1,67 @@
+//! Waiting for the vertical blanking period without blocking the executor.
+//!
+//! The future is woken through a stream of LTDC interrupt events. The interrupt handler must
+//! call [`lcd::mask_line_interrupt`](super::mask_line_interrupt) and push a `()` to the stream.
+
+use core::pin::Pin;
+use futures::{
+    prelude::*,
+    task::{Context, Poll},
+};
+use stm32f7::stm32f7x6::LTDC;
+
+/// The future returned by `Lcd::wait_for_vsync`.
+#[must_use = "futures do nothing unless polled"]
+pub struct WaitForVsync<'l, S> {
+    controller: &'l mut LTDC,
+    interrupts: S,
+    waiting: bool,
+}
+
+impl<'l, S> WaitForVsync<'l, S> {
+    pub(super) fn new(controller: &'l mut LTDC, interrupts: S) -> Self {
+        WaitForVsync {
+            controller,
+            interrupts,
+            waiting: false,
+        }
+    }
+}
+
+impl<'l, S> Future for WaitForVsync<'l, S>
+where
+    S: Stream<Item = ()> + Unpin,
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let this = self.get_mut();
+        loop {
+            match Pin::new(&mut this.interrupts).poll_next(cx) {
+                // Interrupts that arrive before the line interrupt was enabled by this future
+                // belong to an earlier frame.
+                Poll::Ready(Some(())) if !this.waiting => {}
+                Poll::Ready(Some(())) => {
+                    // A buffer swap is only done when the reload was executed.
+                    if this.controller.srcr.read().vbr().bit_is_clear() {
+                        return Poll::Ready(());
+                    }
+                    this.waiting = false;
+                }
+                Poll::Ready(None) => panic!("ltdc interrupt stream closed"),
+                Poll::Pending if this.waiting => return Poll::Pending,
+                Poll::Pending => {
+                    this.waiting = true;
+                    this.controller.icr.write(|w| w.clif().set_bit());
+                    this.controller.ier.modify(|_, w| w.lie().set_bit());
+                }
+            }
+        }
+    }
+}
+
+impl<'l, S> Drop for WaitForVsync<'l, S> {
+    fn drop(&mut self) {
+        self.controller.ier.modify(|_, w| w.lie().clear_bit());
+    }
+}
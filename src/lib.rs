@@ -23,9 +23,12 @@
 #[macro_use]
 extern crate alloc;
 extern crate cortex_m_rt as rt;
+#[cfg(test)]
+extern crate std;
 
 #[macro_use]
 pub mod lcd;
+pub mod audio;
 pub mod ethernet;
 pub mod future_mutex;
 pub mod gpio;
@@ -38,3 +41,5 @@ pub mod sd;
 pub mod system_clock;
 pub mod task_runtime;
 pub mod touch;
+pub mod ui;
+pub mod wm8994;
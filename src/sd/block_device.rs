@@ -0,0 +1,179 @@
+//! An abstraction over storage devices that are accessed in blocks of 512 bytes.
+
+use super::error::{Error, RWErrorType};
+use super::{Sd, MAX_BLOCKS_PER_TRANSFER};
+use crate::gpio::InputPin;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, LittleEndian};
+
+/// The size of a block in bytes.
+pub const BLOCK_SIZE: usize = 512;
+
+/// A storage device that is read and written in blocks of [`BLOCK_SIZE`](BLOCK_SIZE) bytes.
+pub trait BlockDevice {
+    /// The error type of failed block operations.
+    type Error;
+
+    /// Reads `blocks.len()` consecutive blocks starting at the logical block address `lba`.
+    fn read(&mut self, lba: u32, blocks: &mut [[u8; BLOCK_SIZE]]) -> Result<(), Self::Error>;
+
+    /// Writes `blocks` to consecutive blocks starting at the logical block address `lba`.
+    fn write(&mut self, lba: u32, blocks: &[[u8; BLOCK_SIZE]]) -> Result<(), Self::Error>;
+
+    /// Returns the number of blocks of the device.
+    fn num_blocks(&self) -> u32;
+
+    /// Returns the size of a block in bytes.
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+}
+
+impl<'a, D: BlockDevice> BlockDevice for &'a mut D {
+    type Error = D::Error;
+
+    fn read(&mut self, lba: u32, blocks: &mut [[u8; BLOCK_SIZE]]) -> Result<(), Self::Error> {
+        (**self).read(lba, blocks)
+    }
+
+    fn write(&mut self, lba: u32, blocks: &[[u8; BLOCK_SIZE]]) -> Result<(), Self::Error> {
+        (**self).write(lba, blocks)
+    }
+
+    fn num_blocks(&self) -> u32 {
+        (**self).num_blocks()
+    }
+
+    fn block_size(&self) -> usize {
+        (**self).block_size()
+    }
+}
+
+impl<'a, P: InputPin> BlockDevice for Sd<'a, P> {
+    type Error = Error;
+
+    fn read(&mut self, lba: u32, blocks: &mut [[u8; BLOCK_SIZE]]) -> Result<(), Error> {
+        let mut lba = lba;
+        for chunk in blocks.chunks_mut(usize::from(MAX_BLOCKS_PER_TRANSFER)) {
+            let data = self.read_blocks(lba, chunk.len() as u16)?;
+            // The SDMMC FIFO stores the first byte of a word in the lowest 8 bits.
+            for (block, words) in chunk.iter_mut().zip(data.chunks(BLOCK_SIZE / 4)) {
+                LittleEndian::write_u32_into(words, &mut block[..]);
+            }
+            lba += chunk.len() as u32;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, lba: u32, blocks: &[[u8; BLOCK_SIZE]]) -> Result<(), Error> {
+        let mut lba = lba;
+        for chunk in blocks.chunks(usize::from(MAX_BLOCKS_PER_TRANSFER)) {
+            let mut data = vec![0; chunk.len() * BLOCK_SIZE / 4];
+            for (block, words) in chunk.iter().zip(data.chunks_mut(BLOCK_SIZE / 4)) {
+                LittleEndian::read_u32_into(&block[..], words);
+            }
+            self.write_blocks(&data, lba, chunk.len() as u16)?;
+            lba += chunk.len() as u32;
+        }
+        Ok(())
+    }
+
+    fn num_blocks(&self) -> u32 {
+        self.card_info.as_ref().map_or(0, |info| info.log_blk_number)
+    }
+}
+
+/// A block device that keeps its blocks in RAM.
+///
+/// Useful for testing code that works with block devices without real hardware.
+#[derive(Clone)]
+pub struct RamBlockDevice {
+    blocks: Vec<[u8; BLOCK_SIZE]>,
+}
+
+impl RamBlockDevice {
+    /// Creates a zeroed device with `num_blocks` blocks.
+    pub fn new(num_blocks: u32) -> Self {
+        RamBlockDevice {
+            blocks: vec![[0; BLOCK_SIZE]; num_blocks as usize],
+        }
+    }
+
+    /// Creates a device from a disk image. The image is padded with zeros to a whole number
+    /// of blocks.
+    pub fn from_image(image: &[u8]) -> Self {
+        let blocks = image
+            .chunks(BLOCK_SIZE)
+            .map(|chunk| {
+                let mut block = [0; BLOCK_SIZE];
+                block[..chunk.len()].copy_from_slice(chunk);
+                block
+            })
+            .collect();
+        RamBlockDevice { blocks }
+    }
+
+    /// Returns the blocks of the device.
+    pub fn blocks(&self) -> &[[u8; BLOCK_SIZE]] {
+        &self.blocks
+    }
+
+    fn range(&self, lba: u32, len: usize) -> Result<(usize, usize), Error> {
+        let start = lba as usize;
+        match start.checked_add(len) {
+            Some(end) if end <= self.blocks.len() => Ok((start, end)),
+            _ => Err(Error::RWError {
+                t: RWErrorType::AddressOutOfRange,
+            }),
+        }
+    }
+}
+
+impl BlockDevice for RamBlockDevice {
+    type Error = Error;
+
+    fn read(&mut self, lba: u32, blocks: &mut [[u8; BLOCK_SIZE]]) -> Result<(), Error> {
+        let (start, end) = self.range(lba, blocks.len())?;
+        blocks.copy_from_slice(&self.blocks[start..end]);
+        Ok(())
+    }
+
+    fn write(&mut self, lba: u32, blocks: &[[u8; BLOCK_SIZE]]) -> Result<(), Error> {
+        let (start, end) = self.range(lba, blocks.len())?;
+        self.blocks[start..end].copy_from_slice(blocks);
+        Ok(())
+    }
+
+    fn num_blocks(&self) -> u32 {
+        self.blocks.len() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ram_block_device() {
+        let image: Vec<u8> = (0..BLOCK_SIZE + 3).map(|i| i as u8).collect();
+        let mut device = RamBlockDevice::from_image(&image);
+        assert_eq!(device.num_blocks(), 2);
+
+        let mut blocks = [[0xFF; BLOCK_SIZE]; 2];
+        device.read(0, &mut blocks).unwrap();
+        assert_eq!(&blocks[0][..], &image[..BLOCK_SIZE]);
+        // the image is padded with zeros
+        assert_eq!(blocks[1][..3], [0, 1, 2]);
+        assert!(blocks[1][3..].iter().all(|&b| b == 0));
+
+        device.write(1, &[[7; BLOCK_SIZE]]).unwrap();
+        assert_eq!(device.blocks()[1][..], [7; BLOCK_SIZE][..]);
+
+        let out_of_range = Err(Error::RWError {
+            t: RWErrorType::AddressOutOfRange,
+        });
+        assert_eq!(device.read(1, &mut blocks), out_of_range);
+        assert_eq!(device.write(2, &[[0; BLOCK_SIZE]]), out_of_range);
+        assert_eq!(device.read(u32::max_value(), &mut blocks), out_of_range);
+    }
+}
@@ -0,0 +1,108 @@
+//! Detection of card insertion and removal.
+
+use crate::gpio::InputPin;
+use core::pin::Pin;
+use futures::{
+    prelude::*,
+    task::{Context, Poll},
+};
+use stm32f7::stm32f7x6::{EXTI, SYSCFG};
+
+/// A change of the card slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardEvent {
+    /// A card was inserted.
+    Inserted,
+    /// The card was removed.
+    Removed,
+}
+
+/// A stream of card insertions and removals.
+///
+/// The present pin is read whenever the wrapped stream yields an item, and an event is returned
+/// if its level changed since the last event. If a card is inserted on creation, the first event
+/// is `Inserted`.
+///
+/// Normally the wrapped stream is fed by the EXTI interrupt of the present pin, see
+/// [`enable_card_detect_interrupt`](enable_card_detect_interrupt).
+///
+/// # Examples
+/// ```rust
+/// async fn sd_card_task<P, S, I>(mut sd: sd::Sd<'static, P>, card_detect_stream: I)
+/// where
+///     P: InputPin,
+///     I: Stream<Item = ()>,
+/// {
+///     let card_events = sd.card_detect(card_detect_stream);
+///     pin_mut!(card_events);
+///     loop {
+///         match await!(card_events.next()).expect("card detect stream closed") {
+///             sd::CardEvent::Inserted => sd::init(&mut sd).expect("Init failed"),
+///             sd::CardEvent::Removed => sd::de_init(&mut sd),
+///         }
+///     }
+/// }
+/// ```
+#[must_use = "streams do nothing unless polled"]
+pub struct CardDetect<'a, P: InputPin + 'a, S> {
+    present_pin: &'a P,
+    interrupts: S,
+    present: bool,
+}
+
+impl<'a, P: InputPin, S> CardDetect<'a, P, S> {
+    pub(super) fn new(present_pin: &'a P, interrupts: S) -> Self {
+        CardDetect {
+            present_pin,
+            interrupts,
+            present: false,
+        }
+    }
+}
+
+impl<'a, P, S> Stream for CardDetect<'a, P, S>
+where
+    P: InputPin,
+    S: Stream<Item = ()> + Unpin,
+{
+    type Item = CardEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<CardEvent>> {
+        let this = self.get_mut();
+        loop {
+            // The pin is pulled low by an inserted card.
+            let present = !this.present_pin.get();
+            if present != this.present {
+                this.present = present;
+                let event = if present {
+                    CardEvent::Inserted
+                } else {
+                    CardEvent::Removed
+                };
+                return Poll::Ready(Some(event));
+            }
+
+            match Pin::new(&mut this.interrupts).poll_next(cx) {
+                Poll::Ready(Some(())) => {}
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Connects the present pin (C-13) to the EXTI13 line and triggers it on both edges.
+///
+/// The EXTI13 line is part of the EXTI15_10 interrupt. Note that an EXTI line can only be connected
+/// to one port, so this can't be used together with the touch interrupt on pin I-13.
+pub fn enable_card_detect_interrupt(syscfg: &mut SYSCFG, exti: &mut EXTI) {
+    // choose pin C-13 for exti13 line
+    syscfg
+        .exticr4
+        .modify(|_, w| unsafe { w.exti13().bits(0b0010) });
+    // trigger exti13 on rising (card removed) and falling (card inserted)
+    exti.rtsr.modify(|_, w| w.tr13().set_bit());
+    exti.ftsr.modify(|_, w| w.tr13().set_bit());
+    // unmask exti13 line
+    exti.imr.modify(|_, w| w.mr13().set_bit());
+}
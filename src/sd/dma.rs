@@ -0,0 +1,157 @@
+//! DMA transfers between the SDMMC FIFO and memory.
+//!
+//! SDMMC1 is connected to channel 4 of the DMA2 streams 3 and 6. Stream 3 is used for
+//! receiving and stream 6 for transmitting data. The SDMMC controller acts as flow controller,
+//! so the transfer ends when the data path state machine signals the last block.
+
+use stm32f7::stm32f7x6::{DMA2, SDMMC1};
+
+const SDMMC1_CHANNEL: u8 = 4;
+
+/// The maximum number of words of a transfer.
+///
+/// The stream counts the transferred words in the 16 bit NDTR register, which is set to 0xFFFF
+/// when the peripheral controls the flow.
+pub const MAX_WORDS: usize = 0xFFFF;
+
+/// The direction of a DMA transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From the SDMMC FIFO to memory (stream 3).
+    Rx,
+    /// From memory to the SDMMC FIFO (stream 6).
+    Tx,
+}
+
+/// Configures and enables the DMA stream for `direction`.
+///
+/// The buffer must stay valid until the transfer is stopped with `stop()`.
+///
+/// # Panics
+///
+/// Panics if `words` is larger than [`MAX_WORDS`](MAX_WORDS).
+pub fn start(dma: &DMA2, sdmmc: &SDMMC1, direction: Direction, buffer: usize, words: usize) {
+    assert!(words <= MAX_WORDS, "too many words for a DMA transfer");
+    let fifo_address = &sdmmc.fifo as *const _ as u32;
+
+    stop(dma, direction);
+    clear_flags(dma, direction);
+
+    match direction {
+        Direction::Rx => {
+            dma.s3par.write(|w| unsafe { w.pa().bits(fifo_address) });
+            dma.s3m0ar.write(|w| unsafe { w.m0a().bits(buffer as u32) });
+            dma.s3ndtr.write(|w| unsafe { w.ndt().bits(words as u16) });
+            dma.s3fcr.write(|w| unsafe {
+                w.dmdis().set_bit(); // use the FIFO instead of direct mode
+                w.fth().bits(0b11); // full FIFO threshold
+                w
+            });
+            dma.s3cr.write(|w| unsafe {
+                w.chsel().bits(SDMMC1_CHANNEL);
+                w.mburst().bits(0b01); // INCR4
+                w.pburst().bits(0b01); // INCR4
+                w.pl().bits(0b11); // very high priority
+                w.msize().bits(0b10); // 32 bit
+                w.psize().bits(0b10); // 32 bit
+                w.minc().set_bit();
+                w.pinc().clear_bit();
+                w.circ().clear_bit();
+                w.dir().bits(0b00); // peripheral to memory
+                w.pfctrl().set_bit(); // the SDMMC controls the flow
+                w
+            });
+            dma.s3cr.modify(|_, w| w.en().set_bit());
+        }
+        Direction::Tx => {
+            dma.s6par.write(|w| unsafe { w.pa().bits(fifo_address) });
+            dma.s6m0ar.write(|w| unsafe { w.m0a().bits(buffer as u32) });
+            dma.s6ndtr.write(|w| unsafe { w.ndt().bits(words as u16) });
+            dma.s6fcr.write(|w| unsafe {
+                w.dmdis().set_bit(); // use the FIFO instead of direct mode
+                w.fth().bits(0b11); // full FIFO threshold
+                w
+            });
+            dma.s6cr.write(|w| unsafe {
+                w.chsel().bits(SDMMC1_CHANNEL);
+                w.mburst().bits(0b01); // INCR4
+                w.pburst().bits(0b01); // INCR4
+                w.pl().bits(0b11); // very high priority
+                w.msize().bits(0b10); // 32 bit
+                w.psize().bits(0b10); // 32 bit
+                w.minc().set_bit();
+                w.pinc().clear_bit();
+                w.circ().clear_bit();
+                w.dir().bits(0b01); // memory to peripheral
+                w.pfctrl().set_bit(); // the SDMMC controls the flow
+                w
+            });
+            dma.s6cr.modify(|_, w| w.en().set_bit());
+        }
+    }
+}
+
+/// Returns true if the stream for `direction` has finished its transfer.
+pub fn is_complete(dma: &DMA2, direction: Direction) -> bool {
+    match direction {
+        Direction::Rx => {
+            dma.lisr.read().tcif3().bit_is_set() || dma.s3cr.read().en().bit_is_clear()
+        }
+        Direction::Tx => {
+            dma.hisr.read().tcif6().bit_is_set() || dma.s6cr.read().en().bit_is_clear()
+        }
+    }
+}
+
+/// Returns true if a transfer or direct mode error occurred on the stream for `direction`.
+///
+/// FIFO errors are ignored, because they are expected at the end of peripheral controlled
+/// transfers.
+pub fn has_error(dma: &DMA2, direction: Direction) -> bool {
+    match direction {
+        Direction::Rx => {
+            let lisr = dma.lisr.read();
+            lisr.teif3().bit_is_set() || lisr.dmeif3().bit_is_set()
+        }
+        Direction::Tx => {
+            let hisr = dma.hisr.read();
+            hisr.teif6().bit_is_set() || hisr.dmeif6().bit_is_set()
+        }
+    }
+}
+
+/// Disables the stream for `direction` and waits until the disable takes effect.
+pub fn stop(dma: &DMA2, direction: Direction) {
+    match direction {
+        Direction::Rx => {
+            dma.s3cr.modify(|_, w| w.en().clear_bit());
+            while dma.s3cr.read().en().bit_is_set() {}
+        }
+        Direction::Tx => {
+            dma.s6cr.modify(|_, w| w.en().clear_bit());
+            while dma.s6cr.read().en().bit_is_set() {}
+        }
+    }
+}
+
+/// Clears all interrupt flags of the stream for `direction`.
+pub fn clear_flags(dma: &DMA2, direction: Direction) {
+    match direction {
+        Direction::Rx => dma.lifcr.write(|w| {
+            w.ctcif3().set_bit();
+            w.chtif3().set_bit();
+            w.cteif3().set_bit();
+            w.cdmeif3().set_bit();
+            w.cfeif3().set_bit();
+            w
+        }),
+        Direction::Tx => dma.hifcr.write(|w| {
+            w.ctcif6().set_bit();
+            w.chtif6().set_bit();
+            w.cteif6().set_bit();
+            w.cdmeif6().set_bit();
+            w.cfeif6().set_bit();
+            w
+        }),
+    }
+}
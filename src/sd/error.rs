@@ -16,6 +16,10 @@ pub enum Error {
     SdmmcError { t: SdmmcErrorType },
     /// Error during reading from/writing to the card
     RWError { t: RWErrorType },
+    /// The card is write protected or locked, or the blocks are in a write protected group
+    WriteProtected,
+    /// The SDMMC1 interrupt handler was unregistered before the transfer finished
+    InterruptsClosed,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -35,6 +39,10 @@ pub enum RWErrorType {
     TxUnderrun,
     /// FIFO overrun
     RxOverrun,
+    /// The data doesn't match the number of blocks to transfer
+    LengthMismatch,
+    /// The DMA stream reported a transfer error
+    DmaError,
 }
 
 bitflags! {
@@ -0,0 +1,186 @@
+//! Parsing of the master boot record and the FAT boot sector.
+
+use super::{Error, FatType};
+use crate::sd::block_device::BLOCK_SIZE;
+use byteorder::{ByteOrder, LittleEndian};
+
+const BOOT_SIGNATURE_OFFSET: usize = 510;
+const PARTITION_TABLE_OFFSET: usize = 446;
+const PARTITION_ENTRY_SIZE: usize = 16;
+
+const FS_INFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FS_INFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+const FS_INFO_TRAIL_SIGNATURE: u32 = 0xAA55_0000;
+const FS_INFO_FREE_COUNT_OFFSET: usize = 488;
+const FS_INFO_NEXT_FREE_OFFSET: usize = 492;
+
+/// The value of the FSInfo fields that are not known.
+pub const FS_INFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+/// The geometry of a mounted FAT volume. All sector numbers are absolute block addresses.
+#[derive(Debug, Clone)]
+pub struct Volume {
+    pub fat_type: FatType,
+    pub sectors_per_cluster: u32,
+    pub fat_start: u32,
+    pub fat_size: u32,
+    pub num_fats: u32,
+    /// Start of the fixed root directory region (FAT16 only).
+    pub root_dir_start: u32,
+    /// Size of the fixed root directory region (FAT16 only).
+    pub root_dir_sectors: u32,
+    /// First cluster of the root directory (FAT32 only).
+    pub root_cluster: u32,
+    /// The FSInfo sector with the free cluster count (FAT32 only).
+    pub fs_info_sector: Option<u32>,
+    pub data_start: u32,
+    pub cluster_count: u32,
+}
+
+impl Volume {
+    /// Returns the size of a cluster in bytes.
+    pub fn cluster_size(&self) -> u32 {
+        self.sectors_per_cluster * BLOCK_SIZE as u32
+    }
+
+    /// Returns the block address of the first sector of `cluster`.
+    pub fn cluster_lba(&self, cluster: u32) -> u32 {
+        self.data_start + (cluster - 2) * self.sectors_per_cluster
+    }
+
+    /// Returns the block address and byte offset of the FAT entry of `cluster` in the first FAT.
+    pub fn fat_entry_position(&self, cluster: u32) -> (u32, usize) {
+        let offset = match self.fat_type {
+            FatType::Fat16 => cluster * 2,
+            FatType::Fat32 => cluster * 4,
+        };
+        (
+            self.fat_start + offset / BLOCK_SIZE as u32,
+            (offset % BLOCK_SIZE as u32) as usize,
+        )
+    }
+
+    /// Returns the highest valid cluster number.
+    pub fn max_cluster(&self) -> u32 {
+        self.cluster_count + 1
+    }
+}
+
+fn has_boot_signature(block: &[u8; BLOCK_SIZE]) -> bool {
+    block[BOOT_SIGNATURE_OFFSET] == 0x55 && block[BOOT_SIGNATURE_OFFSET + 1] == 0xAA
+}
+
+fn is_fat_boot_sector(block: &[u8; BLOCK_SIZE]) -> bool {
+    // A boot sector starts with a jump instruction and contains a sane BPB.
+    (block[0] == 0xEB || block[0] == 0xE9)
+        && LittleEndian::read_u16(&block[11..13]) == BLOCK_SIZE as u16
+        && block[13].is_power_of_two()
+        && block[16] != 0
+}
+
+/// Returns the start address of the first FAT16/FAT32 partition described by the block `mbr`.
+///
+/// If the block is a FAT boot sector itself (a card formatted without partition table), the
+/// returned address is 0.
+pub fn first_partition<E>(mbr: &[u8; BLOCK_SIZE]) -> Result<u32, Error<E>> {
+    if !has_boot_signature(mbr) {
+        return Err(Error::NoFilesystem);
+    }
+    if is_fat_boot_sector(mbr) {
+        return Ok(0);
+    }
+
+    for i in 0..4 {
+        let entry = &mbr[PARTITION_TABLE_OFFSET + i * PARTITION_ENTRY_SIZE..][..PARTITION_ENTRY_SIZE];
+        match entry[4] {
+            // FAT16 (< 32MB), FAT16, FAT16 (LBA), FAT32 (CHS), FAT32 (LBA)
+            0x04 | 0x06 | 0x0E | 0x0B | 0x0C => return Ok(LittleEndian::read_u32(&entry[8..12])),
+            _ => {}
+        }
+    }
+
+    Err(Error::NoFilesystem)
+}
+
+/// Parses the FAT boot sector `block` of a partition that starts at block address `start`.
+pub fn parse<E>(block: &[u8; BLOCK_SIZE], start: u32) -> Result<Volume, Error<E>> {
+    if !has_boot_signature(block) || !is_fat_boot_sector(block) {
+        return Err(Error::NoFilesystem);
+    }
+
+    let sectors_per_cluster = u32::from(block[13]);
+    let reserved_sectors = u32::from(LittleEndian::read_u16(&block[14..16]));
+    let num_fats = u32::from(block[16]);
+    let root_entry_count = u32::from(LittleEndian::read_u16(&block[17..19]));
+    let total_sectors = match LittleEndian::read_u16(&block[19..21]) {
+        0 => LittleEndian::read_u32(&block[32..36]),
+        n => u32::from(n),
+    };
+    let fat_size = match LittleEndian::read_u16(&block[22..24]) {
+        0 => LittleEndian::read_u32(&block[36..40]),
+        n => u32::from(n),
+    };
+
+    let root_dir_sectors = (root_entry_count * 32 + BLOCK_SIZE as u32 - 1) / BLOCK_SIZE as u32;
+    let first_data_sector = reserved_sectors + num_fats * fat_size + root_dir_sectors;
+    if fat_size == 0 || total_sectors <= first_data_sector {
+        return Err(Error::CorruptFilesystem);
+    }
+    let cluster_count = (total_sectors - first_data_sector) / sectors_per_cluster;
+
+    // The FAT type is determined by the cluster count alone, see the FAT specification.
+    let fat_type = if cluster_count < 4085 {
+        return Err(Error::UnsupportedFatType);
+    } else if cluster_count < 65525 {
+        FatType::Fat16
+    } else {
+        FatType::Fat32
+    };
+
+    let root_cluster = match fat_type {
+        FatType::Fat16 => 0,
+        FatType::Fat32 => LittleEndian::read_u32(&block[44..48]),
+    };
+    let fs_info_sector = match (fat_type, LittleEndian::read_u16(&block[48..50])) {
+        // 0 and 0xFFFF mean that there is no FSInfo sector, it must be in the reserved region
+        (FatType::Fat32, sector) if sector != 0 && u32::from(sector) < reserved_sectors => {
+            Some(start + u32::from(sector))
+        }
+        _ => None,
+    };
+
+    Ok(Volume {
+        fat_type,
+        sectors_per_cluster,
+        fat_start: start + reserved_sectors,
+        fat_size,
+        num_fats,
+        root_dir_start: start + reserved_sectors + num_fats * fat_size,
+        root_dir_sectors,
+        root_cluster,
+        fs_info_sector,
+        data_start: start + first_data_sector,
+        cluster_count,
+    })
+}
+
+/// Returns the free cluster count and the next free cluster of the FSInfo sector `block`, or
+/// `None` if the block has no valid signatures. Unknown values are `FS_INFO_UNKNOWN`.
+pub fn parse_fs_info(block: &[u8; BLOCK_SIZE]) -> Option<(u32, u32)> {
+    if LittleEndian::read_u32(&block[0..4]) != FS_INFO_LEAD_SIGNATURE
+        || LittleEndian::read_u32(&block[484..488]) != FS_INFO_STRUCT_SIGNATURE
+        || LittleEndian::read_u32(&block[508..512]) != FS_INFO_TRAIL_SIGNATURE
+    {
+        return None;
+    }
+    Some((
+        LittleEndian::read_u32(&block[FS_INFO_FREE_COUNT_OFFSET..]),
+        LittleEndian::read_u32(&block[FS_INFO_NEXT_FREE_OFFSET..]),
+    ))
+}
+
+/// Stores the free cluster count and the next free cluster in the FSInfo sector `block`.
+pub fn write_fs_info(block: &mut [u8; BLOCK_SIZE], free_count: u32, next_free: u32) {
+    LittleEndian::write_u32(&mut block[FS_INFO_FREE_COUNT_OFFSET..], free_count);
+    LittleEndian::write_u32(&mut block[FS_INFO_NEXT_FREE_OFFSET..], next_free);
+}
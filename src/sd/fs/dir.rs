@@ -0,0 +1,599 @@
+//! Directory entries, long file names and directory iteration.
+
+use super::{Error, FileSystem};
+use crate::sd::block_device::{BlockDevice, BLOCK_SIZE};
+use alloc::prelude::v1::*;
+use bitflags::bitflags;
+use byteorder::{ByteOrder, LittleEndian};
+
+pub(super) const ENTRY_SIZE: usize = 32;
+const ENTRIES_PER_BLOCK: usize = BLOCK_SIZE / ENTRY_SIZE;
+
+const END_OF_DIRECTORY: u8 = 0x00;
+const DELETED: u8 = 0xE5;
+const LAST_LONG_ENTRY: u8 = 0x40;
+const CHARS_PER_LONG_ENTRY: usize = 13;
+const MAX_NAME_LENGTH: usize = 255;
+// Offsets of the UCS-2 name characters in a long name entry.
+const LONG_NAME_OFFSETS: [usize; CHARS_PER_LONG_ENTRY] =
+    [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+// NT reserved byte flags that mark a lowercase base name or extension.
+const LOWERCASE_BASE: u8 = 0x08;
+const LOWERCASE_EXTENSION: u8 = 0x10;
+/// 1980-01-01 in the FAT date format, the earliest representable date.
+pub(super) const DEFAULT_DATE: u16 = (1 << 5) | 1;
+
+bitflags! {
+    /// The attributes of a directory entry.
+    pub struct Attributes: u8 {
+        const READ_ONLY = 0x01;
+        const HIDDEN    = 0x02;
+        const SYSTEM    = 0x04;
+        const VOLUME_ID = 0x08;
+        const DIRECTORY = 0x10;
+        const ARCHIVE   = 0x20;
+
+        // Marks a long file name entry
+        const LONG_NAME = 0x0F;
+    }
+}
+
+/// The position of a 32 byte entry on the block device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct EntryLocation {
+    pub lba: u32,
+    pub offset: usize,
+}
+
+/// Points to an entry slot in a directory.
+///
+/// The FAT16 root directory is not a cluster chain but a fixed region, it is represented by
+/// cluster number 0.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct DirCursor {
+    cluster: u32,
+    sector: u32,
+    index: usize,
+}
+
+impl DirCursor {
+    pub fn new(dir_cluster: u32) -> Self {
+        DirCursor {
+            cluster: dir_cluster,
+            sector: 0,
+            index: 0,
+        }
+    }
+}
+
+/// An entry of a directory, either a file or a subdirectory.
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    name: String,
+    short_name: [u8; 11],
+    attributes: Attributes,
+    pub(super) first_cluster: u32,
+    pub(super) size: u32,
+    pub(super) location: EntryLocation,
+    lfn_locations: Vec<EntryLocation>,
+}
+
+impl DirEntry {
+    /// Returns the name of the entry. This is the long file name if there is one and the
+    /// formatted 8.3 name otherwise.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the 8.3 short name of the entry, e.g. `README~1.TXT`.
+    pub fn short_name(&self) -> String {
+        format_short_name(&self.short_name, 0)
+    }
+
+    /// Returns the attributes of the entry.
+    pub fn attributes(&self) -> Attributes {
+        self.attributes
+    }
+
+    /// Returns true if the entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.attributes.contains(Attributes::DIRECTORY)
+    }
+
+    /// Returns true if the entry is a regular file.
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+
+    /// Returns the size of the file in bytes. Directories always have size 0.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || self.short_name().eq_ignore_ascii_case(name)
+    }
+
+    pub(super) fn is_dot_entry(&self) -> bool {
+        self.short_name[0] == b'.'
+    }
+}
+
+/// An iterator over the entries of a directory.
+///
+/// Created by [`FileSystem::read_dir`](super::FileSystem::read_dir).
+pub struct ReadDir<'a, D: BlockDevice> {
+    fs: &'a mut FileSystem<D>,
+    cursor: DirCursor,
+    long_name: LongNameBuilder,
+    done: bool,
+}
+
+impl<'a, D: BlockDevice> ReadDir<'a, D> {
+    pub(super) fn new(fs: &'a mut FileSystem<D>, dir_cluster: u32) -> Self {
+        ReadDir {
+            fs,
+            cursor: DirCursor::new(dir_cluster),
+            long_name: LongNameBuilder::new(),
+            done: false,
+        }
+    }
+
+    fn next_entry(&mut self) -> Result<Option<DirEntry>, Error<D::Error>> {
+        while !self.done {
+            let location = self.fs.cursor_location(&self.cursor);
+            let raw = self.fs.read_entry(location)?;
+            self.done = !self.fs.advance_cursor(&mut self.cursor, false)?;
+
+            match raw[0] {
+                END_OF_DIRECTORY => {
+                    self.done = true;
+                    return Ok(None);
+                }
+                DELETED => {
+                    self.long_name.reset();
+                    continue;
+                }
+                _ => {}
+            }
+
+            let attributes = Attributes::from_bits_truncate(raw[11]);
+            if attributes & (Attributes::LONG_NAME | Attributes::DIRECTORY | Attributes::ARCHIVE)
+                == Attributes::LONG_NAME
+            {
+                self.long_name.push(&raw, location);
+                continue;
+            }
+            if attributes.contains(Attributes::VOLUME_ID) {
+                self.long_name.reset();
+                continue;
+            }
+
+            let mut short_name = [0; 11];
+            short_name.copy_from_slice(&raw[..11]);
+            if short_name[0] == 0x05 {
+                // 0x05 is used as escape for a name that really starts with 0xE5
+                short_name[0] = DELETED;
+            }
+            let (name, lfn_locations) = match self.long_name.finish(&short_name) {
+                Some(long) => long,
+                None => (format_short_name(&short_name, raw[12]), Vec::new()),
+            };
+
+            return Ok(Some(DirEntry {
+                name,
+                short_name,
+                attributes,
+                first_cluster: u32::from(LittleEndian::read_u16(&raw[20..22])) << 16
+                    | u32::from(LittleEndian::read_u16(&raw[26..28])),
+                size: LittleEndian::read_u32(&raw[28..32]),
+                location,
+                lfn_locations,
+            }));
+        }
+        Ok(None)
+    }
+}
+
+impl<'a, D: BlockDevice> Iterator for ReadDir<'a, D> {
+    type Item = Result<DirEntry, Error<D::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_entry() {
+            Ok(entry) => entry.map(Ok),
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Collects the long name entries that precede a short name entry.
+struct LongNameBuilder {
+    chars: Vec<u16>,
+    next_sequence: u8,
+    checksum: u8,
+    locations: Vec<EntryLocation>,
+}
+
+impl LongNameBuilder {
+    fn new() -> Self {
+        LongNameBuilder {
+            chars: Vec::new(),
+            next_sequence: 0,
+            checksum: 0,
+            locations: Vec::new(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.chars.clear();
+        self.locations.clear();
+        self.next_sequence = 0;
+    }
+
+    fn push(&mut self, raw: &[u8; ENTRY_SIZE], location: EntryLocation) {
+        let sequence = raw[0] & 0x1F;
+        if raw[0] & LAST_LONG_ENTRY != 0 {
+            // The entries are stored in reverse order, so the last part comes first.
+            self.reset();
+            self.chars = vec![0xFFFF; usize::from(sequence) * CHARS_PER_LONG_ENTRY];
+            self.checksum = raw[13];
+        } else if sequence == 0 || sequence != self.next_sequence || raw[13] != self.checksum {
+            self.reset();
+            return;
+        }
+        if sequence == 0 {
+            self.reset();
+            return;
+        }
+
+        let start = usize::from(sequence - 1) * CHARS_PER_LONG_ENTRY;
+        for (i, &offset) in LONG_NAME_OFFSETS.iter().enumerate() {
+            self.chars[start + i] = LittleEndian::read_u16(&raw[offset..offset + 2]);
+        }
+        self.next_sequence = sequence - 1;
+        self.locations.push(location);
+    }
+
+    fn finish(&mut self, short_name: &[u8; 11]) -> Option<(String, Vec<EntryLocation>)> {
+        let complete = !self.chars.is_empty()
+            && self.next_sequence == 0
+            && self.checksum == short_name_checksum(short_name);
+        let result = if complete {
+            let end = self.chars.iter().position(|&c| c == 0).unwrap_or(self.chars.len());
+            let name: String = core::char::decode_utf16(self.chars[..end].iter().cloned())
+                .map(|c| c.unwrap_or(core::char::REPLACEMENT_CHARACTER))
+                .collect();
+            Some((name, self.locations.clone()))
+        } else {
+            None
+        };
+        self.reset();
+        result
+    }
+}
+
+impl<D: BlockDevice> FileSystem<D> {
+    pub(super) fn cursor_location(&self, cursor: &DirCursor) -> EntryLocation {
+        let lba = if cursor.cluster == 0 {
+            self.volume.root_dir_start + cursor.sector
+        } else {
+            self.volume.cluster_lba(cursor.cluster) + cursor.sector
+        };
+        EntryLocation {
+            lba,
+            offset: cursor.index * ENTRY_SIZE,
+        }
+    }
+
+    /// Moves the cursor to the next entry slot.
+    ///
+    /// Returns `false` if the end of the directory is reached. If `extend` is true, a new
+    /// cluster is appended to the directory instead.
+    pub(super) fn advance_cursor(
+        &mut self,
+        cursor: &mut DirCursor,
+        extend: bool,
+    ) -> Result<bool, Error<D::Error>> {
+        cursor.index += 1;
+        if cursor.index < ENTRIES_PER_BLOCK {
+            return Ok(true);
+        }
+        cursor.index = 0;
+        cursor.sector += 1;
+
+        if cursor.cluster == 0 {
+            // the FAT16 root directory has a fixed size
+            return Ok(cursor.sector < self.volume.root_dir_sectors);
+        }
+        if cursor.sector < self.volume.sectors_per_cluster {
+            return Ok(true);
+        }
+        cursor.sector = 0;
+
+        match self.next_cluster(cursor.cluster)? {
+            Some(next) => {
+                cursor.cluster = next;
+                Ok(true)
+            }
+            None if extend => {
+                cursor.cluster = self.allocate_cluster(Some(cursor.cluster), true)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub(super) fn read_entry(
+        &mut self,
+        location: EntryLocation,
+    ) -> Result<[u8; ENTRY_SIZE], Error<D::Error>> {
+        let block = self.block(location.lba)?;
+        let mut raw = [0; ENTRY_SIZE];
+        raw.copy_from_slice(&block[location.offset..location.offset + ENTRY_SIZE]);
+        Ok(raw)
+    }
+
+    pub(super) fn write_entry(
+        &mut self,
+        location: EntryLocation,
+        raw: &[u8; ENTRY_SIZE],
+    ) -> Result<(), Error<D::Error>> {
+        let block = self.block_mut(location.lba)?;
+        block[location.offset..location.offset + ENTRY_SIZE].copy_from_slice(raw);
+        Ok(())
+    }
+
+    /// Searches the directory starting at `dir_cluster` for an entry named `name`.
+    pub(super) fn find_entry(
+        &mut self,
+        dir_cluster: u32,
+        name: &str,
+    ) -> Result<Option<DirEntry>, Error<D::Error>> {
+        for entry in ReadDir::new(self, dir_cluster) {
+            let entry = entry?;
+            if entry.matches(name) {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Creates a new entry named `name` in the directory starting at `dir_cluster`.
+    pub(super) fn insert_entry(
+        &mut self,
+        dir_cluster: u32,
+        name: &str,
+        attributes: Attributes,
+        first_cluster: u32,
+    ) -> Result<DirEntry, Error<D::Error>> {
+        if !is_valid_name(name) {
+            return Err(Error::InvalidName);
+        }
+        if self.find_entry(dir_cluster, name)?.is_some() {
+            return Err(Error::AlreadyExists);
+        }
+
+        let (short_name, long_name) = match exact_short_name(name) {
+            Some(short_name) => (short_name, Vec::new()),
+            None => {
+                let existing = ReadDir::new(self, dir_cluster)
+                    .map(|entry| entry.map(|e| e.short_name))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let short_name = (1..1_000_000)
+                    .map(|n| numbered_short_name(name, n))
+                    .find(|candidate| !existing.contains(candidate))
+                    .ok_or(Error::AlreadyExists)?;
+                (short_name, name.encode_utf16().collect())
+            }
+        };
+
+        let long_entries = (long_name.len() + CHARS_PER_LONG_ENTRY - 1) / CHARS_PER_LONG_ENTRY;
+        let slots = self.find_free_slots(dir_cluster, long_entries + 1)?;
+        let checksum = short_name_checksum(&short_name);
+
+        // long name entries are written in reverse order
+        for (i, &location) in slots[..long_entries].iter().enumerate() {
+            let sequence = (long_entries - i) as u8;
+            let mut raw = [0; ENTRY_SIZE];
+            raw[0] = if i == 0 {
+                sequence | LAST_LONG_ENTRY
+            } else {
+                sequence
+            };
+            raw[11] = Attributes::LONG_NAME.bits();
+            raw[13] = checksum;
+            let start = usize::from(sequence - 1) * CHARS_PER_LONG_ENTRY;
+            for (j, &offset) in LONG_NAME_OFFSETS.iter().enumerate() {
+                // the name is terminated by 0x0000 and padded with 0xFFFF
+                let c = match long_name.get(start + j) {
+                    Some(&c) => c,
+                    None if start + j == long_name.len() => 0x0000,
+                    None => 0xFFFF,
+                };
+                LittleEndian::write_u16(&mut raw[offset..offset + 2], c);
+            }
+            self.write_entry(location, &raw)?;
+        }
+
+        let location = slots[long_entries];
+        let mut raw = [0; ENTRY_SIZE];
+        raw[..11].copy_from_slice(&short_name);
+        raw[11] = attributes.bits();
+        LittleEndian::write_u16(&mut raw[16..18], DEFAULT_DATE); // creation date
+        LittleEndian::write_u16(&mut raw[18..20], DEFAULT_DATE); // last access date
+        LittleEndian::write_u16(&mut raw[20..22], (first_cluster >> 16) as u16);
+        LittleEndian::write_u16(&mut raw[24..26], DEFAULT_DATE); // write date
+        LittleEndian::write_u16(&mut raw[26..28], first_cluster as u16);
+        self.write_entry(location, &raw)?;
+
+        Ok(DirEntry {
+            name: String::from(name),
+            short_name,
+            attributes,
+            first_cluster,
+            size: 0,
+            location,
+            lfn_locations: slots[..long_entries].to_vec(),
+        })
+    }
+
+    /// Marks the entry and its long name entries as deleted.
+    pub(super) fn remove_entry(&mut self, entry: &DirEntry) -> Result<(), Error<D::Error>> {
+        for &location in entry.lfn_locations.iter().chain(Some(&entry.location)) {
+            let block = self.block_mut(location.lba)?;
+            block[location.offset] = DELETED;
+        }
+        Ok(())
+    }
+
+    /// Updates the first cluster and the size stored in the short name entry at `location`.
+    pub(super) fn update_entry(
+        &mut self,
+        location: EntryLocation,
+        first_cluster: u32,
+        size: u32,
+    ) -> Result<(), Error<D::Error>> {
+        let mut raw = self.read_entry(location)?;
+        LittleEndian::write_u16(&mut raw[20..22], (first_cluster >> 16) as u16);
+        LittleEndian::write_u16(&mut raw[26..28], first_cluster as u16);
+        LittleEndian::write_u32(&mut raw[28..32], size);
+        raw[11] |= Attributes::ARCHIVE.bits();
+        self.write_entry(location, &raw)
+    }
+
+    fn find_free_slots(
+        &mut self,
+        dir_cluster: u32,
+        count: usize,
+    ) -> Result<Vec<EntryLocation>, Error<D::Error>> {
+        let mut cursor = DirCursor::new(dir_cluster);
+        let mut run = Vec::with_capacity(count);
+        loop {
+            let location = self.cursor_location(&cursor);
+            let first_byte = self.block(location.lba)?[location.offset];
+            if first_byte == END_OF_DIRECTORY || first_byte == DELETED {
+                run.push(location);
+                if run.len() == count {
+                    return Ok(run);
+                }
+            } else {
+                run.clear();
+            }
+            // only cluster chains can grow, the FAT16 root directory has a fixed size
+            if !self.advance_cursor(&mut cursor, dir_cluster != 0)? {
+                return Err(Error::DirectoryFull);
+            }
+        }
+    }
+}
+
+/// Computes the checksum of a short name that is stored in the associated long name entries.
+pub fn short_name_checksum(short_name: &[u8; 11]) -> u8 {
+    short_name
+        .iter()
+        .fold(0u8, |sum, &b| ((sum & 1) << 7).wrapping_add(sum >> 1).wrapping_add(b))
+}
+
+/// Formats a raw 11 byte short name as `NAME.EXT`.
+pub fn format_short_name(short_name: &[u8; 11], case_flags: u8) -> String {
+    let convert = |bytes: &[u8], lowercase: bool| -> String {
+        let len = bytes.iter().rposition(|&b| b != b' ').map_or(0, |p| p + 1);
+        bytes[..len]
+            .iter()
+            .map(|&b| {
+                let c = if b.is_ascii() { b as char } else { '_' };
+                if lowercase {
+                    c.to_ascii_lowercase()
+                } else {
+                    c
+                }
+            })
+            .collect()
+    };
+
+    let mut name = convert(&short_name[..8], case_flags & LOWERCASE_BASE != 0);
+    let extension = convert(&short_name[8..], case_flags & LOWERCASE_EXTENSION != 0);
+    if !extension.is_empty() {
+        name.push('.');
+        name.push_str(&extension);
+    }
+    name
+}
+
+fn is_valid_short_char(b: u8) -> bool {
+    match b {
+        b'A'..=b'Z' | b'0'..=b'9' => true,
+        b'$' | b'%' | b'\'' | b'-' | b'_' | b'@' | b'~' | b'`' | b'!' => true,
+        b'(' | b')' | b'{' | b'}' | b'^' | b'#' | b'&' => true,
+        _ => false,
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name.encode_utf16().count() <= MAX_NAME_LENGTH
+        && !name.ends_with(' ')
+        && !name
+            .chars()
+            .any(|c| c < ' ' || "\"*/:<>?\\|".contains(c))
+}
+
+/// Returns the short name if `name` is a valid 8.3 name, which needs no long name entries.
+pub fn exact_short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, extension) = match name.rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None => (name, ""),
+    };
+    if base.is_empty()
+        || base.len() > 8
+        || extension.len() > 3
+        || !base
+            .bytes()
+            .chain(extension.bytes())
+            .all(is_valid_short_char)
+    {
+        return None;
+    }
+
+    let mut short_name = [b' '; 11];
+    short_name[..base.len()].copy_from_slice(base.as_bytes());
+    short_name[8..8 + extension.len()].copy_from_slice(extension.as_bytes());
+    Some(short_name)
+}
+
+/// Generates the `n`th short name alias for the long name `name`, e.g. `LONGNA~1.TXT`.
+pub fn numbered_short_name(name: &str, n: u32) -> [u8; 11] {
+    let to_short_chars = |s: &str| -> Vec<u8> {
+        s.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| {
+                let c = c.to_ascii_uppercase();
+                if c.is_ascii() && is_valid_short_char(c as u8) {
+                    c as u8
+                } else {
+                    b'_'
+                }
+            })
+            .collect()
+    };
+
+    let trimmed = name.trim_start_matches('.');
+    let (base, extension) = match trimmed.rfind('.') {
+        Some(dot) => (to_short_chars(&trimmed[..dot]), to_short_chars(&trimmed[dot + 1..])),
+        None => (to_short_chars(trimmed), Vec::new()),
+    };
+
+    let tail = format!("~{}", n);
+    let base_len = base.len().min(8 - tail.len());
+    let mut short_name = [b' '; 11];
+    short_name[..base_len].copy_from_slice(&base[..base_len]);
+    short_name[base_len..base_len + tail.len()].copy_from_slice(tail.as_bytes());
+    let extension_len = extension.len().min(3);
+    short_name[8..8 + extension_len].copy_from_slice(&extension[..extension_len]);
+    short_name
+}
@@ -0,0 +1,185 @@
+//! Reading, writing and seeking in files.
+
+use super::dir::{DirEntry, EntryLocation};
+use super::{Error, FileSystem};
+use crate::sd::block_device::{BlockDevice, BLOCK_SIZE};
+use core::cmp::min;
+
+/// An open file.
+///
+/// The file is accessed through the [`FileSystem`](super::FileSystem) it was opened with, e.g.
+/// with [`FileSystem::read`](super::FileSystem::read).
+#[derive(Debug, Clone)]
+pub struct File {
+    first_cluster: u32,
+    size: u32,
+    position: u32,
+    /// The cluster that contains `position`, cached to avoid walking the chain on every access.
+    /// Zero if not known yet.
+    cluster: u32,
+    /// The index of `cluster` in the cluster chain.
+    cluster_index: u32,
+    entry: EntryLocation,
+}
+
+/// Possible ways to seek within a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    /// Sets the position to the specified number of bytes.
+    Start(u32),
+    /// Sets the position to the size of the file plus the specified number of bytes.
+    End(i32),
+    /// Sets the position to the current position plus the specified number of bytes.
+    Current(i32),
+}
+
+impl File {
+    pub(super) fn new(entry: &DirEntry) -> Self {
+        File {
+            first_cluster: entry.first_cluster,
+            size: entry.size,
+            position: 0,
+            cluster: 0,
+            cluster_index: 0,
+            entry: entry.location,
+        }
+    }
+
+    /// Returns the size of the file in bytes.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns the current read/write position.
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    /// Returns true if the position is at the end of the file.
+    pub fn is_eof(&self) -> bool {
+        self.position >= self.size
+    }
+}
+
+impl<D: BlockDevice> FileSystem<D> {
+    /// Reads bytes from the current position of `file` into `buf`.
+    ///
+    /// Returns the number of bytes read, which is only smaller than `buf.len()` if the end of
+    /// the file is reached.
+    pub fn read(&mut self, file: &mut File, buf: &mut [u8]) -> Result<usize, Error<D::Error>> {
+        let mut read = 0;
+        while read < buf.len() && file.position < file.size {
+            let cluster = match self.cluster_at_position(file, false)? {
+                Some(cluster) => cluster,
+                None => return Err(Error::CorruptFilesystem),
+            };
+            let (lba, offset) = self.position_in_cluster(cluster, file.position);
+            let len = min(
+                min(BLOCK_SIZE - offset, buf.len() - read),
+                (file.size - file.position) as usize,
+            );
+            let block = self.block(lba)?;
+            buf[read..read + len].copy_from_slice(&block[offset..offset + len]);
+            read += len;
+            file.position += len as u32;
+        }
+        Ok(read)
+    }
+
+    /// Writes `data` at the current position of `file`, growing the file if necessary.
+    ///
+    /// The size stored in the directory entry is updated after the data was written. If the
+    /// write fails, e.g. with `DiskFull`, the size still includes the data that was written
+    /// before, so no allocated cluster is lost, and the position is after that data.
+    pub fn write(&mut self, file: &mut File, data: &[u8]) -> Result<(), Error<D::Error>> {
+        let result = self.write_data(file, data);
+
+        if file.position > file.size {
+            file.size = file.position;
+        }
+        let update_result = self.update_entry(file.entry, file.first_cluster, file.size);
+        result.and(update_result)
+    }
+
+    /// Writes `data` at the current position of `file` without updating the directory entry.
+    fn write_data(&mut self, file: &mut File, data: &[u8]) -> Result<(), Error<D::Error>> {
+        let mut written = 0;
+        while written < data.len() {
+            let cluster = match self.cluster_at_position(file, true)? {
+                Some(cluster) => cluster,
+                None => return Err(Error::CorruptFilesystem),
+            };
+            let (lba, offset) = self.position_in_cluster(cluster, file.position);
+            let len = min(BLOCK_SIZE - offset, data.len() - written);
+            let block = if len == BLOCK_SIZE {
+                self.block_overwritten(lba)?
+            } else {
+                self.block_mut(lba)?
+            };
+            block[offset..offset + len].copy_from_slice(&data[written..written + len]);
+            written += len;
+            file.position += len as u32;
+        }
+        Ok(())
+    }
+
+    /// Changes the position of `file`. The position must stay within the file.
+    ///
+    /// Returns the new position.
+    pub fn seek(&mut self, file: &mut File, pos: SeekFrom) -> Result<u32, Error<D::Error>> {
+        let new_position = match pos {
+            SeekFrom::Start(offset) => i64::from(offset),
+            SeekFrom::End(offset) => i64::from(file.size) + i64::from(offset),
+            SeekFrom::Current(offset) => i64::from(file.position) + i64::from(offset),
+        };
+        if new_position < 0 || new_position > i64::from(file.size) {
+            return Err(Error::InvalidSeek);
+        }
+        file.position = new_position as u32;
+        Ok(file.position)
+    }
+
+    /// Returns the cluster that contains the current position of `file`.
+    ///
+    /// If `allocate` is true, missing clusters are appended to the chain. Otherwise `None` is
+    /// returned if the chain is too short.
+    fn cluster_at_position(
+        &mut self,
+        file: &mut File,
+        allocate: bool,
+    ) -> Result<Option<u32>, Error<D::Error>> {
+        let target_index = file.position / self.volume.cluster_size();
+
+        if file.first_cluster == 0 {
+            if !allocate {
+                return Ok(None);
+            }
+            file.first_cluster = self.allocate_cluster(None, false)?;
+            self.update_entry(file.entry, file.first_cluster, file.size)?;
+        }
+        if file.cluster == 0 || target_index < file.cluster_index {
+            // the chain can only be walked forward, so restart at the first cluster
+            file.cluster = file.first_cluster;
+            file.cluster_index = 0;
+        }
+
+        while file.cluster_index < target_index {
+            file.cluster = match self.next_cluster(file.cluster)? {
+                Some(next) => next,
+                None if allocate => self.allocate_cluster(Some(file.cluster), false)?,
+                None => return Ok(None),
+            };
+            file.cluster_index += 1;
+        }
+        Ok(Some(file.cluster))
+    }
+
+    /// Returns the block address and the offset in the block of `position` in `cluster`.
+    fn position_in_cluster(&self, cluster: u32, position: u32) -> (u32, usize) {
+        let offset = position % self.volume.cluster_size();
+        (
+            self.volume.cluster_lba(cluster) + offset / BLOCK_SIZE as u32,
+            (offset % BLOCK_SIZE as u32) as usize,
+        )
+    }
+}
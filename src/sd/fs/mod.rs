@@ -0,0 +1,774 @@
+//! A FAT16/FAT32 filesystem on top of a [`BlockDevice`](super::BlockDevice).
+//!
+//! The filesystem only talks to the storage through the `BlockDevice` trait, so it works with
+//! SD cards as well as with any other block device.
+//!
+//! # Examples
+//! ```rust
+//! fn main(hw: board::Hardware) -> ! {
+//!     // Setup board...
+//!
+//!     let mut sd = sd::Sd::new(&mut sdmmc, &dma_2, &mut rcc, &pins.sdcard_present);
+//!     sd::init(&mut sd).expect("Init failed");
+//!
+//!     let mut fs = sd::fs::FileSystem::mount(sd).expect("Mount failed");
+//!     let mut file = fs.open("/config/app.txt").expect("File not found");
+//!     let mut buf = [0; 64];
+//!     let len = fs.read(&mut file, &mut buf).expect("Read failed");
+//!     hprintln!("{:?}", &buf[..len]);
+//!
+//!     for entry in fs.read_dir("/").expect("Read dir failed") {
+//!         hprintln!("{}", entry.expect("Read entry failed").name());
+//!     }
+//!
+//!     loop {}
+//! }
+//! ```
+
+pub use self::dir::{Attributes, DirEntry, ReadDir};
+pub use self::file::{File, SeekFrom};
+
+mod boot_sector;
+mod dir;
+mod file;
+
+use self::boot_sector::Volume;
+use super::block_device::{BlockDevice, BLOCK_SIZE};
+use byteorder::{ByteOrder, LittleEndian};
+use core::slice;
+
+const FAT16_END_OF_CHAIN: u32 = 0xFFF8;
+const FAT32_END_OF_CHAIN: u32 = 0x0FFF_FFF8;
+const FAT32_ENTRY_MASK: u32 = 0x0FFF_FFFF;
+
+/// Errors that can occur while accessing the filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The underlying block device failed
+    Device(E),
+    /// No FAT16/FAT32 partition was found
+    NoFilesystem,
+    /// The partition is formatted with FAT12
+    UnsupportedFatType,
+    /// The filesystem structures are inconsistent
+    CorruptFilesystem,
+    /// The file or directory doesn't exist
+    NotFound,
+    /// An entry with the same name already exists
+    AlreadyExists,
+    /// The name is empty or contains invalid characters
+    InvalidName,
+    /// The path refers to a directory, but a file was expected
+    NotAFile,
+    /// The path refers to a file, but a directory was expected
+    NotADirectory,
+    /// There are no free clusters left
+    DiskFull,
+    /// The fixed-size root directory has no free entries left
+    DirectoryFull,
+    /// Seek to a position before the start or after the end of the file
+    InvalidSeek,
+}
+
+/// The FAT variant of a mounted filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    /// FAT with 16 bit cluster numbers
+    Fat16,
+    /// FAT with 28 bit cluster numbers
+    Fat32,
+}
+
+/// A mounted FAT filesystem.
+///
+/// All accesses go through a single block cache, so changes might only reach the device on
+/// [`flush`](FileSystem::flush). The cache is flushed automatically when the `FileSystem` is
+/// unmounted or dropped, but only [`unmount`](FileSystem::unmount) reports errors.
+///
+/// On FAT32 volumes, the free cluster count and the next free cluster in the FSInfo sector are
+/// updated on every flush.
+pub struct FileSystem<D: BlockDevice> {
+    // only `None` after `unmount`
+    device: Option<D>,
+    volume: Volume,
+    cache: [u8; BLOCK_SIZE],
+    cache_lba: Option<u32>,
+    cache_dirty: bool,
+    next_free_cluster: u32,
+    // the number of free clusters, if the FSInfo sector knows it
+    free_clusters: Option<u32>,
+    // the FSInfo sector, if the volume has a valid one
+    fs_info_sector: Option<u32>,
+    fs_info_dirty: bool,
+}
+
+impl<D: BlockDevice> FileSystem<D> {
+    /// Mounts the first FAT16 or FAT32 partition of the device.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NoFilesystem` if there is no FAT partition and `UnsupportedFatType` if the
+    /// partition is formatted with FAT12.
+    pub fn mount(mut device: D) -> Result<Self, Error<D::Error>> {
+        let mut block = [0; BLOCK_SIZE];
+        device
+            .read(0, slice::from_mut(&mut block))
+            .map_err(Error::Device)?;
+        let start = boot_sector::first_partition(&block)?;
+        if start != 0 {
+            device
+                .read(start, slice::from_mut(&mut block))
+                .map_err(Error::Device)?;
+        }
+        let volume = boot_sector::parse(&block, start)?;
+
+        let mut fs = FileSystem {
+            device: Some(device),
+            volume,
+            cache: block,
+            cache_lba: Some(start),
+            cache_dirty: false,
+            next_free_cluster: 2,
+            free_clusters: None,
+            fs_info_sector: None,
+            fs_info_dirty: false,
+        };
+        fs.read_fs_info()?;
+        Ok(fs)
+    }
+
+    /// Writes all pending changes to the device and returns it.
+    pub fn unmount(mut self) -> Result<D, Error<D::Error>> {
+        self.flush()?;
+        Ok(self.device.take().expect("filesystem already unmounted"))
+    }
+
+    /// Returns the FAT variant of the filesystem.
+    pub fn fat_type(&self) -> FatType {
+        self.volume.fat_type
+    }
+
+    /// Returns the size of a cluster in bytes.
+    pub fn cluster_size(&self) -> u32 {
+        self.volume.cluster_size()
+    }
+
+    /// Returns the number of free clusters, if it is known.
+    ///
+    /// The count is only known on FAT32 volumes whose FSInfo sector contains it.
+    pub fn free_clusters(&self) -> Option<u32> {
+        self.free_clusters
+    }
+
+    /// Updates the FSInfo sector and writes the cached block to the device if it was modified.
+    pub fn flush(&mut self) -> Result<(), Error<D::Error>> {
+        if let (true, Some(lba)) = (self.fs_info_dirty, self.fs_info_sector) {
+            let free_count = self.free_clusters.unwrap_or(boot_sector::FS_INFO_UNKNOWN);
+            let next_free = self.next_free_cluster;
+            let block = self.block_mut(lba)?;
+            boot_sector::write_fs_info(block, free_count, next_free);
+            self.fs_info_dirty = false;
+        }
+        self.write_cache()
+    }
+
+    /// Writes the cached block to the device if it was modified.
+    fn write_cache(&mut self) -> Result<(), Error<D::Error>> {
+        if let (true, Some(lba)) = (self.cache_dirty, self.cache_lba) {
+            self.device
+                .as_mut()
+                .expect("filesystem already unmounted")
+                .write(lba, slice::from_ref(&self.cache))
+                .map_err(Error::Device)?;
+            self.cache_dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Loads the free cluster count and the next free cluster from the FSInfo sector of FAT32
+    /// volumes. Values that are out of range are ignored.
+    fn read_fs_info(&mut self) -> Result<(), Error<D::Error>> {
+        let lba = match self.volume.fs_info_sector {
+            Some(lba) => lba,
+            None => return Ok(()),
+        };
+        let (free_count, next_free) = match boot_sector::parse_fs_info(self.block(lba)?) {
+            Some(fs_info) => fs_info,
+            None => return Ok(()),
+        };
+        self.fs_info_sector = Some(lba);
+        if free_count <= self.volume.cluster_count {
+            self.free_clusters = Some(free_count);
+        }
+        if next_free >= 2 && next_free <= self.volume.max_cluster() {
+            self.next_free_cluster = next_free;
+        }
+        Ok(())
+    }
+
+    /// Returns an iterator over the entries of the directory at `path`.
+    pub fn read_dir(&mut self, path: &str) -> Result<ReadDir<D>, Error<D::Error>> {
+        let dir_cluster = self.resolve_dir(path)?;
+        Ok(ReadDir::new(self, dir_cluster))
+    }
+
+    /// Opens the existing file at `path`.
+    pub fn open(&mut self, path: &str) -> Result<File, Error<D::Error>> {
+        let (parent, name) = split_path(path);
+        let dir_cluster = self.resolve_dir(parent)?;
+        let entry = self
+            .find_entry(dir_cluster, name)?
+            .ok_or(Error::NotFound)?;
+        if entry.is_dir() {
+            return Err(Error::NotAFile);
+        }
+        Ok(File::new(&entry))
+    }
+
+    /// Creates a new, empty file at `path`. The parent directory must exist.
+    ///
+    /// Names that don't fit into the 8.3 format are stored as long file names.
+    pub fn create(&mut self, path: &str) -> Result<File, Error<D::Error>> {
+        let (parent, name) = split_path(path);
+        let dir_cluster = self.resolve_dir(parent)?;
+        let entry = self.insert_entry(dir_cluster, name, Attributes::ARCHIVE, 0)?;
+        Ok(File::new(&entry))
+    }
+
+    /// Deletes the file at `path` and frees its clusters.
+    pub fn delete(&mut self, path: &str) -> Result<(), Error<D::Error>> {
+        let (parent, name) = split_path(path);
+        let dir_cluster = self.resolve_dir(parent)?;
+        let entry = self
+            .find_entry(dir_cluster, name)?
+            .ok_or(Error::NotFound)?;
+        if entry.is_dir() {
+            return Err(Error::NotAFile);
+        }
+        self.remove_entry(&entry)?;
+        if entry.first_cluster != 0 {
+            self.free_chain(entry.first_cluster)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the first cluster of the directory at `path`.
+    fn resolve_dir(&mut self, path: &str) -> Result<u32, Error<D::Error>> {
+        let mut dir_cluster = self.root_dir_cluster();
+        for component in path.split('/').filter(|c| !c.is_empty() && *c != ".") {
+            let entry = self
+                .find_entry(dir_cluster, component)?
+                .ok_or(Error::NotFound)?;
+            if !entry.is_dir() {
+                return Err(Error::NotADirectory);
+            }
+            dir_cluster = if entry.is_dot_entry() && entry.first_cluster == 0 {
+                // `..` entries pointing to the root directory store cluster 0
+                self.root_dir_cluster()
+            } else {
+                entry.first_cluster
+            };
+        }
+        Ok(dir_cluster)
+    }
+
+    fn root_dir_cluster(&self) -> u32 {
+        match self.volume.fat_type {
+            FatType::Fat16 => 0,
+            FatType::Fat32 => self.volume.root_cluster,
+        }
+    }
+
+    /// Returns the cached block at `lba`, loading it from the device if necessary.
+    fn block(&mut self, lba: u32) -> Result<&[u8; BLOCK_SIZE], Error<D::Error>> {
+        if self.cache_lba != Some(lba) {
+            self.write_cache()?;
+            // invalidate first, so that a failed read doesn't leave a wrongly tagged cache
+            self.cache_lba = None;
+            self.device
+                .as_mut()
+                .expect("filesystem already unmounted")
+                .read(lba, slice::from_mut(&mut self.cache))
+                .map_err(Error::Device)?;
+            self.cache_lba = Some(lba);
+        }
+        Ok(&self.cache)
+    }
+
+    /// Like `block`, but marks the block as modified.
+    fn block_mut(&mut self, lba: u32) -> Result<&mut [u8; BLOCK_SIZE], Error<D::Error>> {
+        self.block(lba)?;
+        self.cache_dirty = true;
+        Ok(&mut self.cache)
+    }
+
+    /// Like `block_mut`, but doesn't load the block from the device, because the caller
+    /// overwrites all of it.
+    fn block_overwritten(&mut self, lba: u32) -> Result<&mut [u8; BLOCK_SIZE], Error<D::Error>> {
+        if self.cache_lba != Some(lba) {
+            self.write_cache()?;
+            self.cache_lba = Some(lba);
+        }
+        self.cache_dirty = true;
+        Ok(&mut self.cache)
+    }
+
+    /// Reads the FAT entry of `cluster`.
+    fn fat_entry(&mut self, cluster: u32) -> Result<u32, Error<D::Error>> {
+        let (lba, offset) = self.volume.fat_entry_position(cluster);
+        let fat_type = self.volume.fat_type;
+        let block = self.block(lba)?;
+        Ok(match fat_type {
+            FatType::Fat16 => u32::from(LittleEndian::read_u16(&block[offset..offset + 2])),
+            FatType::Fat32 => LittleEndian::read_u32(&block[offset..offset + 4]) & FAT32_ENTRY_MASK,
+        })
+    }
+
+    /// Writes the FAT entry of `cluster` to all copies of the FAT.
+    fn set_fat_entry(&mut self, cluster: u32, value: u32) -> Result<(), Error<D::Error>> {
+        let (lba, offset) = self.volume.fat_entry_position(cluster);
+        let fat_type = self.volume.fat_type;
+        for i in 0..self.volume.num_fats {
+            let block = self.block_mut(lba + i * self.volume.fat_size)?;
+            match fat_type {
+                FatType::Fat16 => LittleEndian::write_u16(&mut block[offset..offset + 2], value as u16),
+                FatType::Fat32 => {
+                    // the upper 4 bits are reserved and must be preserved
+                    let old = LittleEndian::read_u32(&block[offset..offset + 4]);
+                    let new = (old & !FAT32_ENTRY_MASK) | (value & FAT32_ENTRY_MASK);
+                    LittleEndian::write_u32(&mut block[offset..offset + 4], new);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn end_of_chain(&self) -> u32 {
+        match self.volume.fat_type {
+            FatType::Fat16 => FAT16_END_OF_CHAIN,
+            FatType::Fat32 => FAT32_END_OF_CHAIN,
+        }
+    }
+
+    /// Returns the cluster following `cluster` in its chain, or `None` at the end of the chain.
+    fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, Error<D::Error>> {
+        let next = self.fat_entry(cluster)?;
+        if next >= self.end_of_chain() {
+            Ok(None)
+        } else if next < 2 || next > self.volume.max_cluster() {
+            Err(Error::CorruptFilesystem)
+        } else {
+            Ok(Some(next))
+        }
+    }
+
+    /// Allocates a free cluster and appends it to the chain ending in `previous`.
+    ///
+    /// If `zero` is true, the content of the cluster is cleared, which is required for
+    /// directories.
+    fn allocate_cluster(
+        &mut self,
+        previous: Option<u32>,
+        zero: bool,
+    ) -> Result<u32, Error<D::Error>> {
+        let max_cluster = self.volume.max_cluster();
+        let start = self.next_free_cluster;
+        let mut cluster = start;
+        while self.fat_entry(cluster)? != 0 {
+            cluster = if cluster >= max_cluster { 2 } else { cluster + 1 };
+            if cluster == start {
+                if self.free_clusters != Some(0) {
+                    self.free_clusters = Some(0);
+                    self.fs_info_dirty = true;
+                }
+                return Err(Error::DiskFull);
+            }
+        }
+
+        let end_of_chain = self.end_of_chain();
+        self.set_fat_entry(cluster, end_of_chain)?;
+        if let Some(previous) = previous {
+            self.set_fat_entry(previous, cluster)?;
+        }
+        self.next_free_cluster = if cluster >= max_cluster { 2 } else { cluster + 1 };
+        self.free_clusters = self.free_clusters.map(|free| free.saturating_sub(1));
+        self.fs_info_dirty = true;
+
+        if zero {
+            let lba = self.volume.cluster_lba(cluster);
+            for i in 0..self.volume.sectors_per_cluster {
+                let block = self.block_mut(lba + i)?;
+                for b in block.iter_mut() {
+                    *b = 0;
+                }
+            }
+        }
+
+        Ok(cluster)
+    }
+
+    /// Marks all clusters of the chain starting at `cluster` as free.
+    fn free_chain(&mut self, cluster: u32) -> Result<(), Error<D::Error>> {
+        let mut current = Some(cluster);
+        while let Some(cluster) = current {
+            current = self.next_cluster(cluster)?;
+            self.set_fat_entry(cluster, 0)?;
+            self.free_clusters = self.free_clusters.map(|free| free + 1);
+        }
+        if cluster < self.next_free_cluster {
+            self.next_free_cluster = cluster;
+        }
+        self.fs_info_dirty = true;
+        Ok(())
+    }
+}
+
+impl<D: BlockDevice> Drop for FileSystem<D> {
+    fn drop(&mut self) {
+        // Errors can't be reported here, `unmount` returns them.
+        if self.device.is_some() {
+            let _ = self.flush();
+        }
+    }
+}
+
+/// Splits a path into the parent directory and the last component.
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => ("", path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sd::block_device::RamBlockDevice;
+    use alloc::prelude::v1::*;
+
+    const PARTITION_START: u32 = 2048;
+
+    // Formats a RAM device like mkfs.fat, optionally behind an MBR with one partition.
+    fn format(sectors: u32, fat_type: FatType, partitioned: bool) -> RamBlockDevice {
+        let start = if partitioned { PARTITION_START } else { 0 };
+        let mut device = RamBlockDevice::new(start + sectors);
+        let (sectors_per_cluster, reserved, root_entries, entry_size) = match fat_type {
+            FatType::Fat16 => (4, 1, 512, 2),
+            FatType::Fat32 => (1, 32, 0, 4),
+        };
+        let fat_size = (sectors / sectors_per_cluster * entry_size + 511) / 512;
+
+        if partitioned {
+            let mut mbr = [0; BLOCK_SIZE];
+            mbr[446 + 4] = match fat_type {
+                FatType::Fat16 => 0x06,
+                FatType::Fat32 => 0x0C,
+            };
+            LittleEndian::write_u32(&mut mbr[446 + 8..], start);
+            LittleEndian::write_u32(&mut mbr[446 + 12..], sectors);
+            mbr[510] = 0x55;
+            mbr[511] = 0xAA;
+            device.write(0, &[mbr]).unwrap();
+        }
+
+        let mut boot = [0; BLOCK_SIZE];
+        boot[..3].copy_from_slice(&[0xEB, 0x3C, 0x90]);
+        LittleEndian::write_u16(&mut boot[11..], BLOCK_SIZE as u16);
+        boot[13] = sectors_per_cluster as u8;
+        LittleEndian::write_u16(&mut boot[14..], reserved as u16);
+        boot[16] = 2;
+        LittleEndian::write_u16(&mut boot[17..], root_entries);
+        if sectors < 0x10000 {
+            LittleEndian::write_u16(&mut boot[19..], sectors as u16);
+        } else {
+            LittleEndian::write_u32(&mut boot[32..], sectors);
+        }
+        boot[21] = 0xF8;
+        match fat_type {
+            FatType::Fat16 => LittleEndian::write_u16(&mut boot[22..], fat_size as u16),
+            FatType::Fat32 => {
+                LittleEndian::write_u32(&mut boot[36..], fat_size);
+                LittleEndian::write_u32(&mut boot[44..], 2);
+                LittleEndian::write_u16(&mut boot[48..], 1);
+            }
+        }
+        boot[510] = 0x55;
+        boot[511] = 0xAA;
+        device.write(start, &[boot]).unwrap();
+
+        if fat_type == FatType::Fat32 {
+            // all clusters but the root directory are free
+            let clusters = (sectors - reserved - 2 * fat_size) / sectors_per_cluster;
+            let mut fs_info = [0; BLOCK_SIZE];
+            LittleEndian::write_u32(&mut fs_info, 0x4161_5252);
+            LittleEndian::write_u32(&mut fs_info[484..], 0x6141_7272);
+            LittleEndian::write_u32(&mut fs_info[488..], clusters - 1);
+            LittleEndian::write_u32(&mut fs_info[492..], 3);
+            LittleEndian::write_u32(&mut fs_info[508..], 0xAA55_0000);
+            device.write(start + 1, &[fs_info]).unwrap();
+        }
+
+        // the first two FAT entries are reserved, the FAT32 root directory is cluster 2
+        let mut fat = [0; BLOCK_SIZE];
+        match fat_type {
+            FatType::Fat16 => LittleEndian::write_u32(&mut fat, 0xFFFF_FFF8),
+            FatType::Fat32 => {
+                LittleEndian::write_u32(&mut fat, 0x0FFF_FFF8);
+                LittleEndian::write_u32(&mut fat[4..], 0x0FFF_FFFF);
+                LittleEndian::write_u32(&mut fat[8..], 0x0FFF_FFFF);
+            }
+        }
+        for i in 0..2 {
+            device.write(start + reserved + i * fat_size, &[fat]).unwrap();
+        }
+        device
+    }
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7) as u8).collect()
+    }
+
+    fn names(fs: &mut FileSystem<RamBlockDevice>, path: &str) -> Vec<String> {
+        fs.read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().name().to_string())
+            .collect()
+    }
+
+    fn read_all(fs: &mut FileSystem<RamBlockDevice>, path: &str) -> Vec<u8> {
+        let mut file = fs.open(path).unwrap();
+        let mut data = vec![0; file.size() as usize + 1];
+        let len = fs.read(&mut file, &mut data).unwrap();
+        data.truncate(len);
+        data
+    }
+
+    fn create_read_and_delete(device: RamBlockDevice, fat_type: FatType) {
+        let mut fs = FileSystem::mount(device).unwrap();
+        assert_eq!(fs.fat_type(), fat_type);
+
+        let mut config = fs.create("/CONFIG.TXT").unwrap();
+        fs.write(&mut config, b"hello").unwrap();
+        // larger than a cluster, written in two parts that don't end at a block boundary
+        let data = test_data(10000);
+        let mut long = fs.create("A rather long file name.data").unwrap();
+        fs.write(&mut long, &data[..3000]).unwrap();
+        fs.write(&mut long, &data[3000..]).unwrap();
+        assert_eq!(fs.create("config.txt").err(), Some(Error::AlreadyExists));
+        assert_eq!(fs.create("bad?name").err(), Some(Error::InvalidName));
+        // enough entries to span several blocks of the root directory
+        for i in 0..40 {
+            fs.create(&format!("file number {}", i)).unwrap();
+        }
+
+        // everything must be on the device after unmounting
+        let mut fs = FileSystem::mount(fs.unmount().unwrap()).unwrap();
+        let root = names(&mut fs, "/");
+        assert_eq!(root.len(), 42);
+        assert!(root.contains(&"CONFIG.TXT".to_string()));
+        assert!(root.contains(&"file number 39".to_string()));
+        assert_eq!(read_all(&mut fs, "config.txt"), b"hello");
+        // names are matched case-insensitively
+        assert_eq!(read_all(&mut fs, "/a rather long file name.DATA"), data);
+
+        let entry = fs
+            .read_dir("/")
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .find(|entry| entry.name() == "A rather long file name.data")
+            .unwrap();
+        assert_eq!(entry.short_name(), "ARATHE~1.DAT");
+        assert_eq!(entry.size(), 10000);
+        assert!(entry.is_file());
+        assert_eq!(read_all(&mut fs, "ARATHE~1.DAT"), data);
+
+        let mut file = fs.open("ARATHE~1.DAT").unwrap();
+        assert_eq!(fs.seek(&mut file, SeekFrom::Start(4097)), Ok(4097));
+        let mut buf = [0; 3];
+        assert_eq!(fs.read(&mut file, &mut buf), Ok(3));
+        assert_eq!(buf, data[4097..4100]);
+        assert_eq!(fs.seek(&mut file, SeekFrom::Current(-100)), Ok(4000));
+        assert_eq!(fs.seek(&mut file, SeekFrom::End(0)), Ok(10000));
+        assert!(file.is_eof());
+        assert_eq!(fs.seek(&mut file, SeekFrom::End(1)), Err(Error::InvalidSeek));
+
+        // overwrite in the middle, the size stays the same
+        fs.seek(&mut file, SeekFrom::Start(600)).unwrap();
+        fs.write(&mut file, b"changed").unwrap();
+        let mut expected = data.clone();
+        expected[600..607].copy_from_slice(b"changed");
+        assert_eq!(read_all(&mut fs, "ARATHE~1.DAT"), expected);
+
+        // the clusters of deleted files are reused
+        fs.delete("A rather long file name.data").unwrap();
+        assert_eq!(fs.open("A rather long file name.data").err(), Some(Error::NotFound));
+        assert_eq!(names(&mut fs, "/").len(), 41);
+        let mut file = fs.create("another long name.bin").unwrap();
+        fs.write(&mut file, &data).unwrap();
+        let mut fs = FileSystem::mount(fs.unmount().unwrap()).unwrap();
+        assert_eq!(read_all(&mut fs, "another long name.bin"), data);
+        assert_eq!(fs.open("/missing/file.txt").err(), Some(Error::NotFound));
+        assert_eq!(fs.read_dir("/config.txt").err(), Some(Error::NotADirectory));
+    }
+
+    #[test]
+    fn fat16_with_partition_table() {
+        let device = format(32768, FatType::Fat16, true);
+        create_read_and_delete(device, FatType::Fat16);
+    }
+
+    #[test]
+    fn fat32_without_partition_table() {
+        let device = format(70000, FatType::Fat32, false);
+        create_read_and_delete(device, FatType::Fat32);
+    }
+
+    #[test]
+    fn mount_errors() {
+        let result = FileSystem::mount(RamBlockDevice::new(64));
+        assert_eq!(result.err(), Some(Error::NoFilesystem));
+        // too few clusters for FAT16
+        let result = FileSystem::mount(format(8192, FatType::Fat16, true));
+        assert_eq!(result.err(), Some(Error::UnsupportedFatType));
+        let result = FileSystem::mount(RamBlockDevice::new(0));
+        assert!(match result {
+            Err(Error::Device(_)) => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn disk_full() {
+        let mut fs = FileSystem::mount(format(16500, FatType::Fat16, false)).unwrap();
+        let cluster_size = fs.cluster_size();
+        let cluster = vec![0; cluster_size as usize];
+        // one and a half clusters, so the last write fails in the middle
+        let chunk = vec![0; cluster_size as usize * 3 / 2];
+        let mut file = fs.create("big.bin").unwrap();
+        let mut written = 0;
+        let result = loop {
+            match fs.write(&mut file, &chunk) {
+                Ok(()) => written += chunk.len() as u32,
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(result, Error::DiskFull);
+        assert!(written > 4000 * cluster_size);
+
+        // the part of the failed write that fit into the last cluster is kept
+        assert!(file.size() > written);
+        assert_eq!(file.size() % cluster_size, 0);
+        assert_eq!(file.position(), file.size());
+        let size = file.size();
+        let mut fs = FileSystem::mount(fs.unmount().unwrap()).unwrap();
+        assert_eq!(fs.open("big.bin").unwrap().size(), size);
+
+        // freeing the clusters makes the space available again
+        fs.delete("big.bin").unwrap();
+        let mut file = fs.create("small.bin").unwrap();
+        fs.write(&mut file, &cluster).unwrap();
+    }
+
+    #[test]
+    fn fs_info() {
+        let mut fs = FileSystem::mount(format(70000, FatType::Fat32, false)).unwrap();
+        let free = fs.free_clusters().unwrap();
+        assert_eq!(free, fs.volume.cluster_count - 1);
+
+        // four clusters
+        let data = test_data(3 * fs.cluster_size() as usize + 1);
+        let mut file = fs.create("data.bin").unwrap();
+        fs.write(&mut file, &data).unwrap();
+        assert_eq!(fs.free_clusters(), Some(free - 4));
+
+        let device = fs.unmount().unwrap();
+        let fs_info = boot_sector::parse_fs_info(&device.blocks()[1]).unwrap();
+        assert_eq!(fs_info, (free - 4, 7));
+
+        let mut fs = FileSystem::mount(device).unwrap();
+        assert_eq!(fs.free_clusters(), Some(free - 4));
+        fs.delete("data.bin").unwrap();
+        assert_eq!(fs.free_clusters(), Some(free));
+        let device = fs.unmount().unwrap();
+        let fs_info = boot_sector::parse_fs_info(&device.blocks()[1]).unwrap();
+        assert_eq!(fs_info, (free, 3));
+
+        // FAT16 has no FSInfo sector
+        let fs = FileSystem::mount(format(32768, FatType::Fat16, false)).unwrap();
+        assert_eq!(fs.free_clusters(), None);
+    }
+
+    #[test]
+    fn flush_on_drop() {
+        let mut device = format(32768, FatType::Fat16, false);
+        {
+            let mut fs = FileSystem::mount(&mut device).unwrap();
+            let mut file = fs.create("dropped.txt").unwrap();
+            fs.write(&mut file, b"still there").unwrap();
+        }
+        let mut fs = FileSystem::mount(device).unwrap();
+        assert_eq!(read_all(&mut fs, "dropped.txt"), b"still there");
+    }
+
+    // Counts the blocks that are read from the wrapped device.
+    struct CountReads<D> {
+        device: D,
+        reads: usize,
+    }
+
+    impl<D: BlockDevice> BlockDevice for CountReads<D> {
+        type Error = D::Error;
+
+        fn read(&mut self, lba: u32, blocks: &mut [[u8; BLOCK_SIZE]]) -> Result<(), D::Error> {
+            self.reads += blocks.len();
+            self.device.read(lba, blocks)
+        }
+
+        fn write(&mut self, lba: u32, blocks: &[[u8; BLOCK_SIZE]]) -> Result<(), D::Error> {
+            self.device.write(lba, blocks)
+        }
+
+        fn num_blocks(&self) -> u32 {
+            self.device.num_blocks()
+        }
+    }
+
+    #[test]
+    fn whole_block_writes() {
+        let device = CountReads {
+            device: format(32768, FatType::Fat16, false),
+            reads: 0,
+        };
+        let mut fs = FileSystem::mount(device).unwrap();
+        let data: Vec<u8> = (0..8 * BLOCK_SIZE).map(|i| (i * 7) as u8).collect();
+        let mut file = fs.create("blocks.bin").unwrap();
+        fs.write(&mut file, &data[..BLOCK_SIZE]).unwrap();
+
+        // only the directory entry after each write and the FAT when a cluster is allocated are
+        // read, not the overwritten data blocks
+        let reads = fs.device.as_ref().unwrap().reads;
+        for chunk in data[BLOCK_SIZE..].chunks(BLOCK_SIZE) {
+            fs.write(&mut file, chunk).unwrap();
+        }
+        let reads = fs.device.as_ref().unwrap().reads - reads;
+        assert!(reads <= 11, "{} reads", reads);
+
+        // a partial write loads the block first
+        fs.seek(&mut file, SeekFrom::Start(10)).unwrap();
+        fs.write(&mut file, b"partial").unwrap();
+        let mut expected = data.clone();
+        expected[10..17].copy_from_slice(b"partial");
+        let mut read = vec![0; expected.len()];
+        fs.seek(&mut file, SeekFrom::Start(0)).unwrap();
+        assert_eq!(fs.read(&mut file, &mut read), Ok(read.len()));
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn split_paths() {
+        assert_eq!(split_path("/a/b/c.txt"), ("/a/b", "c.txt"));
+        assert_eq!(split_path("c.txt"), ("", "c.txt"));
+        assert_eq!(split_path("/dir/"), ("", "dir"));
+    }
+}
@@ -1,5 +1,6 @@
 use super::error::Error;
-use super::{sdmmc_cmd, CardInfo, CardType, Sd};
+use super::registers::SwitchStatus;
+use super::{read_data, sdmmc_cmd, BusSpeed, CardInfo, CardType, Cid, Csd, Scr, Sd, SpecVersion};
 use crate::gpio::InputPin;
 use stm32f7::stm32f7x6::{RCC, SDMMC1};
 
@@ -18,7 +19,7 @@ use stm32f7::stm32f7x6::{RCC, SDMMC1};
 /// fn main(hw: board::Hardware) -> ! {
 ///     // Setup board...
 ///
-///     let mut sd = sd::Sd::new(sdmmc, &mut gpio, rcc);
+///     let mut sd = sd::Sd::new(sdmmc, &dma_2, rcc, &pins.sdcard_present);
 ///     sd::init(&mut sd).expect("Init failed");
 ///
 ///     loop {}
@@ -31,7 +32,7 @@ use stm32f7::stm32f7x6::{RCC, SDMMC1};
 /// fn main(hw: board::Hardware) -> ! {
 ///     // Setup board...
 ///
-///     let mut sd = sd::Sd::new(sdmmc, &mut gpio, rcc);
+///     let mut sd = sd::Sd::new(sdmmc, &dma_2, rcc, &pins.sdcard_present);
 ///
 ///     loop {
 ///         if sd.card_present() && !sd.card_initialized() {
@@ -44,7 +45,9 @@ use stm32f7::stm32f7x6::{RCC, SDMMC1};
 ///     }
 /// }
 /// ```
-// TODO: Automate the (de-)initialization with interupts?
+///
+/// Instead of polling the present pin, `Sd::card_detect()` provides a stream of insertion and
+/// removal events, which can be driven by the EXTI interrupt of the pin.
 pub fn init<P: InputPin>(sd: &mut Sd<P>) -> Result<(), Error> {
     // Check for SD card
     if !sd.card_present() {
@@ -74,23 +77,62 @@ pub fn init<P: InputPin>(sd: &mut Sd<P>) -> Result<(), Error> {
 
     // Let the card send the CID and enter identification process
     sdmmc_cmd::send_cid(sd.sdmmc)?;
+    card_info.cid = Cid::from_raw(long_response(sd.sdmmc));
 
     // Get the RCA of the card
     card_info.rca = sdmmc_cmd::set_rel_add(sd.sdmmc)?;
 
     sdmmc_cmd::send_csd(sd.sdmmc, u32::from(card_info.rca) << 16)?;
+    card_info.csd = Csd::from_raw(long_response(sd.sdmmc));
 
-    let csd = [
-        sd.sdmmc.resp1.read().cardstatus1().bits(),
-        sd.sdmmc.resp2.read().cardstatus2().bits(),
-        sd.sdmmc.resp3.read().cardstatus3().bits(),
-        sd.sdmmc.resp4.read().cardstatus4().bits(),
-    ];
-
-    get_card_csd(&mut card_info, csd);
+    get_card_csd(&mut card_info);
 
     sdmmc_cmd::sel_desel(sd.sdmmc, u32::from(card_info.rca) << 16)?;
 
+    // All block transfers use blocks of 512 bytes, so the block length is only set once.
+    // High capacity cards ignore it.
+    sdmmc_cmd::block_length(sd.sdmmc, card_info.log_blk_size)?;
+
+    let rca = u32::from(card_info.rca) << 16;
+    let mut scr = [0; 2];
+    read_data(
+        sd.sdmmc,
+        |sdmmc| {
+            sdmmc_cmd::app(sdmmc, rca)?;
+            sdmmc_cmd::app_send_scr(sdmmc)
+        },
+        &mut scr,
+    )?;
+    card_info.scr = Scr::from_raw(scr);
+
+    // Switch the card and the controller to the 4 bit data bus, if the card supports it
+    if card_info.scr.bus_width_4 {
+        sdmmc_cmd::app(sd.sdmmc, rca)?;
+        sdmmc_cmd::app_bus_width(sd.sdmmc, 0b10)?;
+        sd.sdmmc.clkcr.modify(|_, w| unsafe { w.widbus().bits(0b01) });
+    }
+
+    // CMD6 is supported by cards of version 1.10 and newer, which implement command class 10.
+    // If the card doesn't support the high speed mode or the switch fails, the card keeps
+    // running in default speed mode.
+    // The UHS-I modes are not supported, because they need 1.8 V signaling, which is not
+    // available on the board.
+    let switch_supported = card_info.card_type != CardType::SDv1
+        && card_info.scr.spec_version != SpecVersion::V1_0
+        && card_info.csd.command_classes & (1 << 10) != 0;
+    if switch_supported && switch_high_speed(sd.sdmmc).unwrap_or(false) {
+        card_info.bus_speed = BusSpeed::HighSpeed;
+    }
+
+    match card_info.bus_speed {
+        // The identification is done, so the card can run at the default speed of up to 25 MHz.
+        // SDMMC_CK = SDMMCCLK / (CLKDIV + 2) = 48 MHz / 2 = 24 MHz
+        BusSpeed::Default => sd.sdmmc.clkcr.modify(|_, w| unsafe { w.clkdiv().bits(0) }),
+        // Bypass the clock divider for high speed mode of up to 50 MHz.
+        // SDMMC_CK = SDMMCCLK = 48 MHz
+        BusSpeed::HighSpeed => sd.sdmmc.clkcr.modify(|_, w| w.bypass().set_bit()),
+    }
+
     sd.card_info = Some(card_info);
 
     Ok(())
@@ -105,13 +147,19 @@ pub fn de_init<P: InputPin>(sd: &mut Sd<P>) {
         .modify(|_, w| unsafe { w.pwrctrl().bits(0x00) });
 }
 
-/// Initializes the hardware, including the clocks used by the SDMMC-Controller.
+/// Initializes the hardware, including the clocks used by the SDMMC-Controller and the DMA.
 pub fn init_hw(rcc: &mut RCC) {
     // Enable SDMMC1 clock
     rcc.apb2enr.modify(|_, w| w.sdmmc1en().enabled());
 
     // wait for enabling
     while !rcc.apb2enr.read().sdmmc1en().is_enabled() {}
+
+    // Enable DMA2 clock, which is used for the data transfers
+    rcc.ahb1enr.modify(|_, w| w.dma2en().enabled());
+
+    // wait for enabling
+    while !rcc.ahb1enr.read().dma2en().is_enabled() {}
 }
 
 fn power_on(sdmmc: &mut SDMMC1) -> Result<CardType, Error> {
@@ -176,32 +224,49 @@ fn power_on(sdmmc: &mut SDMMC1) -> Result<CardType, Error> {
     Ok(card_type)
 }
 
-fn get_card_csd(card_info: &mut CardInfo, csd: [u32; 4]) {
-    if card_info.card_type == CardType::SDv2HC {
-        let tmp = csd[1] & 0xFF;
-        let mut device_size = (tmp & 0x3F) << 16;
-
-        let tmp = (csd[2] & 0xFFFF_0000) >> 16;
-        device_size |= tmp;
-
-        card_info.blk_number = (device_size + 1) * 1024;
-        card_info.log_blk_number = card_info.blk_number;
-        card_info.blk_size = 512;
-        card_info.log_blk_size = card_info.blk_size;
-    } else {
-        let tmp = csd[1] & 0x3FF;
-        let mut device_size = tmp << 2;
+fn get_card_csd(card_info: &mut CardInfo) {
+    card_info.blk_number = card_info.csd.block_count;
+    card_info.blk_size = card_info.csd.block_len;
+    card_info.log_blk_number = card_info.blk_number * (card_info.blk_size / 512);
+    card_info.log_blk_size = 512;
+}
 
-        let tmp = (csd[2] & 0xFF00_0000) >> 24;
-        device_size |= (tmp & 0xC0) >> 6;
+// Checks with CMD6 whether the card supports the high speed mode and switches to it. Returns
+// true if the card switched to high speed mode.
+fn switch_high_speed(sdmmc: &mut SDMMC1) -> Result<bool, Error> {
+    // Function group 1 (access mode) is set to 1 (high speed), all other groups are unchanged.
+    const CHECK_HIGH_SPEED: u32 = 0x00FF_FFF1;
+    const SWITCH_HIGH_SPEED: u32 = 0x80FF_FFF1;
+
+    let mut status = [0; 16];
+    read_data(
+        sdmmc,
+        |sdmmc| sdmmc_cmd::switch_func(sdmmc, CHECK_HIGH_SPEED),
+        &mut status,
+    )?;
+    let status = SwitchStatus::from_raw(status);
+    if !status.high_speed_supported || !status.high_speed_selected {
+        return Ok(false);
+    }
 
-        let device_size_mul = (csd[2] & 0x0003_8000) >> 15;
+    let mut status = [0; 16];
+    read_data(
+        sdmmc,
+        |sdmmc| sdmmc_cmd::switch_func(sdmmc, SWITCH_HIGH_SPEED),
+        &mut status,
+    )?;
 
-        let rd_blk_len = (csd[1] & 0x000F_0000) >> 16;
+    // The card uses the new timing 8 clock cycles after the status was sent, which is over
+    // before the next command can be sent.
+    Ok(SwitchStatus::from_raw(status).high_speed_selected)
+}
 
-        card_info.blk_number = (device_size + 1) * (1 << (device_size_mul + 2));
-        card_info.blk_size = 1 << rd_blk_len;
-        card_info.log_blk_number = card_info.blk_number * (card_info.blk_size / 512);
-        card_info.log_blk_size = 512;
-    }
+// Returns the content of the response registers after a command with a long response (R2).
+fn long_response(sdmmc: &SDMMC1) -> [u32; 4] {
+    [
+        sdmmc.resp1.read().cardstatus1().bits(),
+        sdmmc.resp2.read().cardstatus2().bits(),
+        sdmmc.resp3.read().cardstatus3().bits(),
+        sdmmc.resp4.read().cardstatus4().bits(),
+    ]
 }
@@ -0,0 +1,470 @@
+//! An append-only record log on top of a [`BlockDevice`](super::BlockDevice).
+//!
+//! The log uses a fixed range of blocks as ring buffer. Records are collected in a block buffer
+//! in RAM and the buffer is written to the next block of the ring when it is full or when the
+//! log is flushed. Every block is written exactly once per round, so the writes are spread
+//! evenly over the range. When the ring is full, the oldest block is overwritten.
+//!
+//! Each block starts with a header that contains a sequence number, which is incremented for
+//! every written block. Each record is prefixed with its length and a CRC32 of its data. On
+//! mount, the range is scanned for the block with the highest sequence number to find the head
+//! of the log. Blocks that were only partially written before a crash are detected by the CRCs
+//! and skipped on replay, so all records that were flushed before remain readable.
+//!
+//! # Block layout
+//!
+//! | Offset | Size | Content                                                  |
+//! |--------|------|----------------------------------------------------------|
+//! | 0      | 4    | Magic number `SLOG`                                      |
+//! | 4      | 4    | Sequence number                                          |
+//! | 8      | 2    | Number of used bytes after the header                    |
+//! | 10     | 2    | Reserved, zero                                           |
+//! | 12     | 4    | CRC32 of the bytes 0 to 11                               |
+//! | 16     | ...  | Records: length (2 bytes), CRC32 (4 bytes), data         |
+//!
+//! All numbers are stored little endian.
+//!
+//! # Examples
+//! ```rust
+//! fn main(hw: board::Hardware) -> ! {
+//!     // Setup board...
+//!
+//!     let mut sd = sd::Sd::new(&mut sdmmc, &dma_2, &mut rcc, &pins.sdcard_present);
+//!     sd::init(&mut sd).expect("Init failed");
+//!
+//!     // use the blocks 2048 to 4095 for the log
+//!     let mut log = sd::log::Log::mount(sd, 2048..4096).expect("Mount failed");
+//!     for record in log.records() {
+//!         hprintln!("{:?}", record.expect("Read failed"));
+//!     }
+//!
+//!     log.append(&[1, 2, 3, 4]).expect("Append failed");
+//!     log.flush().expect("Flush failed");
+//!
+//!     loop {}
+//! }
+//! ```
+
+use super::block_device::{BlockDevice, BLOCK_SIZE};
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, LittleEndian};
+use core::ops::Range;
+use core::slice;
+
+const MAGIC: u32 = 0x474F_4C53; // "SLOG"
+const HEADER_LEN: usize = 16;
+const RECORD_HEADER_LEN: usize = 6;
+
+/// The maximum length of a single record in bytes.
+pub const MAX_RECORD_LEN: usize = BLOCK_SIZE - HEADER_LEN - RECORD_HEADER_LEN;
+
+/// Errors that can occur while accessing the log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The underlying block device failed
+    Device(E),
+    /// The block range is empty or exceeds the device
+    InvalidRange,
+    /// The record is larger than `MAX_RECORD_LEN`
+    RecordTooLarge,
+}
+
+/// An append-only record log in a range of blocks.
+pub struct Log<D: BlockDevice> {
+    device: D,
+    start: u32,
+    len: u32,
+    /// The index of the next block to write, relative to `start`.
+    head: u32,
+    /// The sequence number of the next block to write.
+    sequence: u32,
+    buffer: [u8; BLOCK_SIZE],
+    /// The number of bytes in `buffer` after the header.
+    used: usize,
+}
+
+impl<D: BlockDevice> Log<D> {
+    /// Creates an empty log in `blocks`.
+    ///
+    /// All blocks in the range are overwritten with zeros, so that blocks of a previous log
+    /// aren't mistaken for records of the new one.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidRange` if `blocks` is empty or exceeds the device.
+    pub fn format(mut device: D, blocks: Range<u32>) -> Result<Self, Error<D::Error>> {
+        check_range(&device, &blocks)?;
+
+        let zeros = vec![[0; BLOCK_SIZE]; 16];
+        let mut lba = blocks.start;
+        while lba < blocks.end {
+            let count = (blocks.end - lba).min(zeros.len() as u32);
+            device
+                .write(lba, &zeros[..count as usize])
+                .map_err(Error::Device)?;
+            lba += count;
+        }
+
+        Ok(Log::new(device, blocks, 0, 1))
+    }
+
+    /// Opens the log in `blocks` and searches the head of the log.
+    ///
+    /// Every block of the range is read once, so mounting takes longer for larger ranges. A
+    /// range that doesn't contain a log yet is treated as empty log, but it should be
+    /// initialized with [`format`](Log::format).
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidRange` if `blocks` is empty or exceeds the device.
+    pub fn mount(mut device: D, blocks: Range<u32>) -> Result<Self, Error<D::Error>> {
+        check_range(&device, &blocks)?;
+
+        let mut block = [0; BLOCK_SIZE];
+        let mut newest: Option<(u32, u32)> = None;
+        for index in 0..blocks.end - blocks.start {
+            device
+                .read(blocks.start + index, slice::from_mut(&mut block))
+                .map_err(Error::Device)?;
+            if let Some((sequence, _)) = parse_header(&block) {
+                match newest {
+                    Some((newest_sequence, _)) if newest_sequence >= sequence => {}
+                    _ => newest = Some((sequence, index)),
+                }
+            }
+        }
+
+        let len = blocks.end - blocks.start;
+        let (head, sequence) = match newest {
+            Some((sequence, index)) => ((index + 1) % len, sequence.wrapping_add(1)),
+            None => (0, 1),
+        };
+        Ok(Log::new(device, blocks, head, sequence))
+    }
+
+    fn new(device: D, blocks: Range<u32>, head: u32, sequence: u32) -> Self {
+        Log {
+            device,
+            start: blocks.start,
+            len: blocks.end - blocks.start,
+            head,
+            sequence,
+            buffer: [0; BLOCK_SIZE],
+            used: 0,
+        }
+    }
+
+    /// Appends a record to the log.
+    ///
+    /// The record is buffered in RAM until the buffer is full or [`flush`](Log::flush) is
+    /// called. Records can't span blocks, so a record that doesn't fit in the remaining
+    /// buffer causes the buffer to be written first.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RecordTooLarge` if the record is larger than `MAX_RECORD_LEN`.
+    pub fn append(&mut self, record: &[u8]) -> Result<(), Error<D::Error>> {
+        if record.len() > MAX_RECORD_LEN {
+            return Err(Error::RecordTooLarge);
+        }
+        if HEADER_LEN + self.used + RECORD_HEADER_LEN + record.len() > BLOCK_SIZE {
+            self.flush()?;
+        }
+
+        let offset = HEADER_LEN + self.used;
+        let (record_header, data) = self.buffer[offset..].split_at_mut(RECORD_HEADER_LEN);
+        LittleEndian::write_u16(&mut record_header[0..2], record.len() as u16);
+        LittleEndian::write_u32(&mut record_header[2..6], crc32(record));
+        data[..record.len()].copy_from_slice(record);
+        self.used += RECORD_HEADER_LEN + record.len();
+        Ok(())
+    }
+
+    /// Writes the buffered records to the next block.
+    ///
+    /// The remaining space of the block stays unused, so flushing after every small record
+    /// wastes space.
+    pub fn flush(&mut self) -> Result<(), Error<D::Error>> {
+        if self.used == 0 {
+            return Ok(());
+        }
+
+        for byte in &mut self.buffer[HEADER_LEN + self.used..] {
+            *byte = 0;
+        }
+        LittleEndian::write_u32(&mut self.buffer[0..4], MAGIC);
+        LittleEndian::write_u32(&mut self.buffer[4..8], self.sequence);
+        LittleEndian::write_u16(&mut self.buffer[8..10], self.used as u16);
+        LittleEndian::write_u16(&mut self.buffer[10..12], 0);
+        let header_crc = crc32(&self.buffer[0..12]);
+        LittleEndian::write_u32(&mut self.buffer[12..16], header_crc);
+
+        self.device
+            .write(self.start + self.head, slice::from_ref(&self.buffer))
+            .map_err(Error::Device)?;
+
+        self.head = (self.head + 1) % self.len;
+        self.sequence = self.sequence.wrapping_add(1);
+        self.used = 0;
+        Ok(())
+    }
+
+    /// Returns an iterator over all records, from the oldest to the newest.
+    ///
+    /// Records that are still buffered are included after the written ones.
+    pub fn records(&mut self) -> Records<D> {
+        Records {
+            log: self,
+            index: 0,
+            last_sequence: None,
+            block: [0; BLOCK_SIZE],
+            offset: 0,
+            end: 0,
+        }
+    }
+
+    /// Writes the buffered records and returns the device.
+    pub fn unmount(mut self) -> Result<D, Error<D::Error>> {
+        self.flush()?;
+        Ok(self.device)
+    }
+}
+
+/// An iterator over the records of a [`Log`](Log), returned by [`Log::records`](Log::records).
+///
+/// Blocks and records with an invalid CRC are skipped.
+pub struct Records<'a, D: BlockDevice + 'a> {
+    log: &'a mut Log<D>,
+    /// The number of blocks read so far. The buffer is read after `log.len` blocks.
+    index: u32,
+    last_sequence: Option<u32>,
+    block: [u8; BLOCK_SIZE],
+    /// The position of the next record in `block`.
+    offset: usize,
+    /// The end of the used bytes in `block`.
+    end: usize,
+}
+
+impl<'a, D: BlockDevice> Iterator for Records<'a, D> {
+    type Item = Result<Vec<u8>, Error<D::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.next_in_block() {
+                return Some(Ok(record));
+            }
+            if self.index > self.log.len {
+                return None;
+            }
+
+            if self.index == self.log.len {
+                // all blocks are read, continue with the records that aren't written yet
+                self.block = self.log.buffer;
+                self.offset = HEADER_LEN;
+                self.end = HEADER_LEN + self.log.used;
+            } else {
+                // the oldest block is the one after the newest, which is directly before head
+                let lba = self.log.start + (self.log.head + self.index) % self.log.len;
+                if let Err(err) = self.log.device.read(lba, slice::from_mut(&mut self.block)) {
+                    self.index = self.log.len + 1;
+                    return Some(Err(Error::Device(err)));
+                }
+                match parse_header(&self.block) {
+                    // Blocks of an older round that were not overwritten yet can't appear
+                    // after newer blocks.
+                    Some((sequence, used))
+                        if self.last_sequence.map_or(true, |last| sequence > last) =>
+                    {
+                        self.last_sequence = Some(sequence);
+                        self.offset = HEADER_LEN;
+                        self.end = HEADER_LEN + used;
+                    }
+                    _ => {}
+                }
+            }
+            self.index += 1;
+        }
+    }
+}
+
+impl<'a, D: BlockDevice> Records<'a, D> {
+    // Returns the next valid record of the current block.
+    fn next_in_block(&mut self) -> Option<Vec<u8>> {
+        if self.offset + RECORD_HEADER_LEN > self.end {
+            return None;
+        }
+        let len = usize::from(LittleEndian::read_u16(&self.block[self.offset..]));
+        let crc = LittleEndian::read_u32(&self.block[self.offset + 2..]);
+        let data_start = self.offset + RECORD_HEADER_LEN;
+        if data_start + len > self.end || crc32(&self.block[data_start..data_start + len]) != crc {
+            // the framing can't be trusted anymore, skip the rest of the block
+            self.offset = self.end;
+            return None;
+        }
+        self.offset = data_start + len;
+        Some(self.block[data_start..data_start + len].to_vec())
+    }
+}
+
+fn check_range<D: BlockDevice>(device: &D, blocks: &Range<u32>) -> Result<(), Error<D::Error>> {
+    if blocks.start >= blocks.end || blocks.end > device.num_blocks() {
+        return Err(Error::InvalidRange);
+    }
+    Ok(())
+}
+
+/// Returns the sequence number and the number of used bytes if the block has a valid header.
+fn parse_header(block: &[u8; BLOCK_SIZE]) -> Option<(u32, usize)> {
+    let used = usize::from(LittleEndian::read_u16(&block[8..10]));
+    if LittleEndian::read_u32(&block[0..4]) != MAGIC
+        || LittleEndian::read_u32(&block[12..16]) != crc32(&block[0..12])
+        || HEADER_LEN + used > BLOCK_SIZE
+    {
+        return None;
+    }
+    Some((LittleEndian::read_u32(&block[4..8]), used))
+}
+
+/// Calculates the CRC32 (IEEE 802.3) checksum of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sd::block_device::RamBlockDevice;
+
+    const RANGE: Range<u32> = 10..14;
+
+    fn record(i: u32) -> Vec<u8> {
+        vec![i as u8; (i % 50) as usize + 1]
+    }
+
+    fn records(log: &mut Log<RamBlockDevice>) -> Vec<Vec<u8>> {
+        log.records().map(|record| record.unwrap()).collect()
+    }
+
+    // Applies `f` to the block at `lba`.
+    fn modify_block(device: &mut RamBlockDevice, lba: u32, f: impl FnOnce(&mut [u8; BLOCK_SIZE])) {
+        let mut block = [[0; BLOCK_SIZE]];
+        device.read(lba, &mut block).unwrap();
+        f(&mut block[0]);
+        device.write(lba, &block).unwrap();
+    }
+
+    #[test]
+    fn crc32_check_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+    }
+
+    #[test]
+    fn records_survive_remount() {
+        let mut log = Log::format(RamBlockDevice::new(16), RANGE).unwrap();
+        assert!(records(&mut log).is_empty());
+        for i in 0..20 {
+            log.append(&record(i)).unwrap();
+        }
+        // buffered records are included
+        let expected: Vec<_> = (0..20).map(record).collect();
+        assert_eq!(records(&mut log), expected);
+
+        let mut log = Log::mount(log.unmount().unwrap(), RANGE).unwrap();
+        assert_eq!(records(&mut log), expected);
+
+        // appending continues after the existing records
+        log.append(b"after remount").unwrap();
+        log.flush().unwrap();
+        let mut log = Log::mount(log.unmount().unwrap(), RANGE).unwrap();
+        let all = records(&mut log);
+        assert_eq!(all.len(), 21);
+        assert_eq!(all[..20], expected[..]);
+        assert_eq!(all[20], b"after remount");
+    }
+
+    #[test]
+    fn oldest_blocks_are_overwritten() {
+        let mut log = Log::format(RamBlockDevice::new(16), RANGE).unwrap();
+        for i in 0..200 {
+            log.append(&record(i)).unwrap();
+        }
+        let mut log = Log::mount(log.unmount().unwrap(), RANGE).unwrap();
+        let remaining = records(&mut log);
+        // four full blocks are left, the newest records are complete and in order
+        assert!(remaining.len() > 4 * 10 && remaining.len() < 200);
+        let first = 200 - remaining.len() as u32;
+        let expected: Vec<_> = (first..200).map(record).collect();
+        assert_eq!(remaining, expected);
+    }
+
+    #[test]
+    fn torn_blocks_are_skipped() {
+        let mut log = Log::format(RamBlockDevice::new(16), RANGE).unwrap();
+        // one block per flush, with two records each
+        for i in 0..3 {
+            log.append(&record(2 * i)).unwrap();
+            log.append(&record(2 * i + 1)).unwrap();
+            log.flush().unwrap();
+        }
+        let mut device = log.unmount().unwrap();
+
+        // a corrupted record ends its block, but the records before it stay readable
+        modify_block(&mut device, RANGE.start, |block| {
+            block[HEADER_LEN + RECORD_HEADER_LEN + 1 + RECORD_HEADER_LEN] ^= 0xFF;
+        });
+        // a block with a corrupted header is skipped
+        modify_block(&mut device, RANGE.start + 1, |block| block[5] ^= 0xFF);
+        let mut log = Log::mount(device, RANGE).unwrap();
+        assert_eq!(records(&mut log), vec![record(0), record(4), record(5)]);
+
+        // the head is found even though a block before it is broken
+        log.append(b"new").unwrap();
+        let mut log = Log::mount(log.unmount().unwrap(), RANGE).unwrap();
+        let all = records(&mut log);
+        assert_eq!(all.last().unwrap(), b"new");
+    }
+
+    #[test]
+    fn unformatted_range_is_empty() {
+        let mut device = RamBlockDevice::new(16);
+        modify_block(&mut device, RANGE.start, |block| {
+            for (i, byte) in block.iter_mut().enumerate() {
+                *byte = i as u8;
+            }
+        });
+        let mut log = Log::mount(device, RANGE).unwrap();
+        assert!(records(&mut log).is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Log::mount(RamBlockDevice::new(16), 4..4).err(),
+            Some(Error::InvalidRange)
+        );
+        assert_eq!(
+            Log::format(RamBlockDevice::new(16), 8..17).err(),
+            Some(Error::InvalidRange)
+        );
+
+        let mut log = Log::format(RamBlockDevice::new(16), RANGE).unwrap();
+        let large = [0; MAX_RECORD_LEN + 1];
+        assert_eq!(log.append(&large), Err(Error::RecordTooLarge));
+        assert_eq!(log.append(&large[..MAX_RECORD_LEN]), Ok(()));
+        log.flush().unwrap();
+        assert_eq!(records(&mut log), vec![large[..MAX_RECORD_LEN].to_vec()]);
+    }
+}
@@ -4,21 +4,42 @@
 
 #![allow(missing_docs)]
 
+pub use self::block_device::{BlockDevice, RamBlockDevice};
+pub use self::card_detect::{enable_card_detect_interrupt, CardDetect, CardEvent};
 pub use self::init::{de_init, init};
+pub use self::registers::{Cid, Csd, Scr, SdStatus, SpecVersion, SwitchStatus};
+pub use self::transfer::{ReadBlocks, SdmmcInterrupts, WriteBlocks};
 
+pub mod block_device;
+mod card_detect;
+mod dma;
 pub mod error;
+pub mod fs;
 mod init;
+pub mod log;
+pub mod registers;
 mod sdmmc_cmd;
+mod transfer;
 
+use self::block_device::BLOCK_SIZE;
 use self::error::*;
 use crate::gpio::InputPin;
 use alloc::vec::Vec;
-use core::cmp::min;
-use stm32f7::stm32f7x6::{RCC, SDMMC1};
+use core::ops::Range;
+use futures::Stream;
+use stm32f7::stm32f7x6::{DMA2, RCC, SDMMC1};
+
+/// The number of 32 bit words in a block of 512 bytes.
+const WORDS_PER_BLOCK: usize = 128;
+
+/// The maximum number of blocks of a single DMA transfer (511). Longer reads and writes are
+/// split into several transfers.
+const MAX_BLOCKS_PER_TRANSFER: u16 = (dma::MAX_WORDS / WORDS_PER_BLOCK) as u16;
 
 /// SD handle.
 pub struct Sd<'a, PresentPin: InputPin + 'a> {
     sdmmc: &'a mut SDMMC1,
+    dma: &'a DMA2,
     card_info: Option<CardInfo>,
     present_pin: &'a PresentPin,
 }
@@ -28,13 +49,15 @@ impl<'a, PresentPin: InputPin> Sd<'a, PresentPin> {
     /// card a seperate call to `sd::init()` is necessary.
     /// This function returns a SD handle whether or not a SD Card is inserted.
     ///
+    /// The data transfers use the streams 3 and 6 of `dma`, which must not be used otherwise.
+    ///
     /// # Examples
     /// ```rust
     /// fn main(hw: board::Hardware) -> ! {
     ///     // Setup board...
     ///
     ///     // Create SD handle
-    ///     let mut sd = sd::Sd::new(sdmmc, &mut gpio, rcc);
+    ///     let mut sd = sd::Sd::new(sdmmc, &dma_2, rcc, &pins.sdcard_present);
     ///     // Initialize SD Card
     ///     if let Some(i_err) = sd::init(&mut sd).err() {
     ///         hprintln!("{:?}", i_err);
@@ -43,11 +66,17 @@ impl<'a, PresentPin: InputPin> Sd<'a, PresentPin> {
     ///     loop {}
     /// }
     /// ```
-    pub fn new(sdmmc: &'a mut SDMMC1, rcc: &mut RCC, present_pin: &'a PresentPin) -> Self {
+    pub fn new(
+        sdmmc: &'a mut SDMMC1,
+        dma: &'a DMA2,
+        rcc: &mut RCC,
+        present_pin: &'a PresentPin,
+    ) -> Self {
         self::init::init_hw(rcc);
 
         Sd {
             sdmmc,
+            dma,
             card_info: None,
             present_pin,
         }
@@ -71,7 +100,7 @@ impl<'a, PresentPin: InputPin> Sd<'a, PresentPin> {
     }
 
     /// Reads `number_of_blks` blocks at address `block_add` from the SD Card. A block has a size of 512
-    /// Byte.
+    /// Byte. More than 511 blocks are read in several transfers.
     ///
     /// # Errors
     ///
@@ -82,7 +111,7 @@ impl<'a, PresentPin: InputPin> Sd<'a, PresentPin> {
     /// fn main(hw: board::Hardware) -> ! {
     ///     // Setup board...
     ///
-    ///     let mut sd = sd::Sd::new(sdmmc, &mut gpio, rcc);
+    ///     let mut sd = sd::Sd::new(sdmmc, &dma_2, rcc, &pins.sdcard_present);
     ///     sd::init(&mut sd).expect("Init failed");
     ///
     ///     match sd.read_blocks(42, 2) {
@@ -97,34 +126,27 @@ impl<'a, PresentPin: InputPin> Sd<'a, PresentPin> {
     /// }
     /// ```
     pub fn read_blocks(&mut self, block_add: u32, number_of_blks: u16) -> Result<Vec<u32>, Error> {
-        // This is a wrapper function for the read_blocks_h() function. The read_blocks_h()
-        // function can only read single blocks from the card, because the multi-block mode of the
-        // SDMMC-Controller doesn't work.
-        let mut data = vec![];
-        for i in 0..u32::from(number_of_blks) {
-            let mut block = self.read_blocks_h(block_add + i, 1, 5000)?;
-            data.append(&mut block);
-        }
+        let mut data = vec![0; usize::from(number_of_blks) * WORDS_PER_BLOCK];
+        self.read_blocks_h(&mut data, block_add, 5000)?;
 
         Ok(data)
     }
 
     /// Writes the content of `data` to `number_of_blks` blocks at address `block_add` to the SD
-    /// Card. A block has a size of 512 Byte. If the `data` slice contains more data, then the
-    /// specified number of blocks can save, the rest of the data will not be written to the card.
-    /// If the `data` slice is empty or contains less data, then the specified number of blocks can
-    /// save, the rest of the blocks get filled with 0s.
+    /// Card. A block has a size of 512 Byte, so `data` must contain exactly 128 words per block.
+    /// More than 511 blocks are written in several transfers.
     ///
     /// # Errors
     ///
-    /// Returns an Error if a command to the SDMMC-Controller fails or a timeout occurs.
+    /// Returns a `LengthMismatch` Error if the length of `data` doesn't match the number of
+    /// blocks. Returns an Error if a command to the SDMMC-Controller fails or a timeout occurs.
     ///
     /// # Examples
     /// ```rust
     /// fn main(hw: board::Hardware) -> ! {
     ///     // Setup board...
     ///
-    ///     let mut sd = sd::Sd::new(sdmmc, &mut gpio, rcc);
+    ///     let mut sd = sd::Sd::new(sdmmc, &dma_2, rcc, &pins.sdcard_present);
     ///     sd::init(&mut sd).expect("Init failed");
     ///
     ///     let data = vec![0; 256];
@@ -142,257 +164,534 @@ impl<'a, PresentPin: InputPin> Sd<'a, PresentPin> {
         block_add: u32,
         number_of_blks: u16,
     ) -> Result<(), Error> {
-        // This is a wrapper function for the write_blocks_h() function. The write_blocks_h()
-        // function can only write single blocks to the card, because the multi-block mode of the
-        // SDMMC-Controller doesn't work.
-        for i in 0..u32::from(number_of_blks) {
-            self.write_blocks_h(
-                &data[min((i as usize) * 128, data.len())..],
-                block_add + i,
-                1,
-                5000,
-            )?;
+        if data.len() != usize::from(number_of_blks) * WORDS_PER_BLOCK {
+            return Err(Error::RWError {
+                t: RWErrorType::LengthMismatch,
+            });
         }
 
-        Ok(())
+        self.write_blocks_h(data, block_add, 5000)
     }
 
-    // This function doesn't support multi-block read. See read_blocks().
-    fn read_blocks_h(
-        &mut self,
+    /// Reads `number_of_blks` blocks at address `block_add` from the SD Card without blocking.
+    ///
+    /// The returned future starts the transfer when it is polled the first time and yields to
+    /// the executor until the transfer is done. It is woken by the SDMMC1 interrupt, which is
+    /// registered by [`SdmmcInterrupts::register`](SdmmcInterrupts::register).
+    ///
+    /// # Errors
+    ///
+    /// The future returns the same Errors as `read_blocks()`. It returns an `InterruptsClosed`
+    /// Error if the interrupt handler was unregistered during the transfer.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let mut sdmmc_interrupts = sd::SdmmcInterrupts::register(interrupt_table, Priority::P1)
+    ///     .expect("registering sdmmc1 interrupt failed");
+    ///
+    /// let task = async move {
+    ///     match await!(sd.read_blocks_async(42, 2, &mut sdmmc_interrupts)) {
+    ///         Ok(data) => println!("{:?}", &data[..8]),
+    ///         Err(r_err) => println!("{:?}", r_err),
+    ///     }
+    /// };
+    /// ```
+    pub fn read_blocks_async<'s>(
+        &'s mut self,
         block_add: u32,
         number_of_blks: u16,
-        timeout: u32,
-    ) -> Result<Vec<u32>, Error> {
-        // No blocks to read -> return empty vector
-        if number_of_blks == 0 {
-            return Ok(vec![]);
+        sdmmc_interrupts: &'s mut SdmmcInterrupts,
+    ) -> ReadBlocks<'s, 'a, PresentPin> {
+        ReadBlocks::new(self, sdmmc_interrupts, block_add, number_of_blks, 5000)
+    }
+
+    /// Writes the content of `data` to `number_of_blks` blocks at address `block_add` to the SD
+    /// Card without blocking. `data` must contain exactly 128 words per block.
+    ///
+    /// The returned future is woken by the SDMMC1 interrupt while the data is transferred, see
+    /// `read_blocks_async()`. After the transfer it polls the card status whenever it runs, until
+    /// the card has programmed the data.
+    ///
+    /// # Errors
+    ///
+    /// The future returns the same Errors as `write_blocks()` and `read_blocks_async()`.
+    pub fn write_blocks_async<'s, 'd>(
+        &'s mut self,
+        data: &'d [u32],
+        block_add: u32,
+        number_of_blks: u16,
+        sdmmc_interrupts: &'s mut SdmmcInterrupts,
+    ) -> WriteBlocks<'s, 'a, 'd, PresentPin> {
+        WriteBlocks::new(self, sdmmc_interrupts, data, block_add, number_of_blks, 5000)
+    }
+
+    /// Returns a stream of card insertions and removals. The present pin is checked whenever
+    /// `interrupts` yields an item, see `CardDetect` for details.
+    pub fn card_detect<S>(&self, interrupts: S) -> CardDetect<'a, PresentPin, S>
+    where
+        S: Stream<Item = ()> + Unpin,
+    {
+        CardDetect::new(self.present_pin, interrupts)
+    }
+
+    /// Erases the blocks in the range `blocks`. Erased blocks read as all 0 or all 1, depending on
+    /// `Scr::data_stat_after_erase`.
+    ///
+    /// Erasing whole allocation units (see `sd_status()`) before writing them leads to the most
+    /// predictable write latency.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `WriteProtected` Error if the card or one of the blocks is write protected.
+    /// Returns an Error if the blocks are out of range, a command to the SDMMC-Controller fails
+    /// or a timeout occurs.
+    ///
+    /// # Examples
+    /// ```rust
+    /// fn main(hw: board::Hardware) -> ! {
+    ///     // Setup board...
+    ///
+    ///     let mut sd = sd::Sd::new(sdmmc, &dma_2, rcc, &pins.sdcard_present);
+    ///     sd::init(&mut sd).expect("Init failed");
+    ///
+    ///     // erase the first allocation unit
+    ///     let au_blocks = sd.sd_status().expect("ACMD13 failed").au_size / 512;
+    ///     if let Some(e_err) = sd.erase(0..au_blocks).err() {
+    ///         hprintln!("{:?}", e_err);
+    ///     }
+    ///
+    ///     loop {}
+    /// }
+    /// ```
+    pub fn erase(&mut self, blocks: Range<u32>) -> Result<(), Error> {
+        // No blocks to erase -> nothing to do
+        if blocks.start >= blocks.end {
+            return Ok(());
         }
+        let number_of_blks = blocks.end - blocks.start;
+        let start_add = self.prepare_transfer(blocks.start, number_of_blks)?;
+        if self.write_protected()? {
+            return Err(Error::WriteProtected);
+        }
+        let end_add = match self.card_info {
+            Some(ref card_info) => card_info.card_address(blocks.end - 1),
+            None => return Err(Error::NoSdCard),
+        };
+
+        sdmmc_cmd::erase_start(self.sdmmc, start_add)?;
+        sdmmc_cmd::erase_end(self.sdmmc, end_add)?;
+        sdmmc_cmd::erase(self.sdmmc)?;
+
+        // The card is busy until the blocks are erased, allow 250 ms per started 4 MiB.
+        self.wait_until_ready(5000 + 250 * (number_of_blks / 8192 + 1))
+    }
+
+    /// Returns true if the card is write protected, either by the write protection bits of the
+    /// CSD register or because the card is locked with a password.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `NoSdCard` Error if the card is not initialized. Returns an Error if reading
+    /// the card status fails.
+    pub fn write_protected(&mut self) -> Result<bool, Error> {
+        let card_info = self.card_info.as_ref().ok_or(Error::NoSdCard)?;
+        if card_info.csd.write_protected() {
+            return Ok(true);
+        }
+
+        let status = sdmmc_cmd::send_status(self.sdmmc, u32::from(card_info.rca) << 16)?;
+
+        Ok(status & CardStatusFlags::CARD_IS_LOCKED.bits() != 0)
+    }
+
+    /// Reads the SD Status of the card (ACMD13), which contains the speed class and the size of
+    /// the allocation units.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `NoSdCard` Error if no card is inserted or the card is not initialized. Returns
+    /// an Error if a command to the SDMMC-Controller fails or a timeout occurs.
+    pub fn sd_status(&mut self) -> Result<SdStatus, Error> {
+        if !self.card_present() {
+            return Err(Error::NoSdCard);
+        }
+        let rca = u32::from(self.card_info.as_ref().ok_or(Error::NoSdCard)?.rca) << 16;
+
+        let mut status = [0; 16];
+        read_data(
+            self.sdmmc,
+            |sdmmc| {
+                sdmmc_cmd::app(sdmmc, rca)?;
+                sdmmc_cmd::app_sd_status(sdmmc)
+            },
+            &mut status,
+        )?;
+
+        Ok(SdStatus::from_raw(status))
+    }
+
+    // Checks that the card is ready and the blocks are in bounds. Returns the address that has
+    // to be sent to the card.
+    fn prepare_transfer(&mut self, block_add: u32, number_of_blks: u32) -> Result<u32, Error> {
         // Check if a SD Card is inserted.
         if !self.card_present() {
             return Err(Error::NoSdCard);
         }
-        let mut block_add = block_add;
-        let card_info = self.card_info.as_ref().unwrap();
+        let card_info = self.card_info.as_ref().ok_or(Error::NoSdCard)?;
 
-        // Check if the blocks to read are in bounds.
-        if block_add + u32::from(number_of_blks) > card_info.log_blk_number {
+        // Check if the blocks are in bounds.
+        if u64::from(block_add) + u64::from(number_of_blks) > u64::from(card_info.log_blk_number) {
             return Err(Error::RWError {
                 t: RWErrorType::AddressOutOfRange,
             });
         }
 
-        // On high capacity cards the block_add has to be in bytes and not the block number itself.
-        if card_info.card_type == CardType::SDv2HC {
-            block_add *= card_info.log_blk_size;
-        }
-
-        // Tell the sdmmc the block length...
-        sdmmc_cmd::block_length(self.sdmmc, card_info.log_blk_size)?;
-        // ...and if a single or multiple block should be read
-        // TODO: multi-block read doesn't seem to work with the SDMMC-Controller
-        if number_of_blks > 1 {
-            sdmmc_cmd::read_multi_blk(self.sdmmc, block_add)?;
-        } else {
-            sdmmc_cmd::read_single_blk(self.sdmmc, block_add)?;
-        }
+        Ok(card_info.card_address(block_add))
+    }
 
-        // Set up the Data Path State Machine (DPSM)
-        let data_length = u32::from(number_of_blks) * card_info.log_blk_size;
-        self.sdmmc
-            .dlen
-            .modify(|_, w| unsafe { w.datalength().bits(data_length) });
+    // Set up the Data Path State Machine (DPSM) for a DMA transfer of `number_of_blks` blocks.
+    fn start_data_path(&mut self, number_of_blks: u16, read: bool) {
+        let data_length = u32::from(number_of_blks) * BLOCK_SIZE as u32;
         self.sdmmc
             .dtimer
             .modify(|_, w| unsafe { w.datatime().bits(0xFFFF_FFFF) });
+        self.sdmmc
+            .dlen
+            .modify(|_, w| unsafe { w.datalength().bits(data_length) });
         self.sdmmc.dctrl.modify(|_, w| {
             unsafe { w.dblocksize().bits(0x09) }; // blocksize = 2^n => blocksize = 2^9 = 512
-            w.dtdir().set_bit(); // direction: false -> write, true -> read
+            w.dtdir().bit(read); // direction: false -> write, true -> read
             w.dtmode().clear_bit(); // mode: false -> block, true -> stream
+            w.dmaen().set_bit(); // the FIFO is served by DMA2
             w.dten().set_bit(); // enable data transfer
             w
         });
+    }
 
-        // Read data from the SD Card, until dataend is reached or an error occurs
-        let mut data = vec![];
+    // Waits until the data path reached dataend and the DMA stream finished, then stops the
+    // transfer.
+    fn finish_data_path(
+        &mut self,
+        direction: dma::Direction,
+        number_of_blks: u16,
+        timeout: u32,
+    ) -> Result<(), Error> {
         let timeout = crate::system_clock::ms() as u32 + timeout;
-        while (crate::system_clock::ms() as u32) < timeout
-            && self.sdmmc.sta.read().rxoverr().bit_is_clear()
-            && self.sdmmc.sta.read().dcrcfail().bit_is_clear()
-            && self.sdmmc.sta.read().dtimeout().bit_is_clear()
-            && self.sdmmc.sta.read().dataend().bit_is_clear()
-        {
-            if self.sdmmc.sta.read().rxfifohf().bit_is_set() {
-                for _ in 0..8 {
-                    data.push(self.sdmmc.fifo.read().fifodata().bits());
-                }
+        let result = loop {
+            if (crate::system_clock::ms() as u32) >= timeout {
+                break Err(Error::Timeout);
             }
-        }
+            match self.data_path_finished(direction) {
+                Ok(false) => {}
+                Ok(true) => break Ok(()),
+                Err(err) => break Err(err),
+            }
+        };
 
-        if (crate::system_clock::ms() as u32) >= timeout {
-            return Err(Error::Timeout);
-        }
+        self.stop_data_path(direction, number_of_blks, result)
+    }
 
-        // Needed in multi-block mode to stop the transmission.
-        if self.sdmmc.sta.read().dataend().bit_is_set() && number_of_blks > 1 {
-            sdmmc_cmd::stop_transfer(self.sdmmc)?;
-        }
+    // Stops multi-block transfers with CMD12, disables the DMA stream and the DPSM and clears
+    // all flags. Returns `result` or the error of CMD12.
+    fn stop_data_path(
+        &mut self,
+        direction: dma::Direction,
+        number_of_blks: u16,
+        result: Result<(), Error>,
+    ) -> Result<(), Error> {
+        // Needed in multi-block mode to stop the transmission, even if the transfer failed.
+        let stop_result = if number_of_blks > 1 {
+            sdmmc_cmd::stop_transfer(self.sdmmc)
+        } else {
+            Ok(())
+        };
+
+        dma::stop(self.dma, direction);
+        dma::clear_flags(self.dma, direction);
+        self.sdmmc.dctrl.modify(|_, w| {
+            w.dmaen().clear_bit();
+            w.dten().clear_bit();
+            w
+        });
+        sdmmc_cmd::clear_all_static_status_flags(self.sdmmc);
+
+        result.and(stop_result)
+    }
 
-        // Check for errors
-        if self.sdmmc.sta.read().dtimeout().bit_is_set() {
-            sdmmc_cmd::clear_all_static_status_flags(self.sdmmc);
+    // Returns true if the data path reached dataend and the DMA stream finished. Returns an
+    // Error if the controller or the DMA stream reported a failure.
+    fn data_path_finished(&mut self, direction: dma::Direction) -> Result<bool, Error> {
+        let sta = self.sdmmc.sta.read();
+        if sta.dtimeout().bit_is_set() {
             return Err(Error::RWError {
                 t: RWErrorType::DataTimeout,
             });
         }
-        if self.sdmmc.sta.read().dcrcfail().bit_is_set() {
-            sdmmc_cmd::clear_all_static_status_flags(self.sdmmc);
+        if sta.dcrcfail().bit_is_set() {
             return Err(Error::RWError {
                 t: RWErrorType::DataCrcFailed,
             });
         }
-        if self.sdmmc.sta.read().rxoverr().bit_is_set() {
-            sdmmc_cmd::clear_all_static_status_flags(self.sdmmc);
+        if sta.rxoverr().bit_is_set() {
             return Err(Error::RWError {
                 t: RWErrorType::RxOverrun,
             });
         }
+        if sta.txunderr().bit_is_set() {
+            return Err(Error::RWError {
+                t: RWErrorType::TxUnderrun,
+            });
+        }
+        if dma::has_error(self.dma, direction) {
+            return Err(Error::RWError {
+                t: RWErrorType::DmaError,
+            });
+        }
 
-        // If there is still valid data in the FIFO, empty the FIFO
-        while (crate::system_clock::ms() as u32) < timeout
-            && self.sdmmc.sta.read().rxdavl().bit_is_set()
-        {
-            data.push(self.sdmmc.fifo.read().fifodata().bits());
+        Ok(sta.dataend().bit_is_set() && dma::is_complete(self.dma, direction))
+    }
+
+    // Reads the blocks in transfers of at most `MAX_BLOCKS_PER_TRANSFER` blocks. `data` must
+    // hold a whole number of blocks.
+    fn read_blocks_h(
+        &mut self,
+        data: &mut [u32],
+        block_add: u32,
+        timeout: u32,
+    ) -> Result<(), Error> {
+        let mut block_add = block_add;
+        for chunk in data.chunks_mut(usize::from(MAX_BLOCKS_PER_TRANSFER) * WORDS_PER_BLOCK) {
+            let number_of_blks = (chunk.len() / WORDS_PER_BLOCK) as u16;
+            self.begin_read(chunk, block_add, number_of_blks)?;
+            self.finish_data_path(dma::Direction::Rx, number_of_blks, timeout)?;
+            block_add += u32::from(number_of_blks);
         }
 
-        if (crate::system_clock::ms() as u32) >= timeout {
-            return Err(Error::Timeout);
+        Ok(())
+    }
+
+    // Writes the blocks in transfers of at most `MAX_BLOCKS_PER_TRANSFER` blocks. `data` must
+    // hold a whole number of blocks.
+    fn write_blocks_h(&mut self, data: &[u32], block_add: u32, timeout: u32) -> Result<(), Error> {
+        let mut block_add = block_add;
+        for chunk in data.chunks(usize::from(MAX_BLOCKS_PER_TRANSFER) * WORDS_PER_BLOCK) {
+            let number_of_blks = (chunk.len() / WORDS_PER_BLOCK) as u16;
+            self.begin_write(chunk, block_add, number_of_blks)?;
+            self.finish_data_path(dma::Direction::Tx, number_of_blks, timeout)?;
+
+            // The card is busy until the data is programmed.
+            self.wait_until_ready(timeout)?;
+            block_add += u32::from(number_of_blks);
         }
 
+        Ok(())
+    }
+
+    // Starts the DMA and the DPSM and sends the read command. `data` must hold exactly
+    // `number_of_blks` blocks, which must not be more than `MAX_BLOCKS_PER_TRANSFER`, and stay
+    // valid until the data path is stopped.
+    fn begin_read(
+        &mut self,
+        data: &mut [u32],
+        block_add: u32,
+        number_of_blks: u16,
+    ) -> Result<(), Error> {
+        let card_add = self.prepare_transfer(block_add, u32::from(number_of_blks))?;
+
         sdmmc_cmd::clear_all_static_status_flags(self.sdmmc);
+        self.sdmmc.dctrl.write(|w| w);
+
+        // The DMA and the DPSM have to be ready before the card starts sending.
+        dma::start(
+            self.dma,
+            self.sdmmc,
+            dma::Direction::Rx,
+            data.as_mut_ptr() as usize,
+            data.len(),
+        );
+        self.start_data_path(number_of_blks, true);
+
+        let cmd_result = if number_of_blks > 1 {
+            sdmmc_cmd::read_multi_blk(self.sdmmc, card_add)
+        } else {
+            sdmmc_cmd::read_single_blk(self.sdmmc, card_add)
+        };
+        if let Err(err) = cmd_result {
+            dma::stop(self.dma, dma::Direction::Rx);
+            self.sdmmc.dctrl.write(|w| w);
+            return Err(err);
+        }
 
-        Ok(data)
+        Ok(())
     }
 
-    // This function doesn't support multi-block write. See write_blocks().
-    fn write_blocks_h(
+    // Sends the write command and starts the DMA and the DPSM. `data` must hold exactly
+    // `number_of_blks` blocks, which must not be more than `MAX_BLOCKS_PER_TRANSFER`, and stay
+    // valid until the data path is stopped.
+    fn begin_write(
         &mut self,
         data: &[u32],
         block_add: u32,
         number_of_blks: u16,
-        timeout: u32,
     ) -> Result<(), Error> {
-        // No blocks to read -> return empty vector
-        if number_of_blks == 0 {
-            return Ok(());
-        }
-        // Check if a SD Card is inserted.
-        if !self.card_present() {
-            return Err(Error::NoSdCard);
+        let card_add = self.prepare_transfer(block_add, u32::from(number_of_blks))?;
+        // Only the write protection of the CSD is checked here, a locked card or a protected
+        // group makes the card reject the data, which is reported by CMD12 or CMD13.
+        let csd_protected = self.card_info.as_ref().map(|info| info.csd.write_protected());
+        if csd_protected == Some(true) {
+            return Err(Error::WriteProtected);
         }
-        let mut block_add = block_add;
-        let card_info = self.card_info.as_ref().unwrap();
 
-        // Check if the blocks to read are in bounds.
-        if block_add + u32::from(number_of_blks) > card_info.log_blk_number {
-            return Err(Error::RWError {
-                t: RWErrorType::AddressOutOfRange,
-            });
-        }
-
-        // On high capacity cards the block_add has to be in bytes and not the block number itself.
-        if card_info.card_type == CardType::SDv2HC {
-            block_add *= card_info.log_blk_size;
-        }
+        sdmmc_cmd::clear_all_static_status_flags(self.sdmmc);
+        self.sdmmc.dctrl.write(|w| w);
 
-        // Tell the sdmmc the block length...
-        sdmmc_cmd::block_length(self.sdmmc, card_info.log_blk_size)?;
-        // ...and if a single or multiple block should be written
-        // TODO: multi-block write doesn't seem to work with the SDMMC-Controller
+        // Tell the card that a single or multiple blocks should be written...
         if number_of_blks > 1 {
-            sdmmc_cmd::write_multi_blk(self.sdmmc, block_add)?;
+            sdmmc_cmd::write_multi_blk(self.sdmmc, card_add)?;
         } else {
-            sdmmc_cmd::write_single_blk(self.sdmmc, block_add)?;
+            sdmmc_cmd::write_single_blk(self.sdmmc, card_add)?;
         }
 
-        // Set up the Data Path State Machine (DPSM)
-        let data_length = u32::from(number_of_blks) * card_info.log_blk_size;
-        self.sdmmc
-            .dlen
-            .modify(|_, w| unsafe { w.datalength().bits(data_length) });
-        self.sdmmc
-            .dtimer
-            .modify(|_, w| unsafe { w.datatime().bits(0xFFFF_FFFF) });
-        self.sdmmc.dctrl.modify(|_, w| {
-            unsafe { w.dblocksize().bits(0x09) }; // blocksize = 2^n => blocksize = 2^9 = 512
-            w.dtdir().clear_bit(); // direction: false -> write, true -> read
-            w.dtmode().clear_bit(); // mode: false -> block, true -> stream
-            w.dten().set_bit(); // enable data transfer
-            w
-        });
+        // ...and start feeding the FIFO
+        dma::start(
+            self.dma,
+            self.sdmmc,
+            dma::Direction::Tx,
+            data.as_ptr() as usize,
+            data.len(),
+        );
+        self.start_data_path(number_of_blks, false);
+
+        Ok(())
+    }
 
-        // Write data to the SD Card, until dataend is reached or an error occurs
-        let mut data_counter = 0;
+    // Polls the card status (CMD13) until the card is ready for new data.
+    fn wait_until_ready(&mut self, timeout: u32) -> Result<(), Error> {
         let timeout = crate::system_clock::ms() as u32 + timeout;
-        while (crate::system_clock::ms() as u32) < timeout
-            && self.sdmmc.sta.read().txunderr().bit_is_clear()
-            && self.sdmmc.sta.read().dcrcfail().bit_is_clear()
-            && self.sdmmc.sta.read().dtimeout().bit_is_clear()
-            && self.sdmmc.sta.read().dataend().bit_is_clear()
-        {
-            if self.sdmmc.sta.read().txfifohe().bit_is_set() {
-                // If there is no more data to write, but the sdmmc controller has not reached
-                // dataend yet, write 0s to the FIFO
-                let mut pad_data: &[u32] = &[0; 8][..];
-                if data_counter < data.len() {
-                    pad_data = &data[data_counter..min(data_counter + 8, data.len())];
-                    data_counter += 8;
-                }
-                for d in pad_data {
-                    self.sdmmc
-                        .fifo
-                        .modify(|_, w| unsafe { w.fifodata().bits(*d) });
-                }
+        while (crate::system_clock::ms() as u32) < timeout {
+            if self.card_ready()? {
+                return Ok(());
             }
         }
 
-        if (crate::system_clock::ms() as u32) >= timeout {
-            return Err(Error::Timeout);
-        }
+        Err(Error::Timeout)
+    }
 
-        // Needed in multi-block mode to stop the transmission
-        if self.sdmmc.sta.read().dataend().bit_is_set() && number_of_blks > 1 {
-            sdmmc_cmd::stop_transfer(self.sdmmc)?;
-        }
+    // Returns true if the card reports that it is ready for new data (CMD13).
+    fn card_ready(&mut self) -> Result<bool, Error> {
+        let rca = u32::from(self.card_info.as_ref().ok_or(Error::NoSdCard)?.rca) << 16;
+        let status = sdmmc_cmd::send_status(self.sdmmc, rca)?;
+
+        Ok(status & CardStatusFlags::READY_FOR_DATA.bits() != 0)
+    }
+
+    // Unmasks the SDMMC interrupts that signal the end or the failure of a data transfer.
+    fn enable_data_interrupts(&mut self) {
+        self.sdmmc.mask.modify(|_, w| {
+            w.dataendie().set_bit();
+            w.dcrcfailie().set_bit();
+            w.dtimeoutie().set_bit();
+            w.rxoverrie().set_bit();
+            w.txunderrie().set_bit();
+            w
+        });
+    }
+}
 
-        // Wait a bit for the controller to end the write process.
-        let wait = crate::system_clock::ms() + 100;
-        while crate::system_clock::ms() < wait {}
+// Reads the data block that the card sends in response to the command(s) sent by `cmd` by
+// polling the FIFO. The size of `data` must be a power of two. The card must be in the transfer
+// state.
+//
+// The block length of the card is set to the size of `data` and restored to 512 bytes
+// afterwards, which is the block length that the block transfers rely on.
+fn read_data<F>(sdmmc: &mut SDMMC1, cmd: F, data: &mut [u32]) -> Result<(), Error>
+where
+    F: FnOnce(&mut SDMMC1) -> Result<(), Error>,
+{
+    let block_size = (data.len() * 4) as u32;
+    sdmmc_cmd::block_length(sdmmc, block_size)?;
+
+    let result = read_fifo(sdmmc, cmd, data);
+    let restore_result = sdmmc_cmd::block_length(sdmmc, BLOCK_SIZE as u32);
+
+    result.and(restore_result)
+}
 
-        // Check for errors
-        if self.sdmmc.sta.read().dtimeout().bit_is_set() {
-            sdmmc_cmd::clear_all_static_status_flags(self.sdmmc);
+// Sends the command(s) and polls the FIFO for a single block of `data.len()` words.
+fn read_fifo<F>(sdmmc: &mut SDMMC1, cmd: F, data: &mut [u32]) -> Result<(), Error>
+where
+    F: FnOnce(&mut SDMMC1) -> Result<(), Error>,
+{
+    let block_size = (data.len() * 4) as u32;
+
+    // Set up the DPSM for a single block, which is read without DMA.
+    sdmmc
+        .dtimer
+        .modify(|_, w| unsafe { w.datatime().bits(0xFFFF_FFFF) });
+    sdmmc
+        .dlen
+        .modify(|_, w| unsafe { w.datalength().bits(block_size) });
+    sdmmc.dctrl.write(|w| {
+        // blocksize = 2^n
+        unsafe { w.dblocksize().bits(block_size.trailing_zeros() as u8) };
+        w.dtdir().set_bit(); // direction: read
+        w.dtmode().clear_bit(); // mode: block
+        w.dten().set_bit(); // enable data transfer
+        w
+    });
+
+    cmd(sdmmc)?;
+
+    let mut words = 0;
+    let timeout = crate::system_clock::ms() as u32 + 5000;
+    loop {
+        if (crate::system_clock::ms() as u32) >= timeout {
+            return Err(Error::Timeout);
+        }
+        let sta = sdmmc.sta.read();
+        if sta.dtimeout().bit_is_set() {
             return Err(Error::RWError {
                 t: RWErrorType::DataTimeout,
             });
         }
-        if self.sdmmc.sta.read().dcrcfail().bit_is_set() {
-            sdmmc_cmd::clear_all_static_status_flags(self.sdmmc);
+        if sta.dcrcfail().bit_is_set() {
             return Err(Error::RWError {
                 t: RWErrorType::DataCrcFailed,
             });
         }
-        if self.sdmmc.sta.read().txunderr().bit_is_set() {
-            sdmmc_cmd::clear_all_static_status_flags(self.sdmmc);
+        if sta.rxoverr().bit_is_set() {
             return Err(Error::RWError {
-                t: RWErrorType::TxUnderrun,
+                t: RWErrorType::RxOverrun,
             });
         }
+        if sta.rxdavl().bit_is_set() {
+            let word = sdmmc.fifo.read().fifodata().bits();
+            if words < data.len() {
+                // The registers are sent most significant byte first, but the FIFO stores the
+                // first received byte in the lowest 8 bits of a word.
+                data[words] = word.swap_bytes();
+                words += 1;
+            }
+        } else if sta.dbckend().bit_is_set() {
+            break;
+        }
+    }
 
-        sdmmc_cmd::clear_all_static_status_flags(self.sdmmc);
+    sdmmc_cmd::clear_all_static_status_flags(sdmmc);
 
-        Ok(())
-    }
+    Ok(())
+}
+
+// Masks all SDMMC1 interrupts.
+//
+// This function is called by the SDMMC1 interrupt handler, because the status flags that
+// triggered the interrupt are only cleared by the transfer future that is woken by the handler.
+// The future unmasks the interrupts again before it waits for the next one.
+fn mask_interrupts() {
+    // The only register accessed is MASK, which is not touched by a transfer future while the
+    // interrupt is enabled.
+    let sdmmc = unsafe { &*SDMMC1::ptr() };
+    sdmmc.mask.reset();
 }
 
 /// Different SD card versions.
@@ -406,6 +705,15 @@ pub enum CardType {
     SDv2HC,
 }
 
+/// The bus speed modes of SD cards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusSpeed {
+    /// Default Speed, up to 25 MHz (12.5 MB/s)
+    Default,
+    /// High Speed, up to 50 MHz (25 MB/s). The SDMMC-Controller runs at 48 MHz in this mode.
+    HighSpeed,
+}
+
 /// Various information about the SD card.
 #[derive(Debug)]
 pub struct CardInfo {
@@ -421,6 +729,76 @@ pub struct CardInfo {
     log_blk_number: u32,
     /// Logical block size
     log_blk_size: u32,
+    /// The negotiated bus speed mode
+    bus_speed: BusSpeed,
+    /// Card Identification register
+    cid: Cid,
+    /// Card Specific Data register
+    csd: Csd,
+    /// SD Configuration Register
+    scr: Scr,
+}
+
+impl CardInfo {
+    /// Returns the type of the card.
+    pub fn card_type(&self) -> CardType {
+        self.card_type
+    }
+
+    /// Returns the Relative Card Address (RCA).
+    pub fn rca(&self) -> u16 {
+        self.rca
+    }
+
+    /// Returns the number of physical blocks.
+    pub fn blk_number(&self) -> u32 {
+        self.blk_number
+    }
+
+    /// Returns the size of a physical block in bytes.
+    pub fn blk_size(&self) -> u32 {
+        self.blk_size
+    }
+
+    /// Returns the number of logical blocks, which are always 512 bytes large.
+    pub fn log_blk_number(&self) -> u32 {
+        self.log_blk_number
+    }
+
+    /// Returns the size of a logical block in bytes.
+    pub fn log_blk_size(&self) -> u32 {
+        self.log_blk_size
+    }
+
+    /// Returns the bus speed mode that was negotiated during the initialization.
+    pub fn bus_speed(&self) -> BusSpeed {
+        self.bus_speed
+    }
+
+    /// Returns the decoded Card Identification register.
+    pub fn cid(&self) -> &Cid {
+        &self.cid
+    }
+
+    /// Returns the decoded Card Specific Data register.
+    pub fn csd(&self) -> &Csd {
+        &self.csd
+    }
+
+    /// Returns the decoded SD Configuration Register.
+    pub fn scr(&self) -> &Scr {
+        &self.scr
+    }
+
+    // Returns the address of a block that is sent to the card. On standard capacity cards the
+    // address is in bytes and not the block number itself.
+    fn card_address(&self, block_add: u32) -> u32 {
+        if self.card_type == CardType::SDv2HC {
+            block_add
+        } else {
+            block_add * self.log_blk_size
+        }
+    }
 }
 
 impl Default for CardInfo {
@@ -432,6 +810,10 @@ impl Default for CardInfo {
             blk_size: 0,
             log_blk_number: 0,
             log_blk_size: 0,
+            bus_speed: BusSpeed::Default,
+            cid: Cid::default(),
+            csd: Csd::default(),
+            scr: Scr::default(),
         }
     }
 }
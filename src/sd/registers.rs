@@ -0,0 +1,469 @@
+//! Decoding of the card registers CID, CSD and SCR, of the SD Status and of the switch function
+//! status.
+//!
+//! The raw registers are passed most significant word first, as they are read from the response
+//! registers `RESP1` to `RESP4` of the SDMMC-Controller. See the SD Physical Layer Simplified
+//! Specification, chapter 5, for the register layouts.
+
+use core::str;
+
+/// Card Identification register.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Cid {
+    /// Manufacturer ID, assigned by the SD-3C
+    pub manufacturer_id: u8,
+    /// OEM/Application ID, two ASCII characters
+    pub oem_id: [u8; 2],
+    /// Product name, five ASCII characters
+    pub product_name: [u8; 5],
+    /// Major product revision
+    pub revision_major: u8,
+    /// Minor product revision
+    pub revision_minor: u8,
+    /// Product serial number
+    pub serial_number: u32,
+    /// Manufacturing year
+    pub manufacturing_year: u16,
+    /// Manufacturing month, 1 is January
+    pub manufacturing_month: u8,
+}
+
+impl Cid {
+    /// Decodes the 128 bit CID register.
+    pub fn from_raw(raw: [u32; 4]) -> Cid {
+        let bits = |msb, lsb| bits(&raw, msb, lsb);
+        let revision = bits(63, 56) as u8;
+        Cid {
+            manufacturer_id: bits(127, 120) as u8,
+            oem_id: [bits(119, 112) as u8, bits(111, 104) as u8],
+            product_name: [
+                bits(103, 96) as u8,
+                bits(95, 88) as u8,
+                bits(87, 80) as u8,
+                bits(79, 72) as u8,
+                bits(71, 64) as u8,
+            ],
+            revision_major: revision >> 4,
+            revision_minor: revision & 0xF,
+            serial_number: bits(55, 24),
+            manufacturing_year: 2000 + bits(19, 12) as u16,
+            manufacturing_month: bits(11, 8) as u8,
+        }
+    }
+
+    /// Returns the OEM/Application ID as string or an empty string if it isn't valid ASCII.
+    pub fn oem_id_str(&self) -> &str {
+        str::from_utf8(&self.oem_id).unwrap_or("")
+    }
+
+    /// Returns the product name as string or an empty string if it isn't valid ASCII.
+    pub fn product_name_str(&self) -> &str {
+        str::from_utf8(&self.product_name).unwrap_or("")
+    }
+}
+
+/// Card Specific Data register.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Csd {
+    /// CSD structure version, 1 for standard capacity and 2 for high or extended capacity
+    pub version: u8,
+    /// Number of blocks of the size `block_len`
+    pub block_count: u32,
+    /// Maximum read block length in bytes
+    pub block_len: u32,
+    /// Maximum write block length in bytes
+    pub write_block_len: u32,
+    /// Maximum data transfer rate per data line in kbit/s
+    pub max_transfer_rate: u32,
+    /// Supported command classes, bit n is set if class n is supported
+    pub command_classes: u16,
+    /// True if single blocks can be erased, otherwise only whole sectors can be erased
+    pub erase_single_block: bool,
+    /// Size of an erasable sector in write blocks
+    pub erase_sector_size: u32,
+    /// True if group write protection is supported
+    pub write_protect_group_enable: bool,
+    /// Size of a write protect group in erase sectors
+    pub write_protect_group_size: u32,
+    /// The card is permanently write protected
+    pub permanent_write_protect: bool,
+    /// The card is temporarily write protected
+    pub temporary_write_protect: bool,
+}
+
+impl Csd {
+    /// Decodes the 128 bit CSD register. Both CSD version 1.0 and 2.0 are supported.
+    pub fn from_raw(raw: [u32; 4]) -> Csd {
+        let bits = |msb, lsb| bits(&raw, msb, lsb);
+        let version = bits(127, 126) as u8 + 1;
+
+        let (block_count, block_len) = if version == 1 {
+            let device_size = bits(73, 62);
+            let device_size_mul = bits(49, 47);
+            (
+                (device_size + 1) << (device_size_mul + 2),
+                1 << bits(83, 80),
+            )
+        } else {
+            // The capacity is (C_SIZE + 1) * 512 KiB
+            let device_size = bits(69, 48);
+            ((device_size + 1) * 1024, 512)
+        };
+
+        Csd {
+            version,
+            block_count,
+            block_len,
+            write_block_len: 1 << bits(25, 22),
+            max_transfer_rate: transfer_rate(bits(103, 96) as u8),
+            command_classes: bits(95, 84) as u16,
+            erase_single_block: bits(46, 46) == 1,
+            erase_sector_size: bits(45, 39) + 1,
+            write_protect_group_enable: bits(31, 31) == 1,
+            write_protect_group_size: bits(38, 32) + 1,
+            permanent_write_protect: bits(13, 13) == 1,
+            temporary_write_protect: bits(12, 12) == 1,
+        }
+    }
+
+    /// Returns the capacity of the card in bytes.
+    pub fn capacity(&self) -> u64 {
+        u64::from(self.block_count) * u64::from(self.block_len)
+    }
+
+    /// Returns true if the card is either permanently or temporarily write protected.
+    pub fn write_protected(&self) -> bool {
+        self.permanent_write_protect || self.temporary_write_protect
+    }
+}
+
+/// SD Configuration Register.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Scr {
+    /// The version of the physical layer specification the card supports
+    pub spec_version: SpecVersion,
+    /// True if the card supports the 1 bit data bus
+    pub bus_width_1: bool,
+    /// True if the card supports the 4 bit data bus
+    pub bus_width_4: bool,
+    /// The value of the data after an erase, either all 0 or all 1
+    pub data_stat_after_erase: u8,
+    /// The security version the card supports (CPRM)
+    pub security: u8,
+    /// True if the card supports CMD23 (set block count)
+    pub set_block_count: bool,
+    /// True if the card supports CMD20 (speed class control)
+    pub speed_class_control: bool,
+}
+
+/// Versions of the SD physical layer specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecVersion {
+    /// Version 1.0 and 1.01
+    V1_0,
+    /// Version 1.10
+    V1_10,
+    /// Version 2.00
+    V2,
+    /// Version 3.0x
+    V3,
+    /// Version 4.xx
+    V4,
+    /// Version 5.xx
+    V5,
+    /// Version 6.xx
+    V6,
+    /// Version 7.xx
+    V7,
+    /// Version 8.xx
+    V8,
+    /// A combination of the version fields that isn't defined
+    Unknown,
+}
+
+impl Default for SpecVersion {
+    fn default() -> SpecVersion {
+        SpecVersion::V1_0
+    }
+}
+
+impl Scr {
+    /// Decodes the 64 bit SCR register.
+    pub fn from_raw(raw: [u32; 2]) -> Scr {
+        let bits = |msb, lsb| bits(&raw, msb, lsb);
+
+        let spec_version = match (bits(59, 56), bits(47, 47), bits(42, 42), bits(41, 38)) {
+            (0, 0, 0, 0) => SpecVersion::V1_0,
+            (1, 0, 0, 0) => SpecVersion::V1_10,
+            (2, 0, 0, 0) => SpecVersion::V2,
+            (2, 1, 0, 0) => SpecVersion::V3,
+            (2, 1, 1, 0) => SpecVersion::V4,
+            (2, 1, _, 1) => SpecVersion::V5,
+            (2, 1, _, 2) => SpecVersion::V6,
+            (2, 1, _, 3) => SpecVersion::V7,
+            (2, 1, _, 4) => SpecVersion::V8,
+            _ => SpecVersion::Unknown,
+        };
+        let bus_widths = bits(51, 48);
+        let command_support = bits(33, 32);
+
+        Scr {
+            spec_version,
+            bus_width_1: bus_widths & 0b0001 != 0,
+            bus_width_4: bus_widths & 0b0100 != 0,
+            data_stat_after_erase: bits(55, 55) as u8,
+            security: bits(54, 52) as u8,
+            set_block_count: command_support & 0b10 != 0,
+            speed_class_control: command_support & 0b01 != 0,
+        }
+    }
+}
+
+/// SD Status, which is read with ACMD13.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SdStatus {
+    /// The currently used data bus width in bits
+    pub bus_width: u8,
+    /// True if the card is in secured mode
+    pub secured_mode: bool,
+    /// The size of the protected area. In bytes for high capacity cards, in units of
+    /// `C_SIZE_MULT * READ_BL_LEN` for standard capacity cards.
+    pub protected_area_size: u32,
+    /// The speed class of the card, e.g. 10 for class 10. Zero if the class is unknown.
+    pub speed_class: u8,
+    /// Performance of moving allocation units in MB/s, 0 means infinity
+    pub performance_move: u8,
+    /// The size of an allocation unit (AU) in bytes. Zero if the size is not defined.
+    pub au_size: u32,
+    /// The number of AUs that are erased at a time, zero if the erase timeout isn't supported
+    pub erase_size: u16,
+    /// The timeout in seconds for erasing `erase_size` AUs
+    pub erase_timeout: u8,
+    /// A fixed offset in seconds that is added to the erase timeout
+    pub erase_offset: u8,
+    /// The UHS speed grade in MB/s
+    pub uhs_speed_grade: u8,
+    /// The video speed class of the card
+    pub video_speed_class: u8,
+}
+
+impl SdStatus {
+    /// Decodes the 512 bit SD Status.
+    pub fn from_raw(raw: [u32; 16]) -> SdStatus {
+        let bits = |msb, lsb| bits(&raw, msb, lsb);
+
+        let au_size = match bits(431, 428) {
+            0 => 0,
+            size @ 1..=9 => (16 * 1024) << (size - 1),
+            0xA => 8 * 1024 * 1024,
+            0xB => 12 * 1024 * 1024,
+            0xC => 16 * 1024 * 1024,
+            0xD => 24 * 1024 * 1024,
+            0xE => 32 * 1024 * 1024,
+            _ => 64 * 1024 * 1024,
+        };
+
+        let speed_class = match bits(447, 440) {
+            1 => 2,
+            2 => 4,
+            3 => 6,
+            4 => 10,
+            _ => 0,
+        };
+
+        SdStatus {
+            bus_width: if bits(511, 510) == 0b10 { 4 } else { 1 },
+            secured_mode: bits(509, 509) == 1,
+            protected_area_size: bits(479, 448),
+            speed_class,
+            performance_move: bits(439, 432) as u8,
+            au_size,
+            erase_size: bits(423, 408) as u16,
+            erase_timeout: bits(407, 402) as u8,
+            erase_offset: bits(401, 400) as u8,
+            uhs_speed_grade: match bits(399, 396) {
+                1 => 10,
+                3 => 30,
+                _ => 0,
+            },
+            video_speed_class: bits(391, 384) as u8,
+        }
+    }
+}
+
+/// The switch function status, which is returned by CMD6.
+///
+/// Only the access mode group (group 1), which selects the bus speed mode, is decoded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SwitchStatus {
+    /// The maximum current consumption in mA with the selected functions, zero on error
+    pub max_current: u16,
+    /// True if the card supports the High Speed mode
+    pub high_speed_supported: bool,
+    /// True if the High Speed mode is (or would be) selected
+    pub high_speed_selected: bool,
+}
+
+impl SwitchStatus {
+    /// Decodes the 512 bit switch function status.
+    pub fn from_raw(raw: [u32; 16]) -> SwitchStatus {
+        let bits = |msb, lsb| bits(&raw, msb, lsb);
+
+        SwitchStatus {
+            max_current: bits(511, 496) as u16,
+            high_speed_supported: bits(401, 401) == 1,
+            high_speed_selected: bits(379, 376) == 1,
+        }
+    }
+}
+
+/// Returns the bits `msb` to `lsb` (inclusive) of a register, which is stored most significant
+/// word first. At most 32 bits can be returned.
+fn bits(raw: &[u32], msb: u32, lsb: u32) -> u32 {
+    let len = raw.len() as u32 * 32;
+    (lsb..=msb).rev().fold(0, |value, bit| {
+        let word = raw[((len - 1 - bit) / 32) as usize];
+        (value << 1) | ((word >> (bit % 32)) & 1)
+    })
+}
+
+/// Decodes the TRAN_SPEED field of the CSD to kbit/s.
+fn transfer_rate(tran_speed: u8) -> u32 {
+    // time values multiplied by 10
+    const TIME_VALUES: [u32; 16] = [0, 10, 12, 13, 15, 20, 25, 30, 35, 40, 45, 50, 55, 60, 70, 80];
+    // transfer rate units in kbit/s, the values 4 to 7 are reserved
+    const UNITS: [u32; 8] = [100, 1_000, 10_000, 100_000, 0, 0, 0, 0];
+
+    TIME_VALUES[usize::from((tran_speed >> 3) & 0xF)] * UNITS[usize::from(tran_speed & 0x7)] / 10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cid() {
+        // MID 0x03, OID "SD", PNM "SC32G", PRV 8.0, PSN 0x12345678, MDT 2019-10
+        let cid = Cid::from_raw([0x0353_4453, 0x4333_3247, 0x8012_3456, 0x7801_3A2B]);
+        assert_eq!(cid.manufacturer_id, 0x03);
+        assert_eq!(cid.oem_id_str(), "SD");
+        assert_eq!(cid.product_name_str(), "SC32G");
+        assert_eq!((cid.revision_major, cid.revision_minor), (8, 0));
+        assert_eq!(cid.serial_number, 0x1234_5678);
+        assert_eq!(cid.manufacturing_year, 2019);
+        assert_eq!(cid.manufacturing_month, 10);
+    }
+
+    #[test]
+    fn csd_version_2() {
+        // read from a 32 GB SDHC card
+        let csd = Csd::from_raw([0x400E_0032, 0x5B59_0000, 0xEDC8_7F80, 0x0A40_4000]);
+        assert_eq!(csd.version, 2);
+        assert_eq!(csd.block_count, 62_333_952);
+        assert_eq!(csd.block_len, 512);
+        assert_eq!(csd.capacity(), 31_914_983_424);
+        assert_eq!(csd.write_block_len, 512);
+        assert_eq!(csd.max_transfer_rate, 25_000);
+        assert_eq!(csd.command_classes, 0x5B5);
+        assert!(csd.erase_single_block);
+        assert_eq!(csd.erase_sector_size, 128);
+        assert!(!csd.write_protect_group_enable);
+        assert_eq!(csd.write_protect_group_size, 1);
+        assert!(!csd.write_protected());
+    }
+
+    #[test]
+    fn csd_version_1() {
+        // C_SIZE 3838, C_SIZE_MULT 7, READ_BL_LEN 10, TMP_WRITE_PROTECT set
+        let csd = Csd::from_raw([0x0000_0032, 0x5F5A_03BF, 0x8003_CFFF, 0x8280_5023]);
+        assert_eq!(csd.version, 1);
+        assert_eq!(csd.block_count, 3839 * 512);
+        assert_eq!(csd.block_len, 1024);
+        assert_eq!(csd.capacity(), 2_012_741_632);
+        assert_eq!(csd.write_block_len, 1024);
+        assert_eq!(csd.command_classes, 0x5F5);
+        assert_eq!(csd.erase_sector_size, 32);
+        assert!(csd.write_protect_group_enable);
+        assert_eq!(csd.write_protect_group_size, 128);
+        assert!(!csd.permanent_write_protect);
+        assert!(csd.temporary_write_protect);
+        assert!(csd.write_protected());
+    }
+
+    #[test]
+    fn transfer_rates() {
+        assert_eq!(transfer_rate(0x32), 25_000);
+        assert_eq!(transfer_rate(0x5A), 50_000);
+        assert_eq!(transfer_rate(0x0B), 100_000);
+        assert_eq!(transfer_rate(0x2B), 200_000);
+        assert_eq!(transfer_rate(0x00), 0);
+    }
+
+    #[test]
+    fn scr() {
+        let scr = Scr::from_raw([0x02B5_8003, 0x0000_0000]);
+        assert_eq!(scr.spec_version, SpecVersion::V3);
+        assert!(scr.bus_width_1);
+        assert!(scr.bus_width_4);
+        assert_eq!(scr.data_stat_after_erase, 1);
+        assert_eq!(scr.security, 3);
+        assert!(scr.set_block_count);
+        assert!(scr.speed_class_control);
+
+        let versions = [
+            (0x0001_0000, SpecVersion::V1_0),
+            (0x0101_0000, SpecVersion::V1_10),
+            (0x0201_0000, SpecVersion::V2),
+            (0x0205_8400, SpecVersion::V4),
+            (0x0205_8440, SpecVersion::V5),
+            (0x0205_80C0, SpecVersion::V7),
+            (0x0205_8100, SpecVersion::V8),
+            (0x0305_0000, SpecVersion::Unknown),
+        ];
+        for &(raw, version) in versions.iter() {
+            assert_eq!(Scr::from_raw([raw, 0]).spec_version, version);
+        }
+    }
+
+    #[test]
+    fn sd_status() {
+        let mut raw = [0; 16];
+        raw[..4].copy_from_slice(&[0x8000_0000, 0x0010_0000, 0x0405_9000, 0x082A_101E]);
+        let status = SdStatus::from_raw(raw);
+        assert_eq!(status.bus_width, 4);
+        assert!(!status.secured_mode);
+        assert_eq!(status.protected_area_size, 0x0010_0000);
+        assert_eq!(status.speed_class, 10);
+        assert_eq!(status.performance_move, 5);
+        assert_eq!(status.au_size, 4 * 1024 * 1024);
+        assert_eq!(status.erase_size, 8);
+        assert_eq!(status.erase_timeout, 10);
+        assert_eq!(status.erase_offset, 2);
+        assert_eq!(status.uhs_speed_grade, 10);
+        assert_eq!(status.video_speed_class, 30);
+    }
+
+    #[test]
+    fn switch_status() {
+        let mut raw = [0; 16];
+        raw[0] = 0x00C8_0000;
+        raw[3] = 0x8003_0000;
+        raw[4] = 0x0100_0000;
+        let status = SwitchStatus::from_raw(raw);
+        assert_eq!(status.max_current, 200);
+        assert!(status.high_speed_supported);
+        assert!(status.high_speed_selected);
+        assert_eq!(SwitchStatus::from_raw([0; 16]), SwitchStatus::default());
+    }
+
+    #[test]
+    fn bit_ranges() {
+        let raw = [0x8000_0001, 0xFFFF_0000];
+        assert_eq!(bits(&raw, 63, 63), 1);
+        assert_eq!(bits(&raw, 32, 32), 1);
+        assert_eq!(bits(&raw, 33, 31), 0b011);
+        assert_eq!(bits(&raw, 31, 16), 0xFFFF);
+        assert_eq!(bits(&raw, 15, 0), 0);
+        assert_eq!(bits(&raw, 63, 32), 0x8000_0001);
+    }
+}
@@ -72,6 +72,47 @@ pub fn sel_desel(sdmmc: &mut SDMMC1, rca: u32) -> Result<(), Error> {
     get_cmd_resp1(sdmmc, 7, 5000)
 }
 
+/// Check or switch a card function, e.g. the bus speed mode. The function status is sent as a 64
+/// byte data block. (CMD6)
+pub fn switch_func(sdmmc: &mut SDMMC1, argument: u32) -> Result<(), Error> {
+    send_cmd(sdmmc, argument, 6, true, false, 0x01);
+
+    get_cmd_resp1(sdmmc, 6, 5000)
+}
+
+/// Send ACMD6 to set the width of the data bus. `bus_width` is 0b00 for 1 bit and 0b10 for 4
+/// bit. Always send CMD55 before sending this command.
+pub fn app_bus_width(sdmmc: &mut SDMMC1, bus_width: u32) -> Result<(), Error> {
+    send_cmd(sdmmc, bus_width, 6, true, false, 0x01);
+
+    get_cmd_resp1(sdmmc, 6, 5000)
+}
+
+/// Send ACMD51 to read the SD Configuration Register (SCR). The register is sent as an 8 byte
+/// data block. Always send CMD55 before sending this command.
+pub fn app_send_scr(sdmmc: &mut SDMMC1) -> Result<(), Error> {
+    send_cmd(sdmmc, 0, 51, true, false, 0x01);
+
+    get_cmd_resp1(sdmmc, 51, 5000)
+}
+
+/// Send ACMD13 to read the SD Status. The status is sent as a 64 byte data block. Always send
+/// CMD55 before sending this command.
+pub fn app_sd_status(sdmmc: &mut SDMMC1) -> Result<(), Error> {
+    send_cmd(sdmmc, 0, 13, true, false, 0x01);
+
+    get_cmd_resp1(sdmmc, 13, 5000)
+}
+
+/// Get the card status register of the card. (CMD13)
+pub fn send_status(sdmmc: &mut SDMMC1, rca: u32) -> Result<u32, Error> {
+    send_cmd(sdmmc, rca, 13, true, false, 0x01);
+
+    get_cmd_resp1(sdmmc, 13, 5000)?;
+
+    Ok(sdmmc.resp1.read().cardstatus1().bits())
+}
+
 // Read/Write commands
 /// Set the block length of the blocks to read/write.
 pub fn block_length(sdmmc: &mut SDMMC1, block_size: u32) -> Result<(), Error> {
@@ -89,7 +130,6 @@ pub fn write_single_blk(sdmmc: &mut SDMMC1, block_add: u32) -> Result<(), Error>
 
 /// Instruct the controller, that multiple blocks will be written. End the write process with a
 /// call to `stop_transfer()`.
-// TODO: This doesn't seem to work...
 pub fn write_multi_blk(sdmmc: &mut SDMMC1, block_add: u32) -> Result<(), Error> {
     send_cmd(sdmmc, block_add, 25, true, false, 0x01);
 
@@ -105,7 +145,6 @@ pub fn read_single_blk(sdmmc: &mut SDMMC1, block_add: u32) -> Result<(), Error>
 
 /// Instruct the controller, that multiple blocks will be read. End the read process with a
 /// call to `stop_transfer()`.
-// TODO: This doesn't seem to work...
 pub fn read_multi_blk(sdmmc: &mut SDMMC1, block_add: u32) -> Result<(), Error> {
     send_cmd(sdmmc, block_add, 18, true, false, 0x01);
 
@@ -114,13 +153,37 @@ pub fn read_multi_blk(sdmmc: &mut SDMMC1, block_add: u32) -> Result<(), Error> {
 
 // An alternative, to end multi-block read/write with `stop_transfer()`, is to specify the number of
 // blocks that should be written beforehand.
-// The controller doesn't seem to accept this command and always returns with a CmdRespTimeout Error.
+// CMD23 is optional for SD cards (support is reported in the SCR register) and most cards answer
+// with a CmdRespTimeout Error, so multi-block transfers are always ended with CMD12 instead.
 // pub fn set_blk_count(sdmmc: &mut SDMMC1, number_of_blks: u16) -> Result<(), Error> {
 //     send_cmd(sdmmc, number_of_blks as u32, 23, true, false, 0x01);
 //
 //     get_cmd_resp1(sdmmc, 23, 5000)
 // }
 
+// Erase commands
+/// Set the address of the first block to erase. (CMD32)
+pub fn erase_start(sdmmc: &mut SDMMC1, block_add: u32) -> Result<(), Error> {
+    send_cmd(sdmmc, block_add, 32, true, false, 0x01);
+
+    get_cmd_resp1(sdmmc, 32, 5000)
+}
+
+/// Set the address of the last block to erase. (CMD33)
+pub fn erase_end(sdmmc: &mut SDMMC1, block_add: u32) -> Result<(), Error> {
+    send_cmd(sdmmc, block_add, 33, true, false, 0x01);
+
+    get_cmd_resp1(sdmmc, 33, 5000)
+}
+
+/// Erase the blocks selected with `erase_start()` and `erase_end()`. The card is busy until the
+/// erase is finished. (CMD38)
+pub fn erase(sdmmc: &mut SDMMC1) -> Result<(), Error> {
+    send_cmd(sdmmc, 0, 38, true, false, 0x01);
+
+    get_cmd_resp1(sdmmc, 38, 5000)
+}
+
 /// Stops the tranfer to the card after a multi-block read/write.
 pub fn stop_transfer(sdmmc: &mut SDMMC1) -> Result<(), Error> {
     send_cmd(sdmmc, 0, 12, true, false, 0x01);
@@ -294,9 +357,7 @@ fn check_for_errors(card_status: u32) -> Result<(), Error> {
             t: CardStatusFlags::CARD_ECC_DISABLED,
         })
     } else if card_status & CardStatusFlags::WP_ERASE_SKIP.bits() != 0 {
-        Err(Error::CardError {
-            t: CardStatusFlags::WP_ERASE_SKIP,
-        })
+        Err(Error::WriteProtected)
     } else if card_status & CardStatusFlags::CID_CSD_OVERWRITE.bits() != 0 {
         Err(Error::CardError {
             t: CardStatusFlags::CID_CSD_OVERWRITE,
@@ -322,9 +383,7 @@ fn check_for_errors(card_status: u32) -> Result<(), Error> {
             t: CardStatusFlags::LOCK_UNLOCK_FAILED,
         })
     } else if card_status & CardStatusFlags::WP_VIOLATION.bits() != 0 {
-        Err(Error::CardError {
-            t: CardStatusFlags::WP_VIOLATION,
-        })
+        Err(Error::WriteProtected)
     } else if card_status & CardStatusFlags::ERASE_PARAM.bits() != 0 {
         Err(Error::CardError {
             t: CardStatusFlags::ERASE_PARAM,
@@ -0,0 +1,335 @@
+//! Futures for reading and writing blocks without blocking the executor.
+//!
+//! The futures are woken by the SDMMC1 interrupt, which is registered by
+//! [`SdmmcInterrupts::register`](SdmmcInterrupts::register).
+
+use super::error::*;
+use super::{dma, mask_interrupts, Sd, MAX_BLOCKS_PER_TRANSFER, WORDS_PER_BLOCK};
+use crate::gpio::InputPin;
+use crate::interrupts::{self, Ic, InterruptHandle, InterruptRequest, InterruptTable, Priority};
+use crate::task_runtime::mpsc;
+use alloc::vec::Vec;
+use core::{mem, pin::Pin, slice};
+use futures::{
+    prelude::*,
+    task::{Context, Poll},
+};
+
+/// The SDMMC1 interrupt, which wakes the futures of `Sd::read_blocks_async` and
+/// `Sd::write_blocks_async`.
+///
+/// The interrupt handler masks the SDMMC1 interrupts and wakes the running transfer, which
+/// clears the flags and unmasks the interrupts again before it waits for the next one.
+///
+/// Dropping the interrupts doesn't unregister the handler, it stays registered until the end of
+/// the interrupt scope. Use [`release`](SdmmcInterrupts::release) to unregister it.
+pub struct SdmmcInterrupts {
+    events: mpsc::UnboundedReceiver<()>,
+    interrupt: Option<InterruptHandle<(), InterruptRequest>>,
+}
+
+impl SdmmcInterrupts {
+    /// Registers the handler of the SDMMC1 interrupt with the given priority.
+    ///
+    /// # Errors
+    ///
+    /// Returns an Error if an SDMMC1 handler is already registered.
+    ///
+    /// # Examples
+    /// ```rust
+    /// let mut sdmmc_interrupts = sd::SdmmcInterrupts::register(interrupt_table, Priority::P1)
+    ///     .expect("registering sdmmc1 interrupt failed");
+    ///
+    /// let task = async move {
+    ///     match await!(sd.read_blocks_async(42, 2, &mut sdmmc_interrupts)) {
+    ///         Ok(data) => println!("{:?}", &data[..8]),
+    ///         Err(r_err) => println!("{:?}", r_err),
+    ///     }
+    /// };
+    /// ```
+    pub fn register<'t>(
+        interrupt_table: &mut InterruptTable<'t, Ic<'t>>,
+        priority: Priority,
+    ) -> Result<SdmmcInterrupts, interrupts::Error> {
+        let (sink, events) = mpsc::unbounded();
+        let interrupt =
+            interrupt_table.register(InterruptRequest::SDMMC1, priority, move || {
+                // the transfer future clears the flags and unmasks the interrupts again
+                mask_interrupts();
+                // fails only if the interrupts were dropped
+                let _ = sink.unbounded_send(());
+            })?;
+        Ok(SdmmcInterrupts {
+            events,
+            interrupt: Some(interrupt),
+        })
+    }
+
+    /// Unregisters the SDMMC1 interrupt handler.
+    ///
+    /// The `interrupt_table` must be the one that was passed to
+    /// [`register`](SdmmcInterrupts::register).
+    pub fn release<'t>(mut self, interrupt_table: &mut InterruptTable<'t, Ic<'t>>) {
+        if let Some(interrupt) = self.interrupt.take() {
+            interrupt_table.unregister(interrupt);
+        }
+    }
+}
+
+/// The future returned by `Sd::read_blocks_async`.
+#[must_use = "futures do nothing unless polled"]
+pub struct ReadBlocks<'s, 'a: 's, P: InputPin + 'a> {
+    transfer: Transfer<'s, 'a, P>,
+    data: Vec<u32>,
+}
+
+/// The future returned by `Sd::write_blocks_async`.
+#[must_use = "futures do nothing unless polled"]
+pub struct WriteBlocks<'s, 'a: 's, 'd, P: InputPin + 'a> {
+    transfer: Transfer<'s, 'a, P>,
+    data: &'d [u32],
+}
+
+impl<'s, 'a, P: InputPin> ReadBlocks<'s, 'a, P> {
+    pub(super) fn new(
+        sd: &'s mut Sd<'a, P>,
+        interrupts: &'s mut SdmmcInterrupts,
+        block_add: u32,
+        number_of_blks: u16,
+        timeout: u32,
+    ) -> Self {
+        ReadBlocks {
+            transfer: Transfer::new(
+                sd,
+                interrupts,
+                dma::Direction::Rx,
+                block_add,
+                number_of_blks,
+                timeout,
+            ),
+            data: vec![0; usize::from(number_of_blks) * WORDS_PER_BLOCK],
+        }
+    }
+}
+
+impl<'s, 'a, 'd, P: InputPin> WriteBlocks<'s, 'a, 'd, P> {
+    pub(super) fn new(
+        sd: &'s mut Sd<'a, P>,
+        interrupts: &'s mut SdmmcInterrupts,
+        data: &'d [u32],
+        block_add: u32,
+        number_of_blks: u16,
+        timeout: u32,
+    ) -> Self {
+        let mut transfer = Transfer::new(
+            sd,
+            interrupts,
+            dma::Direction::Tx,
+            block_add,
+            number_of_blks,
+            timeout,
+        );
+        if data.len() != usize::from(number_of_blks) * WORDS_PER_BLOCK {
+            transfer.state = State::Failed(Error::RWError {
+                t: RWErrorType::LengthMismatch,
+            });
+        }
+        WriteBlocks { transfer, data }
+    }
+}
+
+impl<'s, 'a, P: InputPin> Future for ReadBlocks<'s, 'a, P> {
+    type Output = Result<Vec<u32>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let buffer = this.data.as_mut_ptr() as usize;
+        match this.transfer.poll(cx, buffer) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(mem::replace(&mut this.data, Vec::new()))),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<'s, 'a, 'd, P: InputPin> Future for WriteBlocks<'s, 'a, 'd, P> {
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let buffer = this.data.as_ptr() as usize;
+        this.transfer.poll(cx, buffer)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// The next chunk of blocks was not started yet.
+    Idle,
+    /// The DMA and the DPSM are transferring data.
+    Data,
+    /// The data was sent and the card is programming it.
+    Busy,
+    /// The future already returned its result.
+    Done,
+    /// The transfer can't be started, the error is returned on the first poll.
+    Failed(Error),
+}
+
+// The state machine shared by `ReadBlocks` and `WriteBlocks`. The blocks are transferred in
+// chunks of at most `MAX_BLOCKS_PER_TRANSFER` blocks.
+struct Transfer<'s, 'a: 's, P: InputPin + 'a> {
+    sd: &'s mut Sd<'a, P>,
+    interrupts: &'s mut SdmmcInterrupts,
+    direction: dma::Direction,
+    block_add: u32,
+    number_of_blks: u16,
+    // the number of blocks of the previous chunks
+    finished_blks: u16,
+    // the number of blocks of the current chunk
+    chunk_blks: u16,
+    timeout: u32,
+    deadline: u32,
+    state: State,
+}
+
+impl<'s, 'a, P: InputPin> Transfer<'s, 'a, P> {
+    fn new(
+        sd: &'s mut Sd<'a, P>,
+        interrupts: &'s mut SdmmcInterrupts,
+        direction: dma::Direction,
+        block_add: u32,
+        number_of_blks: u16,
+        timeout: u32,
+    ) -> Self {
+        Transfer {
+            sd,
+            interrupts,
+            direction,
+            block_add,
+            number_of_blks,
+            finished_blks: 0,
+            chunk_blks: 0,
+            timeout,
+            deadline: 0,
+            state: State::Idle,
+        }
+    }
+
+    // Starts the transfer of the next chunk at `buffer`, the address of all blocks.
+    fn begin_chunk(&mut self, buffer: usize) -> Result<(), Error> {
+        let remaining = self.number_of_blks - self.finished_blks;
+        self.chunk_blks = remaining.min(MAX_BLOCKS_PER_TRANSFER);
+        let block_add = self.block_add + u32::from(self.finished_blks);
+        let offset = usize::from(self.finished_blks) * WORDS_PER_BLOCK;
+        let words = usize::from(self.chunk_blks) * WORDS_PER_BLOCK;
+        self.deadline = crate::system_clock::ms() as u32 + self.timeout;
+        match self.direction {
+            dma::Direction::Rx => {
+                let data =
+                    unsafe { slice::from_raw_parts_mut((buffer as *mut u32).add(offset), words) };
+                self.sd.begin_read(data, block_add, self.chunk_blks)
+            }
+            dma::Direction::Tx => {
+                let data =
+                    unsafe { slice::from_raw_parts((buffer as *const u32).add(offset), words) };
+                self.sd.begin_write(data, block_add, self.chunk_blks)
+            }
+        }
+    }
+
+    fn timed_out(&self) -> bool {
+        (crate::system_clock::ms() as u32) >= self.deadline
+    }
+
+    // `buffer` is the address of the data, which doesn't move while the future is alive.
+    fn poll(&mut self, cx: &mut Context, buffer: usize) -> Poll<Result<(), Error>> {
+        loop {
+            match self.state {
+                State::Idle => {
+                    // All blocks transferred -> done
+                    if self.finished_blks == self.number_of_blks {
+                        self.state = State::Done;
+                        return Poll::Ready(Ok(()));
+                    }
+                    if let Err(err) = self.begin_chunk(buffer) {
+                        self.state = State::Done;
+                        return Poll::Ready(Err(err));
+                    }
+                    self.state = State::Data;
+                }
+                State::Data => {
+                    let result = match self.sd.data_path_finished(self.direction) {
+                        Ok(false) if self.timed_out() => Err(Error::Timeout),
+                        Ok(false) => {
+                            // Unmask the interrupts before waiting, a flag that was set in the
+                            // meantime triggers the interrupt immediately.
+                            self.sd.enable_data_interrupts();
+                            match Pin::new(&mut self.interrupts.events).poll_next(cx) {
+                                Poll::Ready(Some(())) => continue,
+                                Poll::Ready(None) => Err(Error::InterruptsClosed),
+                                Poll::Pending => return Poll::Pending,
+                            }
+                        }
+                        Ok(true) => Ok(()),
+                        Err(err) => Err(err),
+                    };
+                    let result = self
+                        .sd
+                        .stop_data_path(self.direction, self.chunk_blks, result);
+                    match (result, self.direction) {
+                        (Ok(()), dma::Direction::Tx) => self.state = State::Busy,
+                        (Ok(()), dma::Direction::Rx) => {
+                            self.finished_blks += self.chunk_blks;
+                            self.state = State::Idle;
+                        }
+                        (Err(err), _) => {
+                            self.state = State::Done;
+                            return Poll::Ready(Err(err));
+                        }
+                    }
+                }
+                State::Busy => {
+                    // The card doesn't signal the end of the programming with an interrupt, so
+                    // the status is polled whenever the executor runs this task again.
+                    match self.sd.card_ready() {
+                        Ok(false) if self.timed_out() => {
+                            self.state = State::Done;
+                            return Poll::Ready(Err(Error::Timeout));
+                        }
+                        Ok(false) => {
+                            cx.waker().wake_by_ref();
+                            return Poll::Pending;
+                        }
+                        Ok(true) => {
+                            self.finished_blks += self.chunk_blks;
+                            self.state = State::Idle;
+                        }
+                        Err(err) => {
+                            self.state = State::Done;
+                            return Poll::Ready(Err(err));
+                        }
+                    }
+                }
+                // The future already returned its result and is never woken again.
+                State::Done => return Poll::Pending,
+                State::Failed(err) => {
+                    self.state = State::Done;
+                    return Poll::Ready(Err(err));
+                }
+            }
+        }
+    }
+}
+
+impl<'s, 'a, P: InputPin> Drop for Transfer<'s, 'a, P> {
+    fn drop(&mut self) {
+        // The DMA must not access the buffer after it was freed.
+        if self.state == State::Data {
+            let _ = self
+                .sd
+                .stop_data_path(self.direction, self.chunk_blks, Ok(()));
+        }
+        self.sd.sdmmc.mask.reset();
+    }
+}
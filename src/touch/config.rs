@@ -0,0 +1,126 @@
+//! Configuration registers of the FT5336.
+
+use super::{read_register, read_registers, write_register, Error};
+use crate::i2c::I2C;
+use stm32f7::stm32f7x6 as device;
+
+const GESTURE_ID_REGISTER: u8 = 0x01;
+const THRESHOLD_REGISTER: u8 = 0x80;
+// followed by the time to enter the monitor mode, the active period and the monitor period
+const CONTROL_REGISTER: u8 = 0x86;
+const INTERRUPT_MODE_REGISTER: u8 = 0xA4;
+
+/// How the controller signals touches on its interrupt pin (PI13).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptMode {
+    /// The interrupt line is active as long as the display is touched.
+    Polling,
+    /// The interrupt line is pulsed for each new set of touch points.
+    Trigger,
+}
+
+/// The configuration of the touch controller.
+///
+/// # Examples
+/// ```rust
+/// // make the panel less sensitive and pulse the interrupt line for each report
+/// let config = touch::Config {
+///     threshold: 40,
+///     interrupt_mode: touch::InterruptMode::Trigger,
+///     ..touch::config(&mut i2c_3)?
+/// };
+/// touch::configure(&mut i2c_3, &config)?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// The threshold for the detection of touches. Smaller values make the panel more
+    /// sensitive.
+    pub threshold: u8,
+    /// Whether the controller switches to the monitor mode, which scans less often, when the
+    /// display isn't touched.
+    pub monitor_mode: bool,
+    /// The time in seconds without touches before the controller switches to the monitor mode.
+    pub time_enter_monitor: u8,
+    /// The report period in the active mode, in units of the controller.
+    pub active_period: u8,
+    /// The report period in the monitor mode, in units of the controller.
+    pub monitor_period: u8,
+    /// How touches are signaled on the interrupt pin.
+    pub interrupt_mode: InterruptMode,
+}
+
+/// Reads the current configuration of the touch controller.
+pub fn config(i2c_3: &mut I2C<device::I2C3>) -> Result<Config, Error> {
+    let threshold = read_register(i2c_3, THRESHOLD_REGISTER)?;
+    let mut control = [0; 4];
+    read_registers(i2c_3, CONTROL_REGISTER, &mut control)?;
+    let interrupt_mode = match read_register(i2c_3, INTERRUPT_MODE_REGISTER)? {
+        0 => InterruptMode::Polling,
+        _ => InterruptMode::Trigger,
+    };
+    Ok(Config {
+        threshold,
+        monitor_mode: control[0] & 1 != 0,
+        time_enter_monitor: control[1],
+        active_period: control[2],
+        monitor_period: control[3],
+        interrupt_mode,
+    })
+}
+
+/// Writes the configuration to the touch controller.
+pub fn configure(i2c_3: &mut I2C<device::I2C3>, config: &Config) -> Result<(), Error> {
+    write_register(i2c_3, THRESHOLD_REGISTER, config.threshold)?;
+    let control = [
+        config.monitor_mode as u8,
+        config.time_enter_monitor,
+        config.active_period,
+        config.monitor_period,
+    ];
+    for (register, &value) in (CONTROL_REGISTER..).zip(control.iter()) {
+        write_register(i2c_3, register, value)?;
+    }
+    let interrupt_mode = match config.interrupt_mode {
+        InterruptMode::Polling => 0,
+        InterruptMode::Trigger => 1,
+    };
+    write_register(i2c_3, INTERRUPT_MODE_REGISTER, interrupt_mode)
+}
+
+/// A gesture that the touch controller recognized.
+///
+/// The recognition of the controller is very limited, the
+/// [`GestureRecognizer`](super::GestureRecognizer) supports more gestures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GestureId {
+    /// No gesture was recognized.
+    None,
+    /// A finger moved up.
+    MoveUp,
+    /// A finger moved to the right.
+    MoveRight,
+    /// A finger moved down.
+    MoveDown,
+    /// A finger moved to the left.
+    MoveLeft,
+    /// Two fingers moved apart.
+    ZoomIn,
+    /// Two fingers moved towards each other.
+    ZoomOut,
+    /// An unknown value of the gesture ID register.
+    Unknown(u8),
+}
+
+/// Reads the gesture that the touch controller recognized in the current touches.
+pub fn gesture_id(i2c_3: &mut I2C<device::I2C3>) -> Result<GestureId, Error> {
+    Ok(match read_register(i2c_3, GESTURE_ID_REGISTER)? {
+        0x00 => GestureId::None,
+        0x10 => GestureId::MoveUp,
+        0x14 => GestureId::MoveRight,
+        0x18 => GestureId::MoveDown,
+        0x1C => GestureId::MoveLeft,
+        0x48 => GestureId::ZoomIn,
+        0x49 => GestureId::ZoomOut,
+        id => GestureId::Unknown(id),
+    })
+}
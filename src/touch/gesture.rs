@@ -0,0 +1,542 @@
+//! Recognizing gestures in the touch events.
+
+use super::{TouchEvent, TouchEventKind};
+
+/// The direction of a swipe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwipeDirection {
+    /// Towards smaller x coordinates.
+    Left,
+    /// Towards larger x coordinates.
+    Right,
+    /// Towards smaller y coordinates.
+    Up,
+    /// Towards larger y coordinates.
+    Down,
+}
+
+/// A recognized gesture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gesture {
+    /// A finger touched the display briefly without moving.
+    Tap {
+        /// The x coordinate of the tap.
+        x: u16,
+        /// The y coordinate of the tap.
+        y: u16,
+    },
+    /// A second tap at the same position shortly after a tap. The first tap is reported as
+    /// [`Tap`](Gesture::Tap), the second one only as double tap.
+    DoubleTap {
+        /// The x coordinate of the second tap.
+        x: u16,
+        /// The y coordinate of the second tap.
+        y: u16,
+    },
+    /// A finger touched the display for a long time without moving. It's reported while the
+    /// finger still touches the display, and the following release is no tap.
+    LongPress {
+        /// The x coordinate of the finger.
+        x: u16,
+        /// The y coordinate of the finger.
+        y: u16,
+    },
+    /// A finger moved quickly over the display.
+    Swipe(SwipeDirection),
+    /// Two fingers moved relative to each other. It's reported whenever the scale or the
+    /// rotation changes.
+    Pinch {
+        /// The distance of the fingers in per mille of their distance when the second finger
+        /// touched the display. Values above 1000 mean that the fingers moved apart.
+        scale: u32,
+        /// The clockwise rotation of the line between the fingers in degrees, since the second
+        /// finger touched the display. The value is between -180 and 180.
+        rotation: i32,
+    },
+}
+
+/// The thresholds of the gesture recognition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GestureConfig {
+    /// The distance in pixels that a finger can move and still tap or press.
+    pub tap_slop: u16,
+    /// The maximum time in milliseconds between touching and lifting the finger for a tap.
+    pub tap_duration: usize,
+    /// The maximum time in milliseconds between two taps of a double tap.
+    pub double_tap_interval: usize,
+    /// The time in milliseconds after which a finger that doesn't move is a long press.
+    pub long_press_duration: usize,
+    /// The minimum distance in pixels that a finger moves for a swipe.
+    pub swipe_distance: u16,
+    /// The maximum time in milliseconds between touching and lifting the finger for a swipe.
+    pub swipe_duration: usize,
+}
+
+impl Default for GestureConfig {
+    fn default() -> GestureConfig {
+        GestureConfig {
+            tap_slop: 10,
+            tap_duration: 300,
+            double_tap_interval: 300,
+            long_press_duration: 600,
+            swipe_distance: 60,
+            swipe_duration: 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Finger {
+    id: u8,
+    x: u16,
+    y: u16,
+}
+
+impl Finger {
+    fn new(event: &TouchEvent) -> Finger {
+        Finger {
+            id: event.id,
+            x: event.x,
+            y: event.y,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Idle,
+    OneFinger {
+        start: Finger,
+        start_time: usize,
+        current: Finger,
+        moved: bool,
+        long_pressed: bool,
+    },
+    TwoFingers {
+        fingers: [Finger; 2],
+        start_distance: u32,
+        start_angle: i32,
+        last: (u32, i32),
+    },
+    // a gesture ended, but fingers still touch the display
+    Finished,
+}
+
+/// Recognizes gestures in the events of a [`TouchTracker`](super::TouchTracker).
+///
+/// The recognizer is a state machine that only depends on the events and the times passed to
+/// it.
+#[derive(Debug, Clone)]
+pub struct GestureRecognizer {
+    config: GestureConfig,
+    state: State,
+    // the number of fingers that touch the display
+    fingers: usize,
+    // the position and the time of the last tap, for double taps
+    last_tap: Option<(Finger, usize)>,
+}
+
+impl GestureRecognizer {
+    /// Creates a recognizer with the given thresholds.
+    pub fn new(config: GestureConfig) -> GestureRecognizer {
+        GestureRecognizer {
+            config,
+            state: State::Idle,
+            fingers: 0,
+            last_tap: None,
+        }
+    }
+
+    /// Returns the thresholds.
+    pub fn config(&self) -> &GestureConfig {
+        &self.config
+    }
+
+    /// Processes a touch event and returns the gesture that it completes, if any.
+    pub fn handle(&mut self, event: TouchEvent) -> Option<Gesture> {
+        let long_press = self.poll(event.time);
+        let gesture = match event.kind {
+            TouchEventKind::Down => self.down(&event),
+            TouchEventKind::Move => self.move_finger(&event),
+            TouchEventKind::Up => self.up(&event),
+        };
+        long_press.or(gesture)
+    }
+
+    /// Checks whether the finger is pressed long enough for a long press.
+    ///
+    /// This should be called regularly, because no events are reported while the finger
+    /// doesn't move.
+    pub fn poll(&mut self, time: usize) -> Option<Gesture> {
+        let long_press_duration = self.config.long_press_duration;
+        match &mut self.state {
+            State::OneFinger {
+                start_time,
+                current,
+                moved: false,
+                long_pressed,
+                ..
+            } if !*long_pressed && time.wrapping_sub(*start_time) >= long_press_duration => {
+                *long_pressed = true;
+                Some(Gesture::LongPress {
+                    x: current.x,
+                    y: current.y,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn down(&mut self, event: &TouchEvent) -> Option<Gesture> {
+        self.fingers += 1;
+        self.state = match self.state {
+            State::Idle => State::OneFinger {
+                start: Finger::new(event),
+                start_time: event.time,
+                current: Finger::new(event),
+                moved: false,
+                long_pressed: false,
+            },
+            State::OneFinger { current, .. } => {
+                let fingers = [current, Finger::new(event)];
+                State::TwoFingers {
+                    fingers,
+                    start_distance: distance(fingers).max(1),
+                    start_angle: angle(fingers),
+                    last: (1000, 0),
+                }
+            }
+            // more fingers end the gesture
+            State::TwoFingers { .. } | State::Finished => State::Finished,
+        };
+        None
+    }
+
+    fn move_finger(&mut self, event: &TouchEvent) -> Option<Gesture> {
+        let tap_slop = u32::from(self.config.tap_slop);
+        match &mut self.state {
+            State::OneFinger {
+                start,
+                current,
+                moved,
+                ..
+            } if current.id == event.id => {
+                *current = Finger::new(event);
+                if distance([*start, *current]) > tap_slop {
+                    *moved = true;
+                }
+                None
+            }
+            State::TwoFingers {
+                fingers,
+                start_distance,
+                start_angle,
+                last,
+            } => {
+                let finger = fingers.iter_mut().find(|finger| finger.id == event.id)?;
+                *finger = Finger::new(event);
+                let scale = distance(*fingers) * 1000 / *start_distance;
+                let mut rotation = angle(*fingers) - *start_angle;
+                if rotation > 180 {
+                    rotation -= 360;
+                } else if rotation < -180 {
+                    rotation += 360;
+                }
+                if (scale, rotation) == *last {
+                    return None;
+                }
+                *last = (scale, rotation);
+                Some(Gesture::Pinch { scale, rotation })
+            }
+            _ => None,
+        }
+    }
+
+    fn up(&mut self, event: &TouchEvent) -> Option<Gesture> {
+        self.fingers = self.fingers.saturating_sub(1);
+        let state = self.state;
+        self.state = if self.fingers == 0 {
+            State::Idle
+        } else {
+            State::Finished
+        };
+
+        match state {
+            State::OneFinger {
+                start,
+                start_time,
+                moved,
+                long_pressed: false,
+                ..
+            } => {
+                let duration = event.time.wrapping_sub(start_time);
+                if !moved && duration <= self.config.tap_duration {
+                    Some(self.tap(event))
+                } else if moved && duration <= self.config.swipe_duration {
+                    swipe(start, Finger::new(event), self.config.swipe_distance)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn tap(&mut self, event: &TouchEvent) -> Gesture {
+        let (x, y) = (event.x, event.y);
+        if let Some((last, last_time)) = self.last_tap.take() {
+            let close = distance([last, Finger::new(event)]) <= 2 * u32::from(self.config.tap_slop);
+            if close && event.time.wrapping_sub(last_time) <= self.config.double_tap_interval {
+                return Gesture::DoubleTap { x, y };
+            }
+        }
+        self.last_tap = Some((Finger::new(event), event.time));
+        Gesture::Tap { x, y }
+    }
+}
+
+fn swipe(start: Finger, end: Finger, min_distance: u16) -> Option<Gesture> {
+    let dx = i32::from(end.x) - i32::from(start.x);
+    let dy = i32::from(end.y) - i32::from(start.y);
+    let direction = if dx.abs() >= dy.abs() {
+        if dx < 0 {
+            SwipeDirection::Left
+        } else {
+            SwipeDirection::Right
+        }
+    } else if dy < 0 {
+        SwipeDirection::Up
+    } else {
+        SwipeDirection::Down
+    };
+    if dx.abs().max(dy.abs()) >= i32::from(min_distance) {
+        Some(Gesture::Swipe(direction))
+    } else {
+        None
+    }
+}
+
+// Returns the distance between the fingers in pixels.
+fn distance(fingers: [Finger; 2]) -> u32 {
+    let dx = i64::from(fingers[1].x) - i64::from(fingers[0].x);
+    let dy = i64::from(fingers[1].y) - i64::from(fingers[0].y);
+    sqrt((dx * dx + dy * dy) as u64) as u32
+}
+
+// Returns the angle of the line from the first to the second finger in degrees, clockwise from
+// the x axis, because the y axis points down.
+fn angle(fingers: [Finger; 2]) -> i32 {
+    let dx = i32::from(fingers[1].x) - i32::from(fingers[0].x);
+    let dy = i32::from(fingers[1].y) - i32::from(fingers[0].y);
+    atan2(dy, dx)
+}
+
+// The integer square root, rounded down.
+fn sqrt(value: u64) -> u64 {
+    // Newton's method, starting above the root
+    let mut root = value;
+    let mut next = (value + 1) / 2;
+    while next < root {
+        root = next;
+        next = (root + value / root) / 2;
+    }
+    root
+}
+
+// Approximates `atan2(y, x)` in degrees from -180 to 180, with an error below 0.3 degrees.
+fn atan2(y: i32, x: i32) -> i32 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+    let (ax, ay) = (i64::from(x.abs()), i64::from(y.abs()));
+    // atan(z) ≈ 45z + 15.6z(1 - z) degrees for z in [0, 1], with z in 1/1024
+    let z = ax.min(ay) * 1024 / ax.max(ay);
+    let octant = (45 * 1024 * z + 156 * z * (1024 - z) / 10 + 512 * 1024) / (1024 * 1024);
+    let first_quadrant = if ay > ax { 90 - octant } else { octant };
+    let angle = if x < 0 {
+        180 - first_quadrant
+    } else {
+        first_quadrant
+    };
+    (if y < 0 { -angle } else { angle }) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Touch, TouchFlag, TouchTracker};
+    use super::*;
+    use alloc::vec::Vec;
+
+    // The touches of the fingers at a time in milliseconds.
+    type Trace<'a> = &'a [(usize, &'a [(u8, u16, u16)])];
+
+    // Passes the touches through a tracker and a recognizer and polls after each update.
+    fn gestures(trace: Trace) -> Vec<Gesture> {
+        let mut tracker = TouchTracker::new();
+        let mut recognizer = GestureRecognizer::new(GestureConfig::default());
+        let mut gestures = Vec::new();
+        for &(time, fingers) in trace {
+            let touches: Vec<_> = fingers
+                .iter()
+                .map(|&(id, x, y)| Touch {
+                    x,
+                    y,
+                    id,
+                    flag: TouchFlag::Contact,
+                })
+                .collect();
+            for event in tracker.update(&touches, time) {
+                gestures.extend(recognizer.handle(event));
+            }
+            gestures.extend(recognizer.poll(time));
+        }
+        gestures
+    }
+
+    #[test]
+    fn tap() {
+        let trace: Trace = &[(0, &[(0, 100, 50)]), (100, &[(0, 105, 52)]), (200, &[])];
+        assert_eq!(gestures(trace), [Gesture::Tap { x: 105, y: 52 }]);
+
+        // too long
+        let trace: Trace = &[(0, &[(0, 100, 50)]), (400, &[])];
+        assert_eq!(gestures(trace), []);
+
+        // moved too far
+        let trace: Trace = &[(0, &[(0, 100, 50)]), (50, &[(0, 100, 70)]), (100, &[])];
+        assert_eq!(gestures(trace), []);
+    }
+
+    #[test]
+    fn double_tap() {
+        let trace: Trace = &[
+            (0, &[(0, 100, 50)]),
+            (100, &[]),
+            (300, &[(0, 110, 55)]),
+            (400, &[]),
+            (500, &[(0, 110, 55)]),
+            (600, &[]),
+        ];
+        let expected = [
+            Gesture::Tap { x: 100, y: 50 },
+            Gesture::DoubleTap { x: 110, y: 55 },
+            Gesture::Tap { x: 110, y: 55 },
+        ];
+        assert_eq!(gestures(trace), expected);
+
+        // too late and too far away
+        let trace: Trace = &[
+            (0, &[(0, 100, 50)]),
+            (100, &[]),
+            (500, &[(0, 100, 50)]),
+            (600, &[]),
+            (700, &[(0, 200, 50)]),
+            (800, &[]),
+        ];
+        let taps = [(100, 50), (100, 50), (200, 50)];
+        let expected: Vec<_> = taps.iter().map(|&(x, y)| Gesture::Tap { x, y }).collect();
+        assert_eq!(gestures(trace), expected);
+    }
+
+    #[test]
+    fn swipe_directions() {
+        // from (200, 100) to the point
+        let swipes = [
+            ((100, 100), SwipeDirection::Left),
+            ((300, 100), SwipeDirection::Right),
+            ((200, 10), SwipeDirection::Up),
+            ((200, 190), SwipeDirection::Down),
+            // the larger distance decides
+            ((120, 140), SwipeDirection::Left),
+        ];
+        for &((x, y), direction) in &swipes {
+            let trace: Trace = &[
+                (0, &[(0, 200, 100)]),
+                (100, &[(0, (200 + x) / 2, (100 + y) / 2)]),
+                (200, &[(0, x, y)]),
+                (300, &[]),
+            ];
+            assert_eq!(gestures(trace), [Gesture::Swipe(direction)], "to ({}, {})", x, y);
+        }
+
+        // too short and too slow
+        let trace: Trace = &[(0, &[(0, 200, 100)]), (100, &[(0, 240, 100)]), (200, &[])];
+        assert_eq!(gestures(trace), []);
+        let trace: Trace = &[(0, &[(0, 200, 100)]), (100, &[(0, 300, 100)]), (600, &[])];
+        assert_eq!(gestures(trace), []);
+    }
+
+    #[test]
+    fn long_press() {
+        let press = Gesture::LongPress { x: 102, y: 50 };
+        let trace: Trace = &[
+            (0, &[(0, 100, 50)]),
+            (300, &[(0, 102, 50)]),
+            (599, &[(0, 102, 50)]),
+            (600, &[(0, 102, 50)]),
+            (900, &[(0, 102, 50)]),
+            (1000, &[]),
+        ];
+        // reported once at 600 ms, and the release is no tap
+        assert_eq!(gestures(trace), [press]);
+
+        let mut recognizer = GestureRecognizer::new(GestureConfig::default());
+        let event = |kind, time| TouchEvent {
+            kind,
+            id: 0,
+            x: 102,
+            y: 50,
+            time,
+        };
+        // the time wraps around
+        let start = usize::max_value() - 100;
+        assert_eq!(recognizer.handle(event(TouchEventKind::Down, start)), None);
+        assert_eq!(recognizer.poll(start.wrapping_add(599)), None);
+        assert_eq!(recognizer.poll(start.wrapping_add(600)), Some(press));
+        assert_eq!(recognizer.poll(start.wrapping_add(700)), None);
+
+        // moving the finger prevents the long press
+        let trace: Trace = &[(0, &[(0, 100, 50)]), (300, &[(0, 150, 50)]), (700, &[])];
+        assert_eq!(gestures(trace), []);
+    }
+
+    #[test]
+    fn pinch() {
+        let trace: Trace = &[
+            (0, &[(0, 100, 100)]),
+            (10, &[(0, 100, 100), (1, 200, 100)]),
+            (20, &[(0, 100, 100), (1, 300, 100)]),
+            (30, &[(0, 100, 100), (1, 100, 200)]),
+            (40, &[(0, 100, 100), (1, 100, 200)]),
+            (50, &[(1, 100, 200)]),
+            (60, &[]),
+        ];
+        let expected = [
+            Gesture::Pinch {
+                scale: 2000,
+                rotation: 0,
+            },
+            Gesture::Pinch {
+                scale: 1000,
+                rotation: 90,
+            },
+        ];
+        assert_eq!(gestures(trace), expected);
+    }
+
+    #[test]
+    fn math() {
+        assert_eq!(sqrt(0), 0);
+        assert_eq!(sqrt(15), 3);
+        assert_eq!(sqrt(16), 4);
+        assert_eq!(sqrt(u64::from(u32::max_value()) * 2), 92681);
+        assert_eq!(atan2(0, 0), 0);
+        assert_eq!(atan2(0, 10), 0);
+        assert_eq!(atan2(10, 10), 45);
+        assert_eq!(atan2(10, 0), 90);
+        assert_eq!(atan2(0, -10), 180);
+        assert_eq!(atan2(-10, -10), -135);
+        // tan(30°) ≈ 0.5774
+        assert_eq!(atan2(5774, 10000), 30);
+    }
+}
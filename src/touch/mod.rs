@@ -0,0 +1,170 @@
+//! Touchscreen functions.
+//!
+//! [`touches`](touches) polls the current touch points of the FT5336 controller. The
+//! [`TouchTracker`](TouchTracker) turns the polled points into down, move and up events per
+//! finger, and the [`GestureRecognizer`](GestureRecognizer) recognizes taps, swipes and other
+//! gestures in these events. Both only process their input, so they can be tested on the host
+//! with recorded touch traces.
+//!
+//! The sensitivity, the report rate and the interrupt mode of the controller are set through a
+//! [`Config`](Config). A [`Transform`](Transform) maps the points of the panel to rotated or
+//! mirrored content.
+//!
+//! In async tasks, the [`TouchStream`](TouchStream) yields the events of the tracker whenever the
+//! controller signals new touch points.
+//!
+//! # Examples
+//! ```rust
+//! let mut tracker = touch::TouchTracker::new();
+//! let mut gestures = touch::GestureRecognizer::new(touch::GestureConfig::default());
+//! loop {
+//!     let time = system_clock::ms();
+//!     let touches = touch::touches(&mut i2c_3).unwrap();
+//!     for event in tracker.update(&touches, time) {
+//!         if let Some(gesture) = gestures.handle(event) {
+//!             println!("{:?}", gesture);
+//!         }
+//!     }
+//!     // long presses are recognized while the finger doesn't move
+//!     if let Some(gesture) = gestures.poll(time) {
+//!         println!("{:?}", gesture);
+//!     }
+//! }
+//! ```
+
+pub use self::config::{config, configure, gesture_id, Config, GestureId, InterruptMode};
+pub use self::gesture::{Gesture, GestureConfig, GestureRecognizer, SwipeDirection};
+pub use self::stream::{enable_interrupt, TouchStream};
+pub use self::tracker::{TouchEvent, TouchEventKind, TouchTracker};
+pub use self::transform::{Rotation, Transform};
+
+use crate::i2c::{self, I2C};
+use arrayvec::ArrayVec;
+use stm32f7::stm32f7x6 as device;
+
+mod config;
+mod gesture;
+mod stream;
+mod tracker;
+mod transform;
+
+/// The maximum number of touch points that the controller reports.
+pub const MAX_TOUCHES: usize = 5;
+
+const FT5336_ADDRESS: i2c::Address = i2c::Address::bits_7(0b011_1000);
+const FT5336_FAMILY_ID_REGISTER: u8 = 0xA8;
+const FT5336_FAMILY_ID: u8 = 0x51;
+const FT5336_STATUS_REGISTER: u8 = 0x02;
+
+// Start locations for reading pressed touches
+const FT5336_DATA_REGISTERS: [u8; MAX_TOUCHES] = [0x03, 0x09, 0x0F, 0x15, 0x1B];
+
+/// Errors that can occur while accessing the touch controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The communication over the I2C bus failed.
+    I2c(i2c::Error),
+    /// The family ID register contains the value instead of the ID of the FT5336.
+    UnexpectedFamilyId(u8),
+}
+
+impl From<i2c::Error> for Error {
+    fn from(err: i2c::Error) -> Error {
+        Error::I2c(err)
+    }
+}
+
+/// Checks the whether the device familiy ID register contains the expected value.
+pub fn check_family_id(i2c_3: &mut I2C<device::I2C3>) -> Result<(), Error> {
+    match read_register(i2c_3, FT5336_FAMILY_ID_REGISTER)? {
+        FT5336_FAMILY_ID => Ok(()),
+        id => Err(Error::UnexpectedFamilyId(id)),
+    }
+}
+
+// Reads consecutive registers of the controller, starting at `register`.
+fn read_registers(
+    i2c_3: &mut I2C<device::I2C3>,
+    register: u8,
+    values: &mut [u8],
+) -> Result<(), Error> {
+    i2c_3.connect::<u8, _>(FT5336_ADDRESS, |mut conn| conn.read_bytes(register, values))?;
+    Ok(())
+}
+
+fn read_register(i2c_3: &mut I2C<device::I2C3>, register: u8) -> Result<u8, Error> {
+    let mut value = [0];
+    read_registers(i2c_3, register, &mut value)?;
+    Ok(value[0])
+}
+
+fn write_register(i2c_3: &mut I2C<device::I2C3>, register: u8, value: u8) -> Result<(), Error> {
+    i2c_3.connect::<u8, _>(FT5336_ADDRESS, |mut conn| conn.write(register, value))?;
+    Ok(())
+}
+
+/// The event flag that the controller reports for a touch point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchFlag {
+    /// The finger touched the display.
+    PressDown,
+    /// The finger was lifted.
+    LiftUp,
+    /// The finger is still touching the display.
+    Contact,
+    /// No event.
+    NoEvent,
+}
+
+impl TouchFlag {
+    fn from_bits(bits: u8) -> TouchFlag {
+        match bits & 0b11 {
+            0b00 => TouchFlag::PressDown,
+            0b01 => TouchFlag::LiftUp,
+            0b10 => TouchFlag::Contact,
+            _ => TouchFlag::NoEvent,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Represents a touch point on the display at coordinates (x,y).
+pub struct Touch {
+    /// The x coordinate of the touch point (horizontal).
+    pub x: u16,
+    /// The y coordinate of the touch point (vertical).
+    pub y: u16,
+    /// Identifies the finger. The id stays the same while the finger touches the display.
+    pub id: u8,
+    /// The event flag of the touch point.
+    pub flag: TouchFlag,
+}
+
+/// Returns a list of active touch points.
+///
+/// The coordinates are the ones of the panel, use a [`Transform`](Transform) for rotated
+/// content.
+pub fn touches(i2c_3: &mut I2C<device::I2C3>) -> Result<ArrayVec<[Touch; MAX_TOUCHES]>, Error> {
+    let mut touches = ArrayVec::new();
+    i2c_3.connect::<u8, _>(FT5336_ADDRESS, |mut conn| {
+        let status = conn.read(FT5336_STATUS_REGISTER)?;
+        let mut number_of_touches = status & 0x0F;
+        if usize::from(number_of_touches) > MAX_TOUCHES {
+            number_of_touches = 0;
+        }
+
+        for &data_reg in FT5336_DATA_REGISTERS.iter().take(number_of_touches.into()) {
+            let mut touch_data: [u8; 4] = [0; 4];
+            conn.read_bytes(data_reg, &mut touch_data)?;
+            // the event flag is in the upper bits of the first byte, the id in the third byte
+            let flag = TouchFlag::from_bits(touch_data[0] >> 6);
+            let id = touch_data[2] >> 4;
+            let y = (u16::from(touch_data[0] & 0x0F) << 8) | u16::from(touch_data[1]);
+            let x = (u16::from(touch_data[2] & 0x0F) << 8) | u16::from(touch_data[3]);
+            touches.push(Touch { x, y, id, flag });
+        }
+        Ok(())
+    })?;
+
+    Ok(touches)
+}
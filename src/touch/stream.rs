@@ -0,0 +1,145 @@
+//! An asynchronous stream of touch events.
+
+use super::{touches, TouchEvent, TouchTracker, MAX_TOUCHES};
+use crate::future_mutex::FutureMutex;
+use crate::i2c::I2C;
+use crate::interrupts::exti::Exti15To10;
+use crate::system_clock;
+use crate::task_runtime::mpsc;
+use alloc::sync::Arc;
+use arrayvec::ArrayVec;
+use core::pin::Pin;
+use futures::{
+    prelude::*,
+    task::{Context, Poll},
+};
+use pin_utils::pin_mut;
+use stm32f7::stm32f7x6::{self as device, EXTI, SYSCFG};
+
+/// A stream of the touch events of all fingers.
+///
+/// The touch points are read whenever the wrapped stream yields an item, while holding the I2C
+/// mutex, and the stream yields the events of a [`TouchTracker`](TouchTracker). The event times
+/// are the ones of [`system_clock::ms`](crate::system_clock::ms).
+///
+/// Normally the wrapped stream is the EXTI13 line of the touch controller, which is set up by
+/// [`register`](TouchStream::register). The EXTI15_10 interrupt is shared with other lines, e.g.
+/// the button on pin I-11, so its handler is the [`Exti15To10`](Exti15To10) dispatcher, which
+/// forwards the EXTI13 line to the stream.
+///
+/// If reading the touch points fails, the interrupt is skipped and the touch points are read
+/// again on the next one.
+///
+/// # Examples
+/// ```rust
+/// let mut exti_15_10 = Exti15To10::register(interrupt_table, Priority::P1)
+///     .expect("registering exti15_10 interrupt failed");
+/// let i2c_3_mutex = Arc::new(FutureMutex::new(i2c_3));
+/// let touch_events =
+///     touch::TouchStream::register(&mut exti_15_10, &mut syscfg, &mut exti, i2c_3_mutex.clone());
+///
+/// // in an async task
+/// pin_mut!(touch_events);
+/// let mut gestures = touch::GestureRecognizer::new(touch::GestureConfig::default());
+/// loop {
+///     let event = await!(touch_events.next()).expect("touch stream closed");
+///     if let Some(gesture) = gestures.handle(event) {
+///         println!("{:?}", gesture);
+///     }
+/// }
+/// ```
+#[must_use = "streams do nothing unless polled"]
+pub struct TouchStream<S> {
+    interrupts: S,
+    i2c_3: Arc<FutureMutex<I2C<device::I2C3>>>,
+    tracker: TouchTracker,
+    // the events of the last read in reverse order
+    events: ArrayVec<[TouchEvent; 2 * MAX_TOUCHES]>,
+    // an interrupt arrived, but the touch points were not read yet
+    read_pending: bool,
+}
+
+impl TouchStream<mpsc::UnboundedReceiver<()>> {
+    /// Connects the interrupt pin of the touch controller to the EXTI13 line and creates a stream
+    /// that reads the touch points whenever the line triggers the EXTI15_10 interrupt.
+    ///
+    /// A previous stream of the EXTI13 line, e.g. of the SD card detection, ends.
+    pub fn register(
+        exti_15_10: &mut Exti15To10,
+        syscfg: &mut SYSCFG,
+        exti: &mut EXTI,
+        i2c_3: Arc<FutureMutex<I2C<device::I2C3>>>,
+    ) -> Self {
+        enable_interrupt(syscfg, exti);
+        TouchStream::new(exti_15_10.line(13), i2c_3)
+    }
+}
+
+impl<S> TouchStream<S> {
+    /// Creates a stream that reads the touch points whenever `interrupts` yields an item.
+    pub fn new(interrupts: S, i2c_3: Arc<FutureMutex<I2C<device::I2C3>>>) -> TouchStream<S> {
+        TouchStream {
+            interrupts,
+            i2c_3,
+            tracker: TouchTracker::new(),
+            events: ArrayVec::new(),
+            read_pending: false,
+        }
+    }
+
+    /// Returns the tracker, e.g. to get the fingers that touched the display at the last read.
+    pub fn tracker(&self) -> &TouchTracker {
+        &self.tracker
+    }
+}
+
+/// Connects the interrupt pin of the touch controller (I-13) to the EXTI13 line and triggers it
+/// on the rising edge.
+///
+/// The EXTI13 line is part of the EXTI15_10 interrupt. An EXTI line can only be connected to one
+/// port, so this can't be used together with the card detection of the SD card slot on pin C-13.
+pub fn enable_interrupt(syscfg: &mut SYSCFG, exti: &mut EXTI) {
+    // choose pin I-13 for exti13 line
+    syscfg
+        .exticr4
+        .modify(|_, w| unsafe { w.exti13().bits(0b1000) });
+    // trigger exti13 on rising
+    exti.rtsr.modify(|_, w| w.tr13().set_bit());
+    // unmask exti13 line
+    exti.imr.modify(|_, w| w.mr13().set_bit());
+}
+
+impl<S: Stream<Item = ()> + Unpin> Stream for TouchStream<S> {
+    type Item = TouchEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<TouchEvent>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.events.pop() {
+                return Poll::Ready(Some(event));
+            }
+
+            if !this.read_pending {
+                match Pin::new(&mut this.interrupts).poll_next(cx) {
+                    Poll::Ready(Some(())) => this.read_pending = true,
+                    Poll::Ready(None) => return Poll::Ready(None),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            // The future doesn't hold the lock while it's pending, so it can be recreated on
+            // every poll. It's woken when the mutex is released.
+            let read = this.i2c_3.with(|i2c_3| touches(i2c_3));
+            pin_mut!(read);
+            let result = match read.poll(cx) {
+                Poll::Ready(result) => result,
+                Poll::Pending => return Poll::Pending,
+            };
+            this.read_pending = false;
+            if let Ok(touches) = result {
+                let events = this.tracker.update(&touches, system_clock::ms());
+                this.events = events.into_iter().rev().collect();
+            }
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Turning polled touch points into events per finger.
+
+use super::{Touch, TouchFlag, MAX_TOUCHES};
+use arrayvec::ArrayVec;
+
+/// What happened to a finger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchEventKind {
+    /// The finger touched the display.
+    Down,
+    /// The finger moved while touching the display.
+    Move,
+    /// The finger was lifted. The position is the last position of the finger.
+    Up,
+}
+
+/// An event of a single finger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TouchEvent {
+    /// What happened.
+    pub kind: TouchEventKind,
+    /// Identifies the finger, see [`Touch::id`](super::Touch::id).
+    pub id: u8,
+    /// The x coordinate of the finger.
+    pub x: u16,
+    /// The y coordinate of the finger.
+    pub y: u16,
+    /// The time of the event in milliseconds, as passed to
+    /// [`TouchTracker::update`](TouchTracker::update).
+    pub time: usize,
+}
+
+/// Compares the touch points of successive polls to find out which fingers touched the display,
+/// moved or were lifted.
+///
+/// The fingers are identified by the touch ids of the controller.
+#[derive(Debug, Clone, Default)]
+pub struct TouchTracker {
+    fingers: ArrayVec<[Touch; MAX_TOUCHES]>,
+}
+
+impl TouchTracker {
+    /// Creates a tracker without touching fingers.
+    pub fn new() -> TouchTracker {
+        TouchTracker {
+            fingers: ArrayVec::new(),
+        }
+    }
+
+    /// Returns the fingers that currently touch the display.
+    pub fn fingers(&self) -> &[Touch] {
+        &self.fingers
+    }
+
+    /// Compares the polled touch points with the last ones and returns the events.
+    ///
+    /// `time` is the current time in milliseconds, e.g.
+    /// [`system_clock::ms`](crate::system_clock::ms). The up events come before the down
+    /// events, so that a finger id can be reused in the same poll.
+    pub fn update(
+        &mut self,
+        touches: &[Touch],
+        time: usize,
+    ) -> ArrayVec<[TouchEvent; 2 * MAX_TOUCHES]> {
+        let mut events = ArrayVec::new();
+        let event = |kind, touch: &Touch| TouchEvent {
+            kind,
+            id: touch.id,
+            x: touch.x,
+            y: touch.y,
+            time,
+        };
+        let is_touching = |touch: &&Touch| touch.flag != TouchFlag::LiftUp;
+        let find = |touch: &Touch| touches.iter().filter(is_touching).find(|t| t.id == touch.id);
+
+        for finger in &self.fingers {
+            if find(finger).is_none() {
+                events.push(event(TouchEventKind::Up, finger));
+            }
+        }
+        let mut fingers: ArrayVec<[Touch; MAX_TOUCHES]> = ArrayVec::new();
+        for touch in touches.iter().filter(is_touching) {
+            if fingers.is_full() {
+                break;
+            } else if fingers.iter().any(|finger| finger.id == touch.id) {
+                continue; // reported twice
+            }
+            match self.fingers.iter().find(|finger| finger.id == touch.id) {
+                None => events.push(event(TouchEventKind::Down, touch)),
+                Some(finger) if (finger.x, finger.y) != (touch.x, touch.y) => {
+                    events.push(event(TouchEventKind::Move, touch))
+                }
+                Some(_) => {}
+            }
+            fingers.push(*touch);
+        }
+        self.fingers = fingers;
+        events
+    }
+
+    /// Lifts all fingers, e.g. because the touch controller was reset.
+    pub fn reset(&mut self, time: usize) -> ArrayVec<[TouchEvent; 2 * MAX_TOUCHES]> {
+        self.update(&[], time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn touch(id: u8, x: u16, y: u16) -> Touch {
+        Touch {
+            x,
+            y,
+            id,
+            flag: TouchFlag::Contact,
+        }
+    }
+
+    fn kinds(events: &[TouchEvent]) -> Vec<(TouchEventKind, u8, u16, u16)> {
+        events.iter().map(|e| (e.kind, e.id, e.x, e.y)).collect()
+    }
+
+    #[test]
+    fn down_move_up() {
+        use super::TouchEventKind::{Down, Move, Up};
+        let mut tracker = TouchTracker::new();
+        let events = tracker.update(&[touch(0, 10, 20)], 5);
+        assert_eq!(kinds(&events), [(Down, 0, 10, 20)]);
+        assert_eq!(events[0].time, 5);
+        assert!(tracker.update(&[touch(0, 10, 20)], 6).is_empty());
+
+        let events = tracker.update(&[touch(0, 11, 20), touch(1, 50, 60)], 7);
+        assert_eq!(kinds(&events), [(Move, 0, 11, 20), (Down, 1, 50, 60)]);
+        assert_eq!(tracker.fingers().len(), 2);
+
+        // lifted fingers are reported with their last position
+        let lifted = Touch {
+            flag: TouchFlag::LiftUp,
+            ..touch(1, 55, 65)
+        };
+        let events = tracker.update(&[lifted], 8);
+        assert_eq!(kinds(&events), [(Up, 0, 11, 20), (Up, 1, 50, 60)]);
+        assert!(tracker.fingers().is_empty());
+        assert!(tracker.update(&[], 9).is_empty());
+    }
+
+    #[test]
+    fn lifted_and_repeated_fingers() {
+        use super::TouchEventKind::{Down, Up};
+        let mut tracker = TouchTracker::new();
+        tracker.update(&[touch(0, 10, 20)], 0);
+        // one finger is lifted while another one touches the display
+        let events = tracker.update(&[touch(1, 100, 100)], 1);
+        assert_eq!(kinds(&events), [(Up, 0, 10, 20), (Down, 1, 100, 100)]);
+
+        // touches that are reported twice are one finger
+        let events = tracker.update(&[touch(1, 100, 100), touch(1, 200, 200)], 2);
+        assert!(events.is_empty());
+        assert_eq!(tracker.fingers(), [touch(1, 100, 100)]);
+
+        let events = tracker.reset(3);
+        assert_eq!(kinds(&events), [(Up, 1, 100, 100)]);
+    }
+
+    #[test]
+    fn too_many_touches() {
+        let mut tracker = TouchTracker::new();
+        let touches: Vec<_> = (0..MAX_TOUCHES as u8 + 2).map(|id| touch(id, 1, 1)).collect();
+        let events = tracker.update(&touches, 0);
+        assert_eq!(events.len(), MAX_TOUCHES);
+        assert_eq!(tracker.fingers(), &touches[..MAX_TOUCHES]);
+    }
+
+    #[test]
+    fn events_are_paired() {
+        // a pseudo-random trace of up to 3 fingers
+        let mut state = 7u32;
+        let mut random = move |max: u32| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) % max
+        };
+        let mut tracker = TouchTracker::new();
+        let mut down = [None; 3];
+        for time in 0..1000 {
+            let mut touches = Vec::new();
+            for id in 0..3 {
+                if random(4) != 0 {
+                    touches.push(touch(id, random(3) as u16, random(3) as u16));
+                }
+            }
+            let mut events = tracker.update(&touches, time).into_iter().collect::<Vec<_>>();
+            if time == 999 {
+                events.extend(tracker.reset(time));
+            }
+            for event in events {
+                let finger = &mut down[usize::from(event.id)];
+                match event.kind {
+                    TouchEventKind::Down => assert_eq!(finger.replace((event.x, event.y)), None),
+                    TouchEventKind::Move => {
+                        let last = finger.replace((event.x, event.y)).unwrap();
+                        assert_ne!(last, (event.x, event.y));
+                    }
+                    TouchEventKind::Up => {
+                        assert_eq!(finger.take(), Some((event.x, event.y)));
+                    }
+                }
+            }
+            let fingers = down.iter().filter(|finger| finger.is_some()).count();
+            assert_eq!(fingers, tracker.fingers().len());
+        }
+        assert_eq!(down, [None; 3]);
+    }
+}
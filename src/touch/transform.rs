@@ -0,0 +1,276 @@
+//! Mapping the coordinates of the touch panel to the coordinates of the content.
+
+use super::Touch;
+use crate::lcd::{HEIGHT, WIDTH};
+
+/// The clockwise rotation of the content on the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// The content is not rotated.
+    Deg0,
+    /// The content is rotated by 90 degrees, so its top is at the right of the display.
+    Deg90,
+    /// The content is upside down.
+    Deg180,
+    /// The content is rotated by 270 degrees, so its top is at the left of the display.
+    Deg270,
+}
+
+/// Maps the touch points of the panel to the coordinates of the content.
+///
+/// The offset calibrates a panel that is shifted against the display. It's applied first, then
+/// the coordinates are mirrored and finally rotated. For the rotations by 90 and 270 degrees,
+/// the content is [`HEIGHT`](crate::lcd::HEIGHT) pixels wide and
+/// [`WIDTH`](crate::lcd::WIDTH) pixels high.
+///
+/// # Examples
+/// ```rust
+/// // the board is mounted upside down
+/// let transform = touch::Transform {
+///     rotation: touch::Rotation::Deg180,
+///     ..touch::Transform::new()
+/// };
+/// for touch in touch::touches(&mut i2c_3)? {
+///     let touch = transform.apply(touch);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transform {
+    /// The rotation of the content.
+    pub rotation: Rotation,
+    /// Whether the x coordinates of the panel are mirrored.
+    pub mirror_x: bool,
+    /// Whether the y coordinates of the panel are mirrored.
+    pub mirror_y: bool,
+    /// Added to the x coordinates of the panel.
+    pub offset_x: i16,
+    /// Added to the y coordinates of the panel.
+    pub offset_y: i16,
+}
+
+impl Transform {
+    /// Creates a transform that doesn't change the coordinates.
+    pub fn new() -> Transform {
+        Transform {
+            rotation: Rotation::Deg0,
+            mirror_x: false,
+            mirror_y: false,
+            offset_x: 0,
+            offset_y: 0,
+        }
+    }
+
+    /// Maps the coordinates of the touch point. The id and the flag are kept.
+    pub fn apply(&self, touch: Touch) -> Touch {
+        let (x, y) = self.apply_point(touch.x, touch.y);
+        Touch { x, y, ..touch }
+    }
+
+    /// Maps a point on the panel to the coordinates of the content.
+    pub fn apply_point(&self, x: u16, y: u16) -> (u16, u16) {
+        let (max_x, max_y) = (WIDTH as u16 - 1, HEIGHT as u16 - 1);
+        let x = offset(x, self.offset_x, max_x);
+        let y = offset(y, self.offset_y, max_y);
+        let x = if self.mirror_x { max_x - x } else { x };
+        let y = if self.mirror_y { max_y - y } else { y };
+        match self.rotation {
+            Rotation::Deg0 => (x, y),
+            Rotation::Deg90 => (y, max_x - x),
+            Rotation::Deg180 => (max_x - x, max_y - y),
+            Rotation::Deg270 => (max_y - y, x),
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::new()
+    }
+}
+
+// Adds the offset to the coordinate and keeps the result between 0 and `max`.
+fn offset(value: u16, offset: i16, max: u16) -> u16 {
+    let value = i32::from(value) + i32::from(offset);
+    value.max(0).min(i32::from(max)) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::touch::TouchFlag;
+
+    const MAX_X: u16 = WIDTH as u16 - 1;
+    const MAX_Y: u16 = HEIGHT as u16 - 1;
+
+    fn rotated(rotation: Rotation) -> Transform {
+        Transform {
+            rotation,
+            ..Transform::new()
+        }
+    }
+
+    #[test]
+    fn identity() {
+        let transform = Transform::default();
+        assert_eq!(transform.apply_point(0, 0), (0, 0));
+        assert_eq!(transform.apply_point(12, 34), (12, 34));
+        assert_eq!(transform.apply_point(MAX_X, MAX_Y), (MAX_X, MAX_Y));
+    }
+
+    #[test]
+    fn rotations() {
+        let corners = [(0, 0), (MAX_X, 0), (0, MAX_Y), (MAX_X, MAX_Y), (12, 34)];
+        let expected = [
+            (
+                Rotation::Deg0,
+                [(0, 0), (MAX_X, 0), (0, MAX_Y), (MAX_X, MAX_Y), (12, 34)],
+            ),
+            (
+                Rotation::Deg90,
+                [
+                    (0, MAX_X),
+                    (0, 0),
+                    (MAX_Y, MAX_X),
+                    (MAX_Y, 0),
+                    (34, MAX_X - 12),
+                ],
+            ),
+            (
+                Rotation::Deg180,
+                [
+                    (MAX_X, MAX_Y),
+                    (0, MAX_Y),
+                    (MAX_X, 0),
+                    (0, 0),
+                    (MAX_X - 12, MAX_Y - 34),
+                ],
+            ),
+            (
+                Rotation::Deg270,
+                [
+                    (MAX_Y, 0),
+                    (MAX_Y, MAX_X),
+                    (0, 0),
+                    (0, MAX_X),
+                    (MAX_Y - 34, 12),
+                ],
+            ),
+        ];
+        for &(rotation, points) in expected.iter() {
+            let transform = rotated(rotation);
+            for (&(x, y), &point) in corners.iter().zip(points.iter()) {
+                assert_eq!(
+                    transform.apply_point(x, y),
+                    point,
+                    "{:?} ({}, {})",
+                    rotation,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rotated_content_size() {
+        // the content is HEIGHT pixels wide and WIDTH pixels high after quarter turns
+        for &rotation in [Rotation::Deg90, Rotation::Deg270].iter() {
+            let transform = rotated(rotation);
+            for &(x, y) in [(0, 0), (MAX_X, 0), (0, MAX_Y), (MAX_X, MAX_Y)].iter() {
+                let (x, y) = transform.apply_point(x, y);
+                assert!(x <= MAX_Y && y <= MAX_X);
+            }
+        }
+    }
+
+    #[test]
+    fn mirror_with_rotation() {
+        let all = [
+            Rotation::Deg0,
+            Rotation::Deg90,
+            Rotation::Deg180,
+            Rotation::Deg270,
+        ];
+        for &rotation in all.iter() {
+            for &(mirror_x, mirror_y) in [(true, false), (false, true), (true, true)].iter() {
+                let transform = Transform {
+                    rotation,
+                    mirror_x,
+                    mirror_y,
+                    ..Transform::new()
+                };
+                // mirroring happens before the rotation
+                let x = if mirror_x { MAX_X - 12 } else { 12 };
+                let y = if mirror_y { MAX_Y - 34 } else { 34 };
+                assert_eq!(
+                    transform.apply_point(12, 34),
+                    rotated(rotation).apply_point(x, y),
+                    "{:?} {} {}",
+                    rotation,
+                    mirror_x,
+                    mirror_y
+                );
+            }
+        }
+        let transform = Transform {
+            rotation: Rotation::Deg180,
+            mirror_x: true,
+            mirror_y: true,
+            ..Transform::new()
+        };
+        assert_eq!(transform.apply_point(12, 34), (12, 34));
+    }
+
+    #[test]
+    fn offset_clamping() {
+        let transform = Transform {
+            offset_x: -20,
+            offset_y: 30,
+            ..Transform::new()
+        };
+        assert_eq!(transform.apply_point(100, 100), (80, 130));
+        assert_eq!(transform.apply_point(10, MAX_Y - 10), (0, MAX_Y));
+        assert_eq!(transform.apply_point(0, 0), (0, 30));
+
+        let extreme = Transform {
+            offset_x: i16::max_value(),
+            offset_y: i16::min_value(),
+            ..Transform::new()
+        };
+        assert_eq!(extreme.apply_point(0, MAX_Y), (MAX_X, 0));
+        assert_eq!(
+            extreme.apply_point(u16::max_value(), u16::max_value()),
+            (MAX_X, MAX_Y)
+        );
+    }
+
+    #[test]
+    fn offset_before_mirror_and_rotation() {
+        // the offset calibrates the panel, so it's applied to the raw coordinates
+        let transform = Transform {
+            rotation: Rotation::Deg90,
+            mirror_x: true,
+            mirror_y: false,
+            offset_x: 5,
+            offset_y: -300,
+        };
+        // x: 10 + 5 = 15, mirrored to MAX_X - 15; y: clamped to 0
+        assert_eq!(transform.apply_point(10, 20), (0, 15));
+        // the clamping keeps mirrored coordinates in range
+        assert_eq!(transform.apply_point(MAX_X, 0), (0, MAX_X));
+    }
+
+    #[test]
+    fn apply_keeps_id_and_flag() {
+        let transform = rotated(Rotation::Deg180);
+        let touch = Touch {
+            x: 1,
+            y: 2,
+            id: 3,
+            flag: TouchFlag::LiftUp,
+        };
+        let touch = transform.apply(touch);
+        assert_eq!((touch.x, touch.y), (MAX_X - 1, MAX_Y - 2));
+        assert_eq!((touch.id, touch.flag), (3, TouchFlag::LiftUp));
+    }
+}
@@ -0,0 +1,166 @@
+//! Arranging the widgets in rows and columns.
+
+use super::{Content, Size, Ui, WidgetId};
+use crate::lcd::{Color, Rect};
+use alloc::vec::Vec;
+
+/// The direction in which a container arranges its children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From left to right.
+    Row,
+    /// From top to bottom.
+    Column,
+}
+
+/// Arranges its children in a row or a column.
+///
+/// The children get their preferred size along the direction of the container, and the free
+/// space is divided according to their grow shares (see [`Ui::set_grow`](super::Ui::set_grow)).
+/// Across the direction, the children are stretched to the size of the container.
+///
+/// # Examples
+/// ```rust
+/// // a toolbar with a gap between the buttons and a dark background
+/// let toolbar = ui::Container {
+///     spacing: 8,
+///     background: Some(Color::rgb(32, 32, 32)),
+///     ..ui::Container::row()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Container {
+    /// The direction in which the children are arranged.
+    pub direction: Direction,
+    /// The space between the children.
+    pub spacing: usize,
+    /// The space between the edges of the container and the children.
+    pub padding: usize,
+    /// The color that is drawn behind the children, if any.
+    pub background: Option<Color>,
+}
+
+impl Container {
+    /// Creates a container without background, with a spacing and padding of 4 pixels.
+    pub fn new(direction: Direction) -> Container {
+        Container {
+            direction,
+            spacing: 4,
+            padding: 4,
+            background: None,
+        }
+    }
+
+    /// Creates a container that arranges its children from left to right.
+    pub fn row() -> Container {
+        Container::new(Direction::Row)
+    }
+
+    /// Creates a container that arranges its children from top to bottom.
+    pub fn column() -> Container {
+        Container::new(Direction::Column)
+    }
+}
+
+impl Ui {
+    // Arranges all widgets if the tree or a size changed.
+    pub(super) fn update_layout(&mut self) {
+        if self.needs_layout {
+            self.needs_layout = false;
+            self.layout(self.root(), self.bounds);
+            self.invalidate_all();
+        }
+    }
+
+    fn layout(&mut self, id: WidgetId, bounds: Rect) {
+        let (container, children) = match self.node_mut(id) {
+            Some(node) => {
+                node.bounds = bounds;
+                match &node.content {
+                    Content::Container(container, children) => (*container, children.clone()),
+                    Content::Widget(_) => return,
+                }
+            }
+            None => return,
+        };
+
+        let padding = container.padding;
+        let inner = Rect::new(
+            bounds.x + padding,
+            bounds.y + padding,
+            bounds.width.saturating_sub(2 * padding),
+            bounds.height.saturating_sub(2 * padding),
+        );
+        let (inner_main, inner_cross) = along(container.direction, inner.width, inner.height);
+
+        let mut sizes = Vec::with_capacity(children.len());
+        let mut total_grow = 0;
+        for &child in &children {
+            let size = self.preferred_size(child);
+            let grow = self.node(child).map_or(0, |node| node.grow);
+            sizes.push((along(container.direction, size.width, size.height).0, grow));
+            total_grow += grow;
+        }
+        let spacing = container.spacing * children.len().saturating_sub(1);
+        let used = sizes.iter().map(|&(main, _)| main).sum::<usize>() + spacing;
+        let mut free = inner_main.saturating_sub(used);
+
+        let mut offset = 0;
+        let mut remaining_grow = total_grow;
+        for (&child, &(main, grow)) in children.iter().zip(&sizes) {
+            // the last growing child gets the remainder of the division
+            let extra = if grow == 0 {
+                0
+            } else if grow == remaining_grow {
+                free
+            } else {
+                free * grow as usize / remaining_grow as usize
+            };
+            free -= extra;
+            remaining_grow -= grow;
+
+            let main = (main + extra).min(inner_main.saturating_sub(offset));
+            let child_bounds = match container.direction {
+                Direction::Row => Rect::new(inner.x + offset, inner.y, main, inner_cross),
+                Direction::Column => Rect::new(inner.x, inner.y + offset, inner_cross, main),
+            };
+            self.layout(child, child_bounds);
+            offset = (offset + main + container.spacing).min(inner_main);
+        }
+    }
+
+    // Returns the fixed size or the preferred size of a widget or container.
+    fn preferred_size(&self, id: WidgetId) -> Size {
+        let node = match self.node(id) {
+            Some(node) => node,
+            None => return Size::default(),
+        };
+        if let Some(size) = node.size {
+            return size;
+        }
+        match &node.content {
+            Content::Widget(widget) => widget.preferred_size(&self.theme),
+            Content::Container(container, children) => {
+                let (mut main, mut cross) = (0, 0);
+                for &child in children {
+                    let size = self.preferred_size(child);
+                    let (child_main, child_cross) =
+                        along(container.direction, size.width, size.height);
+                    main += child_main;
+                    cross = cross.max(child_cross);
+                }
+                main += container.spacing * children.len().saturating_sub(1);
+                let (width, height) = along(container.direction, main, cross);
+                Size::new(width + 2 * container.padding, height + 2 * container.padding)
+            }
+        }
+    }
+}
+
+// Returns the size along and across the direction, or converts it back to width and height.
+fn along(direction: Direction, width: usize, height: usize) -> (usize, usize) {
+    match direction {
+        Direction::Row => (width, height),
+        Direction::Column => (height, width),
+    }
+}
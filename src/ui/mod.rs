@@ -0,0 +1,928 @@
+//! A toolkit for touch user interfaces on the LCD.
+//!
+//! The user interface is a retained tree of widgets, which is owned by a [`Ui`](Ui). The widgets
+//! are placed by [`Container`](Container)s, which arrange their children in a row or a column.
+//! When the state of a widget changes, only the area of the widget is drawn again.
+//!
+//! Touches are passed to [`Ui::handle_touches`](Ui::handle_touches), which turns them into
+//! press, move and release events. A press is sent to the topmost widget under the finger, and
+//! the following moves and the release are sent to the same widget, even if the finger leaves
+//! it. The widgets report what the user did, e.g. that a button was clicked, as
+//! [`Action`](Action)s.
+//!
+//! The layout and the event handling don't access the hardware, and drawing works with any
+//! [`Framebuffer`](lcd::Framebuffer), so user interfaces can be tested on the host with a mock
+//! framebuffer.
+//!
+//! # Examples
+//! ```rust
+//! use stm32f7_discovery::ui::{self, Action, Container, Ui};
+//!
+//! let mut ui = Ui::new(
+//!     lcd::Rect::new(0, 0, lcd::WIDTH, lcd::HEIGHT),
+//!     ui::Theme::default(),
+//!     Container::column(),
+//! );
+//! let root = ui.root();
+//! let label = ui.add(root, ui::Label::new("Volume: 50"));
+//! let slider = ui.add(root, ui::Slider::new(0, 100, 50));
+//! let buttons = ui.add_container(root, Container::row());
+//! let mute = ui.add(buttons, ui::Button::new("Mute"));
+//! ui.set_grow(mute, 1);
+//!
+//! loop {
+//!     let touches = touch::touches(&mut i2c_3).unwrap();
+//!     match ui.handle_touches(&touches) {
+//!         Some((id, Action::ValueChanged(value))) if id == slider => {
+//!             let text = format!("Volume: {}", value);
+//!             ui.get_mut::<ui::Label>(label).unwrap().set_text(text);
+//!         }
+//!         Some((id, Action::Clicked)) if id == mute => {
+//!             ui.get_mut::<ui::Slider>(slider).unwrap().set_value(0);
+//!         }
+//!         _ => {}
+//!     }
+//!     ui.draw(&mut layer_1);
+//! }
+//! ```
+
+pub use self::layout::{Container, Direction};
+pub use self::widgets::{Button, Key, Keypad, Label, ListView, ProgressBar, Slider, Toggle};
+
+use crate::lcd::font::{Font, Font8x8};
+use crate::lcd::graphics::{self, Point};
+use crate::lcd::{Color, Framebuffer, PixelBuffer, Rect};
+use crate::touch::{Touch, TouchFlag};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::mem;
+
+mod layout;
+mod widgets;
+
+// More dirty rectangles are merged into their bounding box.
+const MAX_DIRTY_RECTS: usize = 8;
+
+/// The size of a widget in pixels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    /// The number of columns.
+    pub width: usize,
+    /// The number of rows.
+    pub height: usize,
+}
+
+impl Size {
+    /// Creates a size from the width and the height.
+    pub const fn new(width: usize, height: usize) -> Size {
+        Size { width, height }
+    }
+}
+
+/// The colors, the font and the spacing that are used by the widgets.
+#[derive(Clone, Copy)]
+pub struct Theme {
+    /// The font of all text.
+    pub font: &'static dyn Font,
+    /// The color behind the widgets.
+    pub background: Color,
+    /// The color of text.
+    pub foreground: Color,
+    /// The color of buttons, tracks and keys.
+    pub surface: Color,
+    /// The color of pressed buttons and keys.
+    pub pressed: Color,
+    /// The color of active parts, e.g. the filled part of a slider or a selected list item.
+    pub accent: Color,
+    /// The space between the edges of a widget and its content.
+    pub padding: usize,
+    /// The radius of rounded corners.
+    pub radius: u32,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            font: &Font8x8,
+            background: Color::rgb(0, 0, 0),
+            foreground: Color::rgb(255, 255, 255),
+            surface: Color::rgb(64, 64, 64),
+            pressed: Color::rgb(128, 128, 128),
+            accent: Color::rgb(0, 120, 215),
+            padding: 6,
+            radius: 4,
+        }
+    }
+}
+
+/// A touch event, in display coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A finger touched the display.
+    Press(Point),
+    /// The finger moved to a new position.
+    Move(Point),
+    /// The finger was lifted at the last position.
+    Release(Point),
+}
+
+impl Event {
+    /// Returns the position of the finger.
+    pub fn position(self) -> Point {
+        match self {
+            Event::Press(point) | Event::Move(point) | Event::Release(point) => point,
+        }
+    }
+}
+
+/// What the user did with a widget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// A button was clicked.
+    Clicked,
+    /// A toggle was switched to the contained state.
+    Toggled(bool),
+    /// The value of a slider changed.
+    ValueChanged(i32),
+    /// The item with the contained index of a list view was selected.
+    Selected(usize),
+    /// A key of a keypad was pressed.
+    Key(Key),
+    /// An action of a widget of the application.
+    Custom(u32),
+}
+
+/// The reaction of a widget to an event.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Response {
+    /// Whether the appearance of the widget changed, so that it must be drawn again.
+    pub redraw: bool,
+    /// What the user did, if anything.
+    pub action: Option<Action>,
+}
+
+impl Response {
+    /// The widget didn't change.
+    pub fn none() -> Response {
+        Response::default()
+    }
+
+    /// The widget must be drawn again.
+    pub fn redraw() -> Response {
+        Response {
+            redraw: true,
+            action: None,
+        }
+    }
+
+    /// The widget must be drawn again and reports the action.
+    pub fn action(action: Action) -> Response {
+        Response {
+            redraw: true,
+            action: Some(action),
+        }
+    }
+}
+
+/// Converts a reference to [`Any`](core::any::Any), so that widgets can be downcast.
+///
+/// This trait is implemented for all types, widgets don't need to implement it.
+pub trait AsAny {
+    /// Returns `self` as `&dyn Any`.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Returns `self` as `&mut dyn Any`.
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// An element of the user interface.
+///
+/// The toolkit provides the common widgets, but applications can implement their own.
+pub trait Widget: AsAny {
+    /// Returns the size the widget wants to have. Containers may make it larger or smaller.
+    fn preferred_size(&self, theme: &Theme) -> Size;
+
+    /// Draws the widget into its bounds.
+    ///
+    /// The background of the bounds was already drawn. The canvas is clipped to the area that
+    /// is drawn again, which may be smaller than the bounds.
+    fn draw(&self, canvas: &mut Canvas, bounds: Rect, theme: &Theme);
+
+    /// Returns whether the widget receives touch events. Touches on other widgets are ignored.
+    fn is_interactive(&self) -> bool {
+        false
+    }
+
+    /// Handles a touch event.
+    ///
+    /// The widget receives the press on it and all following events until the release, which
+    /// may lie outside of its bounds.
+    fn handle_event(&mut self, _event: Event, _bounds: Rect, _theme: &Theme) -> Response {
+        Response::none()
+    }
+}
+
+/// A framebuffer that only draws the pixels in a clipping rectangle.
+pub struct Canvas<'a> {
+    fb: &'a mut dyn Framebuffer,
+    clip: Rect,
+}
+
+impl<'a> Canvas<'a> {
+    /// Creates a canvas that draws to the part of `fb` that is inside of `clip`.
+    pub fn new(fb: &'a mut dyn Framebuffer, clip: Rect) -> Canvas<'a> {
+        Canvas { fb, clip }
+    }
+
+    /// Returns the clipping rectangle.
+    pub fn clip(&self) -> Rect {
+        self.clip
+    }
+
+    /// Returns a canvas that is additionally clipped to `rect`.
+    pub fn clipped(&mut self, rect: Rect) -> Canvas {
+        Canvas {
+            fb: &mut *self.fb,
+            clip: self.clip.intersection(rect),
+        }
+    }
+}
+
+impl<'a> Framebuffer for Canvas<'a> {
+    fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if self.clip.contains(x, y) {
+            self.fb.set_pixel(x, y, color);
+        }
+    }
+
+    /// Returns the pixel buffer of the framebuffer if the clipping rectangle covers all of it.
+    ///
+    /// Accelerated drawing ignores the clipping rectangle, so clipped canvases return `None` and
+    /// are drawn pixel by pixel.
+    fn pixel_buffer(&mut self) -> Option<PixelBuffer> {
+        let buffer = self.fb.pixel_buffer()?;
+        let all = Rect::new(0, 0, buffer.width(), buffer.height());
+        if self.clip.intersection(all) == all {
+            Some(buffer)
+        } else {
+            None
+        }
+    }
+}
+
+/// Identifies a widget or a container of a [`Ui`](Ui).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WidgetId(usize);
+
+enum Content {
+    Widget(Box<dyn Widget>),
+    Container(Container, Vec<WidgetId>),
+}
+
+struct Node {
+    content: Content,
+    parent: Option<WidgetId>,
+    bounds: Rect,
+    // fixed size instead of the preferred size
+    size: Option<Size>,
+    // the share of the free space of the parent container
+    grow: u32,
+}
+
+/// A tree of widgets, which draws them and dispatches the touch events to them.
+///
+/// The widgets are only drawn again when they changed. This doesn't work with double buffered
+/// layers, because the back buffer doesn't contain the last frame. Call
+/// [`invalidate_all`](Ui::invalidate_all) after each swap for those.
+pub struct Ui {
+    nodes: Vec<Option<Node>>,
+    bounds: Rect,
+    theme: Theme,
+    needs_layout: bool,
+    dirty: Vec<Rect>,
+    // the widget that received the last press
+    captured: Option<WidgetId>,
+    // the id and the last position of the finger that pressed, which is followed until it is
+    // lifted
+    last_touch: Option<(u8, Point)>,
+}
+
+impl Ui {
+    /// Creates a user interface that fills `bounds` and whose root is the given container.
+    pub fn new(bounds: Rect, theme: Theme, root: Container) -> Ui {
+        let root = Node {
+            content: Content::Container(root, Vec::new()),
+            parent: None,
+            bounds,
+            size: None,
+            grow: 0,
+        };
+        Ui {
+            nodes: vec![Some(root)],
+            bounds,
+            theme,
+            needs_layout: true,
+            dirty: vec![bounds],
+            captured: None,
+            last_touch: None,
+        }
+    }
+
+    /// Returns the root container.
+    pub fn root(&self) -> WidgetId {
+        WidgetId(0)
+    }
+
+    /// Returns the theme.
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Changes the theme and draws everything again.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.needs_layout = true;
+        self.invalidate_all();
+    }
+
+    /// Adds a widget as last child of the `parent` container.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` is no container of this user interface.
+    pub fn add<W: Widget + 'static>(&mut self, parent: WidgetId, widget: W) -> WidgetId {
+        self.insert(parent, Content::Widget(Box::new(widget)))
+    }
+
+    /// Adds a container as last child of the `parent` container.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` is no container of this user interface.
+    pub fn add_container(&mut self, parent: WidgetId, container: Container) -> WidgetId {
+        self.insert(parent, Content::Container(container, Vec::new()))
+    }
+
+    fn insert(&mut self, parent: WidgetId, content: Content) -> WidgetId {
+        let id = WidgetId(self.nodes.len());
+        match self.node_mut(parent).map(|node| &mut node.content) {
+            Some(Content::Container(_, children)) => children.push(id),
+            _ => panic!("parent is no container"),
+        }
+        self.nodes.push(Some(Node {
+            content,
+            parent: Some(parent),
+            bounds: Rect::new(0, 0, 0, 0),
+            size: None,
+            grow: 0,
+        }));
+        self.needs_layout = true;
+        id
+    }
+
+    /// Removes a widget or a container with all its children.
+    ///
+    /// The root can't be removed. Removed ids are not reused.
+    pub fn remove(&mut self, id: WidgetId) {
+        if id == self.root() {
+            return;
+        }
+        let node = match self.nodes.get_mut(id.0).and_then(Option::take) {
+            Some(node) => node,
+            None => return,
+        };
+        if let Some(Content::Container(_, children)) =
+            node.parent.and_then(|parent| self.node_mut(parent)).map(|n| &mut n.content)
+        {
+            children.retain(|&child| child != id);
+        }
+        if let Content::Container(_, children) = node.content {
+            for child in children {
+                self.remove(child);
+            }
+        }
+        if self.captured == Some(id) {
+            self.captured = None;
+        }
+        self.needs_layout = true;
+    }
+
+    /// Returns the widget with the given id, if it has the type `W`.
+    pub fn get<W: Widget + 'static>(&self, id: WidgetId) -> Option<&W> {
+        match self.node(id).map(|node| &node.content) {
+            Some(Content::Widget(widget)) => (**widget).as_any().downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the widget with the given id, if it has the type `W`.
+    ///
+    /// The widget is drawn again, because it might be changed. If its preferred size changes,
+    /// call [`relayout`](Ui::relayout).
+    pub fn get_mut<W: Widget + 'static>(&mut self, id: WidgetId) -> Option<&mut W> {
+        let bounds = self.node(id)?.bounds;
+        self.invalidate(bounds);
+        match self.node_mut(id).map(|node| &mut node.content) {
+            Some(Content::Widget(widget)) => (**widget).as_any_mut().downcast_mut(),
+            _ => None,
+        }
+    }
+
+    /// Returns the container with the given id.
+    ///
+    /// The user interface is arranged and drawn again, because the container might be changed.
+    pub fn container_mut(&mut self, id: WidgetId) -> Option<&mut Container> {
+        self.node(id)?;
+        self.needs_layout = true;
+        match self.node_mut(id).map(|node| &mut node.content) {
+            Some(Content::Container(container, _)) => Some(container),
+            _ => None,
+        }
+    }
+
+    /// Sets a fixed size instead of the preferred size of the widget or container.
+    pub fn set_size(&mut self, id: WidgetId, size: Size) {
+        if let Some(node) = self.node_mut(id) {
+            node.size = Some(size);
+            self.needs_layout = true;
+        }
+    }
+
+    /// Sets the share of the free space of the parent container that the widget gets.
+    ///
+    /// The default is 0, so widgets have their preferred size. The free space is divided among
+    /// the children in proportion to their shares.
+    pub fn set_grow(&mut self, id: WidgetId, grow: u32) {
+        if let Some(node) = self.node_mut(id) {
+            node.grow = grow;
+            self.needs_layout = true;
+        }
+    }
+
+    /// Returns the area of the widget on the display.
+    pub fn bounds(&mut self, id: WidgetId) -> Option<Rect> {
+        self.update_layout();
+        self.node(id).map(|node| node.bounds)
+    }
+
+    /// Arranges the widgets again, e.g. because their preferred sizes changed.
+    pub fn relayout(&mut self) {
+        self.needs_layout = true;
+    }
+
+    /// Marks the whole user interface for drawing.
+    pub fn invalidate_all(&mut self) {
+        self.dirty.clear();
+        self.dirty.push(self.bounds);
+    }
+
+    /// Marks an area for drawing.
+    pub fn invalidate(&mut self, rect: Rect) {
+        let mut rect = rect.intersection(self.bounds);
+        if rect.is_empty() {
+            return;
+        }
+        // merge overlapping rectangles, so that no pixel is drawn twice
+        while let Some(i) = self.dirty.iter().position(|r| !r.intersection(rect).is_empty()) {
+            rect = rect.union(self.dirty.swap_remove(i));
+        }
+        if self.dirty.len() == MAX_DIRTY_RECTS {
+            rect = self.dirty.drain(..).fold(rect, Rect::union);
+        }
+        self.dirty.push(rect);
+    }
+
+    /// Returns whether parts of the user interface must be drawn.
+    pub fn needs_redraw(&self) -> bool {
+        self.needs_layout || !self.dirty.is_empty()
+    }
+
+    /// Draws the parts of the user interface that changed since the last call.
+    pub fn draw<F: Framebuffer>(&mut self, fb: &mut F) {
+        self.update_layout();
+        let theme = self.theme;
+        for rect in mem::replace(&mut self.dirty, Vec::new()) {
+            let mut canvas = Canvas::new(&mut *fb, rect);
+            fill(&mut canvas, rect, theme.background);
+            self.draw_node(self.root(), &mut canvas, &theme);
+        }
+    }
+
+    fn draw_node(&self, id: WidgetId, canvas: &mut Canvas, theme: &Theme) {
+        let node = match self.node(id) {
+            Some(node) => node,
+            None => return,
+        };
+        if node.bounds.intersection(canvas.clip()).is_empty() {
+            return;
+        }
+        match &node.content {
+            Content::Widget(widget) => {
+                widget.draw(&mut canvas.clipped(node.bounds), node.bounds, theme)
+            }
+            Content::Container(container, children) => {
+                if let Some(background) = container.background {
+                    fill(canvas, node.bounds, background);
+                }
+                for &child in children {
+                    self.draw_node(child, canvas, theme);
+                }
+            }
+        }
+    }
+
+    /// Passes the current touches to the widgets, see [`touch::touches`](crate::touch::touches).
+    ///
+    /// Only one finger is followed: the first touch that is not lifted presses, and the touches
+    /// with the same [`id`](crate::touch::Touch::id) move it until the finger is lifted or
+    /// missing. Other fingers are ignored meanwhile. The touches are compared to the touches of
+    /// the last call to find out whether the finger was pressed, moved or released, so this
+    /// function should be called regularly, also when there are no touches.
+    ///
+    /// Returns the action of the user and the widget it belongs to.
+    pub fn handle_touches(&mut self, touches: &[Touch]) -> Option<(WidgetId, Action)> {
+        let mut active = touches
+            .iter()
+            .filter(|touch| touch.flag != TouchFlag::LiftUp);
+        let point = |touch: &Touch| Point::new(i32::from(touch.x), i32::from(touch.y));
+        let (event, touch) = match self.last_touch {
+            None => match active.next() {
+                Some(touch) => (Event::Press(point(touch)), Some((touch.id, point(touch)))),
+                None => return None,
+            },
+            Some((id, last)) => match active.find(|touch| touch.id == id).map(point) {
+                Some(point) if point == last => return None,
+                Some(point) => (Event::Move(point), Some((id, point))),
+                None => (Event::Release(last), None),
+            },
+        };
+        self.last_touch = touch;
+        self.handle_event(event)
+    }
+
+    /// Passes a touch event to the widgets.
+    ///
+    /// Returns the action of the user and the widget it belongs to.
+    pub fn handle_event(&mut self, event: Event) -> Option<(WidgetId, Action)> {
+        self.update_layout();
+        let target = match event {
+            Event::Press(point) => {
+                self.captured = self.hit_test(self.root(), point);
+                self.captured
+            }
+            Event::Move(_) => self.captured,
+            Event::Release(_) => self.captured.take(),
+        }?;
+
+        let theme = self.theme;
+        let node = self.node_mut(target)?;
+        let bounds = node.bounds;
+        let response = match &mut node.content {
+            Content::Widget(widget) => widget.handle_event(event, bounds, &theme),
+            Content::Container(..) => return None,
+        };
+        if response.redraw {
+            self.invalidate(bounds);
+        }
+        response.action.map(|action| (target, action))
+    }
+
+    /// Returns the topmost interactive widget at the point.
+    pub fn widget_at(&mut self, point: Point) -> Option<WidgetId> {
+        self.update_layout();
+        self.hit_test(self.root(), point)
+    }
+
+    fn hit_test(&self, id: WidgetId, point: Point) -> Option<WidgetId> {
+        let node = self.node(id)?;
+        let inside = point.x >= 0
+            && point.y >= 0
+            && node.bounds.contains(point.x as usize, point.y as usize);
+        if !inside {
+            return None;
+        }
+        match &node.content {
+            Content::Widget(widget) if widget.is_interactive() => Some(id),
+            Content::Widget(_) => None,
+            // later children are drawn on top
+            Content::Container(_, children) => children
+                .iter()
+                .rev()
+                .filter_map(|&child| self.hit_test(child, point))
+                .next(),
+        }
+    }
+
+    fn node(&self, id: WidgetId) -> Option<&Node> {
+        self.nodes.get(id.0).and_then(Option::as_ref)
+    }
+
+    fn node_mut(&mut self, id: WidgetId) -> Option<&mut Node> {
+        self.nodes.get_mut(id.0).and_then(Option::as_mut)
+    }
+}
+
+// Fills the part of `rect` that is inside the clipping rectangle of the canvas.
+fn fill(canvas: &mut Canvas, rect: Rect, color: Color) {
+    let rect = rect.intersection(canvas.clip());
+    let top_left = Point::new(rect.x as i32, rect.y as i32);
+    graphics::fill_rect(canvas, top_left, rect.width as u32, rect.height as u32, color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lcd::{PixelFormat, HEIGHT, WIDTH};
+
+    const BACKGROUND: Color = Color::rgb(0, 0, 0);
+
+    // A framebuffer that records which pixels were set.
+    struct Pixels {
+        colors: Vec<Color>,
+        written: Vec<bool>,
+    }
+
+    impl Pixels {
+        fn new() -> Pixels {
+            Pixels {
+                colors: vec![Color::rgb(1, 2, 3); WIDTH * HEIGHT],
+                written: vec![false; WIDTH * HEIGHT],
+            }
+        }
+
+        fn color(&self, x: usize, y: usize) -> Color {
+            self.colors[y * WIDTH + x]
+        }
+
+        // Returns the bounding box of the pixels that were set since the last call.
+        fn take_written(&mut self) -> Rect {
+            let mut written = Rect::new(0, 0, 0, 0);
+            for (i, flag) in self.written.iter_mut().enumerate() {
+                if mem::replace(flag, false) {
+                    written = written.union(Rect::new(i % WIDTH, i / WIDTH, 1, 1));
+                }
+            }
+            written
+        }
+    }
+
+    impl Framebuffer for Pixels {
+        fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+            self.colors[y * WIDTH + x] = color;
+            self.written[y * WIDTH + x] = true;
+        }
+    }
+
+    // A widget with a fixed size and color that records its events.
+    struct Probe {
+        size: Size,
+        color: Color,
+        interactive: bool,
+        events: Vec<Event>,
+    }
+
+    impl Probe {
+        fn new(width: usize, height: usize, color: Color) -> Probe {
+            Probe {
+                size: Size::new(width, height),
+                color,
+                interactive: true,
+                events: Vec::new(),
+            }
+        }
+    }
+
+    impl Widget for Probe {
+        fn preferred_size(&self, _theme: &Theme) -> Size {
+            self.size
+        }
+
+        fn draw(&self, canvas: &mut Canvas, bounds: Rect, _theme: &Theme) {
+            fill(canvas, bounds, self.color);
+        }
+
+        fn is_interactive(&self) -> bool {
+            self.interactive
+        }
+
+        fn handle_event(&mut self, event: Event, _bounds: Rect, _theme: &Theme) -> Response {
+            self.events.push(event);
+            match event {
+                Event::Release(_) => Response::action(Action::Custom(self.events.len() as u32)),
+                _ => Response::none(),
+            }
+        }
+    }
+
+    fn screen() -> Rect {
+        Rect::new(0, 0, WIDTH, HEIGHT)
+    }
+
+    fn touch(x: u16, y: u16) -> Touch {
+        Touch {
+            x,
+            y,
+            id: 0,
+            flag: TouchFlag::Contact,
+        }
+    }
+
+    fn events(ui: &Ui, id: WidgetId) -> &[Event] {
+        &ui.get::<Probe>(id).unwrap().events
+    }
+
+    #[test]
+    fn layout() {
+        let mut ui = Ui::new(screen(), Theme::default(), Container::column());
+        let root = ui.root();
+        let top = ui.add(root, Probe::new(100, 20, BACKGROUND));
+        let row = ui.add_container(root, Container::row());
+        let left = ui.add(row, Probe::new(50, 30, BACKGROUND));
+        let right = ui.add(row, Probe::new(60, 10, BACKGROUND));
+        let bottom = ui.add(root, Probe::new(10, 10, BACKGROUND));
+
+        // preferred sizes along the direction, stretched across it
+        assert_eq!(ui.bounds(top), Some(Rect::new(4, 4, 472, 20)));
+        assert_eq!(ui.bounds(row), Some(Rect::new(4, 28, 472, 38)));
+        assert_eq!(ui.bounds(left), Some(Rect::new(8, 32, 50, 30)));
+        assert_eq!(ui.bounds(right), Some(Rect::new(62, 32, 60, 30)));
+        assert_eq!(ui.bounds(bottom), Some(Rect::new(4, 70, 472, 10)));
+
+        // the free space is divided according to the grow shares
+        ui.set_grow(left, 1);
+        ui.set_grow(right, 2);
+        let free = 472 - 8 - 50 - 4 - 60;
+        assert_eq!(ui.bounds(left), Some(Rect::new(8, 32, 50 + free / 3, 30)));
+        let right_bounds = ui.bounds(right).unwrap();
+        assert_eq!(right_bounds.x + right_bounds.width, 4 + 472 - 4);
+
+        // fixed sizes replace the preferred size
+        ui.set_size(row, Size::new(0, 50));
+        assert_eq!(ui.bounds(bottom), Some(Rect::new(4, 82, 472, 10)));
+
+        // children that don't fit are cut off
+        ui.set_size(top, Size::new(0, 1000));
+        assert_eq!(ui.bounds(top), Some(Rect::new(4, 4, 472, 264)));
+        assert_eq!(ui.bounds(bottom), Some(Rect::new(4, 268, 472, 0)));
+
+        ui.remove(row);
+        assert_eq!(ui.bounds(left), None);
+        ui.set_size(top, Size::new(0, 20));
+        assert_eq!(ui.bounds(bottom), Some(Rect::new(4, 28, 472, 10)));
+    }
+
+    #[test]
+    fn draw_changed_areas() {
+        let red = Color::rgb(255, 0, 0);
+        let background = Color::rgb(0, 0, 40);
+        let container = Container {
+            background: Some(background),
+            ..Container::column()
+        };
+        let mut ui = Ui::new(screen(), Theme::default(), container);
+        let root = ui.root();
+        let probe = ui.add(root, Probe::new(10, 20, red));
+        let mut pixels = Pixels::new();
+
+        ui.draw(&mut pixels);
+        assert_eq!(pixels.take_written(), screen());
+        assert_eq!(pixels.color(0, 0), background);
+        assert_eq!(pixels.color(4, 4), red);
+        assert_eq!(pixels.color(WIDTH - 5, 23), red);
+        assert_eq!(pixels.color(4, 24), background);
+        assert!(!ui.needs_redraw());
+
+        ui.draw(&mut pixels);
+        assert!(pixels.take_written().is_empty());
+
+        // only the widget is drawn again
+        ui.get_mut::<Probe>(probe).unwrap().color = BACKGROUND;
+        assert!(ui.needs_redraw());
+        ui.draw(&mut pixels);
+        assert_eq!(pixels.take_written(), Rect::new(4, 4, WIDTH - 8, 20));
+        assert_eq!(pixels.color(4, 4), BACKGROUND);
+
+        ui.invalidate(Rect::new(100, 100, 10, 10));
+        ui.invalidate(Rect::new(105, 105, 10, 10));
+        ui.draw(&mut pixels);
+        assert_eq!(pixels.take_written(), Rect::new(100, 100, 15, 15));
+    }
+
+    #[test]
+    fn canvas_pixel_buffer() {
+        let mut memory = vec![0u32; WIDTH * HEIGHT];
+        let addr = memory.as_mut_ptr() as usize;
+        let mut buffer =
+            unsafe { PixelBuffer::from_raw_parts(addr, WIDTH, HEIGHT, PixelFormat::Argb8888) };
+
+        let mut canvas = Canvas::new(&mut buffer, screen());
+        assert!(canvas.pixel_buffer().is_some());
+        assert!(canvas.clipped(Rect::new(0, 0, WIDTH, 100)).pixel_buffer().is_none());
+        let mut canvas = Canvas::new(&mut buffer, Rect::new(10, 10, 20, 20));
+        assert!(canvas.pixel_buffer().is_none());
+
+        // widgets on clipped canvases only draw inside of the clipping rectangle
+        let red = Color::rgb(255, 0, 0);
+        fill(&mut canvas, screen(), red);
+        assert_eq!(buffer.get_pixel(10, 10), red);
+        assert_eq!(buffer.get_pixel(29, 29), red);
+        assert_eq!(buffer.get_pixel(9, 10), Color::rgba(0, 0, 0, 0));
+        assert_eq!(buffer.get_pixel(30, 29), Color::rgba(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn dispatch_events() {
+        let mut ui = Ui::new(screen(), Theme::default(), Container::row());
+        let root = ui.root();
+        let left = ui.add(root, Probe::new(100, 100, BACKGROUND));
+        let right = ui.add(root, Probe::new(100, 100, BACKGROUND));
+        let mut passive = Probe::new(100, 100, BACKGROUND);
+        passive.interactive = false;
+        let passive = ui.add(root, passive);
+
+        assert_eq!(ui.widget_at(Point::new(10, 10)), Some(left));
+        assert_eq!(ui.widget_at(Point::new(110, 10)), Some(right));
+        assert_eq!(ui.widget_at(Point::new(220, 10)), None);
+        assert_eq!(ui.widget_at(Point::new(-1, 10)), None);
+
+        // the moves and the release go to the pressed widget, even outside of it
+        assert_eq!(ui.handle_touches(&[touch(10, 10)]), None);
+        assert_eq!(ui.handle_touches(&[touch(10, 10)]), None);
+        assert_eq!(ui.handle_touches(&[touch(150, 20)]), None);
+        assert_eq!(ui.handle_touches(&[]), Some((left, Action::Custom(3))));
+        let expected = [
+            Event::Press(Point::new(10, 10)),
+            Event::Move(Point::new(150, 20)),
+            Event::Release(Point::new(150, 20)),
+        ];
+        assert_eq!(events(&ui, left), expected);
+        assert!(events(&ui, right).is_empty());
+
+        // the first touch presses
+        ui.handle_touches(&[touch(150, 20), touch(10, 10)]);
+        assert_eq!(ui.handle_touches(&[]), Some((right, Action::Custom(2))));
+
+        // presses beside interactive widgets are ignored until the release
+        assert_eq!(ui.handle_touches(&[touch(250, 20)]), None);
+        assert_eq!(ui.handle_touches(&[touch(10, 20)]), None);
+        assert_eq!(ui.handle_touches(&[]), None);
+        assert!(events(&ui, passive).is_empty());
+        assert_eq!(events(&ui, left).len(), 3);
+
+        // removing the pressed widget cancels the press
+        ui.handle_event(Event::Press(Point::new(150, 20)));
+        ui.remove(right);
+        assert_eq!(ui.handle_event(Event::Release(Point::new(150, 20))), None);
+    }
+
+    #[test]
+    fn fingers_are_followed_by_id() {
+        let mut ui = Ui::new(screen(), Theme::default(), Container::row());
+        let root = ui.root();
+        let left = ui.add(root, Probe::new(100, 100, BACKGROUND));
+        let right = ui.add(root, Probe::new(100, 100, BACKGROUND));
+        let finger = |id, x, flag| Touch { x, y: 10, id, flag };
+        let (down, contact, up) = (TouchFlag::PressDown, TouchFlag::Contact, TouchFlag::LiftUp);
+
+        // lifted touches don't press
+        assert_eq!(ui.handle_touches(&[finger(1, 150, up)]), None);
+        let touches = [finger(1, 10, up), finger(2, 20, down)];
+        assert_eq!(ui.handle_touches(&touches), None);
+        // other fingers don't move the pressed one, even if they come first
+        let touches = [finger(3, 150, down), finger(2, 30, contact)];
+        assert_eq!(ui.handle_touches(&touches), None);
+        let touches = [finger(3, 160, contact)];
+        assert_eq!(ui.handle_touches(&touches), Some((left, Action::Custom(3))));
+        // another finger presses from the next call on
+        assert_eq!(ui.handle_touches(&touches), None);
+        let expected = [
+            Event::Press(Point::new(20, 10)),
+            Event::Move(Point::new(30, 10)),
+            Event::Release(Point::new(30, 10)),
+        ];
+        assert_eq!(events(&ui, left), expected);
+
+        // lifting the finger releases it
+        assert_eq!(ui.handle_touches(&[finger(3, 170, contact)]), None);
+        let touches = [finger(3, 170, up), finger(4, 10, down)];
+        let action = ui.handle_touches(&touches);
+        assert_eq!(action, Some((right, Action::Custom(3))));
+        let expected = [
+            Event::Press(Point::new(160, 10)),
+            Event::Move(Point::new(170, 10)),
+            Event::Release(Point::new(170, 10)),
+        ];
+        assert_eq!(events(&ui, right), expected);
+    }
+}
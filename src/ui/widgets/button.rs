@@ -0,0 +1,75 @@
+use super::{contains, draw_text_line, fill_rounded_rect, line_height, text_width};
+use crate::lcd::text::Alignment;
+use crate::lcd::Rect;
+use crate::ui::{Action, Canvas, Event, Response, Size, Theme, Widget};
+use alloc::string::String;
+
+/// A button with a text, which reports [`Action::Clicked`](Action::Clicked).
+///
+/// The button is clicked when the finger is lifted on it. Moving the finger away from the button
+/// cancels the click.
+#[derive(Debug, Clone)]
+pub struct Button {
+    text: String,
+    pressed: bool,
+}
+
+impl Button {
+    /// Creates a button with the given text.
+    pub fn new<S: Into<String>>(text: S) -> Button {
+        Button {
+            text: text.into(),
+            pressed: false,
+        }
+    }
+
+    /// Returns the text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Changes the text.
+    pub fn set_text<S: Into<String>>(&mut self, text: S) {
+        self.text = text.into();
+    }
+
+    /// Returns whether the button is currently held down.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
+
+impl Widget for Button {
+    fn preferred_size(&self, theme: &Theme) -> Size {
+        Size::new(
+            text_width(theme, &self.text) + 4 * theme.padding,
+            line_height(theme) + 2 * theme.padding,
+        )
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rect, theme: &Theme) {
+        let color = if self.pressed { theme.pressed } else { theme.surface };
+        fill_rounded_rect(canvas, bounds, theme.radius, color);
+        let foreground = theme.foreground;
+        draw_text_line(canvas, theme, &self.text, bounds, Alignment::Center, foreground);
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Event, bounds: Rect, _theme: &Theme) -> Response {
+        let inside = contains(bounds, event.position());
+        match event {
+            Event::Press(_) | Event::Move(_) if inside != self.pressed => {
+                self.pressed = inside;
+                Response::redraw()
+            }
+            Event::Release(_) if self.pressed => {
+                self.pressed = false;
+                Response::action(Action::Clicked)
+            }
+            _ => Response::none(),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use super::{contains, draw_text_line, fill_rounded_rect, line_height, text_width};
+use crate::lcd::graphics::Point;
+use crate::lcd::text::Alignment;
+use crate::lcd::Rect;
+use crate::ui::{Action, Canvas, Event, Response, Size, Theme, Widget};
+
+/// A key of a [`Keypad`](Keypad).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// A digit from 0 to 9.
+    Digit(u8),
+    /// Deletes the last digit.
+    Delete,
+    /// Confirms the input.
+    Enter,
+}
+
+impl Key {
+    /// Returns the text on the key.
+    pub fn label(self) -> &'static str {
+        const DIGITS: [&str; 10] = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+        match self {
+            Key::Digit(digit) => DIGITS[usize::from(digit % 10)],
+            Key::Delete => "DEL",
+            Key::Enter => "OK",
+        }
+    }
+}
+
+const COLUMNS: usize = 3;
+const ROWS: usize = 4;
+const KEYS: [[Key; COLUMNS]; ROWS] = [
+    [Key::Digit(1), Key::Digit(2), Key::Digit(3)],
+    [Key::Digit(4), Key::Digit(5), Key::Digit(6)],
+    [Key::Digit(7), Key::Digit(8), Key::Digit(9)],
+    [Key::Delete, Key::Digit(0), Key::Enter],
+];
+
+/// A numeric keypad in the layout of a phone, which reports [`Action::Key`](Action::Key).
+///
+/// A key is pressed when the finger is lifted on it. Moving the finger to another key cancels
+/// the key press.
+#[derive(Debug, Clone, Default)]
+pub struct Keypad {
+    // the row and column of the key under the finger
+    pressed: Option<(usize, usize)>,
+}
+
+impl Keypad {
+    /// Creates a keypad.
+    pub fn new() -> Keypad {
+        Keypad { pressed: None }
+    }
+
+    /// Returns the key that is currently held down.
+    pub fn pressed_key(&self) -> Option<Key> {
+        self.pressed.map(|(row, column)| KEYS[row][column])
+    }
+}
+
+// Returns the area of a key, with a gap of `spacing` between the keys.
+fn key_rect(bounds: Rect, row: usize, column: usize, spacing: usize) -> Rect {
+    let left = bounds.x + bounds.width * column / COLUMNS;
+    let right = bounds.x + bounds.width * (column + 1) / COLUMNS;
+    let top = bounds.y + bounds.height * row / ROWS;
+    let bottom = bounds.y + bounds.height * (row + 1) / ROWS;
+    let width = (right - left).saturating_sub(spacing);
+    let height = (bottom - top).saturating_sub(spacing);
+    Rect::new(left, top, width, height)
+}
+
+// Returns the row and the column of the key at the point.
+fn key_at(bounds: Rect, point: Point, spacing: usize) -> Option<(usize, usize)> {
+    if !contains(bounds, point) {
+        return None;
+    }
+    let column = (point.x as usize - bounds.x) * COLUMNS / bounds.width;
+    let row = (point.y as usize - bounds.y) * ROWS / bounds.height;
+    if contains(key_rect(bounds, row, column, spacing), point) {
+        Some((row, column))
+    } else {
+        None // in the gap between the keys
+    }
+}
+
+impl Widget for Keypad {
+    fn preferred_size(&self, theme: &Theme) -> Size {
+        let key_height = line_height(theme) + 2 * theme.padding;
+        let key_width = (text_width(theme, "DEL") + 2 * theme.padding).max(2 * key_height);
+        let spacing = theme.padding / 2;
+        Size::new(
+            COLUMNS * (key_width + spacing),
+            ROWS * (key_height + spacing),
+        )
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rect, theme: &Theme) {
+        let spacing = theme.padding / 2;
+        for (row, keys) in KEYS.iter().enumerate() {
+            for (column, key) in keys.iter().enumerate() {
+                let rect = key_rect(bounds, row, column, spacing);
+                let color = if self.pressed == Some((row, column)) {
+                    theme.pressed
+                } else {
+                    theme.surface
+                };
+                fill_rounded_rect(canvas, rect, theme.radius, color);
+                let foreground = theme.foreground;
+                draw_text_line(canvas, theme, key.label(), rect, Alignment::Center, foreground);
+            }
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Event, bounds: Rect, theme: &Theme) -> Response {
+        let spacing = theme.padding / 2;
+        match event {
+            Event::Press(point) => {
+                self.pressed = key_at(bounds, point, spacing);
+                Response::redraw()
+            }
+            Event::Move(point) => {
+                if self.pressed.is_some() && key_at(bounds, point, spacing) != self.pressed {
+                    self.pressed = None;
+                    Response::redraw()
+                } else {
+                    Response::none()
+                }
+            }
+            Event::Release(_) => match self.pressed.take() {
+                Some((row, column)) => Response::action(Action::Key(KEYS[row][column])),
+                None => Response::none(),
+            },
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use super::{line_height, text_width};
+use crate::lcd::text::{self, Alignment};
+use crate::lcd::{Color, Rect};
+use crate::ui::{Canvas, Size, Theme, Widget};
+use alloc::string::String;
+
+/// Text that is wrapped at the edges of the label.
+#[derive(Debug, Clone)]
+pub struct Label {
+    text: String,
+    alignment: Alignment,
+    color: Option<Color>,
+}
+
+impl Label {
+    /// Creates a left-aligned label in the foreground color of the theme.
+    pub fn new<S: Into<String>>(text: S) -> Label {
+        Label {
+            text: text.into(),
+            alignment: Alignment::Left,
+            color: None,
+        }
+    }
+
+    /// Returns the text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Changes the text.
+    pub fn set_text<S: Into<String>>(&mut self, text: S) {
+        self.text = text.into();
+    }
+
+    /// Sets the horizontal alignment of the lines.
+    pub fn set_alignment(&mut self, alignment: Alignment) {
+        self.alignment = alignment;
+    }
+
+    /// Sets the color of the text. `None` uses the foreground color of the theme.
+    pub fn set_color(&mut self, color: Option<Color>) {
+        self.color = color;
+    }
+}
+
+impl Widget for Label {
+    fn preferred_size(&self, theme: &Theme) -> Size {
+        let lines = self.text.lines().count().max(1);
+        let width = self.text.lines().map(|line| text_width(theme, line)).max();
+        Size::new(width.unwrap_or(0), lines * line_height(theme))
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rect, theme: &Theme) {
+        let color = self.color.unwrap_or(theme.foreground);
+        text::draw_text_box(canvas, &theme.font, &self.text, bounds, self.alignment, color);
+    }
+}
@@ -0,0 +1,167 @@
+use super::{contains, line_height, text_width};
+use crate::lcd::graphics::{self, Point};
+use crate::lcd::text;
+use crate::lcd::Rect;
+use crate::ui::{Action, Canvas, Event, Response, Size, Theme, Widget};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// The distance the finger must move before a touch scrolls instead of selecting.
+const DRAG_THRESHOLD: i32 = 8;
+// The number of rows of the preferred size.
+const VISIBLE_ROWS: usize = 4;
+
+/// A vertical list of text items, which reports [`Action::Selected`](Action::Selected).
+///
+/// The list is scrolled by dragging it. An item is selected when it is tapped without dragging.
+#[derive(Debug, Clone)]
+pub struct ListView {
+    items: Vec<String>,
+    selected: Option<usize>,
+    // the number of pixels that are scrolled out at the top
+    scroll: usize,
+    drag: Option<Drag>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Drag {
+    start_y: i32,
+    start_scroll: usize,
+    scrolling: bool,
+}
+
+impl ListView {
+    /// Creates a list with the given items and without selection.
+    pub fn new(items: Vec<String>) -> ListView {
+        ListView {
+            items,
+            selected: None,
+            scroll: 0,
+            drag: None,
+        }
+    }
+
+    /// Returns the items.
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    /// Replaces the items. The list is scrolled to the top and the selection is removed.
+    pub fn set_items(&mut self, items: Vec<String>) {
+        self.items = items;
+        self.selected = None;
+        self.scroll = 0;
+    }
+
+    /// Adds an item at the end of the list.
+    pub fn push<S: Into<String>>(&mut self, item: S) {
+        self.items.push(item.into());
+    }
+
+    /// Returns the index of the selected item.
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Selects an item without reporting an action. Invalid indices remove the selection.
+    pub fn set_selected(&mut self, index: Option<usize>) {
+        self.selected = index.filter(|&index| index < self.items.len());
+    }
+
+    fn max_scroll(&self, bounds: Rect, theme: &Theme) -> usize {
+        (self.items.len() * row_height(theme)).saturating_sub(bounds.height)
+    }
+}
+
+fn row_height(theme: &Theme) -> usize {
+    (line_height(theme) + 2 * theme.padding).max(1)
+}
+
+impl Widget for ListView {
+    fn preferred_size(&self, theme: &Theme) -> Size {
+        let width = self.items.iter().map(|item| text_width(theme, item)).max();
+        let rows = self.items.len().max(1).min(VISIBLE_ROWS);
+        Size::new(width.unwrap_or(0) + 2 * theme.padding, rows * row_height(theme))
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rect, theme: &Theme) {
+        // the canvas is clipped to the bounds, so rows can be partially scrolled out
+        let row_height = row_height(theme);
+        let left = bounds.x as i32;
+        let right = (bounds.x + bounds.width) as i32 - 1;
+        for (index, item) in self.items.iter().enumerate().skip(self.scroll / row_height) {
+            let top = (bounds.y + index * row_height) as i32 - self.scroll as i32;
+            if top >= (bounds.y + bounds.height) as i32 {
+                break;
+            }
+            if self.selected == Some(index) {
+                let top_left = Point::new(left, top);
+                let (width, height) = (bounds.width as u32, row_height as u32);
+                graphics::fill_rect(canvas, top_left, width, height, theme.accent);
+            }
+            let padding = theme.padding as i32;
+            let position = Point::new(left + padding, top + padding);
+            text::draw_text(canvas, &theme.font, item, position, theme.foreground);
+            let bottom = top + row_height as i32 - 1;
+            let (start, end) = (Point::new(left, bottom), Point::new(right, bottom));
+            graphics::line(canvas, start, end, theme.surface);
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Event, bounds: Rect, theme: &Theme) -> Response {
+        match event {
+            Event::Press(point) => {
+                self.drag = Some(Drag {
+                    start_y: point.y,
+                    start_scroll: self.scroll,
+                    scrolling: false,
+                });
+                Response::none()
+            }
+            Event::Move(point) => {
+                let drag = match &mut self.drag {
+                    Some(drag) => drag,
+                    None => return Response::none(),
+                };
+                let distance = drag.start_y - point.y;
+                if distance.abs() > DRAG_THRESHOLD {
+                    drag.scrolling = true;
+                }
+                if !drag.scrolling {
+                    return Response::none();
+                }
+                let scroll = if distance >= 0 {
+                    drag.start_scroll + distance as usize
+                } else {
+                    drag.start_scroll.saturating_sub(distance.abs() as usize)
+                };
+                let scroll = scroll.min(self.max_scroll(bounds, theme));
+                if scroll == self.scroll {
+                    return Response::none();
+                }
+                self.scroll = scroll;
+                Response::redraw()
+            }
+            Event::Release(point) => {
+                let tapped = match self.drag.take() {
+                    Some(drag) => !drag.scrolling && contains(bounds, point),
+                    None => false,
+                };
+                if !tapped {
+                    return Response::none();
+                }
+                let offset = point.y as usize - bounds.y + self.scroll;
+                let index = offset / row_height(theme);
+                if index >= self.items.len() {
+                    return Response::none();
+                }
+                self.selected = Some(index);
+                Response::action(Action::Selected(index))
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! The widgets of the toolkit.
+
+pub use self::button::Button;
+pub use self::keypad::{Key, Keypad};
+pub use self::label::Label;
+pub use self::list_view::ListView;
+pub use self::progress_bar::ProgressBar;
+pub use self::slider::Slider;
+pub use self::toggle::Toggle;
+
+use super::{Canvas, Theme};
+use crate::lcd::graphics::{self, Point};
+use crate::lcd::text::{self, Alignment};
+use crate::lcd::{Color, Rect};
+
+mod button;
+mod keypad;
+mod label;
+mod list_view;
+mod progress_bar;
+mod slider;
+mod toggle;
+
+// Returns whether the point lies in the rectangle.
+fn contains(rect: Rect, point: Point) -> bool {
+    point.x >= 0 && point.y >= 0 && rect.contains(point.x as usize, point.y as usize)
+}
+
+fn top_left(rect: Rect) -> Point {
+    Point::new(rect.x as i32, rect.y as i32)
+}
+
+fn fill_rounded_rect(canvas: &mut Canvas, rect: Rect, radius: u32, color: Color) {
+    let (width, height) = (rect.width as u32, rect.height as u32);
+    graphics::fill_rounded_rect(canvas, top_left(rect), width, height, radius, color);
+}
+
+// Draws a line of text that is centered vertically in `bounds`.
+fn draw_text_line(
+    canvas: &mut Canvas,
+    theme: &Theme,
+    text: &str,
+    bounds: Rect,
+    alignment: Alignment,
+    color: Color,
+) {
+    let line_height = theme.font.line_height().max(0) as usize;
+    let line = Rect::new(
+        bounds.x,
+        bounds.y + bounds.height.saturating_sub(line_height) / 2,
+        bounds.width,
+        line_height.min(bounds.height),
+    );
+    text::draw_text_box(canvas, &theme.font, text, line, alignment, color);
+}
+
+// Returns the width of a line of text.
+fn text_width(theme: &Theme, text: &str) -> usize {
+    text::text_width(&theme.font, text).max(0) as usize
+}
+
+// Returns the height of a line of text.
+fn line_height(theme: &Theme) -> usize {
+    theme.font.line_height().max(0) as usize
+}
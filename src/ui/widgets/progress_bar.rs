@@ -0,0 +1,49 @@
+use super::{fill_rounded_rect, line_height};
+use crate::lcd::Rect;
+use crate::ui::{Canvas, Size, Theme, Widget};
+
+/// A horizontal bar that shows the progress of an operation.
+#[derive(Debug, Clone)]
+pub struct ProgressBar {
+    value: u32,
+    max: u32,
+}
+
+impl ProgressBar {
+    /// Creates an empty progress bar that is full at `max`.
+    pub fn new(max: u32) -> ProgressBar {
+        ProgressBar { value: 0, max }
+    }
+
+    /// Returns the current progress.
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// Changes the progress. The value is limited to the maximum.
+    pub fn set_value(&mut self, value: u32) {
+        self.value = value.min(self.max);
+    }
+
+    /// Returns the value at which the bar is full.
+    pub fn max(&self) -> u32 {
+        self.max
+    }
+}
+
+impl Widget for ProgressBar {
+    fn preferred_size(&self, theme: &Theme) -> Size {
+        let height = line_height(theme) / 2 + theme.padding;
+        Size::new(20 * height, height)
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rect, theme: &Theme) {
+        fill_rounded_rect(canvas, bounds, theme.radius, theme.surface);
+        if self.max == 0 || self.value == 0 {
+            return;
+        }
+        let width = u64::from(self.value) * bounds.width as u64 / u64::from(self.max);
+        let filled = Rect::new(bounds.x, bounds.y, width as usize, bounds.height);
+        fill_rounded_rect(canvas, filled, theme.radius, theme.accent);
+    }
+}
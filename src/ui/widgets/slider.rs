@@ -0,0 +1,129 @@
+use super::{fill_rounded_rect, line_height};
+use crate::lcd::graphics::{self, Point};
+use crate::lcd::Rect;
+use crate::ui::{Action, Canvas, Event, Response, Size, Theme, Widget};
+
+const TRACK_HEIGHT: usize = 4;
+
+/// A horizontal slider for a value in a range, which reports
+/// [`Action::ValueChanged`](Action::ValueChanged).
+///
+/// The value follows the finger while it is pressed, also outside of the slider.
+#[derive(Debug, Clone)]
+pub struct Slider {
+    min: i32,
+    max: i32,
+    value: i32,
+}
+
+impl Slider {
+    /// Creates a slider for the values from `min` to `max` (inclusive).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is larger than `max`.
+    pub fn new(min: i32, max: i32, value: i32) -> Slider {
+        assert!(min <= max, "invalid slider range");
+        Slider {
+            min,
+            max,
+            value: value.max(min).min(max),
+        }
+    }
+
+    /// Returns the current value.
+    pub fn value(&self) -> i32 {
+        self.value
+    }
+
+    /// Changes the value without reporting an action. The value is limited to the range.
+    pub fn set_value(&mut self, value: i32) {
+        self.value = value.max(self.min).min(self.max);
+    }
+
+    /// Returns the smallest and the largest value.
+    pub fn range(&self) -> (i32, i32) {
+        (self.min, self.max)
+    }
+
+    fn knob_position(&self, bounds: Rect, theme: &Theme) -> i32 {
+        let (start, end) = travel(bounds, theme);
+        let range = i64::from(self.max) - i64::from(self.min);
+        if range == 0 {
+            return start;
+        }
+        let offset = i64::from(self.value) - i64::from(self.min);
+        start + (offset * i64::from(end - start) / range) as i32
+    }
+
+    fn value_at(&self, x: i32, bounds: Rect, theme: &Theme) -> i32 {
+        let (start, end) = travel(bounds, theme);
+        if end == start {
+            return self.min;
+        }
+        let x = x.max(start).min(end);
+        let range = i64::from(self.max) - i64::from(self.min);
+        let span = i64::from(end - start);
+        // round to the nearest value
+        let offset = (i64::from(x - start) * range * 2 + span) / span / 2;
+        (i64::from(self.min) + offset) as i32
+    }
+}
+
+// Returns the radius of the knob.
+fn knob_radius(bounds: Rect, theme: &Theme) -> i32 {
+    let radius = (line_height(theme) + theme.padding) / 2;
+    radius.min(bounds.height / 2).min(bounds.width / 2) as i32
+}
+
+// Returns the first and the last horizontal position of the knob center.
+fn travel(bounds: Rect, theme: &Theme) -> (i32, i32) {
+    let radius = knob_radius(bounds, theme);
+    let start = bounds.x as i32 + radius;
+    let end = (bounds.x + bounds.width) as i32 - 1 - radius;
+    (start, end.max(start))
+}
+
+impl Widget for Slider {
+    fn preferred_size(&self, theme: &Theme) -> Size {
+        let height = line_height(theme) + theme.padding;
+        Size::new(10 * height, height)
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rect, theme: &Theme) {
+        let radius = knob_radius(bounds, theme);
+        if radius == 0 {
+            return;
+        }
+        let (start, end) = travel(bounds, theme);
+        let knob = self.knob_position(bounds, theme);
+        let center_y = bounds.y as i32 + bounds.height as i32 / 2;
+        let track_y = (center_y - TRACK_HEIGHT as i32 / 2).max(0) as usize;
+
+        let track = Rect::new(start as usize, track_y, (end - start) as usize + 1, TRACK_HEIGHT);
+        fill_rounded_rect(canvas, track, TRACK_HEIGHT as u32 / 2, theme.surface);
+        let filled = Rect::new(start as usize, track_y, (knob - start) as usize + 1, TRACK_HEIGHT);
+        fill_rounded_rect(canvas, filled, TRACK_HEIGHT as u32 / 2, theme.accent);
+
+        let center = Point::new(knob, center_y);
+        graphics::fill_circle(canvas, center, radius as u32, theme.foreground);
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Event, bounds: Rect, theme: &Theme) -> Response {
+        match event {
+            Event::Press(point) | Event::Move(point) => {
+                let value = self.value_at(point.x, bounds, theme);
+                if value == self.value {
+                    return Response::none();
+                }
+                self.value = value;
+                Response::action(Action::ValueChanged(value))
+            }
+            Event::Release(_) => Response::none(),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use super::{contains, fill_rounded_rect, line_height};
+use crate::lcd::graphics::{self, Point};
+use crate::lcd::Rect;
+use crate::ui::{Action, Canvas, Event, Response, Size, Theme, Widget};
+
+/// A switch that is either on or off, which reports [`Action::Toggled`](Action::Toggled).
+///
+/// The switch is drawn at the left of its bounds and is toggled when the finger is lifted on it.
+#[derive(Debug, Clone)]
+pub struct Toggle {
+    on: bool,
+}
+
+impl Toggle {
+    /// Creates a switch in the given state.
+    pub fn new(on: bool) -> Toggle {
+        Toggle { on }
+    }
+
+    /// Returns whether the switch is on.
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// Changes the state without reporting an action.
+    pub fn set_on(&mut self, on: bool) {
+        self.on = on;
+    }
+}
+
+// Returns the area of the switch, which is twice as wide as high.
+fn switch_rect(bounds: Rect, theme: &Theme) -> Rect {
+    let height = (line_height(theme) + theme.padding).min(bounds.height);
+    let width = (2 * height).min(bounds.width);
+    Rect::new(bounds.x, bounds.y + (bounds.height - height) / 2, width, height)
+}
+
+impl Widget for Toggle {
+    fn preferred_size(&self, theme: &Theme) -> Size {
+        let height = line_height(theme) + theme.padding;
+        Size::new(2 * height, height)
+    }
+
+    fn draw(&self, canvas: &mut Canvas, bounds: Rect, theme: &Theme) {
+        let switch = switch_rect(bounds, theme);
+        if switch.is_empty() {
+            return;
+        }
+        let track = if self.on { theme.accent } else { theme.surface };
+        fill_rounded_rect(canvas, switch, switch.height as u32 / 2, track);
+
+        let radius = (switch.height as i32 / 2 - 2).max(1);
+        let center_y = switch.y as i32 + switch.height as i32 / 2;
+        let center_x = if self.on {
+            (switch.x + switch.width) as i32 - switch.height as i32 / 2
+        } else {
+            switch.x as i32 + switch.height as i32 / 2
+        };
+        let knob = Point::new(center_x, center_y);
+        graphics::fill_circle(canvas, knob, radius as u32, theme.foreground);
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Event, bounds: Rect, _theme: &Theme) -> Response {
+        match event {
+            Event::Release(point) if contains(bounds, point) => {
+                self.on = !self.on;
+                Response::action(Action::Toggled(self.on))
+            }
+            _ => Response::none(),
+        }
+    }
+}
@@ -0,0 +1,771 @@
+//! Driver for the WM8994 audio codec.
+//!
+//! The codec is configured through I2C3 and exchanges the samples with SAI2. Its AIF1 interface
+//! has two timeslots: the DACs play timeslot 0, the line input is recorded into timeslot 0 and
+//! the digital microphones into timeslot 1.
+//!
+//! All functions access the registers through the [`RegisterAccess`](RegisterAccess) trait,
+//! which is implemented for I2C connections with 16 bit registers. The driver doesn't depend on
+//! the hardware otherwise, so it can be tested on the host with a mock bus that records the
+//! register accesses.
+//!
+//! # Examples
+//! ```rust
+//! let config = wm8994::Config {
+//!     output: Some(wm8994::OutputDevice::Headphone),
+//!     sample_rate: wm8994::SampleRate::Hz48000,
+//!     ..wm8994::Config::default()
+//! };
+//! i2c_3.connect::<u16, _>(wm8994::ADDRESS, |mut conn| {
+//!     match wm8994::init(&mut conn, &config, system_clock::wait_ms) {
+//!         Ok(()) => {}
+//!         Err(wm8994::Error::Access(err)) => return Err(err),
+//!         Err(wm8994::Error::UnknownDevice(id)) => panic!("unknown audio codec {:#x}", id),
+//!     }
+//!     wm8994::set_output_volume(&mut conn, -20)
+//! })?;
+//! ```
+//!
+//! Testing against a mock bus:
+//!
+//! ```rust
+//! use stm32f7_discovery::wm8994::{self, registers::SOFTWARE_RESET};
+//!
+//! struct Recorder(Vec<(u16, u16)>);
+//!
+//! impl wm8994::RegisterAccess for Recorder {
+//!     type Error = ();
+//!
+//!     fn read(&mut self, register: u16) -> Result<u16, ()> {
+//!         Ok(if register == SOFTWARE_RESET { wm8994::DEVICE_ID } else { 0 })
+//!     }
+//!
+//!     fn write(&mut self, register: u16, value: u16) -> Result<(), ()> {
+//!         self.0.push((register, value));
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut bus = Recorder(Vec::new());
+//! wm8994::set_output_volume(&mut bus, 0).unwrap();
+//! assert_eq!(bus.0, [(0x1C, 0x79), (0x1D, 0x179), (0x26, 0x79), (0x27, 0x179)]);
+//! ```
+
+use self::registers::*;
+use crate::i2c::{self, I2cConnection, I2cTrait};
+
+pub mod registers;
+
+/// The I2C address of the codec.
+pub const ADDRESS: i2c::Address = i2c::Address::bits_7(0b001_1010);
+
+/// The value of the `SOFTWARE_RESET` register.
+pub const DEVICE_ID: u16 = 0x8994;
+
+/// The lowest output volume in dB.
+pub const MIN_OUTPUT_VOLUME: i8 = -57;
+/// The highest output volume in dB.
+pub const MAX_OUTPUT_VOLUME: i8 = 6;
+
+/// The lowest input gain in dB.
+pub const MIN_INPUT_GAIN: f32 = -71.625;
+/// The highest input gain in dB.
+pub const MAX_INPUT_GAIN: f32 = 17.625;
+// The resolution of the input gain in dB.
+const INPUT_GAIN_STEP: f32 = 0.375;
+
+// The volume update bit, which applies the left and the right volume at the same time.
+const VOLUME_UPDATE: u16 = 0x100;
+// The bit that unmutes an output.
+const OUTPUT_UNMUTE: u16 = 0x040;
+// The soft mute bit of the AIF1 DAC filters.
+const DAC_MUTE: u16 = 0x200;
+// The bit in the AIF1 ADC mixer routing registers that connects the ADC or DMIC.
+const ADC_TO_AIF1: u16 = 0x002;
+
+/// Errors that can occur during the initialization of the codec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    /// Accessing a register failed.
+    Access(E),
+    /// The device ID is not the one of a WM8994. Contains the read ID.
+    UnknownDevice(u16),
+}
+
+/// Read and write access to the 16 bit registers of the codec.
+///
+/// Implemented for [`I2cConnection`](crate::i2c::I2cConnection)s with 16 bit registers.
+pub trait RegisterAccess {
+    /// The error type of a failed access.
+    type Error;
+
+    /// Reads the value of a register.
+    fn read(&mut self, register: u16) -> Result<u16, Self::Error>;
+
+    /// Writes the value of a register.
+    fn write(&mut self, register: u16, value: u16) -> Result<(), Self::Error>;
+}
+
+impl<'a, I: I2cTrait> RegisterAccess for I2cConnection<'a, I, u16> {
+    type Error = i2c::Error;
+
+    fn read(&mut self, register: u16) -> Result<u16, i2c::Error> {
+        I2cConnection::read(self, register)
+    }
+
+    fn write(&mut self, register: u16, value: u16) -> Result<(), i2c::Error> {
+        I2cConnection::write(self, register, value)
+    }
+}
+
+/// The inputs of the codec that can be recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputSource {
+    /// The digital microphones of the board, recorded into AIF1 timeslot 1.
+    DigitalMicrophone2,
+    /// The line input IN1, recorded into AIF1 timeslot 0.
+    LineIn1,
+}
+
+/// The outputs of the codec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputDevice {
+    /// The headphone jack, driven by DAC1.
+    Headphone,
+    /// The speaker outputs, driven by DAC2.
+    Speaker,
+    /// The headphone jack and the speaker outputs.
+    Both,
+}
+
+impl OutputDevice {
+    fn headphone(self) -> bool {
+        self != OutputDevice::Speaker
+    }
+
+    fn speaker(self) -> bool {
+        self != OutputDevice::Headphone
+    }
+}
+
+/// The sample rates of the AIF1 interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleRate {
+    /// 8 kHz
+    Hz8000,
+    /// 11.025 kHz
+    Hz11025,
+    /// 12 kHz
+    Hz12000,
+    /// 16 kHz
+    Hz16000,
+    /// 22.05 kHz
+    Hz22050,
+    /// 24 kHz
+    Hz24000,
+    /// 32 kHz
+    Hz32000,
+    /// 44.1 kHz
+    Hz44100,
+    /// 48 kHz
+    Hz48000,
+    /// 88.2 kHz
+    Hz88200,
+    /// 96 kHz
+    Hz96000,
+}
+
+/// The number of bits per sample of the AIF1 interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordLength {
+    /// 16 bits
+    Bits16,
+    /// 20 bits
+    Bits20,
+    /// 24 bits
+    Bits24,
+    /// 32 bits
+    Bits32,
+}
+
+/// The configuration that is applied by [`init`](init).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    /// The recorded input, if any.
+    pub input: Option<InputSource>,
+    /// The enabled outputs, if any.
+    pub output: Option<OutputDevice>,
+    /// The sample rate of the AIF1 interface, with a master clock of 256 times the sample rate.
+    pub sample_rate: SampleRate,
+    /// The word length of the AIF1 interface, which uses the I2S format.
+    pub word_length: WordLength,
+    /// The digital gain of the input in dB, see [`set_input_gain`](set_input_gain).
+    pub input_gain: f32,
+    /// The volume of the outputs in dB, see [`set_output_volume`](set_output_volume).
+    pub output_volume: i8,
+}
+
+impl Default for Config {
+    /// Records the digital microphones with 16 kHz, 16 bit and a gain of 17.625 dB.
+    fn default() -> Config {
+        Config {
+            input: Some(InputSource::DigitalMicrophone2),
+            output: None,
+            sample_rate: SampleRate::Hz16000,
+            word_length: WordLength::Bits16,
+            input_gain: MAX_INPUT_GAIN,
+            output_volume: -10,
+        }
+    }
+}
+
+/// Reads the device ID, which is [`DEVICE_ID`](DEVICE_ID) for a WM8994.
+pub fn device_id<R: RegisterAccess>(regs: &mut R) -> Result<u16, R::Error> {
+    regs.read(SOFTWARE_RESET)
+}
+
+/// Resets the codec and configures the inputs, the outputs and the AIF1 interface.
+///
+/// The codec must be clocked by its master clock. `wait_ms` is called with the number of
+/// milliseconds the codec needs to settle, which adds up to 622 ms if an output is enabled.
+///
+/// # Errors
+///
+/// Returns `Error::UnknownDevice` without writing any register if the device ID is not the one
+/// of a WM8994.
+pub fn init<R, D>(regs: &mut R, config: &Config, wait_ms: D) -> Result<(), Error<R::Error>>
+where
+    R: RegisterAccess,
+    D: FnMut(usize),
+{
+    let id = device_id(regs).map_err(Error::Access)?;
+    if id != DEVICE_ID {
+        return Err(Error::UnknownDevice(id));
+    }
+    configure(regs, config, wait_ms).map_err(Error::Access)
+}
+
+// Resets the codec and applies the configuration.
+fn configure<R, D>(regs: &mut R, config: &Config, mut wait_ms: D) -> Result<(), R::Error>
+where
+    R: RegisterAccess,
+    D: FnMut(usize),
+{
+    regs.write(SOFTWARE_RESET, 0)?;
+
+    // wm8994 Errata Work-Arounds
+    regs.write(ERRATA_UNLOCK, 0x0003)?;
+    regs.write(ERRATA, 0x0000)?;
+    regs.write(ERRATA_UNLOCK, 0x0000)?;
+
+    // Enable VMID soft start (fast), Start-up Bias Current Enabled
+    regs.write(ANTIPOP_2, 0x006C)?;
+
+    // Enable bias generator, Enable VMID
+    let mut power_1 = 0x0003;
+    regs.write(POWER_MANAGEMENT_1, power_1)?;
+
+    wait_ms(50);
+
+    if let Some(output) = config.output {
+        configure_output_paths(regs, output)?;
+    }
+    if let Some(input) = config.input {
+        configure_input_paths(regs, input)?;
+    }
+
+    // Clock Configurations
+    set_sample_rate(regs, config.sample_rate)?;
+    set_word_length(regs, config.word_length)?;
+    // slave mode
+    regs.write(AIF1_MASTER_SLAVE, 0x0000)?;
+    // Enable the DSP processing clock for AIF1, Enable the core clock
+    regs.write(CLOCKING_1, 0x000A)?;
+    // Enable AIF1 Clock, AIF1 Clock Source = MCLK1 pin
+    regs.write(AIF1_CLOCKING_1, 0x0001)?;
+
+    if let Some(output) = config.output {
+        power_1 = start_outputs(regs, output, power_1, &mut wait_ms)?;
+        set_output_volume(regs, config.output_volume)?;
+    }
+
+    if let Some(input) = config.input {
+        if input == InputSource::DigitalMicrophone2 {
+            // Enable Microphone bias 1 generator
+            power_1 |= 0x0010;
+            regs.write(POWER_MANAGEMENT_1, power_1)?;
+        }
+
+        // ADC oversample enable
+        regs.write(OVERSAMPLING, 0x0002)?;
+
+        match input {
+            // AIF ADC2 HPF enable, HPF cut = voice mode 1 fc=127Hz at fs=8kHz
+            InputSource::DigitalMicrophone2 => regs.write(AIF1_ADC2_FILTERS, 0x3800)?,
+            // AIF ADC1 HPF enable, HPF cut = hifi mode fc=4Hz at fs=48kHz
+            InputSource::LineIn1 => regs.write(AIF1_ADC1_FILTERS, 0x1800)?,
+        }
+
+        set_input_gain(regs, input, config.input_gain)?;
+    }
+
+    Ok(())
+}
+
+// Powers the DACs of the outputs and routes the AIF1 timeslot 0 to them.
+fn configure_output_paths<R: RegisterAccess>(
+    regs: &mut R,
+    output: OutputDevice,
+) -> Result<(), R::Error> {
+    let headphone = output.headphone();
+    let speaker = output.speaker();
+
+    // Enable AIF1DAC1 (Left), Enable AIF1DAC1 (Right)
+    let mut power_5 = 0x0300;
+    if headphone {
+        // Enable DAC1 (Left), Enable DAC1 (Right)
+        power_5 |= 0x0003;
+    }
+    if speaker {
+        // Enable DAC2 (Left), Enable DAC2 (Right)
+        power_5 |= 0x000C;
+    }
+    regs.write(POWER_MANAGEMENT_5, power_5)?;
+
+    // Enable or disable the AIF1 Timeslot 0 to DAC1 (Left/Right) mixer path
+    regs.write(DAC1_LEFT_MIXER_ROUTING, u16::from(headphone))?;
+    regs.write(DAC1_RIGHT_MIXER_ROUTING, u16::from(headphone))?;
+
+    // Enable or disable the AIF1 Timeslot 0 to DAC2 (Left/Right) mixer path
+    regs.write(DAC2_LEFT_MIXER_ROUTING, u16::from(speaker))?;
+    regs.write(DAC2_RIGHT_MIXER_ROUTING, u16::from(speaker))
+}
+
+// Powers the ADCs or the digital microphones and routes them to their AIF1 timeslot.
+fn configure_input_paths<R: RegisterAccess>(
+    regs: &mut R,
+    input: InputSource,
+) -> Result<(), R::Error> {
+    match input {
+        InputSource::DigitalMicrophone2 => {
+            // Enable AIF1ADC2 (Left), Enable AIF1ADC2 (Right)
+            // Enable DMICDAT2 (Left), Enable DMICDAT2 (Right)
+            // Enable Left ADC, Enable Right ADC
+            regs.write(POWER_MANAGEMENT_4, 0x0C30)?;
+
+            // Enable AIF1 DRC2 Signal Detect & DRC in AIF1ADC2 Left/Right Timeslot 1
+            regs.write(AIF1_DRC2_1, 0x00DB)?;
+
+            // Disable IN1L, IN1R, IN2L, IN2R, Enable Thermal sensor & shutdown
+            regs.write(POWER_MANAGEMENT_2, 0x6000)?;
+
+            // GPIO1 pin configuration GP1_DIR = output, GP1_FN = AIF1 DRC2 signal detect
+            regs.write(GPIO_1, 0x000E)?;
+        }
+        InputSource::LineIn1 => {
+            // IN1LN_TO_IN1L, IN1LP_TO_VMID, IN1RN_TO_IN1R, IN1RP_TO_VMID
+            regs.write(INPUT_MIXER_2, 0x0011)?;
+
+            // Disable mute on IN1L_TO_MIXINL and +30dB on IN1L PGA output
+            regs.write(INPUT_MIXER_3, 0x0035)?;
+
+            // Disable mute on IN1R_TO_MIXINR, Gain = +30dB
+            regs.write(INPUT_MIXER_4, 0x0035)?;
+
+            // Enable AIF1ADC1 (Left), Enable AIF1ADC1 (Right)
+            // Enable Left ADC, Enable Right ADC
+            regs.write(POWER_MANAGEMENT_4, 0x0303)?;
+
+            // Enable AIF1 DRC1 Signal Detect & DRC in AIF1ADC1 Left/Right Timeslot 0
+            regs.write(AIF1_DRC1_1, 0x00DB)?;
+
+            // Enable IN1L and IN1R, Disable IN2L and IN2R, Enable Thermal sensor & shutdown
+            regs.write(POWER_MANAGEMENT_2, 0x6350)?;
+
+            // GPIO1 pin configuration GP1_DIR = output, GP1_FN = AIF1 DRC1 signal detect
+            regs.write(GPIO_1, 0x000D)?;
+        }
+    }
+
+    // Enable the ADC or DMIC (Left/Right) to AIF1 (Left/Right) mixer path
+    set_input_mute(regs, input, false)
+}
+
+// Runs the start-up sequences of the outputs and unmutes the DACs. Returns the new value of
+// the POWER_MANAGEMENT_1 register.
+fn start_outputs<R, D>(
+    regs: &mut R,
+    output: OutputDevice,
+    power_1: u16,
+    wait_ms: &mut D,
+) -> Result<u16, R::Error>
+where
+    R: RegisterAccess,
+    D: FnMut(usize),
+{
+    let mut power_1 = power_1;
+
+    if output.headphone() {
+        // Select DAC1 (Left) to Left Headphone Output PGA (HPOUT1LVOL) path
+        regs.write(OUTPUT_MIXER_1, 0x0100)?;
+        // Select DAC1 (Right) to Right Headphone Output PGA (HPOUT1RVOL) path
+        regs.write(OUTPUT_MIXER_2, 0x0100)?;
+
+        // Start the headphone cold start-up sequence
+        regs.write(WRITE_SEQUENCER_CTRL_1, 0x8100)?;
+        wait_ms(300);
+
+        // Soft un-Mute the AIF1 Timeslot 0 DAC1 path L&R
+        regs.write(AIF1_DAC1_FILTERS_1, 0x0000)?;
+    }
+
+    // Enable SPKRVOL PGA, Enable SPKMIXR, Enable SPKLVOL PGA, Enable SPKMIXL
+    regs.write(POWER_MANAGEMENT_3, 0x0300)?;
+    // Left Speaker Mixer Volume = 0dB
+    regs.write(SPKMIXL_ATTENUATION, 0x0000)?;
+    // Speaker output mode = Class D, Right Speaker Mixer Volume = 0dB
+    regs.write(SPKMIXR_ATTENUATION, 0x0000)?;
+    // Unmute DAC2 (Left) to Left Speaker Mixer (SPKMIXL) path,
+    // Unmute DAC2 (Right) to Right Speaker Mixer (SPKMIXR) path
+    regs.write(SPEAKER_MIXER, 0x0300)?;
+
+    if output.headphone() {
+        // Enable HPOUT1L, Enable HPOUT1R
+        power_1 |= 0x0300;
+    }
+    if output.speaker() {
+        // Enable SPKOUTL, Enable SPKOUTR
+        power_1 |= 0x3000;
+    }
+    regs.write(POWER_MANAGEMENT_1, power_1)?;
+
+    // Enable Class W, Class W Envelope Tracking = AIF1 Timeslot 0
+    regs.write(CLASS_W_1, 0x0005)?;
+
+    // Enable HPOUT1 (Left) and HPOUT1 (Right) intermediate stages
+    regs.write(ANALOGUE_HP_1, 0x0022)?;
+    // Enable Charge Pump
+    regs.write(CHARGE_PUMP_1, 0x9F25)?;
+    wait_ms(15);
+
+    // Select DAC1 (Left) to Left Headphone Output PGA (HPOUT1LVOL) path
+    regs.write(OUTPUT_MIXER_1, 0x0001)?;
+    // Select DAC1 (Right) to Right Headphone Output PGA (HPOUT1RVOL) path
+    regs.write(OUTPUT_MIXER_2, 0x0001)?;
+
+    // Enable Left Output Mixer (MIXOUTL), Enable Right Output Mixer (MIXOUTR)
+    // Enable SPKRVOL PGA, Enable SPKLVOL PGA
+    regs.write(POWER_MANAGEMENT_3, 0x0330)?;
+
+    // Enable DC Servo and trigger start-up mode on left and right channels
+    regs.write(DC_SERVO_1, 0x0033)?;
+    wait_ms(257);
+
+    // Enable HPOUT1 (Left) and HPOUT1 (Right) intermediate and output stages.
+    // Remove clamps
+    regs.write(ANALOGUE_HP_1, 0x00EE)?;
+
+    // Unmute DAC 1 (Left), Unmute DAC 1 (Right)
+    regs.write(DAC1_LEFT_VOLUME, 0x00C0)?;
+    regs.write(DAC1_RIGHT_VOLUME, 0x00C0)?;
+    // Unmute DAC 2 (Left), Unmute DAC 2 (Right)
+    regs.write(DAC2_LEFT_VOLUME, 0x00C0)?;
+    regs.write(DAC2_RIGHT_VOLUME, 0x00C0)?;
+
+    set_output_mute(regs, false)?;
+
+    Ok(power_1)
+}
+
+/// Sets the sample rate of the AIF1 interface, with a master clock of 256 times the sample
+/// rate.
+pub fn set_sample_rate<R: RegisterAccess>(
+    regs: &mut R,
+    sample_rate: SampleRate,
+) -> Result<(), R::Error> {
+    let rate = match sample_rate {
+        SampleRate::Hz8000 => 0x0,
+        SampleRate::Hz11025 => 0x1,
+        SampleRate::Hz12000 => 0x2,
+        SampleRate::Hz16000 => 0x3,
+        SampleRate::Hz22050 => 0x4,
+        SampleRate::Hz24000 => 0x5,
+        SampleRate::Hz32000 => 0x6,
+        SampleRate::Hz44100 => 0x7,
+        SampleRate::Hz48000 => 0x8,
+        SampleRate::Hz88200 => 0x9,
+        SampleRate::Hz96000 => 0xA,
+    };
+    // the lower bits select the clock ratio of 256
+    regs.write(AIF1_RATE, rate << 4 | 0x3)
+}
+
+// Sets the word length of the AIF1 interface and selects the I2S format.
+fn set_word_length<R: RegisterAccess>(
+    regs: &mut R,
+    word_length: WordLength,
+) -> Result<(), R::Error> {
+    let bits = match word_length {
+        WordLength::Bits16 => 0b00,
+        WordLength::Bits20 => 0b01,
+        WordLength::Bits24 => 0b10,
+        WordLength::Bits32 => 0b11,
+    };
+    // the right ADC is sent in the right channel, I2S format
+    regs.write(AIF1_CONTROL_1, 0x4000 | bits << 5 | 0b10 << 3)
+}
+
+/// Sets the volume of the headphone and the speaker outputs in dB.
+///
+/// The volume is clamped to [`MIN_OUTPUT_VOLUME`](MIN_OUTPUT_VOLUME)..=
+/// [`MAX_OUTPUT_VOLUME`](MAX_OUTPUT_VOLUME) and has a resolution of 1 dB.
+pub fn set_output_volume<R: RegisterAccess>(regs: &mut R, volume: i8) -> Result<(), R::Error> {
+    let volume = volume.max(MIN_OUTPUT_VOLUME).min(MAX_OUTPUT_VOLUME);
+    // 0 is -57 dB and 0x39 is 0 dB
+    let value = (volume - MIN_OUTPUT_VOLUME) as u16 | OUTPUT_UNMUTE;
+    regs.write(LEFT_OUTPUT_VOLUME, value)?;
+    regs.write(RIGHT_OUTPUT_VOLUME, value | VOLUME_UPDATE)?;
+    regs.write(SPEAKER_VOLUME_LEFT, value)?;
+    regs.write(SPEAKER_VOLUME_RIGHT, value | VOLUME_UPDATE)
+}
+
+/// Mutes or unmutes the outputs.
+///
+/// The codec ramps the volume down or up, so muting doesn't cause clicks.
+pub fn set_output_mute<R: RegisterAccess>(regs: &mut R, mute: bool) -> Result<(), R::Error> {
+    let value = if mute { DAC_MUTE } else { 0 };
+    regs.write(AIF1_DAC1_FILTERS_1, value)?;
+    regs.write(AIF1_DAC2_FILTERS_1, value)
+}
+
+/// Sets the digital gain of the input in dB.
+///
+/// The gain is clamped to [`MIN_INPUT_GAIN`](MIN_INPUT_GAIN)..=[`MAX_INPUT_GAIN`](MAX_INPUT_GAIN)
+/// and rounded to a multiple of 0.375 dB.
+pub fn set_input_gain<R: RegisterAccess>(
+    regs: &mut R,
+    input: InputSource,
+    gain: f32,
+) -> Result<(), R::Error> {
+    let gain = if gain < MIN_INPUT_GAIN {
+        MIN_INPUT_GAIN
+    } else if gain > MAX_INPUT_GAIN {
+        MAX_INPUT_GAIN
+    } else {
+        gain
+    };
+    // 0 mutes the input, 1 is -71.625 dB and 0xC0 is 0 dB
+    let value = ((gain - MIN_INPUT_GAIN) / INPUT_GAIN_STEP + 0.5) as u16 + 1;
+    let (left, right) = match input {
+        InputSource::DigitalMicrophone2 => (AIF1_ADC2_LEFT_VOLUME, AIF1_ADC2_RIGHT_VOLUME),
+        InputSource::LineIn1 => (AIF1_ADC1_LEFT_VOLUME, AIF1_ADC1_RIGHT_VOLUME),
+    };
+    regs.write(left, value)?;
+    regs.write(right, value | VOLUME_UPDATE)
+}
+
+/// Mutes or unmutes the input by disconnecting it from its AIF1 timeslot.
+pub fn set_input_mute<R: RegisterAccess>(
+    regs: &mut R,
+    input: InputSource,
+    mute: bool,
+) -> Result<(), R::Error> {
+    let value = if mute { 0 } else { ADC_TO_AIF1 };
+    let (left, right) = match input {
+        InputSource::DigitalMicrophone2 => {
+            (AIF1_ADC2_LEFT_MIXER_ROUTING, AIF1_ADC2_RIGHT_MIXER_ROUTING)
+        }
+        InputSource::LineIn1 => (AIF1_ADC1_LEFT_MIXER_ROUTING, AIF1_ADC1_RIGHT_MIXER_ROUTING),
+    };
+    regs.write(left, value)?;
+    regs.write(right, value)
+}
+
+/// Mutes the outputs, disables all paths and resets the codec, which powers down its analog
+/// parts.
+pub fn power_down<R: RegisterAccess>(regs: &mut R) -> Result<(), R::Error> {
+    set_output_mute(regs, true)?;
+    // Disconnect the DACs from the headphone output mixers
+    regs.write(OUTPUT_MIXER_1, 0x0000)?;
+    regs.write(OUTPUT_MIXER_2, 0x0000)?;
+    // Disable the AIF1 DAC path and the DACs
+    regs.write(POWER_MANAGEMENT_5, 0x0000)?;
+    regs.write(SOFTWARE_RESET, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    // Records the written registers. Reads return `id` for the device ID register.
+    struct Recorder {
+        id: Result<u16, ()>,
+        writes: Vec<(u16, u16)>,
+    }
+
+    impl Recorder {
+        fn new() -> Recorder {
+            Recorder {
+                id: Ok(DEVICE_ID),
+                writes: Vec::new(),
+            }
+        }
+    }
+
+    impl RegisterAccess for Recorder {
+        type Error = ();
+
+        fn read(&mut self, register: u16) -> Result<u16, ()> {
+            if register == SOFTWARE_RESET {
+                self.id
+            } else {
+                Ok(0)
+            }
+        }
+
+        fn write(&mut self, register: u16, value: u16) -> Result<(), ()> {
+            self.writes.push((register, value));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn init_default() {
+        let mut bus = Recorder::new();
+        let mut waits = Vec::new();
+        init(&mut bus, &Config::default(), |ms| waits.push(ms)).unwrap();
+        let expected = [
+            (SOFTWARE_RESET, 0),
+            (ERRATA_UNLOCK, 0x0003),
+            (ERRATA, 0x0000),
+            (ERRATA_UNLOCK, 0x0000),
+            (ANTIPOP_2, 0x006C),
+            (POWER_MANAGEMENT_1, 0x0003),
+            (POWER_MANAGEMENT_4, 0x0C30),
+            (AIF1_DRC2_1, 0x00DB),
+            (POWER_MANAGEMENT_2, 0x6000),
+            (GPIO_1, 0x000E),
+            (AIF1_ADC2_LEFT_MIXER_ROUTING, ADC_TO_AIF1),
+            (AIF1_ADC2_RIGHT_MIXER_ROUTING, ADC_TO_AIF1),
+            (AIF1_RATE, 0x0033),
+            (AIF1_CONTROL_1, 0x4010),
+            (AIF1_MASTER_SLAVE, 0x0000),
+            (CLOCKING_1, 0x000A),
+            (AIF1_CLOCKING_1, 0x0001),
+            (POWER_MANAGEMENT_1, 0x0013),
+            (OVERSAMPLING, 0x0002),
+            (AIF1_ADC2_FILTERS, 0x3800),
+            (AIF1_ADC2_LEFT_VOLUME, 0x00EF),
+            (AIF1_ADC2_RIGHT_VOLUME, 0x01EF),
+        ];
+        assert_eq!(bus.writes, expected);
+        assert_eq!(waits, [50]);
+    }
+
+    #[test]
+    fn init_outputs() {
+        let mut bus = Recorder::new();
+        let mut waits = Vec::new();
+        let config = Config {
+            input: None,
+            output: Some(OutputDevice::Both),
+            ..Config::default()
+        };
+        init(&mut bus, &config, |ms| waits.push(ms)).unwrap();
+        assert_eq!(waits, [50, 300, 15, 257]);
+        assert!(bus.writes.contains(&(POWER_MANAGEMENT_5, 0x030F)));
+        assert!(bus.writes.contains(&(POWER_MANAGEMENT_1, 0x3303)));
+        // the volume is set after the start-up sequence
+        let volume = (-10 - MIN_OUTPUT_VOLUME) as u16 | OUTPUT_UNMUTE;
+        let position = bus.writes.iter().position(|&w| w == (LEFT_OUTPUT_VOLUME, volume));
+        assert!(position.unwrap() > bus.writes.len() - 10);
+        assert_eq!(bus.writes.last(), Some(&(SPEAKER_VOLUME_RIGHT, volume | VOLUME_UPDATE)));
+
+        let mut bus = Recorder::new();
+        let config = Config {
+            input: None,
+            output: Some(OutputDevice::Speaker),
+            ..Config::default()
+        };
+        init(&mut bus, &config, |_| {}).unwrap();
+        assert!(bus.writes.contains(&(POWER_MANAGEMENT_5, 0x030C)));
+        assert!(bus.writes.contains(&(DAC1_LEFT_MIXER_ROUTING, 0)));
+        assert!(bus.writes.contains(&(DAC2_LEFT_MIXER_ROUTING, 1)));
+        assert!(!bus.writes.contains(&(WRITE_SEQUENCER_CTRL_1, 0x8100)));
+    }
+
+    #[test]
+    fn init_errors() {
+        let mut bus = Recorder::new();
+        bus.id = Ok(0x8904);
+        let result = init(&mut bus, &Config::default(), |_| {});
+        assert_eq!(result, Err(Error::UnknownDevice(0x8904)));
+        assert!(bus.writes.is_empty());
+
+        bus.id = Err(());
+        let result = init(&mut bus, &Config::default(), |_| {});
+        assert_eq!(result, Err(Error::Access(())));
+        assert!(bus.writes.is_empty());
+    }
+
+    #[test]
+    fn output_volume() {
+        let mut bus = Recorder::new();
+        set_output_volume(&mut bus, 0).unwrap();
+        let expected = [
+            (LEFT_OUTPUT_VOLUME, 0x079),
+            (RIGHT_OUTPUT_VOLUME, 0x179),
+            (SPEAKER_VOLUME_LEFT, 0x079),
+            (SPEAKER_VOLUME_RIGHT, 0x179),
+        ];
+        assert_eq!(bus.writes, expected);
+
+        // the volume is clamped
+        let mut bus = Recorder::new();
+        set_output_volume(&mut bus, i8::max_value()).unwrap();
+        set_output_volume(&mut bus, i8::min_value()).unwrap();
+        assert_eq!(bus.writes[0], (LEFT_OUTPUT_VOLUME, 0x07F));
+        assert_eq!(bus.writes[4], (LEFT_OUTPUT_VOLUME, 0x040));
+    }
+
+    #[test]
+    fn input_gain() {
+        let mut bus = Recorder::new();
+        set_input_gain(&mut bus, InputSource::LineIn1, 0.0).unwrap();
+        assert_eq!(
+            bus.writes,
+            [(AIF1_ADC1_LEFT_VOLUME, 0x0C0), (AIF1_ADC1_RIGHT_VOLUME, 0x1C0)]
+        );
+
+        // the gain is rounded to steps of 0.375 dB and clamped
+        let gains = [(0.2, 0xC1), (-0.1, 0xC0), (100.0, 0xEF), (-100.0, 0x01)];
+        for &(gain, value) in gains.iter() {
+            let mut bus = Recorder::new();
+            set_input_gain(&mut bus, InputSource::DigitalMicrophone2, gain).unwrap();
+            assert_eq!(
+                bus.writes,
+                [
+                    (AIF1_ADC2_LEFT_VOLUME, value),
+                    (AIF1_ADC2_RIGHT_VOLUME, value | VOLUME_UPDATE)
+                ],
+                "gain {}",
+                gain
+            );
+        }
+    }
+
+    #[test]
+    fn output_mute() {
+        let mut bus = Recorder::new();
+        set_output_mute(&mut bus, true).unwrap();
+        set_output_mute(&mut bus, false).unwrap();
+        let expected = [
+            (AIF1_DAC1_FILTERS_1, 0x200),
+            (AIF1_DAC2_FILTERS_1, 0x200),
+            (AIF1_DAC1_FILTERS_1, 0x000),
+            (AIF1_DAC2_FILTERS_1, 0x000),
+        ];
+        assert_eq!(bus.writes, expected);
+    }
+}
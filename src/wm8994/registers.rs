@@ -0,0 +1,114 @@
+//! Addresses of the WM8994 registers that are used by the driver.
+//!
+//! The names follow the register map in the WM8994 datasheet.
+
+/// Writing any value resets all registers, reading returns the device ID `0x8994`.
+pub const SOFTWARE_RESET: u16 = 0x000;
+/// Bias, VMID, microphone bias and output driver enables.
+pub const POWER_MANAGEMENT_1: u16 = 0x001;
+/// Input PGA enables and the thermal sensor.
+pub const POWER_MANAGEMENT_2: u16 = 0x002;
+/// Output mixer and speaker PGA enables.
+pub const POWER_MANAGEMENT_3: u16 = 0x003;
+/// AIF1 ADC path, digital microphone and ADC enables.
+pub const POWER_MANAGEMENT_4: u16 = 0x004;
+/// AIF1 DAC path and DAC enables.
+pub const POWER_MANAGEMENT_5: u16 = 0x005;
+/// Left headphone output volume.
+pub const LEFT_OUTPUT_VOLUME: u16 = 0x01C;
+/// Right headphone output volume.
+pub const RIGHT_OUTPUT_VOLUME: u16 = 0x01D;
+/// Left speaker mixer attenuation.
+pub const SPKMIXL_ATTENUATION: u16 = 0x022;
+/// Right speaker mixer attenuation and speaker output mode.
+pub const SPKMIXR_ATTENUATION: u16 = 0x023;
+/// Left speaker output volume.
+pub const SPEAKER_VOLUME_LEFT: u16 = 0x026;
+/// Right speaker output volume.
+pub const SPEAKER_VOLUME_RIGHT: u16 = 0x027;
+/// Connections of the input pins to the input PGAs.
+pub const INPUT_MIXER_2: u16 = 0x028;
+/// Left input mixer paths and gains.
+pub const INPUT_MIXER_3: u16 = 0x029;
+/// Right input mixer paths and gains.
+pub const INPUT_MIXER_4: u16 = 0x02A;
+/// Left output mixer paths.
+pub const OUTPUT_MIXER_1: u16 = 0x02D;
+/// Right output mixer paths.
+pub const OUTPUT_MIXER_2: u16 = 0x02E;
+/// Speaker mixer paths.
+pub const SPEAKER_MIXER: u16 = 0x036;
+/// VMID soft start and start-up bias current.
+pub const ANTIPOP_2: u16 = 0x039;
+/// Headphone charge pump.
+pub const CHARGE_PUMP_1: u16 = 0x04C;
+/// Class W envelope tracking of the headphone supply.
+pub const CLASS_W_1: u16 = 0x051;
+/// DC servo of the headphone outputs.
+pub const DC_SERVO_1: u16 = 0x054;
+/// Headphone output stages and clamps.
+pub const ANALOGUE_HP_1: u16 = 0x060;
+/// Undocumented register that unlocks the errata registers, see the STM32Cube BSP.
+pub const ERRATA_UNLOCK: u16 = 0x102;
+/// Control of the write sequencer, which runs the headphone start-up sequence.
+pub const WRITE_SEQUENCER_CTRL_1: u16 = 0x110;
+/// AIF1 clock source and enable.
+pub const AIF1_CLOCKING_1: u16 = 0x200;
+/// DSP processing and core clock enables.
+pub const CLOCKING_1: u16 = 0x208;
+/// AIF1 sample rate and clock ratio.
+pub const AIF1_RATE: u16 = 0x210;
+/// AIF1 word length and format.
+pub const AIF1_CONTROL_1: u16 = 0x300;
+/// AIF1 master or slave mode.
+pub const AIF1_MASTER_SLAVE: u16 = 0x302;
+/// Left AIF1 ADC timeslot 0 volume.
+pub const AIF1_ADC1_LEFT_VOLUME: u16 = 0x400;
+/// Right AIF1 ADC timeslot 0 volume.
+pub const AIF1_ADC1_RIGHT_VOLUME: u16 = 0x401;
+/// Left AIF1 ADC timeslot 1 volume.
+pub const AIF1_ADC2_LEFT_VOLUME: u16 = 0x404;
+/// Right AIF1 ADC timeslot 1 volume.
+pub const AIF1_ADC2_RIGHT_VOLUME: u16 = 0x405;
+/// High pass filter of the AIF1 ADC timeslot 0.
+pub const AIF1_ADC1_FILTERS: u16 = 0x410;
+/// High pass filter of the AIF1 ADC timeslot 1.
+pub const AIF1_ADC2_FILTERS: u16 = 0x411;
+/// Soft mute of the AIF1 DAC timeslot 0.
+pub const AIF1_DAC1_FILTERS_1: u16 = 0x420;
+/// Soft mute of the AIF1 DAC timeslot 1.
+pub const AIF1_DAC2_FILTERS_1: u16 = 0x422;
+/// Dynamic range control of the AIF1 timeslot 0.
+pub const AIF1_DRC1_1: u16 = 0x440;
+/// Dynamic range control of the AIF1 timeslot 1.
+pub const AIF1_DRC2_1: u16 = 0x450;
+/// Paths to the left DAC1.
+pub const DAC1_LEFT_MIXER_ROUTING: u16 = 0x601;
+/// Paths to the right DAC1.
+pub const DAC1_RIGHT_MIXER_ROUTING: u16 = 0x602;
+/// Paths to the left DAC2.
+pub const DAC2_LEFT_MIXER_ROUTING: u16 = 0x604;
+/// Paths to the right DAC2.
+pub const DAC2_RIGHT_MIXER_ROUTING: u16 = 0x605;
+/// Paths to the left AIF1 ADC timeslot 0.
+pub const AIF1_ADC1_LEFT_MIXER_ROUTING: u16 = 0x606;
+/// Paths to the right AIF1 ADC timeslot 0.
+pub const AIF1_ADC1_RIGHT_MIXER_ROUTING: u16 = 0x607;
+/// Paths to the left AIF1 ADC timeslot 1.
+pub const AIF1_ADC2_LEFT_MIXER_ROUTING: u16 = 0x608;
+/// Paths to the right AIF1 ADC timeslot 1.
+pub const AIF1_ADC2_RIGHT_MIXER_ROUTING: u16 = 0x609;
+/// Left DAC1 volume and mute.
+pub const DAC1_LEFT_VOLUME: u16 = 0x610;
+/// Right DAC1 volume and mute.
+pub const DAC1_RIGHT_VOLUME: u16 = 0x611;
+/// Left DAC2 volume and mute.
+pub const DAC2_LEFT_VOLUME: u16 = 0x612;
+/// Right DAC2 volume and mute.
+pub const DAC2_RIGHT_VOLUME: u16 = 0x613;
+/// ADC and DAC oversampling.
+pub const OVERSAMPLING: u16 = 0x620;
+/// Function and direction of the GPIO1 pin.
+pub const GPIO_1: u16 = 0x700;
+/// Undocumented errata register, see the STM32Cube BSP.
+pub const ERRATA: u16 = 0x817;